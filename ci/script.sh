@@ -5,3 +5,7 @@ set -ex
 cargo build --verbose --all
 cargo doc --verbose --all
 cargo test --verbose --all
+cargo build --verbose --no-default-features --features utf16
+cargo test --verbose --no-default-features --features utf16
+cargo build --verbose --no-default-features --features utf32
+cargo test --verbose --no-default-features --features utf32
@@ -28,7 +28,7 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 
 // Files that PCRE2 needs to compile.
-const FILES: &'static [&'static str] = &[
+const FILES: &[&str] = &[
     "pcre2_auto_possess.c",
     "pcre2_compile.c",
     "pcre2_config.c",
@@ -78,6 +78,7 @@ fn build_1_pcre2_lib(code_unit_width: &str) {
         .define("PCRE2_STATIC", "1")
         .define("STDC_HEADERS", "1")
         .define("SUPPORT_PCRE2_8", "1")
+        .define("SUPPORT_PCRE2_16", "1")
         .define("SUPPORT_PCRE2_32", "1")
         .define("SUPPORT_UNICODE", "1");
     if target.contains("windows") {
@@ -112,7 +113,7 @@ fn build_1_pcre2_lib(code_unit_width: &str) {
         builder.file(Path::new("pcre2/src").join(file));
     }
 
-    if env::var("PCRE2_SYS_DEBUG").unwrap_or(String::new()) == "1" {
+    if env::var("PCRE2_SYS_DEBUG").unwrap_or_default() == "1" {
         builder.debug(true);
     }
     let output_name = format!("libpcre2-{}", code_unit_width);
@@ -129,11 +130,12 @@ fn feature_enabled(feature: &str) -> bool {
 
 fn main() {
     let do_utf8 = feature_enabled("UTF8");
+    let do_utf16 = feature_enabled("UTF16");
     let do_utf32 = feature_enabled("UTF32");
     let wants_static = feature_enabled("STATIC_PCRE2");
 
-    if !do_utf8 && !do_utf32 {
-        panic!("Must enable at least one of the UTF8 or UTF32 features");
+    if !do_utf8 && !do_utf16 && !do_utf32 {
+        panic!("Must enable at least one of the UTF8, UTF16 or UTF32 features");
     }
 
     println!("cargo:rerun-if-env-changed=PCRE2_SYS_STATIC");
@@ -143,6 +145,7 @@ fn main() {
     let do_static = wants_static
         || target.contains("musl")
         || (do_utf8 && pkg_config::probe_library("libpcre2-8").is_err())
+        || (do_utf16 && pkg_config::probe_library("libpcre2-16").is_err())
         || (do_utf32 && pkg_config::probe_library("libpcre2-32").is_err());
     if !do_static {
         return;
@@ -151,7 +154,7 @@ fn main() {
     // For a static build, make sure our PCRE2 submodule has been loaded.
     if has_git() && !Path::new("pcre2/.git").exists() {
         Command::new("git")
-            .args(&["submodule", "update", "--init"])
+            .args(["submodule", "update", "--init"])
             .status()
             .unwrap();
     }
@@ -159,6 +162,9 @@ fn main() {
     if do_utf8 {
         build_1_pcre2_lib("8");
     }
+    if do_utf16 {
+        build_1_pcre2_lib("16");
+    }
     if do_utf32 {
         build_1_pcre2_lib("32");
     }
@@ -7,4 +7,196 @@ mod bindings;
 mod tests;
 
 // It is weird that this isn't caught by bindgen. Dunno why.
-pub const PCRE2_UNSET: usize = ::std::usize::MAX;
+pub const PCRE2_UNSET: usize = usize::MAX;
+
+// These were added after the PCRE2 release that the bindings were generated
+// from, so bindgen doesn't know about them.
+pub const PCRE2_SUBSTITUTE_LITERAL: u32 = 0x00008000;
+pub const PCRE2_SUBSTITUTE_MATCHED: u32 = 0x00010000;
+pub const PCRE2_SUBSTITUTE_REPLACEMENT_ONLY: u32 = 0x00020000;
+pub const PCRE2_EXTRA_ESCAPED_CR_IS_LF: u32 = 0x00000010;
+pub const PCRE2_EXTRA_ALT_BSUX: u32 = 0x00000020;
+pub const PCRE2_MATCH_INVALID_UTF: u32 = 0x04000000;
+pub const PCRE2_JIT_INVALID_UTF: u32 = 0x00000100;
+
+// pcre2_maketables_free was added in PCRE2 10.34, after the release that the
+// bindings were generated from.
+extern "C" {
+    pub fn pcre2_maketables_free_8(arg1: *mut pcre2_general_context_8, arg2: *const u8);
+    pub fn pcre2_maketables_free_16(arg1: *mut pcre2_general_context_16, arg2: *const u8);
+    pub fn pcre2_maketables_free_32(arg1: *mut pcre2_general_context_32, arg2: *const u8);
+}
+
+// The bindings were generated without PCRE2's callout API, so the parts of it
+// that are used are declared here, following pcre2.h.
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct pcre2_callout_enumerate_block_8 {
+    pub version: u32,
+    pub pattern_position: usize,
+    pub next_item_length: usize,
+    pub callout_number: u32,
+    pub callout_string_offset: usize,
+    pub callout_string_length: usize,
+    pub callout_string: PCRE2_SPTR8,
+}
+extern "C" {
+    pub fn pcre2_callout_enumerate_8(
+        arg1: *const pcre2_code_8,
+        arg2: ::std::option::Option<
+            unsafe extern "C" fn(
+                arg1: *mut pcre2_callout_enumerate_block_8,
+                arg2: *mut ::libc::c_void,
+            ) -> ::libc::c_int,
+        >,
+        arg3: *mut ::libc::c_void,
+    ) -> ::libc::c_int;
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct pcre2_callout_enumerate_block_16 {
+    pub version: u32,
+    pub pattern_position: usize,
+    pub next_item_length: usize,
+    pub callout_number: u32,
+    pub callout_string_offset: usize,
+    pub callout_string_length: usize,
+    pub callout_string: PCRE2_SPTR16,
+}
+extern "C" {
+    pub fn pcre2_callout_enumerate_16(
+        arg1: *const pcre2_code_16,
+        arg2: ::std::option::Option<
+            unsafe extern "C" fn(
+                arg1: *mut pcre2_callout_enumerate_block_16,
+                arg2: *mut ::libc::c_void,
+            ) -> ::libc::c_int,
+        >,
+        arg3: *mut ::libc::c_void,
+    ) -> ::libc::c_int;
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct pcre2_callout_enumerate_block_32 {
+    pub version: u32,
+    pub pattern_position: usize,
+    pub next_item_length: usize,
+    pub callout_number: u32,
+    pub callout_string_offset: usize,
+    pub callout_string_length: usize,
+    pub callout_string: PCRE2_SPTR32,
+}
+extern "C" {
+    pub fn pcre2_callout_enumerate_32(
+        arg1: *const pcre2_code_32,
+        arg2: ::std::option::Option<
+            unsafe extern "C" fn(
+                arg1: *mut pcre2_callout_enumerate_block_32,
+                arg2: *mut ::libc::c_void,
+            ) -> ::libc::c_int,
+        >,
+        arg3: *mut ::libc::c_void,
+    ) -> ::libc::c_int;
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct pcre2_callout_block_8 {
+    pub version: u32,
+    pub callout_number: u32,
+    pub capture_top: u32,
+    pub capture_last: u32,
+    pub offset_vector: *mut usize,
+    pub mark: PCRE2_SPTR8,
+    pub subject: PCRE2_SPTR8,
+    pub subject_length: usize,
+    pub start_match: usize,
+    pub current_position: usize,
+    pub pattern_position: usize,
+    pub next_item_length: usize,
+    pub callout_string_offset: usize,
+    pub callout_string_length: usize,
+    pub callout_string: PCRE2_SPTR8,
+    pub callout_flags: u32,
+}
+extern "C" {
+    pub fn pcre2_set_callout_8(
+        arg1: *mut pcre2_match_context_8,
+        arg2: ::std::option::Option<
+            unsafe extern "C" fn(
+                arg1: *mut pcre2_callout_block_8,
+                arg2: *mut ::libc::c_void,
+            ) -> ::libc::c_int,
+        >,
+        arg3: *mut ::libc::c_void,
+    ) -> ::libc::c_int;
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct pcre2_callout_block_16 {
+    pub version: u32,
+    pub callout_number: u32,
+    pub capture_top: u32,
+    pub capture_last: u32,
+    pub offset_vector: *mut usize,
+    pub mark: PCRE2_SPTR16,
+    pub subject: PCRE2_SPTR16,
+    pub subject_length: usize,
+    pub start_match: usize,
+    pub current_position: usize,
+    pub pattern_position: usize,
+    pub next_item_length: usize,
+    pub callout_string_offset: usize,
+    pub callout_string_length: usize,
+    pub callout_string: PCRE2_SPTR16,
+    pub callout_flags: u32,
+}
+extern "C" {
+    pub fn pcre2_set_callout_16(
+        arg1: *mut pcre2_match_context_16,
+        arg2: ::std::option::Option<
+            unsafe extern "C" fn(
+                arg1: *mut pcre2_callout_block_16,
+                arg2: *mut ::libc::c_void,
+            ) -> ::libc::c_int,
+        >,
+        arg3: *mut ::libc::c_void,
+    ) -> ::libc::c_int;
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct pcre2_callout_block_32 {
+    pub version: u32,
+    pub callout_number: u32,
+    pub capture_top: u32,
+    pub capture_last: u32,
+    pub offset_vector: *mut usize,
+    pub mark: PCRE2_SPTR32,
+    pub subject: PCRE2_SPTR32,
+    pub subject_length: usize,
+    pub start_match: usize,
+    pub current_position: usize,
+    pub pattern_position: usize,
+    pub next_item_length: usize,
+    pub callout_string_offset: usize,
+    pub callout_string_length: usize,
+    pub callout_string: PCRE2_SPTR32,
+    pub callout_flags: u32,
+}
+extern "C" {
+    pub fn pcre2_set_callout_32(
+        arg1: *mut pcre2_match_context_32,
+        arg2: ::std::option::Option<
+            unsafe extern "C" fn(
+                arg1: *mut pcre2_callout_block_32,
+                arg2: *mut ::libc::c_void,
+            ) -> ::libc::c_int,
+        >,
+        arg3: *mut ::libc::c_void,
+    ) -> ::libc::c_int;
+}
@@ -1,6 +1,30 @@
-use crate::ffi::CodeUnitWidth8;
+pub use crate::ffi::CalloutBlock as CalloutBlockImpl;
+pub use crate::ffi::CalloutResult;
+pub use crate::ffi::CodeUnitWidth8;
+pub use crate::ffi::JitStack as JitStackImpl;
+pub use crate::ffi::NameTable as NameTableImpl;
+pub use crate::jit::JitStackPool as JitStackPoolImpl;
+pub use crate::jit::PooledJitStack as PooledJitStackImpl;
+pub use crate::regex_impl::Captures as CapturesImpl;
+pub use crate::regex_impl::CharacterTables as CharacterTablesImpl;
+pub use crate::regex_impl::DfaWorkspace as DfaWorkspaceImpl;
 pub use crate::regex_impl::Match as MatchImpl;
+pub use crate::regex_impl::PartialMatch as PartialMatchImpl;
+pub use crate::regex_impl::{
+    BsrConvention, CalloutInfo, CancellationToken, EmptyMatchPolicy, Extended, FirstCodeUnit,
+    MatchOptions, Newline, NoExpand, PartialMode, Replacer, ReplacerRef, SubstituteOptions,
+};
 use crate::regex_impl::{Regex as RegexImpl, RegexBuilder as RegexBuilderImpl};
+pub use crate::set::Lexer as LexerImpl;
+pub use crate::set::RegexSet as RegexSetImpl;
+pub use crate::set::RegexSetBuilder as RegexSetBuilderImpl;
+pub use crate::set::SetCaptureLocations as SetCaptureLocationsImpl;
+pub use crate::set::{LexError, PatternOptions, SetMatches};
+pub use crate::stream::ChunkMatches as ChunkMatchesImpl;
+pub use crate::stream::Scanner as ScannerImpl;
+pub use crate::stream::StreamMatch as StreamMatchImpl;
+pub use crate::stream::StreamMatcher as StreamMatcherImpl;
+pub use crate::stream::{LineMatch, LineMatches, ReadMatches, SplitRead};
 
 /// A compiled PCRE2 regular expression for matching bytes.
 ///
@@ -17,15 +41,136 @@ pub type RegexBuilder = RegexBuilderImpl<CodeUnitWidth8>;
 /// of the subject string.
 pub type Match<'s> = MatchImpl<'s, CodeUnitWidth8>;
 
+/// A set of compiled PCRE2 regular expressions that are matched against a
+/// subject together.
+pub type RegexSet = RegexSetImpl<CodeUnitWidth8>;
+
+/// A builder for configuring the compilation of a `RegexSet`.
+pub type RegexSetBuilder = RegexSetBuilderImpl<CodeUnitWidth8>;
+
+/// The capture locations of every pattern in a `RegexSet`, which can be
+/// reused across searches.
+pub type SetCaptureLocations = SetCaptureLocationsImpl<CodeUnitWidth8>;
+
+/// An iterator that splits a subject into consecutive tokens using the
+/// patterns of a `RegexSet`.
+///
+/// The lifetime parameters `'r` and `'s` refer to the lifetimes of the set
+/// and of the subject string.
+pub type Lexer<'r, 's> = LexerImpl<'r, 's, CodeUnitWidth8>;
+
+/// An iterator over the named capturing groups of a regex, which borrows
+/// the names from the compiled regex.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type NameTable<'r> = NameTableImpl<'r, CodeUnitWidth8>;
+
+/// Captures represents a group of captured byte strings for a single match.
+///
+/// The lifetime parameter `'s` refers to the lifetime of the matched subject
+/// string.
+pub type Captures<'s> = CapturesImpl<'s, CodeUnitWidth8>;
+
+/// The state of a search when it reaches a callout.
+///
+/// The lifetime parameter `'a` refers to the lifetime of the subject and of
+/// the regex.
+pub type CalloutBlock<'a> = CalloutBlockImpl<'a, CodeUnitWidth8>;
+
+/// Character tables, which determine how PCRE2 classifies and case folds
+/// characters with code points less than 256.
+pub type CharacterTables = CharacterTablesImpl<CodeUnitWidth8>;
+
+/// A JIT stack, which can be installed for the current thread to be shared
+/// by every regex searched on it.
+pub type JitStack = JitStackImpl<CodeUnitWidth8>;
+
+/// A pool of JIT stacks of the same size.
+pub type JitStackPool = JitStackPoolImpl<CodeUnitWidth8>;
+
+/// A JIT stack taken from a `JitStackPool`, which is returned to the pool
+/// when dropped.
+///
+/// The lifetime parameter `'p` refers to the lifetime of the pool.
+pub type PooledJitStack<'p> = PooledJitStackImpl<'p, CodeUnitWidth8>;
+
+/// Scratch space for PCRE2's DFA matching algorithm, which can be reused
+/// across searches.
+pub type DfaWorkspace = DfaWorkspaceImpl<CodeUnitWidth8>;
+
+/// The result of a search that permits partial matches.
+///
+/// The lifetime parameter `'s` refers to the lifetime of the matched portion
+/// of the subject string.
+pub type PartialMatch<'s> = PartialMatchImpl<'s, CodeUnitWidth8>;
+
+/// A match found in a stream of input.
+///
+/// Offsets are relative to the beginning of the stream.
+pub type StreamMatch = StreamMatchImpl<u8>;
+
+/// Finds successive non-overlapping matches in input that is provided in
+/// chunks.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type StreamMatcher<'r> = StreamMatcherImpl<'r, CodeUnitWidth8>;
+
+/// An iterator over successive non-overlapping matches in a subject that is
+/// split into chunks.
+///
+/// The lifetime parameters `'r` and `'c` refer to the lifetimes of the regex
+/// and of the chunks, and `I` is the type of the iterator over the chunks.
+pub type ChunkMatches<'r, 'c, I> = ChunkMatchesImpl<'r, 'c, CodeUnitWidth8, I>;
+
+/// A push-based scanner for finding successive non-overlapping matches in
+/// input that is provided in chunks.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type Scanner<'r> = ScannerImpl<'r, CodeUnitWidth8>;
+
+#[cfg(feature = "bstr")]
+impl<'s> MatchImpl<'s, CodeUnitWidth8> {
+    /// Returns the matched portion of the subject string as a byte string.
+    ///
+    /// Byte strings from the `bstr` crate, such as `&BStr` and `&BString`,
+    /// can be searched directly, since they dereference to `&[u8]`.
+    #[inline]
+    pub fn as_bstr(&self) -> &'s bstr::BStr {
+        bstr::BStr::new(self.as_bytes())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{CodeUnitWidth8, Regex, RegexBuilder};
-    use crate::is_jit_available;
+    use std::borrow::Cow;
+
+    use super::{
+        BsrConvention, CalloutResult, CancellationToken, Captures, CharacterTables, CodeUnitWidth8,
+        EmptyMatchPolicy, Extended, FirstCodeUnit, JitStack, JitStackPool, LexError, MatchOptions,
+        Newline, NoExpand, PartialMode, PatternOptions, Regex, RegexBuilder, RegexSet,
+        RegexSetBuilder, Replacer, SubstituteOptions,
+    };
+    use crate::{
+        is_jit_available, jit_free_unused_memory, jit_target, ErrorKind, PatternPolicy,
+        PolicyViolation, RegexCache,
+    };
 
     fn b(string: &str) -> &[u8] {
         string.as_bytes()
     }
 
+    #[test]
+    fn unwind_safe() {
+        use std::panic::{RefUnwindSafe, UnwindSafe};
+
+        fn assert_unwind_safe<T: UnwindSafe + RefUnwindSafe>() {}
+        assert_unwind_safe::<crate::Error>();
+        assert_unwind_safe::<Regex>();
+        assert_unwind_safe::<RegexBuilder>();
+        assert_unwind_safe::<RegexSet>();
+        assert_unwind_safe::<RegexSetBuilder>();
+    }
+
     fn find_iter_tuples(re: &Regex, subject: &[u8]) -> Vec<(usize, usize)> {
         let mut tuples = vec![];
         for result in re.find_iter(subject) {
@@ -63,6 +208,355 @@ mod tests {
         let re = RegexBuilder::new().crlf(true).build("a$").unwrap();
         let m = re.find(b("a\r\n")).unwrap().unwrap();
         assert_eq!(m.as_pair(), (0, 1));
+
+        // Disabling CRLF only undoes enabling it.
+        let re = RegexBuilder::new()
+            .crlf(true)
+            .crlf(false)
+            .build("a$")
+            .unwrap();
+        assert_eq!(re.newline(), Some(Newline::Lf));
+        let re = RegexBuilder::new()
+            .newline(Newline::Cr)
+            .crlf(false)
+            .build("a$")
+            .unwrap();
+        assert_eq!(re.newline(), Some(Newline::Cr));
+    }
+
+    #[test]
+    fn newline() {
+        let re = RegexBuilder::new()
+            .newline(Newline::Cr)
+            .multi_line(true)
+            .build("^b$")
+            .unwrap();
+        assert_eq!(re.newline(), Some(Newline::Cr));
+        assert!(re.is_match(b("a\rb\rc")).unwrap());
+        assert!(!re.is_match(b("a\nb\nc")).unwrap());
+
+        let re = RegexBuilder::new()
+            .newline(Newline::Nul)
+            .build("a.b")
+            .unwrap();
+        assert!(re.is_match(b("a\nb")).unwrap());
+        assert!(!re.is_match(b("a\x00b")).unwrap());
+    }
+
+    #[test]
+    fn backslash_r() {
+        let re = RegexBuilder::new()
+            .backslash_r(BsrConvention::AnyCrLf)
+            .build(r"a\Rb")
+            .unwrap();
+        assert_eq!(re.backslash_r(), Some(BsrConvention::AnyCrLf));
+        assert!(re.is_match(b("a\r\nb")).unwrap());
+        assert!(!re.is_match(b("a\x0Bb")).unwrap());
+        let re = RegexBuilder::new()
+            .backslash_r(BsrConvention::Unicode)
+            .build(r"a\Rb")
+            .unwrap();
+        assert!(re.is_match(b("a\x0Bb")).unwrap());
+    }
+
+    #[test]
+    fn max_pattern_length() {
+        let mut builder = RegexBuilder::new();
+        builder.max_pattern_length(4);
+        assert!(builder.build("abcd").is_ok());
+        let err = builder.build("abcde").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Compile));
+    }
+
+    #[test]
+    fn parens_nest_limit() {
+        let mut builder = RegexBuilder::new();
+        builder.parens_nest_limit(2);
+        assert!(builder.build("((a))").is_ok());
+        let err = builder.build("(((a)))").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Compile));
+    }
+
+    #[test]
+    fn compile_recursion_guard() {
+        use pcre2_sys::PCRE2_ERROR_PARENTHESES_STACK_CHECK;
+
+        let mut builder = RegexBuilder::new();
+        builder.compile_recursion_guard(|depth| depth < 3);
+        assert!(builder.build("(a)").is_ok());
+        let err = builder.build("((((a))))").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Compile));
+        assert_eq!(err.code(), PCRE2_ERROR_PARENTHESES_STACK_CHECK as i32);
+
+        builder.compile_recursion_guard(|_| panic!("boom"));
+        assert!(builder.build("(a)").is_err());
+    }
+
+    #[test]
+    fn character_tables() {
+        let tables = CharacterTables::current_locale();
+        let re = RegexBuilder::new()
+            .character_tables(tables)
+            .build(r"^\w+$")
+            .unwrap();
+        assert!(re.is_match(b("abc")).unwrap());
+        assert!(!re.is_match(b"\xE9").unwrap());
+
+        assert!(CharacterTables::for_locale("no_such_locale").is_none());
+        if let Some(tables) = CharacterTables::for_locale("de_DE.ISO-8859-1") {
+            assert_eq!(tables.locale(), "de_DE.ISO-8859-1");
+            let re = RegexBuilder::new()
+                .character_tables(tables)
+                .caseless(true)
+                .build(r"^\w+$")
+                .unwrap();
+            assert!(re.is_match(b"\xE9\xC9").unwrap());
+        }
+    }
+
+    #[test]
+    fn shared_character_tables() {
+        let tables = CharacterTables::current_locale();
+        let mut builder = RegexBuilder::new();
+        builder.character_tables(tables.clone());
+        let mut set_builder = RegexSetBuilder::new();
+        set_builder.character_tables(tables.clone());
+        let regexes: Vec<Regex> = (0..10)
+            .map(|i| builder.build(format!(r"\w{}", i)).unwrap())
+            .collect();
+        let set = set_builder.pattern(r"\d+").build().unwrap();
+        // The tables outlive every handle to them used for compiling.
+        drop((tables, builder, set_builder));
+        assert!(regexes[3].is_match(b("a3")).unwrap());
+        assert!(set.is_match(b("42")).unwrap());
+    }
+
+    #[test]
+    fn match_word() {
+        let re = RegexBuilder::new()
+            .match_word(true)
+            .build("foo|bar")
+            .unwrap();
+        assert!(re.is_match(b("a foo b")).unwrap());
+        assert!(!re.is_match(b("foobar")).unwrap());
+        assert_eq!(re.find(b("barn bar")).unwrap().unwrap().as_pair(), (5, 8));
+    }
+
+    #[test]
+    fn match_line() {
+        let re = RegexBuilder::new()
+            .match_line(true)
+            .build("foo|bar")
+            .unwrap();
+        assert!(re.is_match(b("bar\n")).unwrap());
+        assert!(!re.is_match(b("x\nbar\ny")).unwrap());
+        assert!(!re.is_match(b("foobar")).unwrap());
+
+        let re = RegexBuilder::new()
+            .match_line(true)
+            .multi_line(true)
+            .build("foo|bar")
+            .unwrap();
+        assert!(re.is_match(b("x\nbar\ny")).unwrap());
+        assert!(!re.is_match(b("foo bar")).unwrap());
+    }
+
+    #[test]
+    fn bad_escape_is_literal() {
+        assert!(Regex::new(r"a\jb").is_err());
+        let re = RegexBuilder::new()
+            .bad_escape_is_literal(true)
+            .build(r"a\jb")
+            .unwrap();
+        assert!(re.is_match(b("ajb")).unwrap());
+    }
+
+    #[test]
+    fn allow_surrogate_escapes() {
+        let mut builder = RegexBuilder::new();
+        builder.utf(true);
+        assert!(builder.build(r"\x{d800}").is_err());
+        builder.allow_surrogate_escapes(true);
+        assert!(builder.build(r"\x{d800}").is_ok());
+    }
+
+    #[test]
+    fn escaped_cr_is_lf() {
+        let re = RegexBuilder::new()
+            .escaped_cr_is_lf(true)
+            .build(r"a\rb")
+            .unwrap();
+        assert!(re.is_match(b("a\nb")).unwrap());
+        assert!(!re.is_match(b("a\rb")).unwrap());
+    }
+
+    #[test]
+    fn alt_bsux() {
+        let re = RegexBuilder::new()
+            .alt_bsux(true)
+            .build(r"A\u{42}\x43\xZ")
+            .unwrap();
+        assert!(re.is_match(b("ABCxZ")).unwrap());
+    }
+
+    #[test]
+    fn literal() {
+        let re = RegexBuilder::new()
+            .literal(true)
+            .caseless(true)
+            .build("a.b(c")
+            .unwrap();
+        assert!(re.is_match(b("A.B(C")).unwrap());
+        assert!(!re.is_match(b("axb(c")).unwrap());
+        let result = RegexBuilder::new().literal(true).extended(true).build("a");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn dupnames() {
+        assert!(Regex::new(r"(?<n>\d+)px|(?<n>\d+)em").is_err());
+        let re = RegexBuilder::new()
+            .dupnames(true)
+            .build(r"(?<n>\d+)px|(?<n>\d+)em")
+            .unwrap();
+        let caps = re.captures(b("12px")).unwrap().unwrap();
+        assert_eq!(&caps["n"], b("12"));
+        let caps = re.captures(b("34em")).unwrap().unwrap();
+        assert_eq!(&caps["n"], b("34"));
+        assert!(caps.get(1).is_none());
+    }
+
+    #[test]
+    fn no_auto_capture() {
+        let re = RegexBuilder::new()
+            .no_auto_capture(true)
+            .build(r"(a)(?<b>b)")
+            .unwrap();
+        assert_eq!(re.captures_len(), 2);
+        let caps = re.captures(b("ab")).unwrap().unwrap();
+        assert_eq!(&caps[1], b("b"));
+        assert_eq!(&caps["b"], b("b"));
+    }
+
+    #[test]
+    fn ungreedy() {
+        let re = RegexBuilder::new().ungreedy(true).build("a+").unwrap();
+        assert_eq!(re.find(b("aaa")).unwrap().unwrap().as_pair(), (0, 1));
+        let re = RegexBuilder::new().ungreedy(true).build("a+?").unwrap();
+        assert_eq!(re.find(b("aaa")).unwrap().unwrap().as_pair(), (0, 3));
+    }
+
+    #[test]
+    fn dollar_endonly() {
+        assert!(Regex::new(r"^\d+$").unwrap().is_match(b("42\n")).unwrap());
+        let re = RegexBuilder::new()
+            .dollar_endonly(true)
+            .build(r"^\d+$")
+            .unwrap();
+        assert!(re.is_match(b("42")).unwrap());
+        assert!(!re.is_match(b("42\n")).unwrap());
+    }
+
+    #[test]
+    fn allow_empty_class() {
+        assert!(Regex::new("a[]").is_err());
+        let re = RegexBuilder::new()
+            .allow_empty_class(true)
+            .build("a[]|b[^]")
+            .unwrap();
+        assert!(!re.is_match(b("a")).unwrap());
+        assert!(re.is_match(b("bx")).unwrap());
+    }
+
+    #[test]
+    fn anchored() {
+        let re = RegexBuilder::new().anchored(true).build(r"\d").unwrap();
+        assert!(!re.is_match(b("a1")).unwrap());
+        assert_eq!(re.find_at(b("a1"), 1).unwrap().unwrap().as_pair(), (1, 2));
+        assert_eq!(find_iter_tuples(&re, b("12a3")), vec![(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn no_optimizations() {
+        use pcre2_sys::{PCRE2_NO_AUTO_POSSESS, PCRE2_NO_START_OPTIMIZE};
+
+        let re = RegexBuilder::new()
+            .no_auto_possess(true)
+            .no_start_optimize(true)
+            .build("a+b")
+            .unwrap();
+        let options = re.compile_options();
+        assert_ne!(options & PCRE2_NO_AUTO_POSSESS, 0);
+        assert_ne!(options & PCRE2_NO_START_OPTIMIZE, 0);
+        assert!(re.is_match(b("xaab")).unwrap());
+
+        // Without start-of-match optimizations, (*COMMIT) is reached at
+        // every starting position, so the search gives up at the first one.
+        let re = RegexBuilder::new()
+            .no_start_optimize(true)
+            .build("(*COMMIT)abc")
+            .unwrap();
+        assert!(!re.is_match(b("xyzabc")).unwrap());
+        assert!(Regex::new("(*COMMIT)abc")
+            .unwrap()
+            .is_match(b("xyzabc"))
+            .unwrap());
+    }
+
+    #[test]
+    fn firstline() {
+        let re = RegexBuilder::new().firstline(true).build(r"b+\nc").unwrap();
+        assert!(re.is_match(b("abb\nc")).unwrap());
+        assert!(!re.is_match(b("a\nbb\nc")).unwrap());
+    }
+
+    #[test]
+    fn match_unset_backref() {
+        assert!(!Regex::new(r"^(a)?\1b").unwrap().is_match(b("b")).unwrap());
+        let re = RegexBuilder::new()
+            .match_unset_backref(true)
+            .build(r"^(a)?\1b")
+            .unwrap();
+        assert!(re.is_match(b("b")).unwrap());
+        assert!(re.is_match(b("aab")).unwrap());
+    }
+
+    #[test]
+    fn never_backslash_c() {
+        assert!(Regex::new(r"a\Cb").is_ok());
+        let result = RegexBuilder::new().never_backslash_c(true).build(r"a\Cb");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn match_invalid_utf() {
+        let subject = b"\xFFcaf\xC3\xA9\xFF\xFF";
+        assert!(RegexBuilder::new()
+            .utf(true)
+            .build("caf.")
+            .unwrap()
+            .is_match(subject)
+            .is_err());
+
+        let re = RegexBuilder::new()
+            .match_invalid_utf(true)
+            .build("caf.")
+            .unwrap();
+        assert!(re.is_utf());
+        assert_eq!(re.find(subject).unwrap().unwrap().as_pair(), (1, 6));
+    }
+
+    #[test]
+    fn match_invalid_utf_jit() {
+        let subject = b"\xFFcaf\xC3\xA9\xFF\xFF";
+        let re = RegexBuilder::new()
+            .match_invalid_utf(true)
+            .jit_if_available(true)
+            .build("caf.")
+            .unwrap();
+        assert_eq!(re.is_jit(), is_jit_available::<CodeUnitWidth8>());
+        assert_eq!(re.find(subject).unwrap().unwrap().as_pair(), (1, 6));
+        assert!(re.find(b"\xFFcaf\xFF").unwrap().is_none());
     }
 
     #[test]
@@ -187,6 +681,18 @@ mod tests {
         assert_eq!(capture_names_idx["springsteen"], 4);
     }
 
+    #[test]
+    fn name_table() {
+        let re = Regex::new(r"(?P<foo>abc)|(def)|(?P<a>ghi)|(?P<springsteen>jkl)").unwrap();
+        let table: Vec<(usize, &[u8])> = re.name_table().collect();
+        assert_eq!(
+            table,
+            vec![(3, b("a")), (1, b("foo")), (4, b("springsteen"))]
+        );
+        assert_eq!(re.name_table().len(), 3);
+        assert_eq!(Regex::new("(a)").unwrap().name_table().count(), 0);
+    }
+
     #[test]
     fn captures_get() {
         let re = Regex::new(r"[a-z]+(?:([0-9]+)|([A-Z]+))").unwrap();
@@ -209,57 +715,1836 @@ mod tests {
             find_iter_tuples(&re, b"\na\n\n"),
             vec![(0, 0), (1, 1), (3, 3),]
         );
+
+        // Searches never resume in the middle of a character in UTF mode,
+        // even when it's only enabled by the pattern.
+        let re = Regex::new(r"(*UTF)x?").unwrap();
+        assert_eq!(
+            find_iter_tuples(&re, "aé".as_bytes()),
+            vec![(0, 0), (1, 1), (3, 3)]
+        );
     }
 
     #[test]
-    fn captures_iter_empty() {
-        let re = Regex::new(r"(?m:^)").unwrap();
-        assert_eq!(cap_iter_tuples(&re, b""), vec![(0, 0)]);
-        assert_eq!(cap_iter_tuples(&re, b"\n"), vec![(0, 0)]);
-        assert_eq!(cap_iter_tuples(&re, b"\n\n"), vec![(0, 0), (1, 1)]);
-        assert_eq!(cap_iter_tuples(&re, b"\na\n"), vec![(0, 0), (1, 1)]);
+    fn match_options() {
+        let re = Regex::new(r"^\w*$").unwrap();
+        let mut opts = MatchOptions::new();
+        assert!(re.is_match_at_with_options(b("abc"), 0, &opts).unwrap());
+        opts.notbol(true);
+        assert!(!re.is_match_at_with_options(b("abc"), 0, &opts).unwrap());
+        opts.notbol(false).noteol(true);
+        assert!(re
+            .find_at_with_options(b("abc"), 0, &opts)
+            .unwrap()
+            .is_none());
+
+        let re = Regex::new(r"a*").unwrap();
+        let mut opts = MatchOptions::new();
+        let find = |subject, start, opts: &MatchOptions| {
+            re.find_at_with_options(b(subject), start, opts)
+                .unwrap()
+                .map(|m| m.as_pair())
+        };
+        assert_eq!(find("baa", 0, &opts), Some((0, 0)));
+        opts.notempty(true);
+        assert_eq!(find("baa", 0, &opts), Some((1, 3)));
+        assert_eq!(find("b", 0, &opts), None);
+        opts.notempty(false).notempty_atstart(true);
+        assert_eq!(find("bb", 0, &opts), Some((1, 1)));
+
+        let mut locs = re.capture_locations();
+        let m = re.captures_read_at_with_options(&mut locs, b("baa"), 1, &opts);
+        assert_eq!(m.unwrap().map(|m| m.as_pair()), Some((1, 3)));
+    }
+
+    #[test]
+    fn match_options_iter() {
+        let re = RegexBuilder::new()
+            .use_offset_limit(true)
+            .jit_if_available(true)
+            .build(r"a*")
+            .unwrap();
+        let mut opts = MatchOptions::new();
+        opts.notempty(true).no_jit(true);
+        let matches: Vec<_> = re
+            .find_iter_with_options(b("baacaaa"), &opts)
+            .map(|m| m.unwrap().as_pair())
+            .collect();
+        assert_eq!(matches, vec![(1, 3), (4, 7)]);
+
+        opts.offset_limit(Some(3));
+        let matches: Vec<_> = re
+            .captures_iter_with_options(b("baacaaa"), &opts)
+            .map(|caps| caps.unwrap().get(0).unwrap().as_pair())
+            .collect();
+        assert_eq!(matches, vec![(1, 3)]);
+    }
+
+    #[test]
+    fn offset_limit() {
+        let re = RegexBuilder::new()
+            .offset_limit(Some(2))
+            .build(r"a+")
+            .unwrap();
         assert_eq!(
-            cap_iter_tuples(&re, b"\na\n\n"),
-            vec![(0, 0), (1, 1), (3, 3),]
+            re.find(b("bbaaa")).unwrap().map(|m| m.as_pair()),
+            Some((2, 5))
         );
+        assert!(re.find(b("bbbaaa")).unwrap().is_none());
+
+        // A per-search limit overrides the configured one only for that
+        // search.
+        let mut opts = MatchOptions::new();
+        opts.offset_limit(Some(3));
+        let m = re.find_at_with_options(b("bbbaaa"), 0, &opts).unwrap();
+        assert_eq!(m.map(|m| m.as_pair()), Some((3, 6)));
+        assert!(re.find(b("bbbaaa")).unwrap().is_none());
     }
 
     #[test]
-    fn max_jit_stack_size_does_something() {
-        if !is_jit_available::<CodeUnitWidth8>() {
-            return;
-        }
-
-        let hundred = "\
-            ABCDEFGHIJKLMNOPQRSTUVWXY\
-            ABCDEFGHIJKLMNOPQRSTUVWXY\
-            ABCDEFGHIJKLMNOPQRSTUVWXY\
-            ABCDEFGHIJKLMNOPQRSTUVWXY\
-        ";
-        let hay = format!("{}", hundred.repeat(100));
+    fn match_options_limits() {
+        use pcre2_sys::PCRE2_ERROR_MATCHLIMIT;
 
-        // First, try a regex that checks that we can blow the JIT stack limit.
+        let hay = format!("{}b", "a".repeat(20));
         let re = RegexBuilder::new()
-            .ucp(true)
-            .jit(true)
-            .max_jit_stack_size(Some(1))
-            .build(r"((((\w{10})){100}))+")
+            .match_limit(1000)
+            .build(r"^(a+)+$")
             .unwrap();
-        let result = re.is_match(hay.as_bytes());
-        if result.is_ok() {
-            // Skip this test, since for some reason we weren't able to blow
-            // the stack limit.
-            return;
-        }
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("JIT stack limit reached"));
+        let err = re.is_match(hay.as_bytes()).unwrap_err();
+        assert_eq!(err.code(), PCRE2_ERROR_MATCHLIMIT);
 
-        // Now bump up the JIT stack limit and check that it succeeds.
+        let mut opts = MatchOptions::new();
+        opts.match_limit(Some(u32::MAX));
+        let m = re.find_at_with_options(hay.as_bytes(), 0, &opts).unwrap();
+        assert!(m.is_none());
+        // The relaxed limit only applies to searches that ask for it.
+        let err = re.is_match(hay.as_bytes()).unwrap_err();
+        assert_eq!(err.code(), PCRE2_ERROR_MATCHLIMIT);
+
+        let re = Regex::new(r"^(a+)+$").unwrap();
+        opts.match_limit(Some(1000));
+        let err = re
+            .find_at_with_options(hay.as_bytes(), 0, &opts)
+            .unwrap_err();
+        assert_eq!(err.code(), PCRE2_ERROR_MATCHLIMIT);
+        assert!(!re.is_match(hay.as_bytes()).unwrap());
+
+        // Limits don't outlive searches that fail before matching.
+        let mut opts = MatchOptions::new();
+        opts.match_limit(Some(1))
+            .deadline(Some(std::time::Instant::now()));
+        assert!(re.find_at_with_options(b("aaa"), 0, &opts).is_err());
+        assert!(re.is_match(b("aaa")).unwrap());
+
+        // Nor searches that a callout's panic unwinds through.
         let re = RegexBuilder::new()
-            .ucp(true)
-            .jit(true)
-            .max_jit_stack_size(Some(1 << 20))
-            .build(r"((((\w{10})){100}))+")
+            .callout(|block| {
+                if block.subject().ends_with(b"!") {
+                    panic!("boom");
+                }
+                CalloutResult::Continue
+            })
+            .build(r"(?C1)^(a+)+$")
+            .unwrap();
+        let mut opts = MatchOptions::new();
+        opts.match_limit(Some(1000));
+        let result = std::panic::catch_unwind(|| re.find_at_with_options(b("a!"), 0, &opts));
+        assert!(result.is_err());
+        assert!(!re.is_match(hay.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn find_partial() {
+        let re = Regex::new(r"\d{4}-\d{2}|\d+").unwrap();
+        let find = |subject, mode| {
+            re.find_partial_at(b(subject), 0, mode)
+                .unwrap()
+                .map(|m| (m.is_partial(), m.into_match().as_pair()))
+        };
+        assert_eq!(find("abc 2024-0", PartialMode::Soft), Some((false, (4, 8))));
+        assert_eq!(find("abc 2024-0", PartialMode::Hard), Some((true, (4, 10))));
+        assert_eq!(
+            find("abc 2024-01 x", PartialMode::Hard),
+            Some((false, (4, 11)))
+        );
+        assert_eq!(find("abc", PartialMode::Hard), None);
+
+        let re = Regex::new(r"(\w+)@(\w+)\.com").unwrap();
+        let mut locs = re.capture_locations();
+        let m = re
+            .captures_read_partial_at(&mut locs, b("to foo@ex.com"), 0, PartialMode::Soft)
+            .unwrap()
+            .unwrap();
+        assert!(!m.is_partial());
+        assert_eq!(locs.get(2), Some((7, 9)));
+
+        let m = re
+            .captures_read_partial_at(&mut locs, b("to foo@ex"), 0, PartialMode::Soft)
+            .unwrap()
+            .unwrap();
+        assert!(m.is_partial());
+        assert_eq!(m.into_match().as_pair(), (3, 9));
+        assert_eq!(locs.get(0), Some((3, 9)));
+        assert_eq!(locs.get(2), None);
+    }
+
+    #[test]
+    fn dfa_find() {
+        let re = Regex::new(r"a|ab|abc").unwrap();
+        assert_eq!(re.find(b("xabcd")).unwrap().unwrap().as_pair(), (1, 2));
+        assert_eq!(re.dfa_find(b("xabcd")).unwrap().unwrap().as_pair(), (1, 4));
+        assert_eq!(
+            re.dfa_find_at(b("xabcdab"), 2).unwrap().unwrap().as_pair(),
+            (5, 7)
+        );
+        assert!(re.dfa_is_match(b("ab")).unwrap());
+        assert!(!re.dfa_is_match(b("xyz")).unwrap());
+
+        // Catastrophic backtracking isn't a problem for the DFA algorithm.
+        let re = Regex::new(r"(a+)+$").unwrap();
+        let subject = format!("{}b", "a".repeat(40));
+        assert!(!re.dfa_is_match(b(&subject)).unwrap());
+
+        let re = Regex::new(r"(a)\1").unwrap();
+        let err = re.dfa_find(b("aa")).unwrap_err();
+        assert_eq!(err.code(), pcre2_sys::PCRE2_ERROR_DFA_UITEM);
+    }
+
+    #[test]
+    fn find_overlapping_iter() {
+        let find = |re: &Regex, subject| -> Vec<_> {
+            re.find_overlapping_iter(b(subject))
+                .map(|m| m.unwrap().as_pair())
+                .collect()
+        };
+        let re = Regex::new("aa").unwrap();
+        assert_eq!(find(&re, "aaaa"), vec![(0, 2), (1, 3), (2, 4)]);
+        let re = Regex::new("ATA|TAT").unwrap();
+        assert_eq!(find(&re, "GATATAC"), vec![(1, 4), (2, 5), (3, 6)]);
+        let re = Regex::new("").unwrap();
+        assert_eq!(find(&re, "ab"), vec![(0, 0), (1, 1), (2, 2)]);
+
+        let re = RegexBuilder::new().utf(true).build("..").unwrap();
+        assert_eq!(find(&re, "aé!"), vec![(0, 3), (1, 4)]);
+        // A CRLF line terminator is two characters, even in CRLF mode.
+        let re = RegexBuilder::new().crlf(true).build(r"\r?\n").unwrap();
+        assert_eq!(find(&re, "\r\n"), vec![(0, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn min_match_len() {
+        let re = Regex::new(r"\d{4}-\d{2}(-\d\d)?").unwrap();
+        assert_eq!(re.min_match_len(), 7);
+        assert_eq!(Regex::new("a*").unwrap().min_match_len(), 0);
+        let re = RegexBuilder::new().utf(true).build("ééé").unwrap();
+        assert_eq!(re.min_match_len(), 3);
+    }
+
+    #[test]
+    fn serialize() {
+        let bytes = Regex::new(r"(?<year>\d{4})-\d{2}")
+            .unwrap()
+            .serialize()
+            .unwrap();
+        assert!(!bytes.is_empty());
+        assert_ne!(bytes, Regex::new("a").unwrap().serialize().unwrap());
+    }
+
+    #[test]
+    fn deserialize() {
+        let pattern = r"(?<year>\d{4})-\d{2}";
+        let bytes = Regex::new(pattern).unwrap().serialize().unwrap();
+        let builder = RegexBuilder::new();
+        let re = unsafe { builder.deserialize(pattern, &bytes) }.unwrap();
+        assert_eq!(re.as_str(), pattern);
+        let caps = re.captures(b"on 2018-04").unwrap().unwrap();
+        assert_eq!(&caps["year"], b"2018");
+
+        // Mismatched options and truncated data are rejected.
+        let mut caseless = RegexBuilder::new();
+        caseless.caseless(true);
+        let err = unsafe { caseless.deserialize(pattern, &bytes) }.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Serialize));
+        let mut word = RegexBuilder::new();
+        word.match_word(true);
+        let err = unsafe { word.deserialize(pattern, &bytes) }.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Serialize));
+        let word_bytes = word.build(pattern).unwrap().serialize().unwrap();
+        assert!(unsafe { word.deserialize(pattern, &word_bytes) }.is_ok());
+        assert!(unsafe { builder.deserialize(pattern, &word_bytes) }.is_err());
+        let err = unsafe { builder.deserialize(pattern, &bytes[..8]) }.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Serialize));
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] ^= 0xFF;
+        assert!(unsafe { builder.deserialize(pattern, &bad_magic) }.is_err());
+
+        // ... but can fall back to compiling the pattern.
+        let re = unsafe { caseless.deserialize_or_build("A", &bytes) }.unwrap();
+        assert!(re.is_match(b"a").unwrap());
+
+        // Limits on compiling aren't skipped by loading a compiled regex.
+        let mut limited = RegexBuilder::new();
+        limited.max_pattern_length(8);
+        let err = unsafe { limited.deserialize(pattern, &bytes) }.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Serialize));
+        let err = unsafe { limited.deserialize_or_build(pattern, &bytes) }.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Compile));
+        let mut guarded = RegexBuilder::new();
+        guarded.parens_nest_limit(1);
+        assert!(unsafe { guarded.deserialize(pattern, &bytes) }.is_err());
+        guarded
+            .parens_nest_limit(10)
+            .compile_recursion_guard(|_| true);
+        assert!(unsafe { guarded.deserialize(pattern, &bytes) }.is_err());
+    }
+
+    #[test]
+    fn regex_cache() {
+        let dir = std::env::temp_dir().join(format!("pcre2-cache-test-{}", std::process::id()));
+        let cache = unsafe { RegexCache::new(&dir) };
+        let entries = || std::fs::read_dir(&dir).map_or(0, |it| it.count());
+
+        let builder = RegexBuilder::new();
+        let re = cache.get_or_build(&builder, r"\d+").unwrap();
+        assert!(re.is_match(b"42").unwrap());
+        assert_eq!(entries(), 1);
+        let re = cache.get_or_build(&builder, r"\d+").unwrap();
+        assert!(re.is_match(b"42").unwrap());
+        assert_eq!(entries(), 1);
+
+        // A different configuration gets its own entry.
+        let mut caseless = RegexBuilder::new();
+        caseless.caseless(true);
+        let re = cache.get_or_build(&caseless, "a").unwrap();
+        assert!(re.is_match(b"A").unwrap());
+        assert_eq!(entries(), 2);
+
+        // Match-time settings are applied to cached regexes instead.
+        let mut limited = RegexBuilder::new();
+        limited.match_limit(1000);
+        let pattern = String::from(r"^(a+)+$");
+        assert_eq!(builder.cache_key(&pattern), limited.cache_key(&pattern));
+        cache.get_or_build(&builder, pattern.as_str()).unwrap();
+        assert_eq!(entries(), 3);
+        let re = cache.get_or_build(&limited, pattern.as_str()).unwrap();
+        let subject = format!("{}b", "a".repeat(30));
+        assert!(re.is_match(subject.as_bytes()).is_err());
+        assert_eq!(entries(), 3);
+
+        // Builders with compile limits always compile, so the limits apply.
+        let mut guarded = RegexBuilder::new();
+        guarded.compile_recursion_guard(|depth| depth < 2);
+        assert!(cache.get_or_build(&guarded, r"\d+").is_ok());
+        assert!(cache.get_or_build(&guarded, "((((a))))").is_err());
+        let mut limited = RegexBuilder::new();
+        limited.max_pattern_length(2);
+        assert!(cache.get_or_build(&limited, r"\d+").is_err());
+        assert_eq!(entries(), 3);
+
+        // Corrupt entries are ignored and rewritten.
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            std::fs::write(entry.unwrap().path(), b"garbage").unwrap();
+        }
+        let re = cache.get_or_build(&caseless, "a").unwrap();
+        assert!(re.is_match(b"A").unwrap());
+        assert!(cache.get_or_build(&builder, "(").is_err());
+
+        cache.clear().unwrap();
+        assert_eq!(entries(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let re = RegexBuilder::new()
+            .caseless(true)
+            .build(r"\w+@example\.com")
+            .unwrap();
+        let json = serde_json::to_string(&re).unwrap();
+        let re: Regex = serde_json::from_str(&json).unwrap();
+        assert_eq!(re.as_str(), r"\w+@example\.com");
+        assert!(re.is_match(b"ME@EXAMPLE.COM").unwrap());
+
+        let re: Regex = serde_json::from_str(r#"{"pattern": "a+"}"#).unwrap();
+        assert!(!re.is_match(b"A").unwrap());
+        let builder: RegexBuilder = serde_json::from_str(r#"{"caseless": true}"#).unwrap();
+        assert!(builder.build("a+").unwrap().is_match(b"A").unwrap());
+        assert!(serde_json::from_str::<Regex>(r#"{"pattern": "("}"#).is_err());
+
+        // Options that would make matching unsafe or change the search
+        // strategy behind the caller's back can't be deserialized.
+        assert!(serde_json::from_str::<RegexBuilder>(r#"{"utf_check": false}"#).is_err());
+        assert!(serde_json::from_str::<RegexBuilder>(r#"{"jit_fast_path": true}"#).is_err());
+        assert!(serde_json::from_str::<RegexBuilder>(r#"{"version": 2}"#).is_err());
+        let re = r#"{"pattern": "a", "config": {"version": 2}}"#;
+        assert!(serde_json::from_str::<Regex>(re).is_err());
+
+        let config: crate::SerializedConfig =
+            serde_json::from_str(r#"{"version": 1, "multi_line": true}"#).unwrap();
+        assert!(config.multi_line);
+        assert!(!config.caseless);
+
+        let mut builder = RegexBuilder::new();
+        builder.dotall(true).max_pattern_length(100);
+        let json = serde_json::to_string(&builder).unwrap();
+        let builder: RegexBuilder = serde_json::from_str(&json).unwrap();
+        assert!(builder.build(".").unwrap().is_match(b"\n").unwrap());
+        assert!(builder.build("a".repeat(101)).is_err());
+
+        // Callbacks and custom tables can't be serialized.
+        let mut builder = RegexBuilder::new();
+        builder.callout(|_| CalloutResult::Continue);
+        assert!(serde_json::to_string(&builder).is_err());
+        assert!(serde_json::to_string(&builder.build("a").unwrap()).is_err());
+        let mut builder = RegexBuilder::new();
+        builder.compile_recursion_guard(|_| true);
+        assert!(serde_json::to_string(&builder).is_err());
+        let mut builder = RegexBuilder::new();
+        builder.progress(100, |_, _| {});
+        assert!(serde_json::to_string(&builder.build("a").unwrap()).is_err());
+        let mut builder = RegexBuilder::new();
+        builder.character_tables(CharacterTables::current_locale());
+        assert!(serde_json::to_string(&builder).is_err());
+        assert!(serde_json::to_string(&builder.build("a").unwrap()).is_err());
+    }
+
+    #[cfg(feature = "bstr")]
+    #[test]
+    fn bstr() {
+        use bstr::{BStr, BString};
+
+        let re = Regex::new(r"\w+").unwrap();
+        let subject = BString::from(&b"\xFFgr\xC3\xBC\xC3\x9Fe"[..]);
+        let m = re.find(&subject).unwrap().unwrap();
+        assert_eq!(m.as_bstr(), BStr::new("gr"));
+        assert!(re.is_match(subject.as_slice()).unwrap());
+        assert!(re.is_match(BStr::new("x")).unwrap());
+        assert!(format!("{:?}", m).contains(r#"text: "gr""#));
+
+        let re = RegexBuilder::new().utf(true).build(r"\S+").unwrap();
+        let caps = re.captures(BStr::new("grüße")).unwrap().unwrap();
+        assert!(format!("{:?}", caps).contains("grüße"));
+    }
+
+    #[test]
+    fn compile_options() {
+        use pcre2_sys::{PCRE2_CASELESS, PCRE2_UCP, PCRE2_UTF};
+
+        let re = RegexBuilder::new().caseless(true).build("a").unwrap();
+        assert_ne!(re.compile_options() & PCRE2_CASELESS, 0);
+        assert!(!re.is_utf());
+
+        let re = Regex::new("(*UTF)(*UCP)a").unwrap();
+        assert!(re.is_utf());
+        assert_ne!(re.compile_options() & PCRE2_UCP, 0);
+        assert_eq!(re.compile_arg_options() & (PCRE2_UTF | PCRE2_UCP), 0);
+    }
+
+    #[test]
+    fn newline_conventions() {
+        let re = Regex::new("a").unwrap();
+        assert_eq!(re.newline(), Some(Newline::Lf));
+        assert_eq!(re.backslash_r(), Some(BsrConvention::Unicode));
+        let re = RegexBuilder::new().crlf(true).build("a").unwrap();
+        assert_eq!(re.newline(), Some(Newline::AnyCrLf));
+        let re = Regex::new("(*NUL)(*BSR_ANYCRLF)a").unwrap();
+        assert_eq!(re.newline(), Some(Newline::Nul));
+        assert_eq!(re.backslash_r(), Some(BsrConvention::AnyCrLf));
+    }
+
+    #[test]
+    fn is_jit() {
+        assert!(!RegexBuilder::new().build("a").unwrap().is_jit());
+        let re = RegexBuilder::new()
+            .jit_if_available(true)
+            .build("a")
+            .unwrap();
+        assert_eq!(re.is_jit(), is_jit_available::<CodeUnitWidth8>());
+    }
+
+    #[test]
+    fn auto_jit() {
+        let re = RegexBuilder::new()
+            .auto_jit(Some(3))
+            .build(r"(\w+)@(\w+)")
+            .unwrap();
+        let subject = b("mail foo@bar");
+        let mut locs = re.capture_locations();
+        assert!(re.captures_read(&mut locs, subject).unwrap().is_some());
+        assert!(re.is_match(subject).unwrap());
+        assert!(!re.is_jit());
+        // The third search JIT compiles the regex, if possible.
+        assert!(re.is_match(subject).unwrap());
+        assert_eq!(re.is_jit(), is_jit_available::<CodeUnitWidth8>());
+        assert_eq!(re.clone().is_jit(), re.is_jit());
+        assert_eq!(re.find(subject).unwrap().unwrap().as_pair(), (5, 12));
+
+        // Capture locations found before the regex was JIT compiled can
+        // still be used for replacements.
+        let mut dst = vec![];
+        let opts = SubstituteOptions::new();
+        re.replace_matched_into(&mut locs, subject, b("$2"), &opts, &mut dst)
+            .unwrap();
+        assert_eq!(dst, b("mail bar"));
+    }
+
+    #[test]
+    fn jit_compile_later() {
+        let mut re = Regex::new(r"\w+").unwrap();
+        assert!(re.is_match(b("abc")).unwrap());
+        let clone = re.clone();
+        if !is_jit_available::<CodeUnitWidth8>() {
+            assert!(re.jit_compile().is_err());
+            assert!(!re.is_jit());
+            return;
+        }
+        re.jit_compile().unwrap();
+        assert!(re.is_jit());
+        assert!(!clone.is_jit());
+        assert_eq!(re.find(b("  abc")).unwrap().unwrap().as_pair(), (2, 5));
+        // JIT compiling again does nothing.
+        re.jit_compile().unwrap();
+        assert!(re.is_jit());
+    }
+
+    #[test]
+    fn first_code_unit() {
+        let re = Regex::new("(cat|cow)s?").unwrap();
+        assert_eq!(re.first_code_unit(), FirstCodeUnit::Fixed(u32::from(b'c')));
+        let re = RegexBuilder::new()
+            .multi_line(true)
+            .build("^ab|^x")
+            .unwrap();
+        assert_eq!(re.first_code_unit(), FirstCodeUnit::StartOfLine);
+        let re = Regex::new(r"\d+").unwrap();
+        assert_eq!(re.first_code_unit(), FirstCodeUnit::Unknown);
+        assert_eq!(re.required_code_unit(), None);
+
+        // Whether the unit is matched caselessly isn't reported.
+        let c = FirstCodeUnit::Fixed(u32::from(b'c'));
+        let re = RegexBuilder::new().caseless(true).build("cat").unwrap();
+        assert_eq!(re.first_code_unit(), c);
+        assert!(re.is_match(b("Cat")).unwrap());
+        let re = Regex::new("(?i:c)at").unwrap();
+        assert_eq!(re.first_code_unit(), c);
+        let re = Regex::new("(?i)1a").unwrap();
+        assert_eq!(re.first_code_unit(), FirstCodeUnit::Fixed(u32::from(b'1')));
+
+        let re = Regex::new(r"a\d+z").unwrap();
+        assert_eq!(re.required_code_unit(), Some(u32::from(b'z')));
+    }
+
+    #[test]
+    fn compiled_size() {
+        let small = RegexBuilder::new().build("a").unwrap();
+        let large = RegexBuilder::new()
+            .build(r"(\w+)@(\w+)\.(com|org|net){1,20}")
+            .unwrap();
+        assert!(small.compiled_size() > 0);
+        assert!(large.compiled_size() > small.compiled_size());
+        assert_eq!(small.jit_size(), 0);
+        if is_jit_available::<CodeUnitWidth8>() {
+            let re = RegexBuilder::new().jit(true).build("a").unwrap();
+            assert!(re.jit_size() > 0);
+            assert_eq!(re.compiled_size(), small.compiled_size() + re.jit_size());
+        }
+    }
+
+    #[test]
+    fn backrefs() {
+        let re = Regex::new(r"(a)(b)(c)").unwrap();
+        assert_eq!(re.max_backref(), 0);
+        assert!(!re.has_backrefs());
+        let re = Regex::new(r"(a)(b)(c)\2").unwrap();
+        assert_eq!(re.max_backref(), 2);
+        assert!(re.has_backrefs());
+        let re = Regex::new(r"(a)(?<x>b)\k<x>").unwrap();
+        assert_eq!(re.max_backref(), 2);
+    }
+
+    #[test]
+    fn max_lookbehind() {
+        assert_eq!(Regex::new("abc").unwrap().max_lookbehind(), 0);
+        assert_eq!(Regex::new("(?<=foo)bar").unwrap().max_lookbehind(), 3);
+        let re = Regex::new("(?<=ab|xyzw)c|(?<!q)d").unwrap();
+        assert_eq!(re.max_lookbehind(), 4);
+    }
+
+    #[test]
+    fn dfa_find_shortest() {
+        let re = Regex::new(r"\d+(\.\d+)?").unwrap();
+        let subject = b("pi is 3.14");
+        let m = re.dfa_find_shortest(subject).unwrap().unwrap();
+        assert_eq!(m.as_pair(), (6, 7));
+        assert_eq!(re.dfa_find(subject).unwrap().unwrap().as_pair(), (6, 10));
+        assert!(re.dfa_find_shortest_at(subject, 10).unwrap().is_none());
+    }
+
+    #[test]
+    fn dfa_workspace() {
+        let re = Regex::new(r"\d+(\.\d+)?").unwrap();
+        let mut ws = re.dfa_workspace();
+        assert_eq!(ws.size(), 1000);
+        let subject = b("pi is 3.14, e is 2.71");
+        let m = re.dfa_read(&mut ws, subject).unwrap().unwrap();
+        assert_eq!(m.as_pair(), (6, 10));
+        let m = re.dfa_read_at(&mut ws, subject, m.end()).unwrap().unwrap();
+        assert_eq!(m.as_pair(), (17, 21));
+        assert!(re.dfa_read_at(&mut ws, subject, m.end()).unwrap().is_none());
+
+        let mut ws = re.dfa_workspace_with_size(10);
+        let err = re.dfa_read(&mut ws, subject).unwrap_err();
+        assert_eq!(err.code(), pcre2_sys::PCRE2_ERROR_DFA_WSSIZE);
+    }
+
+    #[test]
+    #[should_panic(expected = "DFA workspace was created by a different regex")]
+    fn dfa_workspace_other_regex() {
+        let re = Regex::new(r"(a)(b)(c)").unwrap();
+        let mut ws = Regex::new(r"a").unwrap().dfa_workspace();
+        let _ = re.dfa_read(&mut ws, b("abc"));
+    }
+
+    #[test]
+    fn dfa_restart() {
+        let re = Regex::new(r"\d?\d(jan|feb|mar)\d\d").unwrap();
+        let mut ws = re.dfa_workspace();
+        let m = re
+            .dfa_read_partial_at(&mut ws, b("the date is 23ja"), 0, PartialMode::Hard)
+            .unwrap()
+            .unwrap();
+        assert!(m.is_partial());
+        assert_eq!(m.into_match().as_pair(), (12, 16));
+        assert!(ws.is_restartable());
+
+        let m = re
+            .dfa_restart(&mut ws, b("n0"), PartialMode::Hard)
+            .unwrap()
+            .unwrap();
+        assert!(m.is_partial());
+        let m = re
+            .dfa_restart(&mut ws, b("5 and on"), PartialMode::Hard)
+            .unwrap()
+            .unwrap();
+        assert!(!m.is_partial());
+        assert_eq!(m.into_match().as_bytes(), b("5"));
+        assert!(!ws.is_restartable());
+
+        re.dfa_read_partial_at(&mut ws, b("on 1fe"), 0, PartialMode::Soft)
+            .unwrap()
+            .unwrap();
+        assert!(re
+            .dfa_restart(&mut ws, b("x"), PartialMode::Soft)
+            .unwrap()
+            .is_none());
+        assert!(!ws.is_restartable());
+    }
+
+    #[test]
+    fn regex_set() {
+        let set = RegexSet::new([r"\d+", "foo", r"^\w+$"]).unwrap();
+        assert_eq!(set.len(), 3);
+        assert!(set.is_match(b("foo")).unwrap());
+        assert!(!set.is_match(b("bar baz")).unwrap());
+
+        let matches = set.matches(b("foo 42")).unwrap();
+        assert!(matches.matched_any());
+        assert!(matches.matched(0));
+        assert!(!matches.matched(2));
+        assert_eq!(matches.iter().collect::<Vec<_>>(), vec![0, 1]);
+
+        assert!(!RegexSet::empty().is_match(b("foo")).unwrap());
+        assert!(RegexSet::new(["ok", "(unclosed"]).is_err());
+    }
+
+    #[test]
+    fn regex_set_find() {
+        let set = RegexSet::new([r"\d+", r"[a-z]+", r"[a-z]+\d"]).unwrap();
+        let find = |subject, start| {
+            set.find_at(b(subject), start)
+                .unwrap()
+                .map(|(i, m)| (i, m.as_pair()))
+        };
+        assert_eq!(find("foo1 42", 0), Some((1, (0, 3))));
+        assert_eq!(find("foo1 42", 3), Some((0, (3, 4))));
+        assert_eq!(find("  bar", 0), Some((1, (2, 5))));
+        assert_eq!(find("  ", 0), None);
+    }
+
+    #[test]
+    fn regex_set_captures() {
+        let set = RegexSet::new([r"(?<num>\d+)", r"(\w+)=(\w+)"]).unwrap();
+        let (i, caps) = set.captures(b("x key=val 42")).unwrap().unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(caps.get(1).unwrap().as_bytes(), b("key"));
+        assert_eq!(caps.get(2).unwrap().as_bytes(), b("val"));
+
+        let (i, caps) = set.captures_at(b("x key=val 42"), 9).unwrap().unwrap();
+        assert_eq!(i, 0);
+        assert_eq!(&caps["num"], b("42"));
+
+        let mut locs = set.capture_locations();
+        let (i, m) = set
+            .captures_read_at(&mut locs, b("a=b"), 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!((i, m.as_pair()), (1, (0, 3)));
+        assert_eq!(locs.pattern(), Some(1));
+        assert_eq!(locs.get(2), Some((2, 3)));
+        assert!(set
+            .captures_read_at(&mut locs, b("!"), 0)
+            .unwrap()
+            .is_none());
+        assert_eq!(locs.get(0), None);
+    }
+
+    #[test]
+    fn lexer() {
+        let set = RegexSet::new([r"\s+", r"\d+", r"[a-z]\w*", r"[-+*/=]", r"\s*"]).unwrap();
+        let tokens = set
+            .lexer(b("x = 4*y1"))
+            .map(|t| t.map(|(i, m)| (i, m.as_bytes())))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let expected: Vec<(usize, &[u8])> = vec![
+            (2, b("x")),
+            (0, b(" ")),
+            (3, b("=")),
+            (0, b(" ")),
+            (1, b("4")),
+            (3, b("*")),
+            (2, b("y1")),
+        ];
+        assert_eq!(tokens, expected);
+
+        let mut lexer = set.lexer(b("1 ?"));
+        assert_eq!(lexer.next().unwrap().unwrap().0, 1);
+        assert_eq!(lexer.next().unwrap().unwrap().0, 0);
+        match lexer.next().unwrap() {
+            Err(LexError::NoMatch(offset)) => assert_eq!(offset, 2),
+            result => panic!("expected no match, got {:?}", result),
+        }
+        assert_eq!(lexer.position(), 2);
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn regex_set_builder() {
+        let set = RegexSetBuilder::new()
+            .caseless(true)
+            .pattern("error")
+            .pattern_with_options("WARN", PatternOptions::new().caseless(false))
+            .pattern(r"\bdebug\b")
+            .build()
+            .unwrap();
+        let matched = |subject| set.matches(b(subject)).unwrap().iter().collect::<Vec<_>>();
+        assert_eq!(matched("ERROR: disk full"), vec![0]);
+        assert_eq!(matched("warn: low memory"), Vec::<usize>::new());
+        assert_eq!(matched("WARN and Debug"), vec![1, 2]);
+    }
+
+    #[test]
+    fn stream_matcher() {
+        let re = Regex::new(r"(?<=@)\w+|\d+").unwrap();
+        let mut stream = re.stream_matcher();
+        let mut matches = vec![];
+        for chunk in ["ab 12", "3 x@fo", "o bar@", "baz 45"] {
+            stream.push(b(chunk));
+            while let Some(m) = stream.next_match().unwrap() {
+                matches.push((m.start(), m.end(), m.into_bytes()));
+            }
+        }
+        // The trailing `45` could still be extended by more input.
+        assert_eq!(matches.len(), 3);
+        assert!(stream.retained_offset() >= 20);
+        stream.finish();
+        while let Some(m) = stream.next_match().unwrap() {
+            matches.push((m.start(), m.end(), m.into_bytes()));
+        }
+        assert_eq!(
+            matches,
+            vec![
+                (3, 6, b"123".to_vec()),
+                (9, 12, b"foo".to_vec()),
+                (17, 20, b"baz".to_vec()),
+                (21, 23, b"45".to_vec()),
+            ]
+        );
+
+        let re = Regex::new(r"\Aab|^cd").unwrap();
+        let mut stream = re.stream_matcher();
+        stream.push(b("xa"));
+        stream.push(b("b c"));
+        stream.push(b("d"));
+        stream.finish();
+        assert!(stream.next_match().unwrap().is_none());
+    }
+
+    #[test]
+    fn find_chunks_iter() {
+        let mut subject = String::from("ab 123 x@foo\n<a long tag> cd\n");
+        for i in 0..20 {
+            subject.push_str(&format!("word{} x@y{} <t{}>\n", i, i, i));
+        }
+        let subject = subject.as_bytes();
+        let patterns = [
+            r"(?<=@)\w+|\d+",
+            r"(?<=word1\d )\S+",
+            r"\b\w",
+            r"(?m:^)",
+            r"x*",
+            r"\Aab|^cd",
+            r"<[^>]*>",
+            r"(?s)<a.*",
+        ];
+        for pattern in patterns {
+            let re = Regex::new(pattern).unwrap();
+            let expected = find_iter_tuples(&re, subject);
+            for size in [1, 5, 64, 100, subject.len()] {
+                let found: Vec<(usize, usize)> = re
+                    .find_chunks_iter(subject.chunks(size))
+                    .map(|m| {
+                        let m = m.unwrap();
+                        assert_eq!(m.as_bytes(), &subject[m.start()..m.end()]);
+                        (m.start(), m.end())
+                    })
+                    .collect();
+                assert_eq!(found, expected, "pattern {:?}, size {}", pattern, size);
+            }
+        }
+
+        let re = Regex::new(r"(?:)").unwrap();
+        let found: Vec<_> = re.find_chunks_iter(vec![]).collect();
+        assert_eq!(found.len(), 1);
+        let chunks: [&[u8]; 4] = [b"", b"a", b"", b"b"];
+        assert_eq!(re.find_chunks_iter(chunks).count(), 3);
+    }
+
+    #[test]
+    fn scanner() {
+        let re = Regex::new(r"<[^>]*>").unwrap();
+        let mut scanner = re.scanner();
+        let pairs = |matches: Vec<super::StreamMatch>| -> Vec<(usize, usize)> {
+            matches.iter().map(|m| (m.start(), m.end())).collect()
+        };
+        assert_eq!(pairs(scanner.feed(b("a <b> <c")).unwrap()), vec![(2, 5)]);
+        assert_eq!(pairs(scanner.feed(b("d")).unwrap()), vec![]);
+        assert_eq!(scanner.retained_offset(), 5);
+        assert_eq!(pairs(scanner.feed(b("e> <")).unwrap()), vec![(6, 11)]);
+        assert_eq!(pairs(scanner.finish().unwrap()), vec![]);
+
+        let re = Regex::new(r"\d+").unwrap();
+        let mut scanner = re.scanner();
+        assert_eq!(pairs(scanner.feed(b("1 23")).unwrap()), vec![(0, 1)]);
+        assert_eq!(pairs(scanner.finish().unwrap()), vec![(2, 4)]);
+    }
+
+    #[test]
+    fn find_read_iter() {
+        use std::io::Read;
+
+        let re = Regex::new(r"\w+@\w+").unwrap();
+        let rdr = b("to: foo")
+            .chain(b("@exa"))
+            .chain(b("mple, "))
+            .chain(b("b@c"));
+        let matches: Vec<(usize, usize, Vec<u8>)> = re
+            .find_read_iter(rdr)
+            .map(|m| m.unwrap())
+            .map(|m| (m.start(), m.end(), m.into_bytes()))
+            .collect();
+        assert_eq!(
+            matches,
+            vec![(4, 15, b"foo@example".to_vec()), (17, 20, b"b@c".to_vec())]
+        );
+    }
+
+    #[test]
+    fn split_read() {
+        use std::io::Read;
+
+        let re = Regex::new(r"\r?\n--+\r?\n").unwrap();
+        let rdr = b("a\n-").chain(b("--\r\nb\n--")).chain(b("\n\n---\n"));
+        let records: Vec<Vec<u8>> = re.split_read(rdr).map(|r| r.unwrap()).collect();
+        assert_eq!(
+            records,
+            vec![b"a".to_vec(), b"b".to_vec(), b"".to_vec(), b"".to_vec()]
+        );
+
+        let records: Vec<Vec<u8>> = re.split_read(b("")).map(|r| r.unwrap()).collect();
+        assert_eq!(records, vec![b"".to_vec()]);
+    }
+
+    #[test]
+    fn line_matches() {
+        let re = Regex::new(r"\d+$").unwrap();
+        let rdr = b("a 1 2\nno digits\n3 x\r\n\n45");
+        let lines: Vec<_> = re
+            .line_matches(rdr)
+            .map(|line| line.unwrap())
+            .map(|line| {
+                let matches: Vec<_> = line.matches().map(|m| m.as_pair()).collect();
+                (line.line_number(), line.into_line(), matches)
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (1, b"a 1 2".to_vec(), vec![(4, 5)]),
+                (5, b"45".to_vec(), vec![(0, 2)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn match_anchored() {
+        let re = Regex::new(r"\d+").unwrap();
+        let mut opts = MatchOptions::new();
+        opts.anchored(true);
+        let find = |subject, start| {
+            re.find_at_with_options(b(subject), start, &opts)
+                .unwrap()
+                .map(|m| m.as_pair())
+        };
+        assert_eq!(find("a12b34", 1), Some((1, 3)));
+        assert_eq!(find("a12b34", 0), None);
+        assert_eq!(find("a12b34", 3), None);
+        assert_eq!(find("a12b34", 4), Some((4, 6)));
+        assert!(!re.is_match_at_with_options(b("a12"), 0, &opts).unwrap());
+    }
+
+    #[test]
+    fn end_anchored() {
+        let re = RegexBuilder::new()
+            .end_anchored(true)
+            .build(r"ab|cd")
+            .unwrap();
+        assert!(re.is_match(b("xxcd")).unwrap());
+        assert!(re.is_match(b("xxab")).unwrap());
+        assert!(!re.is_match(b("abxx")).unwrap());
+        assert_eq!(re.find(b("ab cd")).unwrap().unwrap().as_pair(), (3, 5));
+
+        let re = Regex::new(r"ab|cd").unwrap();
+        let mut opts = MatchOptions::new();
+        opts.end_anchored(true);
+        assert!(!re.is_match_at_with_options(b("abxx"), 0, &opts).unwrap());
+        assert!(re.is_match_at_with_options(b("xxab"), 0, &opts).unwrap());
+    }
+
+    #[test]
+    fn find_in() {
+        let re = Regex::new(r"\bfoo\w*").unwrap();
+        let subject = b("foo bar foobar xfoo food");
+        let find = |range| re.find_in(subject, range).unwrap().map(|m| m.as_pair());
+        assert_eq!(find(0..3), Some((0, 3)));
+        // `\b` sees the text before the range, unlike when slicing.
+        assert_eq!(find(16..19), None);
+        assert_eq!(
+            re.find(&subject[16..19]).unwrap().map(|m| m.as_pair()),
+            Some((0, 3))
+        );
+        // Matches that straddle the end of the range aren't reported.
+        assert_eq!(find(1..9), None);
+        assert_eq!(find(1..11), None);
+        assert_eq!(find(1..14), Some((8, 14)));
+        assert_eq!(find(9..19), None);
+        assert!(re.is_match_in(subject, 20..24).unwrap());
+        assert!(!re.is_match_in(subject, 9..19).unwrap());
+        // `\b` and `$` see the text after the range, unlike when slicing.
+        let re_end = Regex::new(r"\bfoo\b").unwrap();
+        assert!(!re_end.is_match_in(subject, 8..11).unwrap());
+        assert!(!re_end.is_match_in(b("foobar"), 0..3).unwrap());
+        assert!(re_end.is_match_in(b("foo bar"), 0..3).unwrap());
+        let re_dollar = Regex::new(r"a+$").unwrap();
+        assert!(!re_dollar.is_match_in(b("aab"), 0..2).unwrap());
+
+        // The offset limit is used when the regex allows it, with the same
+        // results.
+        let re_limited = RegexBuilder::new()
+            .use_offset_limit(true)
+            .build(r"\bfoo\w*")
+            .unwrap();
+        for range in [0..3, 16..19, 1..11, 1..14, 20..24] {
+            let find = |re: &Regex| {
+                re.find_in(subject, range.clone())
+                    .unwrap()
+                    .map(|m| m.as_pair())
+            };
+            assert_eq!(find(&re_limited), find(&re));
+        }
+
+        let mut locs = re.capture_locations();
+        let m = re.captures_read_in(&mut locs, subject, 4..12).unwrap();
+        assert!(m.is_none());
+        let m = re.captures_read_in(&mut locs, subject, 4..14).unwrap();
+        assert_eq!(m.map(|m| m.as_pair()), Some((8, 14)));
+        assert_eq!(locs.get(0), Some((8, 14)));
+    }
+
+    #[test]
+    fn split_inclusive() {
+        fn split(re: &Regex, subject: &str) -> Vec<(String, Option<String>)> {
+            let s = |x: &[u8]| String::from_utf8(x.to_vec()).unwrap();
+            re.split_inclusive(b(subject))
+                .map(|r| r.unwrap())
+                .map(|(field, m)| (s(field), m.map(|m| s(m.as_bytes()))))
+                .collect()
+        }
+        let field = |f: &str, d: Option<&str>| (f.to_string(), d.map(|d| d.to_string()));
+
+        let re = Regex::new(r"\s*[,;]\s*").unwrap();
+        assert_eq!(
+            split(&re, "a, b;c ,"),
+            vec![
+                field("a", Some(", ")),
+                field("b", Some(";")),
+                field("c", Some(" ,")),
+                field("", None),
+            ]
+        );
+        assert_eq!(split(&re, "abc"), vec![field("abc", None)]);
+        assert_eq!(split(&re, ""), vec![field("", None)]);
+
+        let re = Regex::new(r"x*").unwrap();
+        assert_eq!(
+            split(&re, "axb"),
+            vec![
+                field("", Some("")),
+                field("a", Some("x")),
+                field("b", Some("")),
+                field("", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn split_captures() {
+        let re = Regex::new(r"\s*(?:([,;])|(-))\s*").unwrap();
+        let mut fields = vec![];
+        for result in re.split_captures(b("a, b - c")) {
+            let (field, caps) = result.unwrap();
+            fields.push(field);
+            if let Some(caps) = caps {
+                fields.push(caps.get(1).map_or(b("?"), |m| m.as_bytes()));
+                fields.push(caps.get(2).map_or(b("?"), |m| m.as_bytes()));
+            }
+        }
+        assert_eq!(
+            fields,
+            vec![b("a"), b(","), b("?"), b("b"), b("?"), b("-"), b("c")]
+        );
+    }
+
+    #[test]
+    fn empty_match_policy() {
+        let pcre2 = |pattern: &str| {
+            RegexBuilder::new()
+                .empty_match_policy(EmptyMatchPolicy::Pcre2Compatible)
+                .build(pattern)
+                .unwrap()
+        };
+
+        let re = Regex::new(r"a*").unwrap();
+        assert_eq!(find_iter_tuples(&re, b"baaa"), vec![(0, 0), (1, 4)]);
+        let re = pcre2(r"a*");
+        assert_eq!(find_iter_tuples(&re, b"baaa"), vec![(0, 0), (1, 4), (4, 4)]);
+        assert_eq!(cap_iter_tuples(&re, b"baaa"), vec![(0, 0), (1, 4), (4, 4)]);
+        let replaced = re.replace_all(b("baaa"), |_: &Captures| b("-")).unwrap();
+        assert_eq!(&*replaced, b("-b--"));
+        // PCRE2 itself uses the same rules for global substitutions.
+        assert_eq!(&*re.replace_all(b("baaa"), b("-")).unwrap(), b("-b--"));
+
+        // A non-empty match is preferred at the position of an empty match.
+        let re = pcre2(r"(?=a)|a");
+        assert_eq!(
+            find_iter_tuples(&re, b"aa"),
+            vec![(0, 0), (0, 1), (1, 1), (1, 2)]
+        );
+
+        // After an empty match, skip whole characters and line terminators.
+        let re = pcre2(r"x?");
+        assert_eq!(
+            find_iter_tuples(&re, "é".as_bytes()),
+            vec![(0, 0), (1, 1), (2, 2)]
+        );
+        let re = RegexBuilder::new()
+            .empty_match_policy(EmptyMatchPolicy::Pcre2Compatible)
+            .utf(true)
+            .crlf(true)
+            .build(r"x?")
+            .unwrap();
+        assert_eq!(find_iter_tuples(&re, "é".as_bytes()), vec![(0, 0), (2, 2)]);
+        assert_eq!(find_iter_tuples(&re, b"\r\n"), vec![(0, 0), (2, 2)]);
+        // The same goes for settings made in the pattern itself.
+        let re = pcre2(r"(*UTF)(*CRLF)x?");
+        assert_eq!(find_iter_tuples(&re, "é".as_bytes()), vec![(0, 0), (2, 2)]);
+        assert_eq!(find_iter_tuples(&re, b"\r\n"), vec![(0, 0), (2, 2)]);
+    }
+
+    #[test]
+    fn captures_iter_empty() {
+        let re = Regex::new(r"(?m:^)").unwrap();
+        assert_eq!(cap_iter_tuples(&re, b""), vec![(0, 0)]);
+        assert_eq!(cap_iter_tuples(&re, b"\n"), vec![(0, 0)]);
+        assert_eq!(cap_iter_tuples(&re, b"\n\n"), vec![(0, 0), (1, 1)]);
+        assert_eq!(cap_iter_tuples(&re, b"\na\n"), vec![(0, 0), (1, 1)]);
+        assert_eq!(
+            cap_iter_tuples(&re, b"\na\n\n"),
+            vec![(0, 0), (1, 1), (3, 3),]
+        );
+    }
+
+    #[test]
+    fn replace() {
+        let re = Regex::new(r"(?<first>\w+)\s+(\w+)").unwrap();
+        let subject = b("hello world foo bar");
+        assert_eq!(
+            re.replace(subject, b("$2 ${first}")).unwrap(),
+            b("world hello foo bar")
+        );
+        assert_eq!(
+            re.replace_all(subject, b("$2 ${first}")).unwrap(),
+            b("world hello bar foo")
+        );
+        assert_eq!(re.replace_all(subject, b("$$")).unwrap(), b("$ $"));
+
+        // No match should return the subject without copying it.
+        let re = Regex::new(r"z").unwrap();
+        assert!(matches!(
+            re.replace_all(subject, b("y")).unwrap(),
+            Cow::Borrowed(_)
+        ));
+
+        // Unknown groups are an error.
+        let re = Regex::new(r"(\w+)").unwrap();
+        assert!(re.replace(subject, b("$2")).is_err());
+    }
+
+    #[test]
+    fn replace_closure() {
+        let re = Regex::new(r"(?<key>\w+)=(?<val>\w+)").unwrap();
+        let subject = b("a=x b=y");
+        let got = re
+            .replace_all(subject, |caps: &Captures| {
+                let mut rep = caps["val"].to_ascii_uppercase();
+                rep.extend_from_slice(&caps["key"]);
+                rep
+            })
+            .unwrap();
+        assert_eq!(got, b("Xa Yb"));
+
+        let mut count = 0;
+        let mut rep = |_: &Captures| {
+            count += 1;
+            count.to_string()
+        };
+        assert_eq!(re.replace_all(subject, rep.by_ref()).unwrap(), b("1 2"));
+        assert_eq!(re.replace(subject, rep.by_ref()).unwrap(), b("3 b=y"));
+    }
+
+    #[test]
+    fn replacen() {
+        let re = Regex::new(r"(\w)(\w)").unwrap();
+        let subject = b("ab cd ef gh");
+        assert_eq!(
+            re.replacen(subject, 0, b("$2$1")).unwrap(),
+            b("ba dc fe hg")
+        );
+        assert_eq!(
+            re.replacen(subject, 1, b("$2$1")).unwrap(),
+            b("ba cd ef gh")
+        );
+        assert_eq!(
+            re.replacen(subject, 3, b("$2$1")).unwrap(),
+            b("ba dc fe gh")
+        );
+        assert!(re.replacen(subject, 2, b("$3")).is_err());
+
+        let rep = |caps: &Captures| caps[1].to_ascii_uppercase();
+        assert_eq!(re.replacen(subject, 2, rep).unwrap(), b("A C ef gh"));
+    }
+
+    #[test]
+    fn replace_empty_matches() {
+        let cases = [
+            (r"a*", "baaa", EmptyMatchPolicy::RegexCompatible, "XbX"),
+            (r"a*", "baaa", EmptyMatchPolicy::Pcre2Compatible, "XbXX"),
+            (
+                r"(?=a)|a",
+                "aaa",
+                EmptyMatchPolicy::RegexCompatible,
+                "XaXaXa",
+            ),
+            (
+                r"(?=a)|a",
+                "aaa",
+                EmptyMatchPolicy::Pcre2Compatible,
+                "XXXXXX",
+            ),
+        ];
+        for &(pattern, subject, policy, expected) in &cases {
+            let re = RegexBuilder::new()
+                .empty_match_policy(policy)
+                .build(pattern)
+                .unwrap();
+            let subject = b(subject);
+            assert_eq!(re.replace_all(subject, b("X")).unwrap(), b(expected));
+            // Every kind of replacer and every limit finds the same matches.
+            for limit in 0..4 {
+                let template = re.replacen(subject, limit, b("X")).unwrap();
+                let literal = re.replacen(subject, limit, NoExpand(b("X"))).unwrap();
+                let closure = re.replacen(subject, limit, |_: &Captures| b("X")).unwrap();
+                assert_eq!(template, literal);
+                assert_eq!(template, closure);
+                if limit == 0 {
+                    assert_eq!(template, b(expected));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn replace_extended() {
+        let re = Regex::new(r"(\w+)(?:-(\d+))?").unwrap();
+        let subject = b("foo-1 bar");
+        let rep = Extended(b(r"\U$1\E${2:-0}${2:+!:?}"));
+        assert_eq!(
+            re.replace_all(subject, rep.clone()).unwrap(),
+            b("FOO1! BAR0?")
+        );
+        assert_eq!(re.replacen(subject, 2, rep).unwrap(), b("FOO1! BAR0?"));
+
+        // Without the extended syntax, case forcing isn't supported.
+        assert_eq!(
+            re.replace_all(subject, b(r"\U$1")).unwrap(),
+            b(r"\Ufoo \Ubar")
+        );
+    }
+
+    #[test]
+    fn replace_into() {
+        let re = Regex::new(r"\d").unwrap();
+        let mut dst = b("> ").to_vec();
+        assert_eq!(re.replace_all_into(b("a1b2"), b("#"), &mut dst).unwrap(), 2);
+        assert_eq!(re.replace_into(b("c3d4"), b("#"), &mut dst).unwrap(), 1);
+        assert_eq!(re.replacen_into(b("e5f6"), 2, b("#"), &mut dst).unwrap(), 2);
+        assert_eq!(re.replace_all_into(b("xyz"), b("#"), &mut dst).unwrap(), 0);
+        let rep = |caps: &Captures| caps[0].repeat(2);
+        assert_eq!(re.replace_all_into(b("7"), rep, &mut dst).unwrap(), 1);
+        assert_eq!(dst, b("> a#b#c#d4e#f#xyz77"));
+
+        // On error, nothing is appended, even if some matches were replaced.
+        let re = Regex::new(r"(\d)|x").unwrap();
+        assert!(re.replace_all_into(b("1x"), b("$1"), &mut dst).is_err());
+        assert_eq!(dst, b("> a#b#c#d4e#f#xyz77"));
+    }
+
+    #[test]
+    fn expand() {
+        let re = Regex::new(r"(?<first>\w+)\s+(\w+)").unwrap();
+        let caps = re.captures(b("hello world")).unwrap().unwrap();
+        let mut dst = vec![];
+        caps.expand(b("$2 ${first} $$ ${1}x $1x $9 $ ${2"), &mut dst);
+        assert_eq!(dst, b("world hello $ hellox   $ ${2"));
+    }
+
+    #[test]
+    fn replace_with_options() {
+        let re = Regex::new(r"(\d)|(x)").unwrap();
+        let subject = b("a1b2cx");
+        let mut opts = SubstituteOptions::new();
+        assert_eq!(
+            &*re.replace_with_options(subject, b("[$1]"), &opts).unwrap(),
+            b("a[1]b2cx")
+        );
+        // Group 1 is unset for the match of `x`.
+        opts.global(true);
+        let err = re
+            .replace_with_options(subject, b("[$1]"), &opts)
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Substitute));
+        opts.unset_empty(true);
+        assert_eq!(
+            &*re.replace_with_options(subject, b("[$1]"), &opts).unwrap(),
+            b("a[1]b[2]c[]")
+        );
+        opts.unknown_unset(true);
+        assert_eq!(
+            &*re.replace_with_options(subject, b("[$1$9]"), &opts)
+                .unwrap(),
+            b("a[1]b[2]c[]")
+        );
+        opts.replacement_only(true);
+        assert_eq!(
+            &*re.replace_with_options(subject, b("[$1]"), &opts).unwrap(),
+            b("[1][2][]")
+        );
+        assert!(re
+            .replace_with_options(b("abc"), b("[$1]"), &opts)
+            .unwrap()
+            .is_empty());
+        opts.replacement_only(false).literal(true);
+        assert_eq!(
+            &*re.replace_with_options(subject, b("$1"), &opts).unwrap(),
+            b("a$1b$1c$1")
+        );
+
+        let mut dst = b("> ").to_vec();
+        let count = re.replace_with_options_into(subject, b("-"), &opts, &mut dst);
+        assert_eq!(count.unwrap(), 3);
+        assert_eq!(dst, b("> a-b-c-"));
+    }
+
+    #[test]
+    fn replace_matched() {
+        let re = Regex::new(r"(\w+)@(\w+)").unwrap();
+        let subject = b("mail foo@bar and baz@quux");
+        let mut locs = re.capture_locations();
+        let mut opts = SubstituteOptions::new();
+        let replace = |locs: &mut _, subject, rep, opts: &_| {
+            let mut dst = vec![];
+            re.replace_matched_into(locs, subject, b(rep), opts, &mut dst)
+                .map(|count| (count, String::from_utf8(dst).unwrap()))
+        };
+
+        // Nothing has been matched yet.
+        let got = replace(&mut locs, subject, "$2", &opts).unwrap();
+        assert_eq!(got, (0, "mail foo@bar and baz@quux".to_string()));
+
+        assert!(re.captures_read(&mut locs, subject).unwrap().is_some());
+        let got = replace(&mut locs, subject, "$2", &opts).unwrap();
+        assert_eq!(got, (1, "mail bar and baz@quux".to_string()));
+
+        let err = replace(&mut locs, b("foo"), "$2", &opts).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Substitute));
+
+        opts.replacement_only(true);
+        let got = replace(&mut locs, subject, "<$1>", &opts).unwrap();
+        assert_eq!(got, (1, "<foo>".to_string()));
+
+        opts.replacement_only(false).global(true);
+        let got = replace(&mut locs, subject, "$2", &opts).unwrap();
+        assert_eq!(got, (2, "mail bar and quux".to_string()));
+    }
+
+    #[test]
+    fn replace_no_expand() {
+        let re = Regex::new(r"\d").unwrap();
+        let rep = NoExpand(b("$0\\"));
+        assert_eq!(
+            &*re.replace(b("a1b2c3"), rep.clone()).unwrap(),
+            b("a$0\\b2c3")
+        );
+        assert_eq!(
+            &*re.replace_all(b("a1b2c3"), rep.clone()).unwrap(),
+            b("a$0\\b$0\\c$0\\")
+        );
+        assert_eq!(
+            &*re.replacen(b("a1b2c3"), 2, rep).unwrap(),
+            b("a$0\\b$0\\c3")
+        );
+    }
+
+    #[test]
+    fn check_replacement() {
+        let re = Regex::new(r"(?<first>\w+)\s+(\w+)").unwrap();
+        let check = |rep: &str| {
+            re.check_replacement(b(rep))
+                .map_err(|e| (e.code(), e.offset()))
+        };
+        assert!(check("$2 ${first} $$ ${*MARK}").is_ok());
+        assert_eq!(
+            check("ok $3"),
+            Err((pcre2_sys::PCRE2_ERROR_NOSUBSTRING, Some(5)))
+        );
+        assert_eq!(
+            check("${second}"),
+            Err((pcre2_sys::PCRE2_ERROR_NOSUBSTRING, Some(9)))
+        );
+        assert_eq!(
+            check("$1 ${2"),
+            Err((pcre2_sys::PCRE2_ERROR_REPMISSINGBRACE, Some(6)))
+        );
+        assert_eq!(
+            check("$."),
+            Err((pcre2_sys::PCRE2_ERROR_BADREPLACEMENT, Some(1)))
+        );
+        // The extended syntax is only accepted when requested.
+        assert!(check("${2:-none}").is_err());
+        assert!(re.check_replacement(Extended(b("\\U${2:-none}"))).is_ok());
+        // Both branches of a conditional are checked.
+        let err = re
+            .check_replacement(Extended(b("${2:+$1:$4}")))
+            .unwrap_err();
+        assert_eq!(
+            (err.code(), err.offset()),
+            (pcre2_sys::PCRE2_ERROR_NOSUBSTRING, Some(10))
+        );
+        let err = re.check_replacement(Extended(b("$1 \\k"))).unwrap_err();
+        assert_eq!(
+            (err.code(), err.offset()),
+            (pcre2_sys::PCRE2_ERROR_BADREPESCAPE, Some(5))
+        );
+        // Replacers that aren't templates can't be invalid.
+        assert!(re.check_replacement(NoExpand(b("$3"))).is_ok());
+        assert!(re.check_replacement(|_: &Captures| b("$3")).is_ok());
+    }
+
+    #[test]
+    fn replace_template_append() {
+        let re = Regex::new(r"(?<first>\w+)\s+(\w+)").unwrap();
+        let caps = re.captures(b("hello world")).unwrap().unwrap();
+        let mut dst = vec![];
+        let mut template = b("$2 ${first}$$");
+        template.replace_append(&caps, &mut dst);
+        assert_eq!(dst, b("world hello$"));
+
+        // Templates expand the same way as when PCRE2 replaces.
+        let subject = b("hello world");
+        for &template in &[
+            b("$2 ${first} $$"),
+            b("$1a ${1}a $first."),
+            b("${2}$1$0 $2_"),
+            b("$*MARK${*MARK}!"),
+        ] {
+            let mut dst = vec![];
+            let mut rep = template;
+            rep.replace_append(&caps, &mut dst);
+            assert_eq!(re.replace(subject, template).unwrap(), dst);
+        }
+        let mut dst = vec![];
+        Extended(b("\\U$1\\E-${2:+yes:no}")).replace_append(&caps, &mut dst);
+        assert_eq!(dst, b("HELLO-yes"));
+        assert_eq!(
+            re.replace(subject, Extended(b("\\U$1\\E-${2:+yes:no}")))
+                .unwrap(),
+            dst
+        );
+
+        // Templates that PCRE2 rejects append nothing.
+        for &template in &[b("$first_"), b("$3"), b("$")] {
+            let mut dst = b("x").to_vec();
+            let mut rep = template;
+            rep.replace_append(&caps, &mut dst);
+            assert_eq!(dst, b("x"));
+        }
+
+        // Matches found after the regex was JIT compiled automatically are
+        // expanded with the JIT compiled code.
+        if is_jit_available::<CodeUnitWidth8>() {
+            let re = RegexBuilder::new()
+                .auto_jit(Some(1))
+                .build(r"(\w+)")
+                .unwrap();
+            for _ in 0..2 {
+                let caps = re.captures(subject).unwrap().unwrap();
+                let mut dst = vec![];
+                b("<$1>").replace_append(&caps, &mut dst);
+                assert_eq!(dst, b("<hello>"));
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::useless_format)]
+    fn max_jit_stack_size_does_something() {
+        if !is_jit_available::<CodeUnitWidth8>() {
+            return;
+        }
+
+        let hundred = "\
+            ABCDEFGHIJKLMNOPQRSTUVWXY\
+            ABCDEFGHIJKLMNOPQRSTUVWXY\
+            ABCDEFGHIJKLMNOPQRSTUVWXY\
+            ABCDEFGHIJKLMNOPQRSTUVWXY\
+        ";
+        let hay = format!("{}", hundred.repeat(100));
+
+        // First, try a regex that checks that we can blow the JIT stack limit.
+        let re = RegexBuilder::new()
+            .ucp(true)
+            .jit(true)
+            .max_jit_stack_size(Some(1))
+            .build(r"((((\w{10})){100}))+")
+            .unwrap();
+        let result = re.is_match(hay.as_bytes());
+        if result.is_ok() {
+            // Skip this test, since for some reason we weren't able to blow
+            // the stack limit.
+            return;
+        }
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("JIT stack limit reached"));
+
+        // Now bump up the JIT stack limit and check that it succeeds.
+        let re = RegexBuilder::new()
+            .ucp(true)
+            .jit(true)
+            .max_jit_stack_size(Some(1 << 20))
+            .build(r"((((\w{10})){100}))+")
+            .unwrap();
+        assert!(re.is_match(hay.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn callouts() {
+        let re = Regex::new(r#"a(?C1)b(?C"chk")c(?C)"#).unwrap();
+        let callouts = re.callouts();
+        let numbers: Vec<u32> = callouts.iter().map(|c| c.number()).collect();
+        assert_eq!(numbers, vec![1, 0, 0]);
+        assert_eq!(callouts[0].string(), None);
+        assert_eq!(callouts[0].pattern_position(), 6);
+        assert_eq!(callouts[1].string(), Some("chk"));
+        assert_eq!(callouts[1].string_offset(), Some(11));
+        assert_eq!(callouts[2].string(), None);
+
+        assert!(Regex::new("abc").unwrap().callouts().is_empty());
+    }
+
+    #[test]
+    fn callout() {
+        use std::error::Error as _;
+
+        let re = RegexBuilder::new()
+            .callout(|block| {
+                let (s, e) = block.group(1).unwrap();
+                let n: u32 = std::str::from_utf8(&block.subject()[s..e])
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                match block.number() {
+                    1 if n % 2 == 1 => CalloutResult::Fail,
+                    2 if n > 100 => CalloutResult::Abort("number too large".into()),
+                    _ => CalloutResult::Continue,
+                }
+            })
+            .build(r"\b(\d+)\b(?C1)(?C2)")
+            .unwrap();
+        let m = re.find(b("13 24")).unwrap();
+        assert_eq!(m.map(|m| m.as_pair()), Some((3, 5)));
+        assert!(re.find(b("13 15")).unwrap().is_none());
+
+        let err = re.find(b("13 240")).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Callout));
+        assert_eq!(err.source().unwrap().to_string(), "number too large");
+        assert!(err.to_string().contains("number too large"));
+        // The error doesn't stick to later searches.
+        assert!(re.is_match(b("24")).unwrap());
+
+        // Automatic callouts of interruptible regexes aren't passed on.
+        let re = RegexBuilder::new()
+            .interruptible(true)
+            .callout(|block| {
+                assert_eq!(block.number(), 1);
+                CalloutResult::Continue
+            })
+            .build(r"a+(?C1)b")
+            .unwrap();
+        assert!(re.is_match(b("xaab")).unwrap());
+    }
+
+    #[test]
+    fn progress() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(AtomicUsize::new(0));
+        let re = {
+            let (calls, last) = (Arc::clone(&calls), Arc::clone(&last));
+            RegexBuilder::new()
+                .progress(100, move |start, len| {
+                    assert_eq!(len, 401);
+                    assert!(start >= last.swap(start, Ordering::SeqCst));
+                    calls.fetch_add(1, Ordering::SeqCst);
+                })
+                .build(r"(?:a|b)+$")
+                .unwrap()
+        };
+        let hay = format!("{}c", "ab".repeat(200));
+        assert!(!re.is_match(hay.as_bytes()).unwrap());
+        assert!(calls.load(Ordering::SeqCst) > 0);
+        assert!(re.is_match(b("abab")).unwrap());
+    }
+
+    #[test]
+    fn find_with_deadline() {
+        use std::time::{Duration, Instant};
+
+        let hay = format!("{}b", "a".repeat(40));
+        let re = RegexBuilder::new()
+            .interruptible(true)
+            .match_limit(u32::MAX)
+            .build(r"^(a+)+$")
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let err = re.find_with_deadline(hay.as_bytes(), deadline).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::TimedOut));
+        assert!(Instant::now() < deadline + Duration::from_secs(5));
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let m = re.find_with_deadline(b("aaa"), deadline).unwrap();
+        assert_eq!(m.map(|m| m.as_pair()), Some((0, 3)));
+
+        let re = Regex::new(r"a+").unwrap();
+        assert!(re.find_with_deadline(b("aaa"), deadline).is_err());
+    }
+
+    #[test]
+    fn cancellation() {
+        use std::thread;
+        use std::time::Duration;
+
+        let hay = format!("{}b", "a".repeat(40));
+        let re = RegexBuilder::new()
+            .interruptible(true)
+            .match_limit(u32::MAX)
+            .build(r"^(a+)+$")
+            .unwrap();
+        let token = CancellationToken::new();
+        let mut opts = MatchOptions::new();
+        opts.cancellation(Some(token.clone()));
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+        let err = re
+            .find_at_with_options(hay.as_bytes(), 0, &opts)
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Cancelled));
+        canceller.join().unwrap();
+
+        assert!(re.is_match(b("aaa")).unwrap());
+    }
+
+    #[test]
+    fn hardened() {
+        use pcre2_sys::PCRE2_ERROR_MATCHLIMIT;
+
+        let mut builder = RegexBuilder::new();
+        builder.hardened();
+        assert!(builder.build(r"a\Cb").is_err());
+        assert!(builder.build("a".repeat(100_000)).is_err());
+        assert!(builder
+            .build(format!("{}a{}", "(".repeat(100), ")".repeat(100)))
+            .is_err());
+
+        let re = builder.build(r"^(a+)+$").unwrap();
+        let hay = format!("{}b", "a".repeat(40));
+        let err = re.is_match(hay.as_bytes()).unwrap_err();
+        assert_eq!(err.code(), PCRE2_ERROR_MATCHLIMIT);
+        assert!(re.is_match(b("aaa")).unwrap());
+    }
+
+    #[test]
+    fn pattern_policy() {
+        let mut policy = PatternPolicy::new();
+        assert!(policy.check(&Regex::new(r"(a)\1(?C1)\C").unwrap()).is_ok());
+
+        policy
+            .deny_backrefs(true)
+            .deny_backslash_c(true)
+            .allowed_callouts(Some([1, 2]))
+            .max_lookbehind(Some(2));
+        let check = |pattern: &str| policy.check(&Regex::new(pattern).unwrap());
+        assert!(check(r"(a)(?C1)(?<=ab)b(?C2)").is_ok());
+        assert!(matches!(check(r"(a)\1"), Err(PolicyViolation::Backref)));
+        assert!(matches!(
+            check(r"(?<n>a)\k<n>"),
+            Err(PolicyViolation::Backref)
+        ));
+        assert!(matches!(check(r"a\Cb"), Err(PolicyViolation::BackslashC)));
+        assert!(matches!(
+            check(r"(?<=abc)d"),
+            Err(PolicyViolation::Lookbehind(3))
+        ));
+        match check(r#"a(?C1)b(?C"x")"#) {
+            Err(PolicyViolation::Callout(callout)) => {
+                assert_eq!(callout.string(), Some("x"));
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        policy.deny_callouts(true);
+        let err = policy.check(&Regex::new(r"a(?C1)").unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "pattern contains callout 1 at offset 6");
+    }
+
+    #[test]
+    fn max_capture_groups() {
+        let mut builder = RegexBuilder::new();
+        builder.max_capture_groups(2);
+        assert!(builder.build(r"(a)(?<n>b)(?:c)").is_ok());
+        let err = builder.build(r"(a)(b)(c)").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::CaptureLimit));
+        assert!(builder.no_auto_capture(true).build(r"(a)(b)(c)").is_ok());
+    }
+
+    #[test]
+    fn jit_target_matches_availability() {
+        let target = jit_target::<CodeUnitWidth8>();
+        assert_eq!(target.is_some(), is_jit_available::<CodeUnitWidth8>());
+        if let Some(target) = target {
+            assert!(!target.is_empty());
+            assert!(!target.contains('\0'));
+        }
+    }
+
+    #[test]
+    fn free_unused_jit_memory() {
+        let re = RegexBuilder::new()
+            .jit_if_available(true)
+            .build(r"\w+")
+            .unwrap();
+        drop(re);
+        jit_free_unused_memory::<CodeUnitWidth8>();
+        // Compiling and matching still works afterwards.
+        let re = RegexBuilder::new()
+            .jit_if_available(true)
+            .build(r"\w+")
+            .unwrap();
+        assert!(re.is_match(b("abc")).unwrap());
+    }
+
+    #[test]
+    fn jit_stack() {
+        if !is_jit_available::<CodeUnitWidth8>() {
+            assert!(JitStack::new(32 << 10, 1 << 20).is_err());
+            return;
+        }
+        assert!(JitStack::new(1 << 20, 32 << 10).is_err());
+        // This needs far more than the default 32 KB JIT stack.
+        let re = RegexBuilder::new().jit(true).build(r"(a|b)*c").unwrap();
+        let subject = "ab".repeat(100_000) + "c";
+        assert!(re.is_match(subject.as_bytes()).is_err());
+
+        let mut stack = JitStack::new(32 << 10, 64 << 20).unwrap();
+        let m = stack.install(|| re.is_match(subject.as_bytes()));
+        assert!(m.unwrap());
+        assert!(re.is_match(subject.as_bytes()).is_err());
+
+        let pool = JitStackPool::new(32 << 10, 64 << 20);
+        let m = pool.install(|| re.is_match(subject.as_bytes())).unwrap();
+        assert!(m.unwrap());
+        // The stack was returned to the pool and is reused.
+        let m = pool.get().unwrap().install(|| re.find(subject.as_bytes()));
+        assert_eq!(m.unwrap().unwrap().as_pair(), (0, subject.len()));
+    }
+
+    #[test]
+    fn jit_partial() {
+        let re = RegexBuilder::new()
+            .jit_if_available(true)
+            .jit_partial(PartialMode::Hard, true)
+            .jit_partial(PartialMode::Soft, true)
+            .build(r"\d{4}")
+            .unwrap();
+        let find = |subject, mode| {
+            re.find_partial_at(b(subject), 0, mode)
+                .unwrap()
+                .map(|m| (m.is_partial(), m.into_match().as_pair()))
+        };
+        assert_eq!(find("abc 20", PartialMode::Hard), Some((true, (4, 6))));
+        assert_eq!(find("abc 20", PartialMode::Soft), Some((true, (4, 6))));
+        assert_eq!(find("1234", PartialMode::Soft), Some((false, (0, 4))));
+        assert_eq!(find("abc", PartialMode::Hard), None);
+
+        let mut matcher = re.stream_matcher();
+        matcher.push(b("ab 12"));
+        assert!(matcher.next_match().unwrap().is_none());
+        matcher.push(b("34 cd"));
+        let m = matcher.next_match().unwrap().unwrap();
+        assert_eq!((m.start(), m.end()), (3, 7));
+    }
+
+    #[test]
+    fn jit_fast_path() {
+        let mut builder = RegexBuilder::new();
+        builder.jit_if_available(true);
+        unsafe {
+            builder.jit_fast_path(true);
+        }
+        let re = builder.build(r"\b\w+\b").unwrap();
+        assert_eq!(re.find(b("ab cd")).unwrap().unwrap().as_pair(), (0, 2));
+        assert_eq!(
+            re.find_at(b("ab cd"), 1).unwrap().unwrap().as_pair(),
+            (3, 5)
+        );
+        assert!(re.find(b("  ")).unwrap().is_none());
+        // Searches with options the fast path doesn't support fall back to
+        // the primary match routine.
+        let mut opts = MatchOptions::new();
+        opts.anchored(true);
+        let m = re.find_at_with_options(b(" ab"), 0, &opts).unwrap();
+        assert!(m.is_none());
+        let mut opts = MatchOptions::new();
+        opts.notbol(true);
+        let m = re.find_at_with_options(b(" ab"), 0, &opts).unwrap();
+        assert_eq!(m.unwrap().as_pair(), (1, 3));
+    }
+
+    #[test]
+    fn match_limit() {
+        use pcre2_sys::PCRE2_ERROR_MATCHLIMIT;
+
+        let hay = format!("{}b", "a".repeat(30));
+        let re = RegexBuilder::new()
+            .match_limit(1000)
+            .build(r"^(a+)+$")
+            .unwrap();
+        let err = re.is_match(hay.as_bytes()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Match));
+        assert_eq!(err.code(), PCRE2_ERROR_MATCHLIMIT);
+
+        // Iterators stop after reporting an error.
+        let mut it = re.find_iter(hay.as_bytes());
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+        let mut it = re.captures_iter(hay.as_bytes());
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+
+        let re = RegexBuilder::new()
+            .match_limit(1000)
+            .build(r"^a+$")
+            .unwrap();
+        assert!(!re.is_match(hay.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn depth_limit() {
+        use pcre2_sys::PCRE2_ERROR_DEPTHLIMIT;
+
+        let hay = format!("{}c", "a".repeat(1000));
+        let re = RegexBuilder::new()
+            .depth_limit(10)
+            .build(r"(a|b)+c")
+            .unwrap();
+        let err = re.is_match(hay.as_bytes()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Match));
+        assert_eq!(err.code(), PCRE2_ERROR_DEPTHLIMIT);
+
+        let re = RegexBuilder::new()
+            .depth_limit(10_000)
+            .build(r"(a|b)+c")
+            .unwrap();
+        assert!(re.is_match(hay.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn heap_limit() {
+        use pcre2_sys::PCRE2_ERROR_HEAPLIMIT;
+
+        let hay = format!("{}c", "a".repeat(10_000));
+        let re = RegexBuilder::new().heap_limit(1).build(r"(a|b)+c").unwrap();
+        let err = re.is_match(hay.as_bytes()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Match));
+        assert_eq!(err.code(), PCRE2_ERROR_HEAPLIMIT);
+
+        let re = RegexBuilder::new()
+            .heap_limit(100_000)
+            .build(r"(a|b)+c")
             .unwrap();
         assert!(re.is_match(hay.as_bytes()).unwrap());
     }
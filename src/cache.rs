@@ -0,0 +1,291 @@
+/*!
+This module provides an on-disk cache of compiled regexes.
+*/
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::debug;
+
+use crate::error::Error;
+use crate::ffi::CodeUnitWidth;
+use crate::regex_impl::{Regex, RegexBuilder};
+
+/// The first bytes of every cache entry, which also version its format.
+const MAGIC: &[u8; 8] = b"PCRE2RC1";
+
+/// The file extension of cache entries.
+const EXTENSION: &str = "pcre2";
+
+/// The file extension of entries that are still being written.
+const TMP_EXTENSION: &str = "tmp";
+
+/// The number of entries written by this process so far, which makes the
+/// names of temporary files unique.
+static STORES: AtomicUsize = AtomicUsize::new(0);
+
+/// A cache of compiled regexes stored in a directory.
+///
+/// Compiling large or numerous patterns can dominate startup time. This
+/// cache stores each regex in PCRE2's serialization format (see
+/// `Regex::serialize`) in a file named after its key. The key is derived from
+/// the pattern, the compile options and character tables of the builder, the
+/// version of PCRE2, the version of this crate, the code unit width, the
+/// endianness and the pointer width. Changing any of these results in a cache
+/// miss, in which case the pattern is compiled and the entry is written
+/// again. Match-time settings such as `match_limit` are applied to regexes
+/// loaded from the cache, so they don't affect the key.
+///
+/// Each entry records its complete key and a checksum, so hash collisions and
+/// truncated files are detected and also treated as misses. Errors while
+/// reading or writing the cache are never reported to the caller: the only
+/// error returned by `get_or_build` is a failure to compile the pattern.
+///
+/// JIT compiled code can't be cached. If the builder enables JIT, then it
+/// is applied to regexes loaded from the cache as usual.
+///
+/// Builders with compile limits (`compile_recursion_guard`,
+/// `max_pattern_length` or `parens_nest_limit`) bypass the cache, since
+/// those limits can only be enforced by compiling the pattern.
+#[derive(Clone, Debug)]
+pub struct RegexCache {
+    dir: PathBuf,
+}
+
+impl RegexCache {
+    /// Create a cache that stores its entries in the given directory.
+    ///
+    /// The directory is created when the first entry is written.
+    ///
+    /// # Safety
+    ///
+    /// Loading a cache entry trusts its contents, in the same way as
+    /// `RegexBuilder::deserialize`: PCRE2 doesn't validate compiled code, so
+    /// searching with a regex loaded from a crafted entry is undefined
+    /// behavior. The checksum protects against accidental corruption but not
+    /// against tampering, since anyone who can write an entry can also write
+    /// a matching checksum. The caller must guarantee that no untrusted party
+    /// can write to the directory, e.g. by using a directory that only the
+    /// current user can write to.
+    pub unsafe fn new<P: Into<PathBuf>>(dir: P) -> RegexCache {
+        RegexCache { dir: dir.into() }
+    }
+
+    /// Returns the directory in which this cache stores its entries.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Load the regex for `pattern` compiled with `builder` from the cache,
+    /// or compile it and store it in the cache if it isn't there.
+    ///
+    /// If the pattern fails to compile, then an error is returned and
+    /// nothing is stored. If the builder has compile limits, then the
+    /// pattern is always compiled and the cache isn't used.
+    pub fn get_or_build<W: CodeUnitWidth, Pat: Into<W::Pattern>>(
+        &self,
+        builder: &RegexBuilder<W>,
+        pattern: Pat,
+    ) -> Result<Regex<W>, Error> {
+        let pattern = pattern.into();
+        if builder.has_compile_guards() {
+            return builder.build(pattern);
+        }
+        let key = builder.cache_key(&pattern);
+        let path = self.path(&key);
+        if let Some(bytes) = load(&path, &key) {
+            // SAFETY: The caller of `RegexCache::new` guaranteed that the
+            // entries in this directory were written by this cache, and
+            // `load` verified that this entry is intact and has our key.
+            match unsafe { builder.deserialize(pattern.clone(), &bytes) } {
+                Ok(re) => return Ok(re),
+                Err(err) => {
+                    debug!("ignoring cache entry {}: {}", path.display(), err);
+                }
+            }
+        }
+        let re = builder.build(pattern)?;
+        match re.serialize() {
+            Ok(bytes) => {
+                if let Err(err) = self.store(&path, &key, &bytes) {
+                    debug!("failed to write cache entry {}: {}", path.display(), err);
+                }
+            }
+            Err(err) => debug!("failed to serialize regex for the cache: {}", err),
+        }
+        Ok(re)
+    }
+
+    /// Remove all entries from this cache.
+    ///
+    /// This also removes temporary files left behind by writes that were
+    /// interrupted. Other files in the cache directory are left alone. If the
+    /// directory doesn't exist, then this does nothing.
+    pub fn clear(&self) -> io::Result<()> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if is_entry(&path) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the path of the entry for the given key.
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.{}", fnv1a(key.as_bytes()), EXTENSION))
+    }
+
+    /// Write an entry, replacing any existing one atomically so that
+    /// concurrent readers never see a partially written entry.
+    fn store(&self, path: &Path, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let mut entry = Vec::with_capacity(MAGIC.len() + 16 + key.len() + bytes.len());
+        entry.extend_from_slice(MAGIC);
+        entry.extend_from_slice(&(key.len() as u64).to_le_bytes());
+        entry.extend_from_slice(key.as_bytes());
+        entry.extend_from_slice(&fnv1a(bytes).to_le_bytes());
+        entry.extend_from_slice(bytes);
+
+        fs::create_dir_all(&self.dir)?;
+        // Every write uses its own temporary file, even when several threads
+        // or processes store the same key at once.
+        let tmp = path.with_extension(format!(
+            "{}.{}.{}.{}",
+            EXTENSION,
+            std::process::id(),
+            STORES.fetch_add(1, Ordering::Relaxed),
+            TMP_EXTENSION
+        ));
+        let mut file = OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+        file.write_all(&entry)
+            .and_then(|()| {
+                drop(file);
+                fs::rename(&tmp, path)
+            })
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&tmp);
+            })
+    }
+}
+
+/// Returns true if the file at `path` is a cache entry or a temporary file
+/// written by `RegexCache::store`.
+fn is_entry(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) if ext == EXTENSION => true,
+        Some(ext) if ext == TMP_EXTENSION => path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.contains(&format!(".{}.", EXTENSION))),
+        _ => false,
+    }
+}
+
+/// Read the serialized regex from the entry at `path`, if it exists, is
+/// intact and was written for `key`.
+fn load(path: &Path, key: &str) -> Option<Vec<u8>> {
+    let entry = fs::read(path).ok()?;
+    let rest = entry.strip_prefix(MAGIC)?;
+    let (len, rest) = split_u64(rest)?;
+    let len = usize::try_from(len).ok()?;
+    if rest.len() < len || &rest[..len] != key.as_bytes() {
+        return None;
+    }
+    let (checksum, bytes) = split_u64(&rest[len..])?;
+    if fnv1a(bytes) != checksum {
+        debug!("cache entry {} is corrupt", path.display());
+        return None;
+    }
+    Some(bytes.to_vec())
+}
+
+/// Split a little endian `u64` off the front of `bytes`.
+fn split_u64(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    if bytes.len() < 8 {
+        return None;
+    }
+    let (n, rest) = bytes.split_at(8);
+    Some((u64::from_le_bytes(n.try_into().unwrap()), rest))
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+///
+/// This is used instead of `std`'s hasher because its output must be stable
+/// across Rust versions.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{fnv1a, load, RegexCache};
+
+    #[test]
+    fn fnv1a_vectors() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = std::env::temp_dir().join(format!("pcre2-cache-{}", std::process::id()));
+        // SAFETY: The directory is private to this test.
+        let cache = unsafe { RegexCache::new(&dir) };
+        let path = cache.path("key");
+        cache.store(&path, "key", b"code").unwrap();
+        assert_eq!(load(&path, "key").as_deref(), Some(&b"code"[..]));
+        assert_eq!(load(&path, "other"), None);
+
+        // Corrupt and truncated entries are ignored.
+        let mut entry = fs::read(&path).unwrap();
+        *entry.last_mut().unwrap() ^= 1;
+        fs::write(&path, &entry).unwrap();
+        assert_eq!(load(&path, "key"), None);
+        fs::write(&path, &entry[..12]).unwrap();
+        assert_eq!(load(&path, "key"), None);
+
+        // Leftover temporary files are removed as well, but other files are
+        // left alone.
+        let tmp = path.with_extension("pcre2.1.2.tmp");
+        fs::write(&tmp, b"partial").unwrap();
+        let other = dir.join("other.tmp");
+        fs::write(&other, b"other").unwrap();
+        cache.clear().unwrap();
+        assert!(!path.exists());
+        assert!(!tmp.exists());
+        assert!(other.exists());
+        fs::remove_file(&other).unwrap();
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_stores() {
+        let dir = std::env::temp_dir().join(format!("pcre2-cache-race-{}", std::process::id()));
+        // SAFETY: The directory is private to this test.
+        let cache = unsafe { RegexCache::new(&dir) };
+        let path = cache.path("key");
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| cache.store(&path, "key", b"code").unwrap());
+            }
+        });
+        assert_eq!(load(&path, "key").as_deref(), Some(&b"code"[..]));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        cache.clear().unwrap();
+        fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
 use std::error;
 use std::fmt;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
 
 use libc::c_int;
 use pcre2_sys::*;
@@ -14,6 +16,25 @@ pub struct Error {
     kind: ErrorKind,
     code: c_int,
     offset: Option<usize>,
+    source: Option<Source>,
+}
+
+/// The error that a callout aborted a search with.
+///
+/// Arbitrary errors aren't `RefUnwindSafe`, but this one is only ever read
+/// through shared references once the search that produced it has returned,
+/// so a panic can't observe it half-updated. Asserting unwind safety here
+/// keeps `Error` (and every type that contains one) unwind safe.
+#[derive(Clone)]
+struct Source(Arc<dyn error::Error + Send + Sync>);
+
+impl UnwindSafe for Source {}
+impl RefUnwindSafe for Source {}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
 /// The kind of an error indicates the type of operation that was attempted
@@ -32,6 +53,21 @@ pub enum ErrorKind {
     Info,
     /// An error occurred while setting an option.
     Option,
+    /// An error occurred while substituting a replacement.
+    Substitute,
+    /// An error occurred while serializing or deserializing a compiled regex.
+    Serialize,
+    /// A callout aborted a search with an error of its own, which is
+    /// available via `std::error::Error::source`.
+    Callout,
+    /// A search was aborted because its deadline passed.
+    TimedOut,
+    /// A search was aborted because it was cancelled with a
+    /// `CancellationToken`.
+    Cancelled,
+    /// A pattern has more capturing groups than permitted by
+    /// `RegexBuilder::max_capture_groups`.
+    CaptureLimit,
     /// Hints that destructuring should not be exhaustive.
     ///
     /// This enum may grow additional variants, so this makes sure clients
@@ -46,8 +82,9 @@ impl Error {
     pub(crate) fn compile(code: c_int, offset: usize) -> Error {
         Error {
             kind: ErrorKind::Compile,
-            code: code,
+            code,
             offset: Some(offset),
+            source: None,
         }
     }
 
@@ -55,8 +92,9 @@ impl Error {
     pub(crate) fn jit(code: c_int) -> Error {
         Error {
             kind: ErrorKind::JIT,
-            code: code,
+            code,
             offset: None,
+            source: None,
         }
     }
 
@@ -64,8 +102,9 @@ impl Error {
     pub(crate) fn matching(code: c_int) -> Error {
         Error {
             kind: ErrorKind::Match,
-            code: code,
+            code,
             offset: None,
+            source: None,
         }
     }
 
@@ -73,8 +112,9 @@ impl Error {
     pub(crate) fn info(code: c_int) -> Error {
         Error {
             kind: ErrorKind::Info,
-            code: code,
+            code,
             offset: None,
+            source: None,
         }
     }
 
@@ -82,8 +122,83 @@ impl Error {
     pub(crate) fn option(code: c_int) -> Error {
         Error {
             kind: ErrorKind::Option,
-            code: code,
+            code,
             offset: None,
+            source: None,
+        }
+    }
+
+    /// Create a new substitution error.
+    pub(crate) fn substitute(code: c_int) -> Error {
+        Error {
+            kind: ErrorKind::Substitute,
+            code,
+            offset: None,
+            source: None,
+        }
+    }
+
+    /// Create a new serialization error.
+    pub(crate) fn serialize(code: c_int) -> Error {
+        Error {
+            kind: ErrorKind::Serialize,
+            code,
+            offset: None,
+            source: None,
+        }
+    }
+
+    /// Create a new error for a search that a callout aborted with the given
+    /// error.
+    pub(crate) fn callout(err: Box<dyn error::Error + Send + Sync>) -> Error {
+        Error {
+            kind: ErrorKind::Callout,
+            code: PCRE2_ERROR_CALLOUT,
+            offset: None,
+            source: Some(Source(Arc::from(err))),
+        }
+    }
+
+    /// Create a new error for a search that was aborted because its
+    /// deadline passed.
+    pub(crate) fn timed_out() -> Error {
+        Error {
+            kind: ErrorKind::TimedOut,
+            code: PCRE2_ERROR_CALLOUT,
+            offset: None,
+            source: None,
+        }
+    }
+
+    /// Create a new error for a search that was cancelled.
+    pub(crate) fn cancelled() -> Error {
+        Error {
+            kind: ErrorKind::Cancelled,
+            code: PCRE2_ERROR_CALLOUT,
+            offset: None,
+            source: None,
+        }
+    }
+
+    /// Create a new error for a pattern with more capturing groups than
+    /// permitted.
+    pub(crate) fn capture_limit() -> Error {
+        Error {
+            kind: ErrorKind::CaptureLimit,
+            code: 0,
+            offset: None,
+            source: None,
+        }
+    }
+
+    /// Create a new error for an invalid replacement template, which was
+    /// detected at the given offset into the template.
+    pub(crate) fn replacement(code: c_int, offset: usize) -> Error {
+        Error {
+            kind: ErrorKind::Substitute,
+            code,
+            offset: Some(offset),
+            source: None,
         }
     }
 
@@ -108,7 +223,8 @@ impl Error {
     ///
     /// The offset is typically only available for compile time errors, and
     /// is supposed to indicate the general position in the pattern where an
-    /// error occurred.
+    /// error occurred. Errors reported by `Regex::check_replacement` also
+    /// carry the position in the replacement template.
     pub fn offset(&self) -> Option<usize> {
         self.offset
     }
@@ -131,7 +247,24 @@ impl Error {
     }
 
     /// Returns the error message from PCRE2.
-    #[cfg(not(feature = "utf8"))]
+    #[cfg(all(not(feature = "utf8"), feature = "utf16"))]
+    pub fn error_message(&self) -> String {
+        // PCRE2 docs say a buffer size of 120 bytes is enough, but we're
+        // cautious and double it.
+        let mut buf = [0u16; 240];
+        let rc = unsafe { pcre2_get_error_message_16(self.code, buf.as_mut_ptr(), buf.len()) };
+        // Errors are only ever constructed from codes reported by PCRE2, so
+        // our code should always be valid.
+        assert!(rc != PCRE2_ERROR_BADDATA, "used an invalid error code");
+        // PCRE2 docs claim 120 bytes is enough, and we use more, so...
+        assert!(rc != PCRE2_ERROR_NOMEMORY, "buffer size too small");
+        // Sanity check that we do indeed have a non-negative result. 0 is OK.
+        assert!(rc >= 0, "expected non-negative but got {}", rc);
+        String::from_utf16(&buf[..rc as usize]).expect("valid UTF-16")
+    }
+
+    /// Returns the error message from PCRE2.
+    #[cfg(not(any(feature = "utf8", feature = "utf16")))]
     pub fn error_message(&self) -> String {
         // PCRE2 docs say a buffer size of 120 bytes is enough, but we're
         // cautious and double it.
@@ -155,6 +288,12 @@ impl error::Error for Error {
     fn description(&self) -> &str {
         "pcre2 error"
     }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|err| &*err.0 as &(dyn error::Error + 'static))
+    }
 }
 
 impl fmt::Display for Error {
@@ -185,6 +324,40 @@ impl fmt::Display for Error {
             ErrorKind::Option => {
                 write!(f, "PCRE2: error setting option: {}", msg)
             }
+            ErrorKind::Substitute => match self.offset {
+                None => {
+                    write!(f, "PCRE2: error substituting: {}", msg)
+                }
+                Some(offset) => {
+                    write!(
+                        f,
+                        "PCRE2: error in replacement at offset {}: {}",
+                        offset, msg
+                    )
+                }
+            },
+            ErrorKind::Serialize => {
+                write!(
+                    f,
+                    "PCRE2: error serializing or deserializing pattern: {}",
+                    msg
+                )
+            }
+            ErrorKind::Callout => match self.source {
+                None => write!(f, "PCRE2: callout aborted matching: {}", msg),
+                Some(ref err) => {
+                    write!(f, "PCRE2: callout aborted matching: {}", err.0)
+                }
+            },
+            ErrorKind::TimedOut => {
+                write!(f, "PCRE2: search timed out")
+            }
+            ErrorKind::Cancelled => {
+                write!(f, "PCRE2: search cancelled")
+            }
+            ErrorKind::CaptureLimit => {
+                write!(f, "PCRE2: pattern has too many capturing groups")
+            }
             _ => unreachable!(),
         }
     }
@@ -199,6 +372,7 @@ impl fmt::Debug for Error {
             .field("code", &self.code)
             .field("offset", &self.offset)
             .field("message", &self.error_message())
+            .field("source", &self.source)
             .finish()
     }
 }
@@ -5,22 +5,34 @@ unsafety, but this layer will take care of the obvious things, such as
 resource management and error handling.
 */
 
+use std::any::Any;
+use std::cell::Cell;
 use std::cmp;
+use std::error;
+use std::fmt;
 use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe, RefUnwindSafe};
 use std::ptr;
 use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::LocalKey;
+use std::time::Instant;
 
-use libc::c_void;
+use libc::{c_int, c_void};
 use pcre2_sys::*;
 
 use crate::error::Error;
 
 pub trait NameTableEntry {
+    /// The type of the characters in the name.
+    type Char;
+
     /// The index of the named subpattern.
     fn index(&self) -> usize;
 
-    /// The name of the named subpattern.
-    fn name(&self) -> String;
+    /// The name of the named subpattern, borrowed from the name table.
+    fn name(&self) -> &[Self::Char];
 }
 
 #[cfg(feature = "utf8")]
@@ -34,6 +46,13 @@ pub struct name_table_entry_8 {
     name: u8,
 }
 
+#[cfg(feature = "utf16")]
+#[repr(C)]
+pub struct name_table_entry_16 {
+    match_index: u16,
+    name: u16, // See above re: flexible array member
+}
+
 #[cfg(feature = "utf32")]
 #[repr(C)]
 pub struct name_table_entry_32 {
@@ -43,43 +62,60 @@ pub struct name_table_entry_32 {
 
 #[cfg(feature = "utf8")]
 impl NameTableEntry for name_table_entry_8 {
+    type Char = u8;
+
     fn index(&self) -> usize {
         ((self.match_index_msb as usize) << 8) | (self.match_index_lsb as usize)
     }
 
-    fn name(&self) -> String {
+    fn name(&self) -> &[u8] {
         // The name is nul-terminated.
         let name = &self.name as *const u8;
         let mut len = 0;
-        while unsafe { *name.offset(len as isize) } != 0 {
+        while unsafe { *name.add(len) } != 0 {
+            len += 1;
+        }
+        unsafe { slice::from_raw_parts(name, len) }
+    }
+}
+
+#[cfg(feature = "utf16")]
+impl NameTableEntry for name_table_entry_16 {
+    type Char = u16;
+
+    fn index(&self) -> usize {
+        self.match_index as usize
+    }
+
+    fn name(&self) -> &[u16] {
+        // The name is nul-terminated.
+        let name = &self.name as *const u16;
+        let mut len = 0;
+        while unsafe { *name.add(len) } != 0 {
             len += 1;
         }
-        let bytes = unsafe { slice::from_raw_parts(name, len) };
-        String::from_utf8_lossy(bytes).into_owned()
+        unsafe { slice::from_raw_parts(name, len) }
     }
 }
 
 #[cfg(feature = "utf32")]
 impl NameTableEntry for name_table_entry_32 {
+    type Char = char;
+
     fn index(&self) -> usize {
         self.match_index as usize
     }
 
-    fn name(&self) -> String {
+    fn name(&self) -> &[char] {
         // The name is nul-terminated.
-        let replacement: char = '\u{FFFD}';
         let name = &self.name as *const u32;
         let mut len = 0;
-        let mut result = String::new();
-        loop {
-            let c = unsafe { *name.offset(len) };
-            if c == 0 {
-                break;
-            }
-            result.push(char::from_u32(c).unwrap_or(replacement));
+        while unsafe { *name.add(len) } != 0 {
             len += 1;
         }
-        result
+        // SAFETY: Names are copied from the pattern, which is a sequence of
+        // chars, so every code unit in a name is a valid char.
+        unsafe { slice::from_raw_parts(name as *const char, len) }
     }
 }
 
@@ -90,19 +126,81 @@ pub trait CodeUnitWidth: std::fmt::Debug {
     type pcre2_match_context;
     type pcre2_match_data;
     type pcre2_jit_stack;
-    type PCRE2_CHAR;
-    type PCRE2_SPTR;
-    type name_table_entry: NameTableEntry;
-    type SubjectChar: Copy;
+    type pcre2_callout_enumerate_block;
+    type pcre2_callout_block;
+    type PCRE2_CHAR: Copy + Into<u32>;
+    type PCRE2_SPTR: Copy;
+    type name_table_entry: NameTableEntry<Char = Self::SubjectChar>;
+    type SubjectChar: Copy + Into<u32>;
     type Pattern: Clone + std::fmt::Debug;
 
     fn escape_subject(subject: &[Self::SubjectChar]) -> String;
 
+    fn name_to_string(name: &[Self::SubjectChar]) -> String;
+
+    /// Returns true if the given code unit continues a UTF encoded codepoint
+    /// rather than starting a new one.
+    fn is_utf_continuation(unit: Self::SubjectChar) -> bool;
+
+    /// Check that code units written by PCRE2, such as the output of
+    /// `pcre2_substitute`, are valid subject characters. If they aren't, the
+    /// PCRE2 error code describing the problem is returned.
+    ///
+    /// Every code unit is a valid subject character by default.
+    fn check_output(_output: &[Self::PCRE2_CHAR]) -> Result<(), c_int> {
+        Ok(())
+    }
+
+    /// Convert a pattern given as a string into a pattern of this width.
+    fn pattern_from_str(pattern: &str) -> Self::Pattern;
+
+    /// Returns the code units of the given pattern.
+    fn pattern_units(pattern: &Self::Pattern) -> Vec<u32>;
+
+    /// Join the given patterns into one, without anything in between.
+    fn pattern_concat(parts: &[Self::Pattern]) -> Self::Pattern;
+
     fn pattern_to_sptr_len(pattern: &Self::Pattern) -> (Self::PCRE2_SPTR, usize);
     fn subject_to_sptr_len(subject: &[Self::SubjectChar]) -> (Self::PCRE2_SPTR, usize);
 
     unsafe fn pcre2_config(arg1: u32, arg2: *mut ::libc::c_void) -> ::libc::c_int;
     unsafe fn pcre2_code_free(arg1: *mut Self::pcre2_code);
+    unsafe fn pcre2_code_copy(arg1: *const Self::pcre2_code) -> *mut Self::pcre2_code;
+    unsafe fn pcre2_callout_enumerate(
+        arg1: *const Self::pcre2_code,
+        arg2: Option<
+            unsafe extern "C" fn(
+                arg1: *mut Self::pcre2_callout_enumerate_block,
+                arg2: *mut c_void,
+            ) -> c_int,
+        >,
+        arg3: *mut c_void,
+    ) -> c_int;
+    /// Convert a block passed to a `pcre2_callout_enumerate` callback into a
+    /// description of its callout.
+    ///
+    /// # Safety
+    ///
+    /// The block must have been passed to the callback by PCRE2.
+    unsafe fn callout_from_enumerate_block(
+        block: &Self::pcre2_callout_enumerate_block,
+    ) -> CalloutEntry;
+    unsafe fn pcre2_set_callout(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: Option<
+            unsafe extern "C" fn(arg1: *mut Self::pcre2_callout_block, arg2: *mut c_void) -> c_int,
+        >,
+        arg3: *mut c_void,
+    ) -> c_int;
+    /// Convert a block passed to a callout function into the state of the
+    /// search at the callout.
+    ///
+    /// # Safety
+    ///
+    /// The block must have been passed to the callout function by PCRE2.
+    unsafe fn callout_block(block: &Self::pcre2_callout_block) -> CalloutBlock<'_, Self>
+    where
+        Self: Sized;
     unsafe fn pcre2_compile(
         arg1: Self::PCRE2_SPTR,
         arg2: usize,
@@ -117,6 +215,19 @@ pub trait CodeUnitWidth: std::fmt::Debug {
         arg3: *mut ::libc::c_void,
     ) -> ::libc::c_int;
 
+    unsafe fn pcre2_serialize_encode(
+        arg1: *mut *const Self::pcre2_code,
+        arg2: i32,
+        arg3: *mut *mut u8,
+        arg4: *mut usize,
+    ) -> i32;
+    unsafe fn pcre2_serialize_free(arg1: *mut u8);
+    unsafe fn pcre2_serialize_decode(
+        arg1: *mut *mut Self::pcre2_code,
+        arg2: i32,
+        arg3: *const u8,
+    ) -> i32;
+
     unsafe fn pcre2_match(
         arg1: *const Self::pcre2_code,
         arg2: Self::PCRE2_SPTR,
@@ -127,6 +238,44 @@ pub trait CodeUnitWidth: std::fmt::Debug {
         arg7: *mut Self::pcre2_match_context,
     ) -> ::libc::c_int;
 
+    unsafe fn pcre2_jit_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+    ) -> ::libc::c_int;
+
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn pcre2_dfa_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+        arg8: *mut ::libc::c_int,
+        arg9: usize,
+    ) -> ::libc::c_int;
+
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn pcre2_substitute(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+        arg8: Self::PCRE2_SPTR,
+        arg9: usize,
+        arg10: *mut Self::PCRE2_CHAR,
+        arg11: *mut usize,
+    ) -> ::libc::c_int;
+
     unsafe fn pcre2_jit_stack_create(
         arg1: ::libc::size_t,
         arg2: ::libc::size_t,
@@ -134,17 +283,62 @@ pub trait CodeUnitWidth: std::fmt::Debug {
     unsafe fn pcre2_jit_compile(arg1: *mut Self::pcre2_code, arg2: u32) -> ::libc::c_int;
     unsafe fn pcre2_jit_stack_assign(
         arg1: *mut Self::pcre2_match_context,
+        arg2: Option<unsafe extern "C" fn(*mut c_void) -> *mut Self::pcre2_jit_stack>,
         arg3: *mut ::libc::c_void,
     );
+    /// Returns the JIT stack installed for the current thread by
+    /// `JitStack::install`.
+    fn thread_jit_stack() -> &'static LocalKey<Cell<ThreadJitStack>>;
     unsafe fn pcre2_jit_stack_free(arg1: *mut Self::pcre2_jit_stack);
+    unsafe fn pcre2_jit_free_unused_memory();
 
     unsafe fn pcre2_compile_context_create() -> *mut Self::pcre2_compile_context;
     unsafe fn pcre2_set_newline(arg1: *mut Self::pcre2_compile_context, arg2: u32)
         -> ::libc::c_int;
+    unsafe fn pcre2_set_bsr(arg1: *mut Self::pcre2_compile_context, arg2: u32) -> ::libc::c_int;
+    unsafe fn pcre2_set_max_pattern_length(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: usize,
+    ) -> ::libc::c_int;
+    unsafe fn pcre2_set_parens_nest_limit(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int;
+    unsafe fn pcre2_set_compile_extra_options(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int;
+    unsafe fn pcre2_set_compile_recursion_guard(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: Option<unsafe extern "C" fn(u32, *mut c_void) -> c_int>,
+        arg3: *mut c_void,
+    ) -> ::libc::c_int;
+    unsafe fn pcre2_set_character_tables(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: *const u8,
+    ) -> ::libc::c_int;
+    unsafe fn pcre2_maketables() -> *const u8;
+    unsafe fn pcre2_maketables_free(tables: *const u8);
     unsafe fn pcre2_compile_context_free(arg1: *mut Self::pcre2_compile_context);
 
     unsafe fn pcre2_match_context_create() -> *mut Self::pcre2_match_context;
     unsafe fn pcre2_match_context_free(arg1: *mut Self::pcre2_match_context);
+    unsafe fn pcre2_set_offset_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: usize,
+    ) -> ::libc::c_int;
+    unsafe fn pcre2_set_match_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int;
+    unsafe fn pcre2_set_heap_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int;
+    unsafe fn pcre2_set_depth_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int;
 
     unsafe fn pcre2_match_data_create_from_pattern(
         arg1: *const Self::pcre2_code,
@@ -153,12 +347,28 @@ pub trait CodeUnitWidth: std::fmt::Debug {
 
     unsafe fn pcre2_get_ovector_pointer(arg1: *mut Self::pcre2_match_data) -> *mut usize;
     unsafe fn pcre2_get_ovector_count(arg1: *mut Self::pcre2_match_data) -> u32;
+    /// Returns the name of the last mark passed on the matching path of the
+    /// most recent match, or `None` if there wasn't one.
+    ///
+    /// # Safety
+    ///
+    /// The match data must be valid, and so must the code it was last used
+    /// with, since the name points into the compiled pattern.
+    unsafe fn pcre2_get_mark<'a>(
+        arg1: *mut Self::pcre2_match_data,
+    ) -> Option<&'a [Self::PCRE2_CHAR]>;
 }
 
+/// The 8-bit code unit width, used for matching on bytes.
 #[cfg(feature = "utf8")]
 #[derive(Debug)]
 pub struct CodeUnitWidth8;
 
+#[cfg(feature = "utf8")]
+thread_local! {
+    static THREAD_JIT_STACK_8: Cell<ThreadJitStack> = const { Cell::new(ThreadJitStack::NONE) };
+}
+
 #[cfg(feature = "utf8")]
 impl CodeUnitWidth for CodeUnitWidth8 {
     type pcre2_code = pcre2_code_8;
@@ -168,10 +378,22 @@ impl CodeUnitWidth for CodeUnitWidth8 {
     type pcre2_match_context = pcre2_match_context_8;
     type pcre2_match_data = pcre2_match_data_8;
     type pcre2_jit_stack = pcre2_jit_stack_8;
+    type pcre2_callout_enumerate_block = pcre2_callout_enumerate_block_8;
+    type pcre2_callout_block = pcre2_callout_block_8;
     type name_table_entry = name_table_entry_8;
     type SubjectChar = u8;
     type Pattern = String;
 
+    fn is_utf_continuation(unit: Self::SubjectChar) -> bool {
+        unit & 0xC0 == 0x80
+    }
+
+    #[cfg(feature = "bstr")]
+    fn escape_subject(subject: &[Self::SubjectChar]) -> String {
+        bstr::BStr::new(subject).to_string()
+    }
+
+    #[cfg(not(feature = "bstr"))]
     fn escape_subject(subject: &[Self::SubjectChar]) -> String {
         use std::ascii::escape_default;
         // Escape bytes.
@@ -183,6 +405,22 @@ impl CodeUnitWidth for CodeUnitWidth8 {
         s
     }
 
+    fn name_to_string(name: &[Self::SubjectChar]) -> String {
+        String::from_utf8_lossy(name).into_owned()
+    }
+
+    fn pattern_from_str(pattern: &str) -> Self::Pattern {
+        pattern.to_string()
+    }
+
+    fn pattern_units(pattern: &Self::Pattern) -> Vec<u32> {
+        pattern.bytes().map(u32::from).collect()
+    }
+
+    fn pattern_concat(parts: &[Self::Pattern]) -> Self::Pattern {
+        parts.concat()
+    }
+
     fn pattern_to_sptr_len(pattern: &Self::Pattern) -> (Self::PCRE2_SPTR, usize) {
         (pattern.as_ptr(), pattern.len())
     }
@@ -197,6 +435,77 @@ impl CodeUnitWidth for CodeUnitWidth8 {
     unsafe fn pcre2_code_free(arg1: *mut Self::pcre2_code) {
         pcre2_code_free_8(arg1)
     }
+    unsafe fn pcre2_code_copy(arg1: *const Self::pcre2_code) -> *mut Self::pcre2_code {
+        pcre2_code_copy_8(arg1)
+    }
+    unsafe fn pcre2_callout_enumerate(
+        arg1: *const Self::pcre2_code,
+        arg2: Option<
+            unsafe extern "C" fn(
+                arg1: *mut Self::pcre2_callout_enumerate_block,
+                arg2: *mut c_void,
+            ) -> c_int,
+        >,
+        arg3: *mut c_void,
+    ) -> c_int {
+        pcre2_callout_enumerate_8(arg1, arg2, arg3)
+    }
+    unsafe fn callout_from_enumerate_block(
+        block: &Self::pcre2_callout_enumerate_block,
+    ) -> CalloutEntry {
+        let string = if block.callout_string.is_null() {
+            None
+        } else {
+            Some((
+                block.callout_string_offset,
+                String::from_utf8_lossy(slice::from_raw_parts(
+                    block.callout_string,
+                    block.callout_string_length,
+                ))
+                .into_owned(),
+            ))
+        };
+        CalloutEntry {
+            number: block.callout_number,
+            pattern_position: block.pattern_position,
+            next_item_length: block.next_item_length,
+            string,
+        }
+    }
+    unsafe fn pcre2_set_callout(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: Option<
+            unsafe extern "C" fn(arg1: *mut Self::pcre2_callout_block, arg2: *mut c_void) -> c_int,
+        >,
+        arg3: *mut c_void,
+    ) -> c_int {
+        pcre2_set_callout_8(arg1, arg2, arg3)
+    }
+    unsafe fn callout_block(block: &Self::pcre2_callout_block) -> CalloutBlock<'_, Self> {
+        let string = if block.callout_string.is_null() {
+            None
+        } else {
+            Some(slice::from_raw_parts(
+                block.callout_string,
+                block.callout_string_length,
+            ))
+        };
+        CalloutBlock {
+            number: block.callout_number,
+            string,
+            subject: slice::from_raw_parts(block.subject, block.subject_length),
+            start: block.start_match,
+            position: block.current_position,
+            pattern_position: block.pattern_position,
+            next_item_length: block.next_item_length,
+            // Be defensive in case PCRE2 doesn't pass an offset vector.
+            ovector: if block.offset_vector.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(block.offset_vector, 2 * block.capture_top as usize)
+            },
+        }
+    }
     unsafe fn pcre2_compile(
         arg1: Self::PCRE2_SPTR,
         arg2: usize,
@@ -219,13 +528,20 @@ impl CodeUnitWidth for CodeUnitWidth8 {
     }
     unsafe fn pcre2_jit_stack_assign(
         arg1: *mut Self::pcre2_match_context,
+        arg2: Option<unsafe extern "C" fn(*mut c_void) -> *mut Self::pcre2_jit_stack>,
         arg3: *mut ::libc::c_void,
     ) {
-        pcre2_jit_stack_assign_8(arg1, None, arg3)
+        pcre2_jit_stack_assign_8(arg1, arg2, arg3)
+    }
+    fn thread_jit_stack() -> &'static LocalKey<Cell<ThreadJitStack>> {
+        &THREAD_JIT_STACK_8
     }
     unsafe fn pcre2_jit_stack_free(arg1: *mut Self::pcre2_jit_stack) {
         pcre2_jit_stack_free_8(arg1)
     }
+    unsafe fn pcre2_jit_free_unused_memory() {
+        pcre2_jit_free_unused_memory_8(ptr::null_mut())
+    }
 
     unsafe fn pcre2_pattern_info(
         arg1: *const Self::pcre2_code,
@@ -235,6 +551,25 @@ impl CodeUnitWidth for CodeUnitWidth8 {
         pcre2_pattern_info_8(arg1, arg2, arg3)
     }
 
+    unsafe fn pcre2_serialize_encode(
+        arg1: *mut *const Self::pcre2_code,
+        arg2: i32,
+        arg3: *mut *mut u8,
+        arg4: *mut usize,
+    ) -> i32 {
+        pcre2_serialize_encode_8(arg1, arg2, arg3, arg4, ptr::null_mut())
+    }
+    unsafe fn pcre2_serialize_free(arg1: *mut u8) {
+        pcre2_serialize_free_8(arg1)
+    }
+    unsafe fn pcre2_serialize_decode(
+        arg1: *mut *mut Self::pcre2_code,
+        arg2: i32,
+        arg3: *const u8,
+    ) -> i32 {
+        pcre2_serialize_decode_8(arg1, arg2, arg3, ptr::null_mut())
+    }
+
     unsafe fn pcre2_match(
         arg1: *const Self::pcre2_code,
         arg2: Self::PCRE2_SPTR,
@@ -247,12 +582,80 @@ impl CodeUnitWidth for CodeUnitWidth8 {
         pcre2_match_8(arg1, arg2, arg3, arg4, arg5, arg6, arg7)
     }
 
+    unsafe fn pcre2_jit_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+    ) -> ::libc::c_int {
+        pcre2_jit_match_8(arg1, arg2, arg3, arg4, arg5, arg6, arg7)
+    }
+
+    unsafe fn pcre2_dfa_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+        arg8: *mut ::libc::c_int,
+        arg9: usize,
+    ) -> ::libc::c_int {
+        pcre2_dfa_match_8(arg1, arg2, arg3, arg4, arg5, arg6, arg7, arg8, arg9)
+    }
+
+    unsafe fn pcre2_substitute(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+        arg8: Self::PCRE2_SPTR,
+        arg9: usize,
+        arg10: *mut Self::PCRE2_CHAR,
+        arg11: *mut usize,
+    ) -> ::libc::c_int {
+        pcre2_substitute_8(
+            arg1, arg2, arg3, arg4, arg5, arg6, arg7, arg8, arg9, arg10, arg11,
+        )
+    }
+
     unsafe fn pcre2_compile_context_create() -> *mut Self::pcre2_compile_context {
         pcre2_compile_context_create_8(ptr::null_mut())
     }
     unsafe fn pcre2_match_context_free(arg1: *mut Self::pcre2_match_context) {
         pcre2_match_context_free_8(arg1)
     }
+    unsafe fn pcre2_set_offset_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: usize,
+    ) -> ::libc::c_int {
+        pcre2_set_offset_limit_8(arg1, arg2)
+    }
+    unsafe fn pcre2_set_match_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_match_limit_8(arg1, arg2)
+    }
+    unsafe fn pcre2_set_heap_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_heap_limit_8(arg1, arg2)
+    }
+    unsafe fn pcre2_set_depth_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_depth_limit_8(arg1, arg2)
+    }
 
     unsafe fn pcre2_set_newline(
         arg1: *mut Self::pcre2_compile_context,
@@ -260,6 +663,46 @@ impl CodeUnitWidth for CodeUnitWidth8 {
     ) -> ::libc::c_int {
         pcre2_set_newline_8(arg1, arg2)
     }
+    unsafe fn pcre2_set_bsr(arg1: *mut Self::pcre2_compile_context, arg2: u32) -> ::libc::c_int {
+        pcre2_set_bsr_8(arg1, arg2)
+    }
+    unsafe fn pcre2_set_max_pattern_length(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: usize,
+    ) -> ::libc::c_int {
+        pcre2_set_max_pattern_length_8(arg1, arg2)
+    }
+    unsafe fn pcre2_set_parens_nest_limit(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_parens_nest_limit_8(arg1, arg2)
+    }
+    unsafe fn pcre2_set_compile_extra_options(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_compile_extra_options_8(arg1, arg2)
+    }
+    unsafe fn pcre2_set_compile_recursion_guard(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: Option<unsafe extern "C" fn(u32, *mut c_void) -> c_int>,
+        arg3: *mut c_void,
+    ) -> ::libc::c_int {
+        pcre2_set_compile_recursion_guard_8(arg1, arg2, arg3)
+    }
+    unsafe fn pcre2_set_character_tables(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: *const u8,
+    ) -> ::libc::c_int {
+        pcre2_set_character_tables_8(arg1, arg2)
+    }
+    unsafe fn pcre2_maketables() -> *const u8 {
+        pcre2_maketables_8(ptr::null_mut())
+    }
+    unsafe fn pcre2_maketables_free(tables: *const u8) {
+        pcre2_maketables_free_8(ptr::null_mut(), tables)
+    }
     unsafe fn pcre2_compile_context_free(arg1: *mut Self::pcre2_compile_context) {
         pcre2_compile_context_free_8(arg1)
     }
@@ -282,53 +725,160 @@ impl CodeUnitWidth for CodeUnitWidth8 {
     unsafe fn pcre2_get_ovector_count(arg1: *mut Self::pcre2_match_data) -> u32 {
         pcre2_get_ovector_count_8(arg1)
     }
+    unsafe fn pcre2_get_mark<'a>(
+        arg1: *mut Self::pcre2_match_data,
+    ) -> Option<&'a [Self::PCRE2_CHAR]> {
+        let mark = pcre2_get_mark_8(arg1);
+        if mark.is_null() {
+            return None;
+        }
+        // The name is nul-terminated and preceded by its length.
+        let len = *mark.sub(1) as usize;
+        Some(slice::from_raw_parts(mark, len))
+    }
 }
 
-#[cfg(feature = "utf32")]
+/// The 16-bit code unit width, used for matching on UTF-16 code units.
+#[cfg(feature = "utf16")]
 #[derive(Debug)]
-pub struct CodeUnitWidth32;
+pub struct CodeUnitWidth16;
 
-#[cfg(feature = "utf32")]
-impl CodeUnitWidth for CodeUnitWidth32 {
-    type pcre2_code = pcre2_code_32;
-    type PCRE2_CHAR = PCRE2_UCHAR32;
-    type PCRE2_SPTR = PCRE2_SPTR32;
-    type pcre2_compile_context = pcre2_compile_context_32;
-    type pcre2_match_context = pcre2_match_context_32;
-    type pcre2_match_data = pcre2_match_data_32;
-    type pcre2_jit_stack = pcre2_jit_stack_32;
-    type name_table_entry = name_table_entry_32;
-    type SubjectChar = char;
-    type Pattern = Box<[char]>;
+#[cfg(feature = "utf16")]
+thread_local! {
+    static THREAD_JIT_STACK_16: Cell<ThreadJitStack> = const { Cell::new(ThreadJitStack::NONE) };
+}
+
+#[cfg(feature = "utf16")]
+impl CodeUnitWidth for CodeUnitWidth16 {
+    type pcre2_code = pcre2_code_16;
+    type PCRE2_CHAR = PCRE2_UCHAR16;
+    type PCRE2_SPTR = PCRE2_SPTR16;
+    type pcre2_compile_context = pcre2_compile_context_16;
+    type pcre2_match_context = pcre2_match_context_16;
+    type pcre2_match_data = pcre2_match_data_16;
+    type pcre2_jit_stack = pcre2_jit_stack_16;
+    type pcre2_callout_enumerate_block = pcre2_callout_enumerate_block_16;
+    type pcre2_callout_block = pcre2_callout_block_16;
+    type name_table_entry = name_table_entry_16;
+    type SubjectChar = u16;
+    type Pattern = Box<[u16]>;
+
+    fn is_utf_continuation(unit: Self::SubjectChar) -> bool {
+        // Low surrogates continue the codepoint that a high surrogate starts.
+        (0xDC00..=0xDFFF).contains(&unit)
+    }
 
     fn escape_subject(subject: &[Self::SubjectChar]) -> String {
-        use std::ascii::escape_default;
-        // Escape bytes.
+        // Escape code units that aren't printable ASCII.
         let mut s = String::new();
-        for &c in subject {
-            let mut bytes = [0; 4];
-            for &b in c.encode_utf8(&mut bytes).as_bytes() {
-                // Escape the byte.
-                let escaped = escape_default(b).collect::<Vec<_>>();
-                s.push_str(&String::from_utf8_lossy(&escaped));
+        for &unit in subject {
+            match u8::try_from(unit) {
+                Ok(b) if b.is_ascii_graphic() || b == b' ' => s.push(char::from(b)),
+                _ => s.push_str(&format!("\\u{{{:04X}}}", unit)),
             }
         }
         s
     }
 
+    fn name_to_string(name: &[Self::SubjectChar]) -> String {
+        String::from_utf16_lossy(name)
+    }
+
+    fn pattern_from_str(pattern: &str) -> Self::Pattern {
+        pattern.encode_utf16().collect()
+    }
+
+    fn pattern_units(pattern: &Self::Pattern) -> Vec<u32> {
+        pattern.iter().copied().map(u32::from).collect()
+    }
+
+    fn pattern_concat(parts: &[Self::Pattern]) -> Self::Pattern {
+        parts.concat().into_boxed_slice()
+    }
+
     fn pattern_to_sptr_len(pattern: &Self::Pattern) -> (Self::PCRE2_SPTR, usize) {
-        (pattern.as_ptr() as *const u32, pattern.len())
+        (pattern.as_ptr(), pattern.len())
     }
 
     fn subject_to_sptr_len(subject: &[Self::SubjectChar]) -> (Self::PCRE2_SPTR, usize) {
-        (subject.as_ptr() as *const u32, subject.len())
+        (subject.as_ptr(), subject.len())
     }
 
     unsafe fn pcre2_config(arg1: u32, arg2: *mut ::libc::c_void) -> ::libc::c_int {
-        pcre2_config_32(arg1, arg2)
+        pcre2_config_16(arg1, arg2)
     }
     unsafe fn pcre2_code_free(arg1: *mut Self::pcre2_code) {
-        pcre2_code_free_32(arg1)
+        pcre2_code_free_16(arg1)
+    }
+    unsafe fn pcre2_code_copy(arg1: *const Self::pcre2_code) -> *mut Self::pcre2_code {
+        pcre2_code_copy_16(arg1)
+    }
+    unsafe fn pcre2_callout_enumerate(
+        arg1: *const Self::pcre2_code,
+        arg2: Option<
+            unsafe extern "C" fn(
+                arg1: *mut Self::pcre2_callout_enumerate_block,
+                arg2: *mut c_void,
+            ) -> c_int,
+        >,
+        arg3: *mut c_void,
+    ) -> c_int {
+        pcre2_callout_enumerate_16(arg1, arg2, arg3)
+    }
+    unsafe fn callout_from_enumerate_block(
+        block: &Self::pcre2_callout_enumerate_block,
+    ) -> CalloutEntry {
+        let string = if block.callout_string.is_null() {
+            None
+        } else {
+            Some((
+                block.callout_string_offset,
+                String::from_utf16_lossy(slice::from_raw_parts(
+                    block.callout_string,
+                    block.callout_string_length,
+                )),
+            ))
+        };
+        CalloutEntry {
+            number: block.callout_number,
+            pattern_position: block.pattern_position,
+            next_item_length: block.next_item_length,
+            string,
+        }
+    }
+    unsafe fn pcre2_set_callout(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: Option<
+            unsafe extern "C" fn(arg1: *mut Self::pcre2_callout_block, arg2: *mut c_void) -> c_int,
+        >,
+        arg3: *mut c_void,
+    ) -> c_int {
+        pcre2_set_callout_16(arg1, arg2, arg3)
+    }
+    unsafe fn callout_block(block: &Self::pcre2_callout_block) -> CalloutBlock<'_, Self> {
+        let string = if block.callout_string.is_null() {
+            None
+        } else {
+            Some(slice::from_raw_parts(
+                block.callout_string,
+                block.callout_string_length,
+            ))
+        };
+        CalloutBlock {
+            number: block.callout_number,
+            string,
+            subject: slice::from_raw_parts(block.subject, block.subject_length),
+            start: block.start_match,
+            position: block.current_position,
+            pattern_position: block.pattern_position,
+            next_item_length: block.next_item_length,
+            // Be defensive in case PCRE2 doesn't pass an offset vector.
+            ovector: if block.offset_vector.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(block.offset_vector, 2 * block.capture_top as usize)
+            },
+        }
     }
     unsafe fn pcre2_compile(
         arg1: Self::PCRE2_SPTR,
@@ -338,26 +888,33 @@ impl CodeUnitWidth for CodeUnitWidth32 {
         arg5: *mut ::libc::size_t,
         arg6: *mut Self::pcre2_compile_context,
     ) -> *mut Self::pcre2_code {
-        pcre2_compile_32(arg1, arg2, arg3, arg4, arg5, arg6)
+        pcre2_compile_16(arg1, arg2, arg3, arg4, arg5, arg6)
     }
 
     unsafe fn pcre2_jit_stack_create(
         arg1: ::libc::size_t,
         arg2: ::libc::size_t,
     ) -> *mut Self::pcre2_jit_stack {
-        pcre2_jit_stack_create_32(arg1, arg2, ptr::null_mut())
+        pcre2_jit_stack_create_16(arg1, arg2, ptr::null_mut())
     }
     unsafe fn pcre2_jit_compile(arg1: *mut Self::pcre2_code, arg2: u32) -> ::libc::c_int {
-        pcre2_jit_compile_32(arg1, arg2)
+        pcre2_jit_compile_16(arg1, arg2)
     }
     unsafe fn pcre2_jit_stack_assign(
         arg1: *mut Self::pcre2_match_context,
+        arg2: Option<unsafe extern "C" fn(*mut c_void) -> *mut Self::pcre2_jit_stack>,
         arg3: *mut ::libc::c_void,
     ) {
-        pcre2_jit_stack_assign_32(arg1, None, arg3)
+        pcre2_jit_stack_assign_16(arg1, arg2, arg3)
+    }
+    fn thread_jit_stack() -> &'static LocalKey<Cell<ThreadJitStack>> {
+        &THREAD_JIT_STACK_16
     }
     unsafe fn pcre2_jit_stack_free(arg1: *mut Self::pcre2_jit_stack) {
-        pcre2_jit_stack_free_32(arg1)
+        pcre2_jit_stack_free_16(arg1)
+    }
+    unsafe fn pcre2_jit_free_unused_memory() {
+        pcre2_jit_free_unused_memory_16(ptr::null_mut())
     }
 
     unsafe fn pcre2_pattern_info(
@@ -365,7 +922,414 @@ impl CodeUnitWidth for CodeUnitWidth32 {
         arg2: u32,
         arg3: *mut ::libc::c_void,
     ) -> ::libc::c_int {
-        pcre2_pattern_info_32(arg1, arg2, arg3)
+        pcre2_pattern_info_16(arg1, arg2, arg3)
+    }
+
+    unsafe fn pcre2_serialize_encode(
+        arg1: *mut *const Self::pcre2_code,
+        arg2: i32,
+        arg3: *mut *mut u8,
+        arg4: *mut usize,
+    ) -> i32 {
+        pcre2_serialize_encode_16(arg1, arg2, arg3, arg4, ptr::null_mut())
+    }
+    unsafe fn pcre2_serialize_free(arg1: *mut u8) {
+        pcre2_serialize_free_16(arg1)
+    }
+    unsafe fn pcre2_serialize_decode(
+        arg1: *mut *mut Self::pcre2_code,
+        arg2: i32,
+        arg3: *const u8,
+    ) -> i32 {
+        pcre2_serialize_decode_16(arg1, arg2, arg3, ptr::null_mut())
+    }
+
+    unsafe fn pcre2_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+    ) -> ::libc::c_int {
+        pcre2_match_16(arg1, arg2, arg3, arg4, arg5, arg6, arg7)
+    }
+
+    unsafe fn pcre2_jit_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+    ) -> ::libc::c_int {
+        pcre2_jit_match_16(arg1, arg2, arg3, arg4, arg5, arg6, arg7)
+    }
+
+    unsafe fn pcre2_dfa_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+        arg8: *mut ::libc::c_int,
+        arg9: usize,
+    ) -> ::libc::c_int {
+        pcre2_dfa_match_16(arg1, arg2, arg3, arg4, arg5, arg6, arg7, arg8, arg9)
+    }
+
+    unsafe fn pcre2_substitute(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+        arg8: Self::PCRE2_SPTR,
+        arg9: usize,
+        arg10: *mut Self::PCRE2_CHAR,
+        arg11: *mut usize,
+    ) -> ::libc::c_int {
+        pcre2_substitute_16(
+            arg1, arg2, arg3, arg4, arg5, arg6, arg7, arg8, arg9, arg10, arg11,
+        )
+    }
+
+    unsafe fn pcre2_compile_context_create() -> *mut Self::pcre2_compile_context {
+        pcre2_compile_context_create_16(ptr::null_mut())
+    }
+    unsafe fn pcre2_match_context_free(arg1: *mut Self::pcre2_match_context) {
+        pcre2_match_context_free_16(arg1)
+    }
+    unsafe fn pcre2_set_offset_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: usize,
+    ) -> ::libc::c_int {
+        pcre2_set_offset_limit_16(arg1, arg2)
+    }
+    unsafe fn pcre2_set_match_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_match_limit_16(arg1, arg2)
+    }
+    unsafe fn pcre2_set_heap_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_heap_limit_16(arg1, arg2)
+    }
+    unsafe fn pcre2_set_depth_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_depth_limit_16(arg1, arg2)
+    }
+
+    unsafe fn pcre2_set_newline(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_newline_16(arg1, arg2)
+    }
+    unsafe fn pcre2_set_bsr(arg1: *mut Self::pcre2_compile_context, arg2: u32) -> ::libc::c_int {
+        pcre2_set_bsr_16(arg1, arg2)
+    }
+    unsafe fn pcre2_set_max_pattern_length(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: usize,
+    ) -> ::libc::c_int {
+        pcre2_set_max_pattern_length_16(arg1, arg2)
+    }
+    unsafe fn pcre2_set_parens_nest_limit(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_parens_nest_limit_16(arg1, arg2)
+    }
+    unsafe fn pcre2_set_compile_extra_options(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_compile_extra_options_16(arg1, arg2)
+    }
+    unsafe fn pcre2_set_compile_recursion_guard(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: Option<unsafe extern "C" fn(u32, *mut c_void) -> c_int>,
+        arg3: *mut c_void,
+    ) -> ::libc::c_int {
+        pcre2_set_compile_recursion_guard_16(arg1, arg2, arg3)
+    }
+    unsafe fn pcre2_set_character_tables(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: *const u8,
+    ) -> ::libc::c_int {
+        pcre2_set_character_tables_16(arg1, arg2)
+    }
+    unsafe fn pcre2_maketables() -> *const u8 {
+        pcre2_maketables_16(ptr::null_mut())
+    }
+    unsafe fn pcre2_maketables_free(tables: *const u8) {
+        pcre2_maketables_free_16(ptr::null_mut(), tables)
+    }
+    unsafe fn pcre2_compile_context_free(arg1: *mut Self::pcre2_compile_context) {
+        pcre2_compile_context_free_16(arg1)
+    }
+    unsafe fn pcre2_match_context_create() -> *mut Self::pcre2_match_context {
+        pcre2_match_context_create_16(ptr::null_mut())
+    }
+
+    unsafe fn pcre2_match_data_create_from_pattern(
+        arg1: *const Self::pcre2_code,
+    ) -> *mut Self::pcre2_match_data {
+        pcre2_match_data_create_from_pattern_16(arg1, ptr::null_mut())
+    }
+    unsafe fn pcre2_match_data_free(arg1: *mut Self::pcre2_match_data) {
+        pcre2_match_data_free_16(arg1)
+    }
+
+    unsafe fn pcre2_get_ovector_pointer(arg1: *mut Self::pcre2_match_data) -> *mut usize {
+        pcre2_get_ovector_pointer_16(arg1)
+    }
+    unsafe fn pcre2_get_ovector_count(arg1: *mut Self::pcre2_match_data) -> u32 {
+        pcre2_get_ovector_count_16(arg1)
+    }
+    unsafe fn pcre2_get_mark<'a>(
+        arg1: *mut Self::pcre2_match_data,
+    ) -> Option<&'a [Self::PCRE2_CHAR]> {
+        let mark = pcre2_get_mark_16(arg1);
+        if mark.is_null() {
+            return None;
+        }
+        // The name is nul-terminated and preceded by its length.
+        let len = *mark.sub(1) as usize;
+        Some(slice::from_raw_parts(mark, len))
+    }
+}
+
+/// The 32-bit code unit width, used for matching on sequences of chars.
+#[cfg(feature = "utf32")]
+#[derive(Debug)]
+pub struct CodeUnitWidth32;
+
+#[cfg(feature = "utf32")]
+thread_local! {
+    static THREAD_JIT_STACK_32: Cell<ThreadJitStack> = const { Cell::new(ThreadJitStack::NONE) };
+}
+
+#[cfg(feature = "utf32")]
+impl CodeUnitWidth for CodeUnitWidth32 {
+    type pcre2_code = pcre2_code_32;
+    type PCRE2_CHAR = PCRE2_UCHAR32;
+    type PCRE2_SPTR = PCRE2_SPTR32;
+    type pcre2_compile_context = pcre2_compile_context_32;
+    type pcre2_match_context = pcre2_match_context_32;
+    type pcre2_match_data = pcre2_match_data_32;
+    type pcre2_jit_stack = pcre2_jit_stack_32;
+    type pcre2_callout_enumerate_block = pcre2_callout_enumerate_block_32;
+    type pcre2_callout_block = pcre2_callout_block_32;
+    type name_table_entry = name_table_entry_32;
+    type SubjectChar = char;
+    type Pattern = Box<[char]>;
+
+    fn is_utf_continuation(_: Self::SubjectChar) -> bool {
+        false
+    }
+
+    fn check_output(output: &[Self::PCRE2_CHAR]) -> Result<(), c_int> {
+        // PCRE2 happily produces code points that aren't valid `char`s, for
+        // example from a `\x{d800}` escape in an extended replacement.
+        match output.iter().find(|&&c| char::from_u32(c).is_none()) {
+            None => Ok(()),
+            Some(&c) if c > 0x10FFFF => Err(PCRE2_ERROR_UTF32_ERR2),
+            Some(_) => Err(PCRE2_ERROR_UTF32_ERR1),
+        }
+    }
+
+    fn escape_subject(subject: &[Self::SubjectChar]) -> String {
+        use std::ascii::escape_default;
+        // Escape bytes.
+        let mut s = String::new();
+        for &c in subject {
+            let mut bytes = [0; 4];
+            for &b in c.encode_utf8(&mut bytes).as_bytes() {
+                // Escape the byte.
+                let escaped = escape_default(b).collect::<Vec<_>>();
+                s.push_str(&String::from_utf8_lossy(&escaped));
+            }
+        }
+        s
+    }
+
+    fn name_to_string(name: &[Self::SubjectChar]) -> String {
+        name.iter().collect()
+    }
+
+    fn pattern_from_str(pattern: &str) -> Self::Pattern {
+        pattern.chars().collect()
+    }
+
+    fn pattern_units(pattern: &Self::Pattern) -> Vec<u32> {
+        pattern.iter().copied().map(u32::from).collect()
+    }
+
+    fn pattern_concat(parts: &[Self::Pattern]) -> Self::Pattern {
+        parts.concat().into_boxed_slice()
+    }
+
+    fn pattern_to_sptr_len(pattern: &Self::Pattern) -> (Self::PCRE2_SPTR, usize) {
+        (pattern.as_ptr() as *const u32, pattern.len())
+    }
+
+    fn subject_to_sptr_len(subject: &[Self::SubjectChar]) -> (Self::PCRE2_SPTR, usize) {
+        (subject.as_ptr() as *const u32, subject.len())
+    }
+
+    unsafe fn pcre2_config(arg1: u32, arg2: *mut ::libc::c_void) -> ::libc::c_int {
+        pcre2_config_32(arg1, arg2)
+    }
+    unsafe fn pcre2_code_free(arg1: *mut Self::pcre2_code) {
+        pcre2_code_free_32(arg1)
+    }
+    unsafe fn pcre2_code_copy(arg1: *const Self::pcre2_code) -> *mut Self::pcre2_code {
+        pcre2_code_copy_32(arg1)
+    }
+    unsafe fn pcre2_callout_enumerate(
+        arg1: *const Self::pcre2_code,
+        arg2: Option<
+            unsafe extern "C" fn(
+                arg1: *mut Self::pcre2_callout_enumerate_block,
+                arg2: *mut c_void,
+            ) -> c_int,
+        >,
+        arg3: *mut c_void,
+    ) -> c_int {
+        pcre2_callout_enumerate_32(arg1, arg2, arg3)
+    }
+    unsafe fn callout_from_enumerate_block(
+        block: &Self::pcre2_callout_enumerate_block,
+    ) -> CalloutEntry {
+        let string = if block.callout_string.is_null() {
+            None
+        } else {
+            Some((
+                block.callout_string_offset,
+                slice::from_raw_parts(
+                    block.callout_string as *const char,
+                    block.callout_string_length,
+                )
+                .iter()
+                .collect(),
+            ))
+        };
+        CalloutEntry {
+            number: block.callout_number,
+            pattern_position: block.pattern_position,
+            next_item_length: block.next_item_length,
+            string,
+        }
+    }
+    unsafe fn pcre2_set_callout(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: Option<
+            unsafe extern "C" fn(arg1: *mut Self::pcre2_callout_block, arg2: *mut c_void) -> c_int,
+        >,
+        arg3: *mut c_void,
+    ) -> c_int {
+        pcre2_set_callout_32(arg1, arg2, arg3)
+    }
+    unsafe fn callout_block(block: &Self::pcre2_callout_block) -> CalloutBlock<'_, Self> {
+        let string = if block.callout_string.is_null() {
+            None
+        } else {
+            Some(slice::from_raw_parts(
+                block.callout_string as *const char,
+                block.callout_string_length,
+            ))
+        };
+        CalloutBlock {
+            number: block.callout_number,
+            string,
+            subject: slice::from_raw_parts(block.subject as *const char, block.subject_length),
+            start: block.start_match,
+            position: block.current_position,
+            pattern_position: block.pattern_position,
+            next_item_length: block.next_item_length,
+            // Be defensive in case PCRE2 doesn't pass an offset vector.
+            ovector: if block.offset_vector.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(block.offset_vector, 2 * block.capture_top as usize)
+            },
+        }
+    }
+    unsafe fn pcre2_compile(
+        arg1: Self::PCRE2_SPTR,
+        arg2: usize,
+        arg3: u32,
+        arg4: *mut ::libc::c_int,
+        arg5: *mut ::libc::size_t,
+        arg6: *mut Self::pcre2_compile_context,
+    ) -> *mut Self::pcre2_code {
+        pcre2_compile_32(arg1, arg2, arg3, arg4, arg5, arg6)
+    }
+
+    unsafe fn pcre2_jit_stack_create(
+        arg1: ::libc::size_t,
+        arg2: ::libc::size_t,
+    ) -> *mut Self::pcre2_jit_stack {
+        pcre2_jit_stack_create_32(arg1, arg2, ptr::null_mut())
+    }
+    unsafe fn pcre2_jit_compile(arg1: *mut Self::pcre2_code, arg2: u32) -> ::libc::c_int {
+        pcre2_jit_compile_32(arg1, arg2)
+    }
+    unsafe fn pcre2_jit_stack_assign(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: Option<unsafe extern "C" fn(*mut c_void) -> *mut Self::pcre2_jit_stack>,
+        arg3: *mut ::libc::c_void,
+    ) {
+        pcre2_jit_stack_assign_32(arg1, arg2, arg3)
+    }
+    fn thread_jit_stack() -> &'static LocalKey<Cell<ThreadJitStack>> {
+        &THREAD_JIT_STACK_32
+    }
+    unsafe fn pcre2_jit_stack_free(arg1: *mut Self::pcre2_jit_stack) {
+        pcre2_jit_stack_free_32(arg1)
+    }
+    unsafe fn pcre2_jit_free_unused_memory() {
+        pcre2_jit_free_unused_memory_32(ptr::null_mut())
+    }
+
+    unsafe fn pcre2_pattern_info(
+        arg1: *const Self::pcre2_code,
+        arg2: u32,
+        arg3: *mut ::libc::c_void,
+    ) -> ::libc::c_int {
+        pcre2_pattern_info_32(arg1, arg2, arg3)
+    }
+
+    unsafe fn pcre2_serialize_encode(
+        arg1: *mut *const Self::pcre2_code,
+        arg2: i32,
+        arg3: *mut *mut u8,
+        arg4: *mut usize,
+    ) -> i32 {
+        pcre2_serialize_encode_32(arg1, arg2, arg3, arg4, ptr::null_mut())
+    }
+    unsafe fn pcre2_serialize_free(arg1: *mut u8) {
+        pcre2_serialize_free_32(arg1)
+    }
+    unsafe fn pcre2_serialize_decode(
+        arg1: *mut *mut Self::pcre2_code,
+        arg2: i32,
+        arg3: *const u8,
+    ) -> i32 {
+        pcre2_serialize_decode_32(arg1, arg2, arg3, ptr::null_mut())
     }
 
     unsafe fn pcre2_match(
@@ -380,12 +1344,80 @@ impl CodeUnitWidth for CodeUnitWidth32 {
         pcre2_match_32(arg1, arg2, arg3, arg4, arg5, arg6, arg7)
     }
 
+    unsafe fn pcre2_jit_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+    ) -> ::libc::c_int {
+        pcre2_jit_match_32(arg1, arg2, arg3, arg4, arg5, arg6, arg7)
+    }
+
+    unsafe fn pcre2_dfa_match(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+        arg8: *mut ::libc::c_int,
+        arg9: usize,
+    ) -> ::libc::c_int {
+        pcre2_dfa_match_32(arg1, arg2, arg3, arg4, arg5, arg6, arg7, arg8, arg9)
+    }
+
+    unsafe fn pcre2_substitute(
+        arg1: *const Self::pcre2_code,
+        arg2: Self::PCRE2_SPTR,
+        arg3: usize,
+        arg4: usize,
+        arg5: u32,
+        arg6: *mut Self::pcre2_match_data,
+        arg7: *mut Self::pcre2_match_context,
+        arg8: Self::PCRE2_SPTR,
+        arg9: usize,
+        arg10: *mut Self::PCRE2_CHAR,
+        arg11: *mut usize,
+    ) -> ::libc::c_int {
+        pcre2_substitute_32(
+            arg1, arg2, arg3, arg4, arg5, arg6, arg7, arg8, arg9, arg10, arg11,
+        )
+    }
+
     unsafe fn pcre2_compile_context_create() -> *mut Self::pcre2_compile_context {
         pcre2_compile_context_create_32(ptr::null_mut())
     }
     unsafe fn pcre2_match_context_free(arg1: *mut Self::pcre2_match_context) {
         pcre2_match_context_free_32(arg1)
     }
+    unsafe fn pcre2_set_offset_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: usize,
+    ) -> ::libc::c_int {
+        pcre2_set_offset_limit_32(arg1, arg2)
+    }
+    unsafe fn pcre2_set_match_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_match_limit_32(arg1, arg2)
+    }
+    unsafe fn pcre2_set_heap_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_heap_limit_32(arg1, arg2)
+    }
+    unsafe fn pcre2_set_depth_limit(
+        arg1: *mut Self::pcre2_match_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_depth_limit_32(arg1, arg2)
+    }
 
     unsafe fn pcre2_set_newline(
         arg1: *mut Self::pcre2_compile_context,
@@ -393,6 +1425,46 @@ impl CodeUnitWidth for CodeUnitWidth32 {
     ) -> ::libc::c_int {
         pcre2_set_newline_32(arg1, arg2)
     }
+    unsafe fn pcre2_set_bsr(arg1: *mut Self::pcre2_compile_context, arg2: u32) -> ::libc::c_int {
+        pcre2_set_bsr_32(arg1, arg2)
+    }
+    unsafe fn pcre2_set_max_pattern_length(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: usize,
+    ) -> ::libc::c_int {
+        pcre2_set_max_pattern_length_32(arg1, arg2)
+    }
+    unsafe fn pcre2_set_parens_nest_limit(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_parens_nest_limit_32(arg1, arg2)
+    }
+    unsafe fn pcre2_set_compile_extra_options(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: u32,
+    ) -> ::libc::c_int {
+        pcre2_set_compile_extra_options_32(arg1, arg2)
+    }
+    unsafe fn pcre2_set_compile_recursion_guard(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: Option<unsafe extern "C" fn(u32, *mut c_void) -> c_int>,
+        arg3: *mut c_void,
+    ) -> ::libc::c_int {
+        pcre2_set_compile_recursion_guard_32(arg1, arg2, arg3)
+    }
+    unsafe fn pcre2_set_character_tables(
+        arg1: *mut Self::pcre2_compile_context,
+        arg2: *const u8,
+    ) -> ::libc::c_int {
+        pcre2_set_character_tables_32(arg1, arg2)
+    }
+    unsafe fn pcre2_maketables() -> *const u8 {
+        pcre2_maketables_32(ptr::null_mut())
+    }
+    unsafe fn pcre2_maketables_free(tables: *const u8) {
+        pcre2_maketables_free_32(ptr::null_mut(), tables)
+    }
     unsafe fn pcre2_compile_context_free(arg1: *mut Self::pcre2_compile_context) {
         pcre2_compile_context_free_32(arg1)
     }
@@ -415,6 +1487,17 @@ impl CodeUnitWidth for CodeUnitWidth32 {
     unsafe fn pcre2_get_ovector_count(arg1: *mut Self::pcre2_match_data) -> u32 {
         pcre2_get_ovector_count_32(arg1)
     }
+    unsafe fn pcre2_get_mark<'a>(
+        arg1: *mut Self::pcre2_match_data,
+    ) -> Option<&'a [Self::PCRE2_CHAR]> {
+        let mark = pcre2_get_mark_32(arg1);
+        if mark.is_null() {
+            return None;
+        }
+        // The name is nul-terminated and preceded by its length.
+        let len = *mark.sub(1) as usize;
+        Some(slice::from_raw_parts(mark, len))
+    }
 }
 
 /// Returns true if and only if PCRE2 believes that JIT is available.
@@ -428,6 +1511,114 @@ pub fn is_jit_available<W: CodeUnitWidth>() -> bool {
     rc == 1
 }
 
+/// Returns a description of the architecture PCRE2's JIT generates code for,
+/// such as `x86 64bit (little endian + unaligned)`, or `None` if the JIT
+/// isn't available.
+pub fn jit_target<W: CodeUnitWidth>() -> Option<String> {
+    if !is_jit_available::<W>() {
+        return None;
+    }
+    // Asking for the length first includes room for the NUL terminator.
+    let len = unsafe { W::pcre2_config(PCRE2_CONFIG_JITTARGET, ptr::null_mut()) };
+    if len < 0 {
+        panic!("BUG: {}", Error::jit(len));
+    }
+    let mut buf: Vec<W::SubjectChar> = Vec::with_capacity(len as usize);
+    // SAFETY: The buffer has room for the whole string, which PCRE2 always
+    // writes as ASCII and which is therefore valid for every code unit type.
+    // PCRE2 returns the number of code units written, including the NUL
+    // terminator.
+    let written =
+        unsafe { W::pcre2_config(PCRE2_CONFIG_JITTARGET, buf.as_mut_ptr() as *mut c_void) };
+    if written < 1 {
+        panic!("BUG: {}", Error::jit(written));
+    }
+    unsafe { buf.set_len(written as usize - 1) };
+    Some(W::name_to_string(&buf))
+}
+
+/// Release the memory that PCRE2's JIT has allocated for compiled code but
+/// no longer uses.
+///
+/// The JIT allocates executable memory in large chunks, which are kept
+/// around after the regexes using them are dropped. Long running processes
+/// that compile and drop many JIT compiled regexes can call this
+/// periodically to return the unused chunks to the operating system.
+///
+/// This crate always uses PCRE2's default memory management, so no general
+/// context is passed to PCRE2. This has no effect if the JIT isn't
+/// available.
+///
+/// This corresponds to `pcre2_jit_free_unused_memory`.
+pub fn jit_free_unused_memory<W: CodeUnitWidth>() {
+    unsafe { W::pcre2_jit_free_unused_memory() }
+}
+
+/// Returns the newline convention PCRE2 uses when a compile context doesn't
+/// set one, as one of the `PCRE2_NEWLINE_*` values.
+pub fn default_newline<W: CodeUnitWidth>() -> u32 {
+    let mut rc: u32 = 0;
+    let error_code =
+        unsafe { W::pcre2_config(PCRE2_CONFIG_NEWLINE, &mut rc as *mut _ as *mut c_void) };
+    if error_code < 0 {
+        panic!("BUG: {}", Error::option(error_code));
+    }
+    rc
+}
+
+/// Returns the convention for what `\R` matches that PCRE2 uses when a
+/// compile context doesn't set one, as one of the `PCRE2_BSR_*` values.
+pub fn default_bsr<W: CodeUnitWidth>() -> u32 {
+    let mut rc: u32 = 0;
+    let error_code = unsafe { W::pcre2_config(PCRE2_CONFIG_BSR, &mut rc as *mut _ as *mut c_void) };
+    if error_code < 0 {
+        panic!("BUG: {}", Error::option(error_code));
+    }
+    rc
+}
+
+/// Returns the limit PCRE2 uses when a match context doesn't set one, where
+/// `what` is one of `PCRE2_CONFIG_MATCHLIMIT`, `PCRE2_CONFIG_DEPTHLIMIT` or
+/// `PCRE2_CONFIG_HEAPLIMIT`.
+fn default_limit<W: CodeUnitWidth>(what: u32) -> u32 {
+    let mut rc: u32 = 0;
+    let error_code = unsafe { W::pcre2_config(what, &mut rc as *mut _ as *mut c_void) };
+    if error_code < 0 {
+        panic!("BUG: {}", Error::option(error_code));
+    }
+    rc
+}
+
+/// The length of PCRE2's character tables, in bytes.
+pub const TABLES_LENGTH: usize = 1088;
+
+/// Build character tables for the current locale with `pcre2_maketables`.
+///
+/// If memory could not be allocated for the tables, then this panics.
+pub fn maketables<W: CodeUnitWidth>() -> Box<[u8]> {
+    let tables = unsafe { W::pcre2_maketables() };
+    assert!(!tables.is_null(), "could not allocate character tables");
+    // SAFETY: PCRE2 returned a TABLES_LENGTH byte allocation, which we copy
+    // before freeing it with the same (default) general context.
+    let copy = unsafe { slice::from_raw_parts(tables, TABLES_LENGTH) }.into();
+    unsafe { W::pcre2_maketables_free(tables) };
+    copy
+}
+
+/// Returns true if the character tables in bytes produced by
+/// `pcre2_serialize_encode` are PCRE2's built-in tables.
+///
+/// The tables follow the header, so this compares them against those of a
+/// regex compiled without setting any tables.
+pub fn serialized_default_tables<W: CodeUnitWidth>(bytes: &[u8]) -> bool {
+    const START: usize = 4 * 4;
+    let default = Code::<W>::new(&W::pattern_from_str(""), 0, CompileContext::new())
+        .and_then(|code| code.serialize())
+        .expect("serializing an empty regex");
+    let tables = |bytes: &[u8]| bytes.get(START..START + TABLES_LENGTH).map(<[u8]>::to_vec);
+    tables(bytes) == tables(&default)
+}
+
 /// Returns the version of PCRE2 being used.
 ///
 /// The tuple returned corresponds to the major and minor version, e.g.,
@@ -439,7 +1630,9 @@ pub fn version() -> (u32, u32) {
 /// A low level representation of a compiled PCRE2 code object.
 pub struct Code<W: CodeUnitWidth> {
     code: *mut W::pcre2_code,
-    compiled_jit: bool,
+    /// The PCRE2_JIT_* options this code was JIT compiled with, or `0` if it
+    /// wasn't JIT compiled.
+    jit_options: u32,
     // We hang on to this but don't use it so that it gets freed when the
     // compiled code gets freed. It's not clear whether this is necessary or
     // not, but presumably doesn't cost us much to be conservative.
@@ -488,26 +1681,109 @@ impl<W: CodeUnitWidth> Code<W> {
         } else {
             Ok(Code {
                 code,
-                compiled_jit: false,
+                jit_options: 0,
                 ctx,
             })
         }
     }
 
-    /// JIT compile this code object.
+    /// Return a copy of this code object that isn't JIT compiled, even if
+    /// this one is.
+    ///
+    /// This panics if memory could not be allocated for the copy.
+    pub fn copy(&self) -> Code<W> {
+        let code = unsafe { W::pcre2_code_copy(self.code) };
+        assert!(!code.is_null(), "failed to copy code object");
+        // The copy refers to the same character tables, so it must keep
+        // them alive as well.
+        let mut ctx = CompileContext::new();
+        ctx.tables = self.ctx.tables.clone();
+        Code {
+            code,
+            jit_options: 0,
+            ctx,
+        }
+    }
+
+    /// JIT compile this code object for complete matches, and additionally
+    /// for partial matches in the modes given by `options`, which may
+    /// contain PCRE2_JIT_PARTIAL_SOFT and PCRE2_JIT_PARTIAL_HARD.
     ///
     /// If there was a problem performing JIT compilation, then this returns
     /// an error.
-    pub fn jit_compile(&mut self) -> Result<(), Error> {
-        let error_code = unsafe { W::pcre2_jit_compile(self.code, PCRE2_JIT_COMPLETE) };
+    pub fn jit_compile(&mut self, options: u32) -> Result<(), Error> {
+        let options = options | PCRE2_JIT_COMPLETE;
+        let error_code = unsafe { W::pcre2_jit_compile(self.code, options) };
         if error_code == 0 {
-            self.compiled_jit = true;
+            self.jit_options = options;
             Ok(())
         } else {
             Err(Error::jit(error_code))
         }
     }
 
+    /// Returns true if and only if this code object was JIT compiled.
+    pub fn compiled_jit(&self) -> bool {
+        self.jit_options != 0
+    }
+
+    /// Returns the PCRE2_JIT_* options this code object was JIT compiled
+    /// with, or `0` if it wasn't JIT compiled.
+    pub fn jit_options(&self) -> u32 {
+        self.jit_options
+    }
+
+    /// Serialize this compiled regex into a sequence of bytes with
+    /// `pcre2_serialize_encode`.
+    ///
+    /// JIT compiled code isn't included.
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let mut codes = [self.code as *const W::pcre2_code];
+        let mut bytes: *mut u8 = ptr::null_mut();
+        let mut size: usize = 0;
+        let rc = unsafe { W::pcre2_serialize_encode(codes.as_mut_ptr(), 1, &mut bytes, &mut size) };
+        if rc < 0 {
+            return Err(Error::serialize(rc));
+        }
+        // SAFETY: On success, PCRE2 allocated `size` bytes at `bytes`, which
+        // we copy before handing the allocation back to PCRE2.
+        let serialized = unsafe { slice::from_raw_parts(bytes, size) }.to_vec();
+        unsafe { W::pcre2_serialize_free(bytes) };
+        Ok(serialized)
+    }
+
+    /// Decode the first compiled regex from bytes produced by
+    /// `pcre2_serialize_encode`.
+    ///
+    /// PCRE2 checks the magic number, its version, the code unit width and
+    /// the pointer width recorded in the header, and reports a mismatch as
+    /// an error.
+    ///
+    /// # Safety
+    ///
+    /// PCRE2 doesn't validate anything beyond the header, so the caller
+    /// must guarantee that `bytes` was produced by `pcre2_serialize_encode`
+    /// and hasn't been modified since.
+    pub unsafe fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        // The header (magic, version, config and number of codes) is
+        // followed by the character tables, both of which PCRE2
+        // reads without knowing the length of the buffer.
+        if bytes.len() < 4 * 4 + TABLES_LENGTH {
+            return Err(Error::serialize(PCRE2_ERROR_BADSERIALIZEDDATA));
+        }
+        let mut code: *mut W::pcre2_code = ptr::null_mut();
+        let rc = W::pcre2_serialize_decode(&mut code, 1, bytes.as_ptr());
+        if rc < 0 {
+            return Err(Error::serialize(rc));
+        }
+        assert!(!code.is_null(), "PCRE2 decoded a null code object");
+        Ok(Code {
+            code,
+            jit_options: 0,
+            ctx: CompileContext::new(),
+        })
+    }
+
     /// Build and return an ordered sequence of all capture group names in this
     /// compiled regex.
     ///
@@ -518,25 +1794,27 @@ impl<W: CodeUnitWidth> Code<W> {
     /// If there was a problem querying the compiled object for information,
     /// then this returns an error.
     pub fn capture_names(&self) -> Result<Vec<Option<String>>, Error> {
-        let name_count = self.name_count()?;
-        let name_entry_size_in_bytes =
-            self.name_entry_size()? * std::mem::size_of::<W::PCRE2_CHAR>();
-        let name_table = self.raw_name_table()?;
         let mut names = vec![None; self.capture_count()?];
-        for i in 0..name_count {
-            let entry = unsafe {
-                name_table
-                    .cast::<u8>()
-                    .add(i * name_entry_size_in_bytes)
-                    .cast::<W::name_table_entry>()
-                    .as_ref()
-                    .unwrap()
-            };
-            names[entry.index()] = Some(entry.name());
+        for (index, name) in self.name_table()? {
+            names[index] = Some(W::name_to_string(name));
         }
         Ok(names)
     }
 
+    /// Returns an iterator over the entries in the name table of this
+    /// compiled regex, without copying the names.
+    ///
+    /// If there was a problem querying the compiled object for information,
+    /// then this returns an error.
+    pub fn name_table(&self) -> Result<NameTable<'_, W>, Error> {
+        Ok(NameTable {
+            table: self.raw_name_table()?.cast::<u8>(),
+            entry_size: self.name_entry_size()? * std::mem::size_of::<W::PCRE2_CHAR>(),
+            remaining: self.name_count()?,
+            _code: PhantomData,
+        })
+    }
+
     /// Return the underlying raw pointer to the code object.
     pub fn as_ptr(&self) -> *const W::pcre2_code {
         self.code
@@ -595,6 +1873,184 @@ impl<W: CodeUnitWidth> Code<W> {
         }
     }
 
+    /// Returns the length, in characters, of the longest lookbehind in this
+    /// regex. Note that `\b` and `\B` count as a lookbehind of one character
+    /// in newer PCRE2 releases.
+    pub fn max_lookbehind(&self) -> Result<usize, Error> {
+        let mut len: u32 = 0;
+        let rc = unsafe {
+            W::pcre2_pattern_info(
+                self.as_ptr(),
+                PCRE2_INFO_MAXLOOKBEHIND,
+                &mut len as *mut u32 as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            Err(Error::info(rc))
+        } else {
+            Ok(len as usize)
+        }
+    }
+
+    /// Returns the number of the highest capturing group that is referred to
+    /// by a backreference in this regex, or `0` if there are none.
+    pub fn backref_max(&self) -> Result<usize, Error> {
+        let mut max: u32 = 0;
+        let rc = unsafe {
+            W::pcre2_pattern_info(
+                self.as_ptr(),
+                PCRE2_INFO_BACKREFMAX,
+                &mut max as *mut u32 as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            Err(Error::info(rc))
+        } else {
+            Ok(max as usize)
+        }
+    }
+
+    /// Returns true if and only if this regex contains `\C`.
+    pub fn has_backslash_c(&self) -> Result<bool, Error> {
+        Ok(self.info_u32(PCRE2_INFO_HASBACKSLASHC)? == 1)
+    }
+
+    /// Returns the size, in bytes, of the memory used by this compiled regex,
+    /// excluding any JIT compiled code.
+    pub fn size(&self) -> Result<usize, Error> {
+        let mut size: usize = 0;
+        let rc = unsafe {
+            W::pcre2_pattern_info(
+                self.as_ptr(),
+                PCRE2_INFO_SIZE,
+                &mut size as *mut usize as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            Err(Error::info(rc))
+        } else {
+            Ok(size)
+        }
+    }
+
+    /// Returns the size, in bytes, of the JIT compiled code of this regex,
+    /// or `0` if it hasn't been JIT compiled.
+    pub fn jit_size(&self) -> Result<usize, Error> {
+        let mut size: usize = 0;
+        let rc = unsafe {
+            W::pcre2_pattern_info(
+                self.as_ptr(),
+                PCRE2_INFO_JITSIZE,
+                &mut size as *mut usize as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            Err(Error::info(rc))
+        } else {
+            Ok(size)
+        }
+    }
+
+    /// Returns the type of the first code unit of any match, as reported by
+    /// `PCRE2_INFO_FIRSTCODETYPE`, along with that code unit if the type is
+    /// `1`.
+    pub fn first_code_unit(&self) -> Result<(u32, Option<u32>), Error> {
+        let kind = self.info_u32(PCRE2_INFO_FIRSTCODETYPE)?;
+        if kind != 1 {
+            return Ok((kind, None));
+        }
+        Ok((kind, Some(self.info_u32(PCRE2_INFO_FIRSTCODEUNIT)?)))
+    }
+
+    /// Returns the last literal code unit that any match must contain, if
+    /// PCRE2 recorded one.
+    pub fn last_code_unit(&self) -> Result<Option<u32>, Error> {
+        if self.info_u32(PCRE2_INFO_LASTCODETYPE)? != 1 {
+            return Ok(None);
+        }
+        Ok(Some(self.info_u32(PCRE2_INFO_LASTCODEUNIT)?))
+    }
+
+    /// Returns the compile options that are in effect for this regex,
+    /// including those set by the pattern itself.
+    pub fn all_options(&self) -> Result<u32, Error> {
+        self.info_u32(PCRE2_INFO_ALLOPTIONS)
+    }
+
+    /// Returns the compile options that were passed when compiling this
+    /// regex.
+    pub fn arg_options(&self) -> Result<u32, Error> {
+        self.info_u32(PCRE2_INFO_ARGOPTIONS)
+    }
+
+    /// Returns the extra compile options that were set in the compile
+    /// context when compiling this regex.
+    pub fn extra_options(&self) -> Result<u32, Error> {
+        self.info_u32(PCRE2_INFO_EXTRAOPTIONS)
+    }
+
+    /// Returns the `PCRE2_NEWLINE_*` constant for the newline convention
+    /// that this regex was compiled with.
+    pub fn newline(&self) -> Result<u32, Error> {
+        self.info_u32(PCRE2_INFO_NEWLINE)
+    }
+
+    /// Returns the `PCRE2_BSR_*` constant for the characters that `\R`
+    /// matches in this regex.
+    pub fn bsr(&self) -> Result<u32, Error> {
+        self.info_u32(PCRE2_INFO_BSR)
+    }
+
+    /// Queries a piece of information about this regex that PCRE2 reports
+    /// as a `uint32_t`.
+    fn info_u32(&self, what: u32) -> Result<u32, Error> {
+        let mut value: u32 = 0;
+        let rc = unsafe {
+            W::pcre2_pattern_info(self.as_ptr(), what, &mut value as *mut u32 as *mut c_void)
+        };
+        if rc != 0 {
+            Err(Error::info(rc))
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Returns a lower bound on the length, in characters, of any string
+    /// that this regex matches.
+    pub fn min_length(&self) -> Result<usize, Error> {
+        let mut len: u32 = 0;
+        let rc = unsafe {
+            W::pcre2_pattern_info(
+                self.as_ptr(),
+                PCRE2_INFO_MINLENGTH,
+                &mut len as *mut u32 as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            Err(Error::info(rc))
+        } else {
+            Ok(len as usize)
+        }
+    }
+
+    /// Returns every callout in this compiled pattern, in the order in
+    /// which they appear in the pattern.
+    pub fn callouts(&self) -> Result<Vec<CalloutEntry>, Error> {
+        let mut callouts = vec![];
+        let rc = unsafe {
+            W::pcre2_callout_enumerate(
+                self.as_ptr(),
+                Some(enumerate_callout::<W>),
+                &mut callouts as *mut Vec<CalloutEntry> as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            Err(Error::info(rc))
+        } else {
+            Ok(callouts)
+        }
+    }
+
     /// Returns the total number of capturing groups in this regex. This
     /// includes the capturing group for the entire pattern, so that this is
     /// always 1 more than the number of syntactic groups in the pattern.
@@ -615,8 +2071,310 @@ impl<W: CodeUnitWidth> Code<W> {
     }
 }
 
+/// A callout in a compiled pattern, as reported by `pcre2_callout_enumerate`.
+#[derive(Clone, Debug)]
+pub struct CalloutEntry {
+    /// The number of the callout, which is `0` for string callouts.
+    pub number: u32,
+    /// The offset in the pattern of the item following the callout.
+    pub pattern_position: usize,
+    /// The length of the item following the callout.
+    pub next_item_length: usize,
+    /// For string callouts, the offset in the pattern of the string and the
+    /// string itself, without its delimiters.
+    pub string: Option<(usize, String)>,
+}
+
+/// The callback passed to `pcre2_callout_enumerate`, where `data` points to
+/// the `Vec` that collects the callouts.
+unsafe extern "C" fn enumerate_callout<W: CodeUnitWidth>(
+    block: *mut W::pcre2_callout_enumerate_block,
+    data: *mut c_void,
+) -> c_int {
+    let callouts = &mut *(data as *mut Vec<CalloutEntry>);
+    callouts.push(W::callout_from_enumerate_block(&*block));
+    0
+}
+
+/// The state of a search when it reaches a callout, as passed to a callback
+/// installed with `RegexBuilder::callout`.
+///
+/// The lifetime parameter `'a` refers to the lifetime of the subject and of
+/// the compiled regex.
+pub struct CalloutBlock<'a, W: CodeUnitWidth> {
+    number: u32,
+    string: Option<&'a [W::SubjectChar]>,
+    subject: &'a [W::SubjectChar],
+    start: usize,
+    position: usize,
+    pattern_position: usize,
+    next_item_length: usize,
+    ovector: &'a [usize],
+}
+
+impl<'a, W: CodeUnitWidth> CalloutBlock<'a, W> {
+    /// Returns the number of this callout, which is `0` for string callouts
+    /// and `255` for automatic callouts.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Returns the string argument of this callout, without its delimiters,
+    /// if it is a string callout.
+    pub fn string(&self) -> Option<&'a [W::SubjectChar]> {
+        self.string
+    }
+
+    /// Returns the subject being searched.
+    pub fn subject(&self) -> &'a [W::SubjectChar] {
+        self.subject
+    }
+
+    /// Returns the offset in the subject at which the current match attempt
+    /// started.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the current offset in the subject.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the offset in the pattern of the item that follows this
+    /// callout.
+    pub fn pattern_position(&self) -> usize {
+        self.pattern_position
+    }
+
+    /// Returns the length of the item in the pattern that follows this
+    /// callout.
+    pub fn next_item_length(&self) -> usize {
+        self.next_item_length
+    }
+
+    /// Returns the start and end offsets of the capturing group with the
+    /// given index, if it has matched so far in the current match attempt.
+    ///
+    /// The group at index `0` always spans from `start` to `position`.
+    pub fn group(&self, i: usize) -> Option<(usize, usize)> {
+        if i == 0 {
+            return Some((self.start, self.position));
+        }
+        let (s, e) = (*self.ovector.get(2 * i)?, *self.ovector.get(2 * i + 1)?);
+        if s == PCRE2_UNSET {
+            None
+        } else {
+            Some((s, e))
+        }
+    }
+}
+
+impl<'a, W: CodeUnitWidth> fmt::Debug for CalloutBlock<'a, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CalloutBlock")
+            .field("number", &self.number)
+            .field("string", &self.string.map(W::name_to_string))
+            .field("start", &self.start)
+            .field("position", &self.position)
+            .field("pattern_position", &self.pattern_position)
+            .finish()
+    }
+}
+
+/// What a callback installed with `RegexBuilder::callout` wants the search
+/// to do next.
+#[derive(Debug)]
+pub enum CalloutResult {
+    /// Carry on matching.
+    Continue,
+    /// Fail at the current point, which makes PCRE2 backtrack and try the
+    /// next alternative, as if the pattern couldn't match here.
+    Fail,
+    /// Abort the search, which then fails with an error whose kind is
+    /// `ErrorKind::Callout` and whose source is the given error.
+    Abort(Box<dyn error::Error + Send + Sync>),
+}
+
+/// A callback that is called whenever a search reaches a callout.
+pub type CalloutFn<W> =
+    Arc<dyn Fn(&CalloutBlock<'_, W>) -> CalloutResult + Send + Sync + RefUnwindSafe>;
+
+/// A callback that reports the progress of long searches, installed with
+/// `RegexBuilder::progress`.
+#[derive(Clone)]
+pub struct Progress {
+    /// The number of automatic callouts between calls to the callback.
+    pub interval: u32,
+    /// The callback, which receives the offset at which the current match
+    /// attempt started and the length of the subject.
+    pub callback: Arc<dyn Fn(usize, usize) + Send + Sync + RefUnwindSafe>,
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The number that PCRE2 gives to automatic callouts.
+const AUTO_CALLOUT_NUMBER: u32 = 255;
+
+/// The number of callouts between checks of the deadline of a search, which
+/// keeps the cost of reading the clock low.
+const DEADLINE_CHECK_INTERVAL: u32 = 256;
+
+/// The callbacks installed in a match context, along with the outcome of the
+/// last callout that aborted a search, since PCRE2 only reports an error
+/// code.
+struct CalloutState<W: CodeUnitWidth> {
+    callout: Option<CalloutFn<W>>,
+    progress: Option<Progress>,
+    /// Whether the code was compiled with PCRE2_AUTO_CALLOUT, in which case
+    /// automatic callouts are never passed to the callout callback.
+    auto_callouts: bool,
+    /// The number of automatic callouts since progress was last reported.
+    ticks: u32,
+    /// The deadline of the current search, if any, and the number of
+    /// callouts since it was last checked.
+    deadline: Option<Instant>,
+    deadline_ticks: u32,
+    /// The flag that cancels the current search when set, if any.
+    cancelled: Option<Arc<AtomicBool>>,
+    /// The error to report for the search that a callout aborted.
+    error: Option<Error>,
+    /// The payload of a callback's panic, which is resumed once PCRE2 has
+    /// returned.
+    panic: Option<AssertUnwindSafe<Box<dyn Any + Send>>>,
+}
+
+impl<W: CodeUnitWidth> CalloutState<W> {
+    /// Handle a callout, where automatic callouts report progress and all
+    /// other callouts are passed to the callout callback.
+    fn call(&mut self, block: &CalloutBlock<'_, W>) -> Result<CalloutResult, Error> {
+        if let Some(ref cancelled) = self.cancelled {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(Error::cancelled());
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            self.deadline_ticks += 1;
+            if self.deadline_ticks >= DEADLINE_CHECK_INTERVAL {
+                self.deadline_ticks = 0;
+                if Instant::now() >= deadline {
+                    return Err(Error::timed_out());
+                }
+            }
+        }
+        if self.auto_callouts && block.number() == AUTO_CALLOUT_NUMBER {
+            if let Some(ref progress) = self.progress {
+                self.ticks += 1;
+                if self.ticks >= progress.interval {
+                    self.ticks = 0;
+                    (progress.callback)(block.start(), block.subject().len());
+                }
+            }
+            return Ok(CalloutResult::Continue);
+        }
+        Ok(match self.callout {
+            Some(ref callout) => callout(block),
+            None => CalloutResult::Continue,
+        })
+    }
+}
+
+/// The callout function passed to PCRE2, where `data` points to the callout
+/// state owned by the match data.
+unsafe extern "C" fn call_callout<W: CodeUnitWidth>(
+    block: *mut W::pcre2_callout_block,
+    data: *mut c_void,
+) -> c_int {
+    // SAFETY: `data` points to the state owned by the match data, which
+    // outlives every search using it, and searches require mutable access to
+    // the match data.
+    let state = unsafe { &mut *(data as *mut CalloutState<W>) };
+    let block = unsafe { W::callout_block(&*block) };
+    // Unwinding into C is undefined behavior, so a panic aborts the search
+    // and is resumed once PCRE2 has returned.
+    match panic::catch_unwind(AssertUnwindSafe(|| state.call(&block))) {
+        Ok(Ok(CalloutResult::Continue)) => 0,
+        Ok(Ok(CalloutResult::Fail)) => 1,
+        Ok(Ok(CalloutResult::Abort(err))) => {
+            state.error = Some(Error::callout(err));
+            PCRE2_ERROR_CALLOUT
+        }
+        Ok(Err(err)) => {
+            state.error = Some(err);
+            PCRE2_ERROR_CALLOUT
+        }
+        Err(payload) => {
+            state.panic = Some(AssertUnwindSafe(payload));
+            PCRE2_ERROR_CALLOUT
+        }
+    }
+}
+
+/// An iterator over the name table of a compiled regex, which yields the
+/// index and the name of each named capturing group.
+///
+/// Entries are in alphabetical order of their names. Names are borrowed from
+/// the compiled regex, so iterating doesn't allocate.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub struct NameTable<'r, W: CodeUnitWidth> {
+    /// A pointer to the next entry.
+    table: *const u8,
+    /// The size of each entry, in bytes.
+    entry_size: usize,
+    /// The number of entries that have yet to be yielded.
+    remaining: usize,
+    _code: PhantomData<&'r Code<W>>,
+}
+
+impl<'r, W: CodeUnitWidth> Iterator for NameTable<'r, W> {
+    type Item = (usize, &'r [W::SubjectChar]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // SAFETY: There are `remaining` entries of `entry_size` bytes left in
+        // the name table, which lives as long as the code object.
+        let entry = unsafe { &*self.table.cast::<W::name_table_entry>() };
+        self.table = self.table.wrapping_add(self.entry_size);
+        self.remaining -= 1;
+        Some((entry.index(), entry.name()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'r, W: CodeUnitWidth> ExactSizeIterator for NameTable<'r, W> {}
+
+impl<'r, W: CodeUnitWidth> fmt::Debug for NameTable<'r, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NameTable({})", self.remaining)
+    }
+}
+
+/// A callback that decides whether compiling may continue at the given depth
+/// of nested parentheses.
+pub type RecursionGuard = Arc<dyn Fn(u32) -> bool + Send + Sync + RefUnwindSafe>;
+
 /// A low level representation of PCRE2's compilation context.
-pub struct CompileContext<W: CodeUnitWidth>(*mut W::pcre2_compile_context);
+///
+/// The context owns the recursion guard and character tables installed in
+/// it, if any, since PCRE2 only stores pointers to them. Compiled code keeps
+/// using the character tables, so the context must outlive it.
+pub struct CompileContext<W: CodeUnitWidth> {
+    ctx: *mut W::pcre2_compile_context,
+    recursion_guard: Option<Box<RecursionGuard>>,
+    tables: Option<Arc<[u8]>>,
+}
 
 // SAFETY: Compile contexts are safe to read from multiple threads
 // simultaneously. No interior mutability is used, so Sync is safe.
@@ -625,7 +2383,7 @@ unsafe impl<W: CodeUnitWidth> Sync for CompileContext<W> {}
 
 impl<W: CodeUnitWidth> Drop for CompileContext<W> {
     fn drop(&mut self) {
-        unsafe { W::pcre2_compile_context_free(self.0) }
+        unsafe { W::pcre2_compile_context_free(self.ctx) }
     }
 }
 
@@ -636,7 +2394,11 @@ impl<W: CodeUnitWidth> CompileContext<W> {
     pub fn new() -> Self {
         let ctx = unsafe { W::pcre2_compile_context_create() };
         assert!(!ctx.is_null(), "could not allocate compile context");
-        CompileContext(ctx)
+        CompileContext {
+            ctx,
+            recursion_guard: None,
+            tables: None,
+        }
     }
 
     /// Set the PCRE2 newline sequence.
@@ -645,7 +2407,20 @@ impl<W: CodeUnitWidth> CompileContext<W> {
     /// PCRE2_NEWLINE_CRLF, PCRE2_NEWLINE_ANYCRLF, PCRE2_NEWLINE_ANY or
     /// PCRE2_NEWLINE_NUL. Using any other value results in an error.
     pub fn set_newline(&mut self, value: u32) -> Result<(), Error> {
-        let rc = unsafe { W::pcre2_set_newline(self.0, value) };
+        let rc = unsafe { W::pcre2_set_newline(self.ctx, value) };
+        if rc == 0 {
+            Ok(())
+        } else {
+            Err(Error::option(rc))
+        }
+    }
+
+    /// Set what `\R` matches.
+    ///
+    /// Valid values are: PCRE2_BSR_UNICODE or PCRE2_BSR_ANYCRLF. Using any
+    /// other value results in an error.
+    pub fn set_bsr(&mut self, value: u32) -> Result<(), Error> {
+        let rc = unsafe { W::pcre2_set_bsr(self.ctx, value) };
         if rc == 0 {
             Ok(())
         } else {
@@ -653,25 +2428,268 @@ impl<W: CodeUnitWidth> CompileContext<W> {
         }
     }
 
+    /// Set the maximum length of a pattern, in code units.
+    pub fn set_max_pattern_length(&mut self, len: usize) {
+        // This always returns 0.
+        unsafe { W::pcre2_set_max_pattern_length(self.ctx, len) };
+    }
+
+    /// Set the maximum depth of nested parentheses in a pattern.
+    pub fn set_parens_nest_limit(&mut self, limit: u32) {
+        // This always returns 0.
+        unsafe { W::pcre2_set_parens_nest_limit(self.ctx, limit) };
+    }
+
+    /// Set the `PCRE2_EXTRA_*` compile options, replacing any that were
+    /// set before.
+    pub fn set_compile_extra_options(&mut self, options: u32) {
+        // This always returns 0.
+        unsafe { W::pcre2_set_compile_extra_options(self.ctx, options) };
+    }
+
+    /// Install a callback that PCRE2 calls with the current depth of nested
+    /// parentheses while compiling a pattern. If it returns false, then
+    /// compilation fails with `PCRE2_ERROR_PARENTHESES_STACK_CHECK`.
+    ///
+    /// A panic in the callback also fails compilation.
+    pub fn set_compile_recursion_guard(&mut self, guard: RecursionGuard) {
+        let guard = Box::new(guard);
+        let data = &*guard as *const RecursionGuard as *mut c_void;
+        // This always returns 0.
+        unsafe { W::pcre2_set_compile_recursion_guard(self.ctx, Some(recursion_guard), data) };
+        self.recursion_guard = Some(guard);
+    }
+
+    /// Set the character tables used for compiling, which must have been
+    /// produced by `maketables`.
+    ///
+    /// The tables are shared with any other compile contexts using them.
+    pub fn set_character_tables(&mut self, tables: Arc<[u8]>) {
+        assert_eq!(tables.len(), TABLES_LENGTH);
+        // This always returns 0.
+        unsafe { W::pcre2_set_character_tables(self.ctx, tables.as_ptr()) };
+        self.tables = Some(tables);
+    }
+
     fn as_mut_ptr(&mut self) -> *mut W::pcre2_compile_context {
-        self.0
+        self.ctx
+    }
+}
+
+/// The trampoline that PCRE2 calls for the recursion guard installed by
+/// `CompileContext::set_compile_recursion_guard`.
+unsafe extern "C" fn recursion_guard(depth: u32, data: *mut c_void) -> c_int {
+    // SAFETY: `data` points to the guard owned by the compile context, which
+    // outlives every compilation using it.
+    let guard = unsafe { &*(data as *const RecursionGuard) };
+    // Unwinding into C is undefined behavior, so a panic aborts compiling.
+    match panic::catch_unwind(AssertUnwindSafe(|| guard(depth))) {
+        Ok(true) => 0,
+        Ok(false) | Err(_) => 1,
+    }
+}
+
+/// A JIT stack, which holds the backtracking state of searches executed by
+/// PCRE2's JIT.
+///
+/// By default, every regex creates a JIT stack of the size given by
+/// `RegexBuilder::max_jit_stack_size` for each thread it's used in, or uses
+/// a 32 KB stack on the machine stack if no size is given. A `JitStack`
+/// instead can be installed for the current thread with `install`, and is
+/// then used by every JIT compiled regex searched on that thread, which
+/// makes it possible to share one large stack between many regexes.
+///
+/// A JIT stack can only be used by one search at a time. Searches that are
+/// started from a callout while the installed stack is in use fall back to
+/// the regex's own JIT stack.
+pub struct JitStack<W: CodeUnitWidth> {
+    stack: *mut W::pcre2_jit_stack,
+    max_size: usize,
+}
+
+// SAFETY: A JIT stack is a block of memory that isn't tied to the thread
+// that created it. It must not be used by multiple searches simultaneously,
+// which `install` guarantees by requiring mutable access and restricting its
+// use to the current thread.
+unsafe impl<W: CodeUnitWidth> Send for JitStack<W> {}
+
+impl<W: CodeUnitWidth> Drop for JitStack<W> {
+    fn drop(&mut self) {
+        unsafe { W::pcre2_jit_stack_free(self.stack) }
+    }
+}
+
+impl<W: CodeUnitWidth> fmt::Debug for JitStack<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JitStack")
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+impl<W: CodeUnitWidth> JitStack<W> {
+    /// Create a new JIT stack that starts out with `start_size` bytes and
+    /// may grow up to `max_size` bytes.
+    ///
+    /// This returns an error if the JIT isn't available or if the stack
+    /// couldn't be allocated, which includes when `start_size` is greater
+    /// than `max_size`.
+    pub fn new(start_size: usize, max_size: usize) -> Result<JitStack<W>, Error> {
+        if !is_jit_available::<W>() {
+            return Err(Error::jit(PCRE2_ERROR_JIT_BADOPTION));
+        }
+        if start_size > max_size {
+            return Err(Error::jit(PCRE2_ERROR_NOMEMORY));
+        }
+        let stack = unsafe { W::pcre2_jit_stack_create(start_size, max_size) };
+        if stack.is_null() {
+            return Err(Error::jit(PCRE2_ERROR_NOMEMORY));
+        }
+        Ok(JitStack { stack, max_size })
+    }
+
+    /// Returns the maximum size of this stack, in bytes.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Use this stack for every search executed by PCRE2's JIT on the
+    /// current thread while `f` runs.
+    ///
+    /// This takes precedence over the JIT stacks of the regexes that are
+    /// searched. Installing a stack while another one is installed replaces
+    /// it until `f` returns. To use a stack for a single search, wrap just
+    /// that search. A thread-per-core runtime can instead wrap the loop that
+    /// runs on each thread, so that a single stack per thread is used for
+    /// every regex.
+    pub fn install<R, F: FnOnce() -> R>(&mut self, f: F) -> R {
+        let stack = self.stack as *mut c_void;
+        let prev = W::thread_jit_stack().with(|slot| {
+            let prev = slot.get();
+            slot.set(ThreadJitStack {
+                stack,
+                owner: 0,
+                ..prev
+            });
+            prev
+        });
+        let _guard = ThreadJitStackGuard::<W> {
+            prev,
+            _marker: PhantomData,
+        };
+        f()
+    }
+}
+
+/// The JIT stack installed for a thread by `JitStack::install`, along with
+/// the searches running on that thread.
+#[derive(Clone, Copy)]
+pub struct ThreadJitStack {
+    stack: *mut c_void,
+    /// The nesting depth of the search that is using the stack, or `0` if
+    /// it's unused.
+    owner: usize,
+    /// The number of nested searches running on this thread, since a
+    /// callout can start another search.
+    depth: usize,
+}
+
+impl ThreadJitStack {
+    const NONE: ThreadJitStack = ThreadJitStack {
+        stack: ptr::null_mut(),
+        owner: 0,
+        depth: 0,
+    };
+}
+
+/// Restores the previously installed JIT stack of the current thread when
+/// dropped.
+struct ThreadJitStackGuard<W: CodeUnitWidth> {
+    prev: ThreadJitStack,
+    _marker: PhantomData<W>,
+}
+
+impl<W: CodeUnitWidth> Drop for ThreadJitStackGuard<W> {
+    fn drop(&mut self) {
+        W::thread_jit_stack().with(|slot| {
+            let current = slot.get();
+            slot.set(ThreadJitStack {
+                depth: current.depth,
+                ..self.prev
+            });
+        });
+    }
+}
+
+/// Tracks a search on the current thread, so that the installed JIT stack
+/// isn't handed to a search started from one of its callouts while the
+/// search is still using it.
+struct SearchDepthGuard<W: CodeUnitWidth>(PhantomData<W>);
+
+impl<W: CodeUnitWidth> SearchDepthGuard<W> {
+    fn new() -> SearchDepthGuard<W> {
+        W::thread_jit_stack().with(|slot| {
+            let mut current = slot.get();
+            current.depth += 1;
+            slot.set(current);
+        });
+        SearchDepthGuard(PhantomData)
+    }
+}
+
+impl<W: CodeUnitWidth> Drop for SearchDepthGuard<W> {
+    fn drop(&mut self) {
+        W::thread_jit_stack().with(|slot| {
+            let mut current = slot.get();
+            if current.owner == current.depth {
+                current.owner = 0;
+            }
+            current.depth -= 1;
+            slot.set(current);
+        });
     }
 }
 
+/// The callback PCRE2 calls to get the JIT stack for a search. `data` is the
+/// JIT stack of the match data, or null to use the default stack.
+unsafe extern "C" fn jit_stack_callback<W: CodeUnitWidth>(
+    data: *mut c_void,
+) -> *mut W::pcre2_jit_stack {
+    W::thread_jit_stack().with(|slot| {
+        let mut current = slot.get();
+        // A substitution calls this for every match it finds, so the search
+        // that owns the stack may ask for it more than once.
+        if current.stack.is_null() || (current.owner != 0 && current.owner != current.depth) {
+            return data as *mut W::pcre2_jit_stack;
+        }
+        current.owner = current.depth;
+        slot.set(current);
+        current.stack as *mut W::pcre2_jit_stack
+    })
+}
+
 /// Configuration for PCRE2's match context.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct MatchConfig {
     /// When set, a custom JIT stack will be created with the given maximum
     /// size.
     pub max_jit_stack_size: Option<usize>,
-}
-
-impl Default for MatchConfig {
-    fn default() -> MatchConfig {
-        MatchConfig {
-            max_jit_stack_size: None,
-        }
-    }
+    /// When set, the maximum number of times PCRE2's internal match function
+    /// may be called during a single search.
+    pub match_limit: Option<u32>,
+    /// When set, the maximum amount of heap memory, in kibibytes, that a
+    /// single search may use.
+    pub heap_limit: Option<u32>,
+    /// When set, a callback that reports the progress of searches from
+    /// automatic callouts. This requires the regex to be compiled with
+    /// PCRE2_AUTO_CALLOUT.
+    pub progress: Option<Progress>,
+    /// When set, the largest offset at which an unanchored match may start.
+    /// This requires the regex to be compiled with PCRE2_USE_OFFSET_LIMIT.
+    pub offset_limit: Option<usize>,
+    /// When set, the maximum depth of nested backtracking during a single
+    /// search.
+    pub depth_limit: Option<u32>,
 }
 
 /// A low level representation of a match data block.
@@ -684,8 +2702,20 @@ pub struct MatchData<W: CodeUnitWidth> {
     match_context: *mut W::pcre2_match_context,
     match_data: *mut W::pcre2_match_data,
     jit_stack: Option<*mut W::pcre2_jit_stack>,
+    /// Whether the JIT stack has been set up, see `prepare_jit`.
+    jit_ready: bool,
+    /// The code that the most recent search was executed with.
+    searched_code: *const W::pcre2_code,
+    callout: Option<Box<CalloutState<W>>>,
     ovector_ptr: *const usize,
     ovector_count: u32,
+    matched: bool,
+    partial: bool,
+    /// A workspace for DFA searches that don't bring their own, which is
+    /// allocated by the first such search.
+    dfa_workspace: Vec<c_int>,
+    /// Whether the code was compiled with PCRE2_AUTO_CALLOUT.
+    auto_callouts: bool,
     _marker: PhantomData<W>,
 }
 
@@ -718,33 +2748,84 @@ impl<W: CodeUnitWidth> MatchData<W> {
         let match_context = unsafe { W::pcre2_match_context_create() };
         assert!(!match_context.is_null(), "failed to allocate match context");
 
+        if let Some(limit) = config.match_limit {
+            // SAFETY: The match context was just created and setting a limit
+            // can't fail.
+            unsafe { W::pcre2_set_match_limit(match_context, limit) };
+        }
+        if let Some(limit) = config.heap_limit {
+            // SAFETY: See above.
+            unsafe { W::pcre2_set_heap_limit(match_context, limit) };
+        }
+        if let Some(limit) = config.offset_limit {
+            // SAFETY: See above.
+            unsafe { W::pcre2_set_offset_limit(match_context, limit) };
+        }
+        if let Some(limit) = config.depth_limit {
+            // SAFETY: See above.
+            unsafe { W::pcre2_set_depth_limit(match_context, limit) };
+        }
+
         let match_data = unsafe { W::pcre2_match_data_create_from_pattern(code.as_ptr()) };
         assert!(!match_data.is_null(), "failed to allocate match data block");
 
-        let jit_stack = match config.max_jit_stack_size {
-            None => None,
-            Some(_) if !code.compiled_jit => None,
-            Some(max) => {
-                let stack = unsafe { W::pcre2_jit_stack_create(cmp::min(max, 32 * 1 << 10), max) };
-                assert!(!stack.is_null(), "failed to allocate JIT stack");
-
-                unsafe { W::pcre2_jit_stack_assign(match_context, stack as *mut c_void) };
-                Some(stack)
-            }
-        };
-
         let ovector_ptr = unsafe { W::pcre2_get_ovector_pointer(match_data) };
         assert!(!ovector_ptr.is_null(), "got NULL ovector pointer");
         let ovector_count = unsafe { W::pcre2_get_ovector_count(match_data) };
-        MatchData {
+        let mut data = MatchData {
             config,
             match_context,
             match_data,
-            jit_stack,
+            jit_stack: None,
+            jit_ready: false,
+            searched_code: ptr::null(),
+            callout: None,
             ovector_ptr,
             ovector_count,
+            matched: false,
+            partial: false,
+            dfa_workspace: vec![],
+            auto_callouts: code
+                .arg_options()
+                .is_ok_and(|options| options & PCRE2_AUTO_CALLOUT != 0),
             _marker: PhantomData,
+        };
+        data.prepare_jit(code);
+        if let Some(progress) = data.config.progress.clone() {
+            data.callout_state().progress = Some(progress);
+        }
+        data
+    }
+
+    /// Set up this match data for searching the given code with PCRE2's JIT,
+    /// if it's JIT compiled and this hasn't been done yet.
+    ///
+    /// This is done when the match data is created, but code can also be
+    /// JIT compiled after match data was created for it.
+    fn prepare_jit(&mut self, code: &Code<W>) {
+        if self.jit_ready || !code.compiled_jit() {
+            return;
+        }
+        self.jit_ready = true;
+        if let Some(max) = self.config.max_jit_stack_size {
+            let stack = unsafe { W::pcre2_jit_stack_create(cmp::min(max, 32 << 10), max) };
+            assert!(!stack.is_null(), "failed to allocate JIT stack");
+            self.jit_stack = Some(stack);
         }
+        // The callback prefers a JIT stack installed for the current thread
+        // and otherwise falls back to ours, if any.
+        let data = self
+            .jit_stack
+            .map_or(ptr::null_mut(), |stack| stack as *mut c_void);
+        unsafe {
+            W::pcre2_jit_stack_assign(self.match_context, Some(jit_stack_callback::<W>), data)
+        };
+    }
+
+    /// Returns true if the most recent search using this match data was
+    /// executed with the given code.
+    pub fn searched_with(&self, code: &Code<W>) -> bool {
+        ptr::eq(self.searched_code, code.as_ptr())
     }
 
     /// Return the configuration for this match data object.
@@ -752,11 +2833,159 @@ impl<W: CodeUnitWidth> MatchData<W> {
         &self.config
     }
 
+    /// Install a callback that is called whenever a search using this match
+    /// data reaches a callout.
+    pub fn set_callout(&mut self, callout: CalloutFn<W>) {
+        self.callout_state().callout = Some(callout);
+    }
+
+    /// Return the callback installed with `set_callout`, if any.
+    pub fn callout(&self) -> Option<&CalloutFn<W>> {
+        self.callout.as_ref()?.callout.as_ref()
+    }
+
+    /// Set the deadline of subsequent searches, after which they fail with
+    /// an error whose kind is `ErrorKind::TimedOut`, or `None` to remove it.
+    ///
+    /// The deadline is only checked at callouts, so it only has an effect
+    /// when the regex was compiled with PCRE2_AUTO_CALLOUT.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        if deadline.is_none() && self.callout.is_none() {
+            return;
+        }
+        let state = self.callout_state();
+        state.deadline = deadline;
+        state.deadline_ticks = 0;
+    }
+
+    /// Set the flag that cancels subsequent searches when it is set, after
+    /// which they fail with an error whose kind is `ErrorKind::Cancelled`,
+    /// or `None` to remove it.
+    ///
+    /// As with `set_deadline`, the flag is only checked at callouts.
+    pub fn set_cancellation(&mut self, cancelled: Option<Arc<AtomicBool>>) {
+        if cancelled.is_none() && self.callout.is_none() {
+            return;
+        }
+        self.callout_state().cancelled = cancelled;
+    }
+
+    /// Return the callout state of this match data, installing the callout
+    /// function in the match context the first time.
+    fn callout_state(&mut self) -> &mut CalloutState<W> {
+        let match_context = self.match_context;
+        let auto_callouts = self.auto_callouts;
+        self.callout.get_or_insert_with(|| {
+            let mut state = Box::new(CalloutState {
+                callout: None,
+                progress: None,
+                auto_callouts,
+                ticks: 0,
+                deadline: None,
+                deadline_ticks: 0,
+                cancelled: None,
+                error: None,
+                panic: None,
+            });
+            let data = &mut *state as *mut CalloutState<W> as *mut c_void;
+            // This always returns 0.
+            unsafe { W::pcre2_set_callout(match_context, Some(call_callout::<W>), data) };
+            state
+        })
+    }
+
+    /// Convert the error code returned by a PCRE2 routine into an error,
+    /// using the given constructor unless a callout aborted the routine.
+    ///
+    /// If a callout panicked, then the panic is resumed.
+    fn error(&mut self, rc: c_int, new: fn(c_int) -> Error) -> Error {
+        if rc == PCRE2_ERROR_CALLOUT {
+            if let Some(state) = self.callout.as_mut() {
+                if let Some(payload) = state.panic.take() {
+                    panic::resume_unwind(payload.0);
+                }
+                if let Some(err) = state.error.take() {
+                    return err;
+                }
+            }
+        }
+        new(rc)
+    }
+
+    /// Set the offset limit used by subsequent searches, which is the largest
+    /// offset at which an unanchored match may start. Use `PCRE2_UNSET` to
+    /// remove the limit.
+    ///
+    /// This overrides the offset limit in this match data's configuration,
+    /// and `reset_offset_limit` restores it.
+    ///
+    /// The limit only has an effect when the regex was compiled with
+    /// PCRE2_USE_OFFSET_LIMIT. Otherwise, searching with a limit set fails.
+    pub fn set_offset_limit(&mut self, limit: usize) {
+        // SAFETY: The match context is valid for as long as this match data
+        // block is, and setting the offset limit can't fail.
+        unsafe {
+            W::pcre2_set_offset_limit(self.match_context, limit);
+        }
+    }
+
+    /// Restore the offset limit from this match data's configuration after
+    /// it was overridden by `set_offset_limit`.
+    pub fn reset_offset_limit(&mut self) {
+        self.set_offset_limit(self.config.offset_limit.unwrap_or(PCRE2_UNSET));
+    }
+
+    /// Override the match, depth and heap limits used by subsequent searches.
+    /// Each limit that is `None` is left as it is.
+    ///
+    /// `reset_limits` restores the limits in this match data's configuration.
+    pub fn set_limits(
+        &mut self,
+        match_limit: Option<u32>,
+        depth_limit: Option<u32>,
+        heap_limit: Option<u32>,
+    ) {
+        // SAFETY: The match context is valid for as long as this match data
+        // block is, and setting a limit can't fail.
+        unsafe {
+            if let Some(limit) = match_limit {
+                W::pcre2_set_match_limit(self.match_context, limit);
+            }
+            if let Some(limit) = depth_limit {
+                W::pcre2_set_depth_limit(self.match_context, limit);
+            }
+            if let Some(limit) = heap_limit {
+                W::pcre2_set_heap_limit(self.match_context, limit);
+            }
+        }
+    }
+
+    /// Restore the match, depth and heap limits from this match data's
+    /// configuration after they were overridden by `set_limits`. Limits that
+    /// aren't configured are restored to PCRE2's defaults.
+    pub fn reset_limits(&mut self) {
+        let match_limit = self
+            .config
+            .match_limit
+            .unwrap_or_else(|| default_limit::<W>(PCRE2_CONFIG_MATCHLIMIT));
+        let depth_limit = self
+            .config
+            .depth_limit
+            .unwrap_or_else(|| default_limit::<W>(PCRE2_CONFIG_DEPTHLIMIT));
+        let heap_limit = self
+            .config
+            .heap_limit
+            .unwrap_or_else(|| default_limit::<W>(PCRE2_CONFIG_HEAPLIMIT));
+        self.set_limits(Some(match_limit), Some(depth_limit), Some(heap_limit));
+    }
+
     /// Execute PCRE2's primary match routine on the given subject string
     /// starting at the given offset. The provided options are passed to PCRE2
     /// as is.
     ///
-    /// This returns false if no match occurred.
+    /// This returns false if no match occurred. When partial matching is
+    /// requested, this returns true for a partial match as well, which can
+    /// be distinguished from a complete match via `partial`.
     ///
     /// Match offsets can be extracted via `ovector`.
     ///
@@ -784,6 +3013,9 @@ impl<W: CodeUnitWidth> MatchData<W> {
         }
         let (subj_ptr, subj_len) = W::subject_to_sptr_len(subject);
 
+        self.prepare_jit(code);
+        self.searched_code = code.as_ptr();
+        let _depth = SearchDepthGuard::<W>::new();
         let rc = W::pcre2_match(
             code.as_ptr(),
             subj_ptr,
@@ -793,19 +3025,313 @@ impl<W: CodeUnitWidth> MatchData<W> {
             self.match_data,
             self.match_context,
         );
+        self.finish_find(rc)
+    }
+
+    /// Execute PCRE2's JIT fast path on the given subject string starting at
+    /// the given offset. This behaves like `find`, except that `code` must
+    /// have been JIT compiled and the only options PCRE2 honors are
+    /// PCRE2_NOTBOL, PCRE2_NOTEOL, PCRE2_NOTEMPTY, PCRE2_NOTEMPTY_ATSTART,
+    /// PCRE2_PARTIAL_HARD and PCRE2_PARTIAL_SOFT.
+    ///
+    /// # Safety
+    ///
+    /// The JIT fast path skips all of the sanity checks that `find` performs,
+    /// including the UTF check. Callers must guarantee that `code` was JIT
+    /// compiled for the requested matching mode, that `start` is a valid
+    /// offset into `subject` and, in UTF mode, that the subject string is
+    /// valid UTF. Otherwise, the behavior is undefined.
+    pub unsafe fn find_jit(
+        &mut self,
+        code: &Code<W>,
+        mut subject: &[W::SubjectChar],
+        start: usize,
+        options: u32,
+    ) -> Result<bool, Error> {
+        debug_assert!(code.compiled_jit(), "code must be JIT compiled");
+        if subject.is_empty() {
+            subject = &[];
+        }
+        let (subj_ptr, subj_len) = W::subject_to_sptr_len(subject);
+
+        self.prepare_jit(code);
+        self.searched_code = code.as_ptr();
+        let _depth = SearchDepthGuard::<W>::new();
+        let rc = W::pcre2_jit_match(
+            code.as_ptr(),
+            subj_ptr,
+            subj_len,
+            start,
+            options,
+            self.match_data,
+            self.match_context,
+        );
+        self.finish_find(rc)
+    }
+
+    /// Record the outcome of a search with the given return code from
+    /// `pcre2_match` or `pcre2_jit_match`.
+    unsafe fn finish_find(&mut self, rc: c_int) -> Result<bool, Error> {
+        self.matched = rc > 0;
+        self.partial = rc == PCRE2_ERROR_PARTIAL;
+        if self.partial {
+            // PCRE2 only sets the first pair of offsets for a partial match
+            // and leaves the rest as they were, so unset them to avoid
+            // reporting capture groups from a previous match.
+            let ovector = slice::from_raw_parts_mut(
+                self.ovector_ptr as *mut usize,
+                self.ovector_count as usize * 2,
+            );
+            for offset in &mut ovector[2..] {
+                *offset = PCRE2_UNSET;
+            }
+        }
         if rc == PCRE2_ERROR_NOMATCH {
             Ok(false)
-        } else if rc > 0 {
+        } else if rc > 0 || self.partial {
             Ok(true)
         } else {
             // We always create match data with
             // pcre2_match_data_create_from_pattern, so the ovector should
             // always be big enough.
             assert!(rc != 0, "ovector should never be too small");
-            Err(Error::matching(rc))
+            Err(self.error(rc, Error::matching))
+        }
+    }
+
+    /// Execute PCRE2's DFA match routine on the given subject string starting
+    /// at the given offset, using the given workspace. The provided options
+    /// are passed to PCRE2 as is.
+    ///
+    /// The DFA routine finds every match that starts at the leftmost
+    /// possible position. This returns the number of those matches that
+    /// were recorded in the ovector, longest first, which is `0` if no match
+    /// occurred. If there are more matches than fit in the ovector, then
+    /// only the longest ones are recorded. Capture groups are not supported.
+    ///
+    /// When partial matching is requested, a partial match is reported as a
+    /// single match, which can be distinguished from a complete match via
+    /// `partial`.
+    ///
+    /// # Safety
+    ///
+    /// This routine is marked unsafe for the same reasons as `find`.
+    pub unsafe fn dfa_find(
+        &mut self,
+        code: &Code<W>,
+        mut subject: &[W::SubjectChar],
+        start: usize,
+        options: u32,
+        workspace: &mut [c_int],
+    ) -> Result<usize, Error> {
+        // See `find` for why we do this.
+        if subject.is_empty() {
+            subject = &[];
+        }
+        let (subj_ptr, subj_len) = W::subject_to_sptr_len(subject);
+
+        self.searched_code = code.as_ptr();
+        let rc = W::pcre2_dfa_match(
+            code.as_ptr(),
+            subj_ptr,
+            subj_len,
+            start,
+            options,
+            self.match_data,
+            self.match_context,
+            workspace.as_mut_ptr(),
+            workspace.len(),
+        );
+        // The DFA routine never sets capture groups, so the match can't be
+        // reused for substitution.
+        self.matched = false;
+        self.partial = rc == PCRE2_ERROR_PARTIAL;
+        if rc == PCRE2_ERROR_NOMATCH {
+            Ok(0)
+        } else if self.partial {
+            Ok(1)
+        } else if rc > 0 {
+            Ok(rc as usize)
+        } else if rc == 0 {
+            // There were more matches than fit in the ovector, so it has
+            // been filled with the longest ones.
+            Ok(self.ovector_count as usize)
+        } else {
+            Err(self.error(rc, Error::matching))
+        }
+    }
+
+    /// Like `dfa_find`, but uses a workspace with room for `size` integers
+    /// that is kept in this match data block, so that it isn't allocated for
+    /// every search.
+    ///
+    /// # Safety
+    ///
+    /// This routine is marked unsafe for the same reasons as `find`.
+    pub unsafe fn dfa_find_with_own_workspace(
+        &mut self,
+        code: &Code<W>,
+        subject: &[W::SubjectChar],
+        start: usize,
+        options: u32,
+        size: usize,
+    ) -> Result<usize, Error> {
+        let mut workspace = std::mem::take(&mut self.dfa_workspace);
+        workspace.resize(size, 0);
+        let result = self.dfa_find(code, subject, start, options, &mut workspace);
+        self.dfa_workspace = workspace;
+        result
+    }
+
+    /// Execute PCRE2's substitution routine on the given subject string
+    /// starting at the given offset, appending the result to `dst`. The
+    /// provided options are passed to PCRE2 as is, except that
+    /// PCRE2_SUBSTITUTE_OVERFLOW_LENGTH is always added so that `dst` can be
+    /// grown to fit the output.
+    ///
+    /// This returns the number of substitutions performed. Note that when no
+    /// substitutions are made, PCRE2 still copies the subject to `dst`.
+    ///
+    /// # Safety
+    ///
+    /// This routine is marked unsafe for the same reasons as `find`. In
+    /// particular, PCRE2_NO_UTF_CHECK also disables validation of the
+    /// replacement string, which must then be valid UTF-8 in UTF mode.
+    pub unsafe fn substitute(
+        &mut self,
+        code: &Code<W>,
+        subject: &[W::SubjectChar],
+        start: usize,
+        options: u32,
+        replacement: &[W::SubjectChar],
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        self.prepare_jit(code);
+        self.searched_code = code.as_ptr();
+        let result = self.substitute_impl(code, subject, start, options, replacement, dst);
+        // Unless PCRE2 was told to reuse the existing match without
+        // searching for more, it has overwritten the match data.
+        if options & PCRE2_SUBSTITUTE_MATCHED == 0 || options & PCRE2_SUBSTITUTE_GLOBAL != 0 {
+            self.matched = false;
+        }
+        match result {
+            Err(err) if err.code() == PCRE2_ERROR_CALLOUT => {
+                Err(self.error(err.code(), Error::substitute))
+            }
+            result => result,
+        }
+    }
+
+    /// Expand the replacement for the match that was most recently found
+    /// with this match data block in `subject`, appending the result to
+    /// `dst`. This is `substitute` with PCRE2_SUBSTITUTE_MATCHED, for which
+    /// PCRE2 neither searches nor changes the match data block, so it only
+    /// requires shared access.
+    ///
+    /// # Safety
+    ///
+    /// This routine is marked unsafe for the same reasons as `substitute`.
+    /// In addition, `code` and `subject` must be the ones that the match was
+    /// found with, and the options must not include PCRE2_SUBSTITUTE_GLOBAL.
+    pub unsafe fn substitute_matched(
+        &self,
+        code: &Code<W>,
+        subject: &[W::SubjectChar],
+        options: u32,
+        replacement: &[W::SubjectChar],
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        debug_assert_eq!(options & PCRE2_SUBSTITUTE_GLOBAL, 0);
+        let options = options | PCRE2_SUBSTITUTE_MATCHED;
+        self.substitute_impl(code, subject, 0, options, replacement, dst)
+    }
+
+    /// Run `pcre2_substitute` for `substitute` and `substitute_matched`.
+    ///
+    /// Errors aborting a search are reported with their error code only, so
+    /// errors raised by callouts must still be recovered with `error`.
+    unsafe fn substitute_impl(
+        &self,
+        code: &Code<W>,
+        mut subject: &[W::SubjectChar],
+        start: usize,
+        options: u32,
+        replacement: &[W::SubjectChar],
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        // See `find` for why we do this.
+        if subject.is_empty() {
+            subject = &[];
+        }
+        let (subj_ptr, subj_len) = W::subject_to_sptr_len(subject);
+        let (rep_ptr, rep_len) = W::subject_to_sptr_len(replacement);
+
+        // PCRE2 always writes a trailing NUL terminator, so we need room for
+        // at least one more code unit than the output itself. Reserving room
+        // for both the subject and the replacement up front means that we
+        // usually don't need to retry.
+        dst.reserve(subj_len + rep_len + 1);
+        loop {
+            let old_len = dst.len();
+            let spare = dst.spare_capacity_mut();
+            let mut out_len = spare.len();
+            let _depth = SearchDepthGuard::<W>::new();
+            let rc = W::pcre2_substitute(
+                code.as_ptr(),
+                subj_ptr,
+                subj_len,
+                start,
+                options | PCRE2_SUBSTITUTE_OVERFLOW_LENGTH,
+                self.match_data,
+                self.match_context,
+                rep_ptr,
+                rep_len,
+                spare.as_mut_ptr().cast::<W::PCRE2_CHAR>(),
+                &mut out_len,
+            );
+            if rc >= 0 {
+                // PCRE2 reports the output length without the terminator.
+                // The output isn't part of `dst` until it has been checked,
+                // since it may not consist of valid subject characters.
+                let output = slice::from_raw_parts(spare.as_ptr().cast(), out_len);
+                if let Err(code) = W::check_output(output) {
+                    return Err(Error::substitute(code));
+                }
+                dst.set_len(old_len + out_len);
+                return Ok(rc as usize);
+            } else if rc == PCRE2_ERROR_NOMEMORY {
+                // With PCRE2_SUBSTITUTE_OVERFLOW_LENGTH, out_len is now the
+                // required size of the output buffer, terminator included.
+                dst.reserve(out_len);
+            } else if out_len != PCRE2_UNSET && rc != PCRE2_ERROR_CALLOUT {
+                // For errors in the replacement, PCRE2 reports the offset in
+                // the replacement at which the error was detected.
+                return Err(Error::replacement(rc, out_len));
+            } else {
+                return Err(Error::substitute(rc));
+            }
         }
     }
 
+    /// Returns true if and only if the most recent call to `find` on this
+    /// match data block found a match, and the match has not since been
+    /// overwritten by `substitute`.
+    ///
+    /// When this returns false, the contents of the ovector are unspecified.
+    pub fn matched(&self) -> bool {
+        self.matched
+    }
+
+    /// Returns true if and only if the most recent call to `find` on this
+    /// match data block found a partial match.
+    ///
+    /// For a partial match, only the first pair of offsets in the ovector is
+    /// set. It spans from the start of the partial match to the end of the
+    /// subject. All other offsets are `PCRE2_UNSET`.
+    pub fn partial(&self) -> bool {
+        self.partial
+    }
+
     /// Return the ovector corresponding to this match data.
     ///
     /// The ovector represents match offsets as pairs. This always returns
@@ -819,4 +3345,35 @@ impl<W: CodeUnitWidth> MatchData<W> {
         // but this isn't actually 100% clear!
         unsafe { slice::from_raw_parts(self.ovector_ptr, self.ovector_count as usize * 2) }
     }
+
+    /// Return the ovector corresponding to this match data for writing, e.g.
+    /// to fill it with capture groups found by a search with other code.
+    ///
+    /// Since the ovector then no longer reflects a search with this match
+    /// data block, `matched` returns false afterwards.
+    pub fn ovector_mut(&mut self) -> &mut [usize] {
+        self.matched = false;
+        // SAFETY: See `ovector`. PCRE2 hands out the ovector as a mutable
+        // pointer, and we have exclusive access to the match data.
+        unsafe {
+            slice::from_raw_parts_mut(
+                self.ovector_ptr as *mut usize,
+                self.ovector_count as usize * 2,
+            )
+        }
+    }
+
+    /// Returns the name of the last mark passed on the matching path of the
+    /// most recent match, or `None` if there wasn't one.
+    ///
+    /// The name is only meaningful if the most recent call to `find` found a
+    /// match.
+    ///
+    /// # Safety
+    ///
+    /// The name points into the code that was most recently searched with,
+    /// so that code must outlive the returned slice.
+    pub unsafe fn mark(&self) -> Option<&[W::PCRE2_CHAR]> {
+        W::pcre2_get_mark(self.match_data)
+    }
 }
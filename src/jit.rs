@@ -0,0 +1,136 @@
+/*!
+This module provides a pool of JIT stacks that can be shared by many threads.
+*/
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use crate::error::Error;
+use crate::ffi::{CodeUnitWidth, JitStack};
+
+/// A pool of JIT stacks of the same size.
+///
+/// Stacks are taken from the pool with `get` and are returned to it when the
+/// `PooledJitStack` is dropped, so that threads that only search for a while,
+/// such as the workers of a thread pool, can reuse stacks instead of
+/// allocating a new one each time.
+pub struct JitStackPool<W: CodeUnitWidth> {
+    start_size: usize,
+    max_size: usize,
+    stacks: Mutex<Vec<JitStack<W>>>,
+}
+
+impl<W: CodeUnitWidth> fmt::Debug for JitStackPool<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JitStackPool")
+            .field("start_size", &self.start_size)
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+impl<W: CodeUnitWidth> JitStackPool<W> {
+    /// Create a new empty pool of JIT stacks that start out with
+    /// `start_size` bytes and may grow up to `max_size` bytes.
+    ///
+    /// Stacks are created when they're first needed. See `JitStack::new`
+    /// for the errors that can occur.
+    pub fn new(start_size: usize, max_size: usize) -> JitStackPool<W> {
+        JitStackPool {
+            start_size,
+            max_size,
+            stacks: Mutex::new(vec![]),
+        }
+    }
+
+    /// Take a JIT stack from this pool, creating a new one if the pool is
+    /// empty.
+    ///
+    /// The stack is returned to the pool when the guard is dropped.
+    pub fn get(&self) -> Result<PooledJitStack<'_, W>, Error> {
+        let stack = self.stacks.lock().unwrap().pop();
+        let stack = match stack {
+            Some(stack) => stack,
+            None => JitStack::new(self.start_size, self.max_size)?,
+        };
+        Ok(PooledJitStack {
+            pool: self,
+            stack: Some(stack),
+        })
+    }
+
+    /// Take a JIT stack from this pool and install it for the current thread
+    /// while `f` runs. See `JitStack::install`.
+    pub fn install<R, F: FnOnce() -> R>(&self, f: F) -> Result<R, Error> {
+        Ok(self.get()?.install(f))
+    }
+}
+
+/// A JIT stack taken from a `JitStackPool`, which is returned to the pool
+/// when dropped.
+///
+/// The lifetime parameter `'p` refers to the lifetime of the pool.
+pub struct PooledJitStack<'p, W: CodeUnitWidth> {
+    pool: &'p JitStackPool<W>,
+    stack: Option<JitStack<W>>,
+}
+
+impl<'p, W: CodeUnitWidth> fmt::Debug for PooledJitStack<'p, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledJitStack").field(&**self).finish()
+    }
+}
+
+impl<'p, W: CodeUnitWidth> Drop for PooledJitStack<'p, W> {
+    fn drop(&mut self) {
+        if let Some(stack) = self.stack.take() {
+            self.pool.stacks.lock().unwrap().push(stack);
+        }
+    }
+}
+
+impl<'p, W: CodeUnitWidth> Deref for PooledJitStack<'p, W> {
+    type Target = JitStack<W>;
+
+    fn deref(&self) -> &JitStack<W> {
+        self.stack.as_ref().unwrap()
+    }
+}
+
+impl<'p, W: CodeUnitWidth> DerefMut for PooledJitStack<'p, W> {
+    fn deref_mut(&mut self) -> &mut JitStack<W> {
+        self.stack.as_mut().unwrap()
+    }
+}
+
+#[cfg(all(test, feature = "utf8"))]
+mod tests {
+    use super::JitStackPool;
+    use crate::ffi::{is_jit_available, CodeUnitWidth8};
+
+    #[test]
+    fn pool_reuses_stacks() {
+        let pool = JitStackPool::<CodeUnitWidth8>::new(32 << 10, 1 << 20);
+        if !is_jit_available::<CodeUnitWidth8>() {
+            assert!(pool.get().is_err());
+            return;
+        }
+        let pooled = |pool: &JitStackPool<CodeUnitWidth8>| pool.stacks.lock().unwrap().len();
+        let (a, b) = (pool.get().unwrap(), pool.get().unwrap());
+        assert_eq!(pooled(&pool), 0);
+        drop((a, b));
+        assert_eq!(pooled(&pool), 2);
+
+        let stack = pool.get().unwrap();
+        assert_eq!(stack.max_size(), 1 << 20);
+        assert_eq!(pooled(&pool), 1);
+        assert_eq!(pool.install(|| 42).unwrap(), 42);
+        assert_eq!(pooled(&pool), 1);
+        drop(stack);
+        assert_eq!(pooled(&pool), 2);
+
+        let pool = JitStackPool::<CodeUnitWidth8>::new(1 << 20, 32 << 10);
+        assert!(pool.get().is_err());
+    }
+}
@@ -11,17 +11,39 @@ are welcome to improve this.
 
 #![deny(missing_docs)]
 
+pub use crate::cache::RegexCache;
 pub use crate::error::{Error, ErrorKind};
-pub use crate::ffi::{is_jit_available, version};
+pub use crate::ffi::{is_jit_available, jit_free_unused_memory, jit_target, version};
+pub use crate::policy::{PatternPolicy, PolicyViolation};
+#[cfg(feature = "serde")]
+pub use crate::regex_impl::SerializedConfig;
 
 /**
 PCRE2 regular expressions for matching on arbitrary bytes.
 */
 #[cfg(feature = "utf8")]
 pub mod bytes;
+mod cache;
 mod error;
 mod ffi;
+mod jit;
+mod policy;
 mod regex_impl;
+mod set;
+mod stream;
+
+/**
+PCRE2 regular expressions for matching on Rust strings.
+*/
+#[cfg(feature = "utf8")]
+pub mod utf8;
+
+/**
+PCRE2 regular expressions for matching on UTF-16 slices, such as Windows
+wide strings.
+*/
+#[cfg(feature = "utf16")]
+pub mod utf16;
 
 /**
 PCRE2 regular expressions for matching on UTF-32 slices.
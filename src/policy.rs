@@ -0,0 +1,352 @@
+/*!
+This module provides a policy for checking which features compiled patterns
+use.
+*/
+
+use std::error;
+use std::fmt;
+
+use crate::ffi::CodeUnitWidth;
+use crate::regex_impl::{CalloutInfo, Regex};
+
+/// A policy that restricts which features a pattern may use.
+///
+/// Limits such as `RegexBuilder::match_limit` bound the resources that a
+/// search may use, but patterns from untrusted sources may also need to
+/// follow a syntax policy, e.g. to guarantee that every pattern can be
+/// searched with the DFA matching algorithm. A policy is checked against a
+/// compiled regex with `check`, using what PCRE2 reports about the compiled
+/// pattern, so it can't be circumvented by spelling a feature differently.
+/// The only exception is `deny_recursion`, since PCRE2 doesn't report
+/// whether a pattern uses recursion, so the pattern text is scanned instead.
+///
+/// Every feature is permitted by default.
+#[derive(Clone, Debug, Default)]
+pub struct PatternPolicy {
+    deny_backrefs: bool,
+    deny_recursion: bool,
+    deny_backslash_c: bool,
+    deny_callouts: bool,
+    allowed_callouts: Option<Vec<u32>>,
+    max_lookbehind: Option<usize>,
+}
+
+impl PatternPolicy {
+    /// Create a new policy that permits every feature.
+    pub fn new() -> PatternPolicy {
+        PatternPolicy::default()
+    }
+
+    /// Reject patterns with backreferences, including named backreferences
+    /// and references in conditions such as `(?(1)a|b)`.
+    pub fn deny_backrefs(&mut self, yes: bool) -> &mut Self {
+        self.deny_backrefs = yes;
+        self
+    }
+
+    /// Reject patterns with recursion or subroutine calls, such as `(?R)`,
+    /// `(?1)`, `(?-1)`, `(?&name)`, `(?P>name)` or `\g<name>`.
+    ///
+    /// Since PCRE2 doesn't report whether a compiled pattern contains such
+    /// calls, this scans the pattern text. The scan doesn't recognize comments
+    /// in extended mode, so a call that is commented out is rejected as well.
+    pub fn deny_recursion(&mut self, yes: bool) -> &mut Self {
+        self.deny_recursion = yes;
+        self
+    }
+
+    /// Reject patterns that contain `\C`.
+    ///
+    /// `RegexBuilder::never_backslash_c` rejects such patterns when they're
+    /// compiled instead.
+    pub fn deny_backslash_c(&mut self, yes: bool) -> &mut Self {
+        self.deny_backslash_c = yes;
+        self
+    }
+
+    /// Reject patterns with callouts, such as `(?C1)` or `(?C"text")`.
+    pub fn deny_callouts(&mut self, yes: bool) -> &mut Self {
+        self.deny_callouts = yes;
+        self
+    }
+
+    /// Only permit callouts with the given numbers, or any callout when
+    /// `None` is given. String callouts have number `0`.
+    ///
+    /// This has no effect if `deny_callouts` is enabled.
+    pub fn allowed_callouts<I>(&mut self, numbers: Option<I>) -> &mut Self
+    where
+        I: IntoIterator<Item = u32>,
+    {
+        self.allowed_callouts = numbers.map(|numbers| numbers.into_iter().collect());
+        self
+    }
+
+    /// Reject patterns with a lookbehind assertion that moves back more
+    /// than the given number of characters, or permit any lookbehind when
+    /// `None` is given. A limit of `0` rejects every lookbehind.
+    ///
+    /// This is checked against `Regex::max_lookbehind`, so the same caveats
+    /// apply. In particular, `\b` and `\B` count as a lookbehind of one
+    /// character in newer PCRE2 releases.
+    pub fn max_lookbehind(&mut self, len: Option<usize>) -> &mut Self {
+        self.max_lookbehind = len;
+        self
+    }
+
+    /// Check that the given regex follows this policy, returning the first
+    /// violation found otherwise.
+    pub fn check<W: CodeUnitWidth>(&self, re: &Regex<W>) -> Result<(), PolicyViolation> {
+        if self.deny_backrefs && re.has_backrefs() {
+            return Err(PolicyViolation::Backref);
+        }
+        if self.deny_recursion && has_subroutine_call(&W::pattern_units(re.as_str())) {
+            return Err(PolicyViolation::Recursion);
+        }
+        if self.deny_backslash_c && re.has_backslash_c() {
+            return Err(PolicyViolation::BackslashC);
+        }
+        if self.deny_callouts || self.allowed_callouts.is_some() {
+            for callout in re.callouts() {
+                let allowed = !self.deny_callouts
+                    && self
+                        .allowed_callouts
+                        .as_ref()
+                        .is_some_and(|allowed| allowed.contains(&callout.number()));
+                if !allowed {
+                    return Err(PolicyViolation::Callout(callout));
+                }
+            }
+        }
+        if let Some(max) = self.max_lookbehind {
+            let len = re.max_lookbehind();
+            if len > max {
+                return Err(PolicyViolation::Lookbehind(len));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A way in which a pattern violates a `PatternPolicy`.
+#[derive(Clone, Debug)]
+pub enum PolicyViolation {
+    /// The pattern contains a backreference.
+    Backref,
+    /// The pattern contains a recursion or subroutine call.
+    Recursion,
+    /// The pattern contains `\C`.
+    BackslashC,
+    /// The pattern contains a callout that isn't permitted.
+    Callout(CalloutInfo),
+    /// The pattern contains a lookbehind assertion that moves back the given
+    /// number of characters, which exceeds the limit.
+    Lookbehind(usize),
+}
+
+impl error::Error for PolicyViolation {}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyViolation::Backref => {
+                write!(f, "pattern contains a backreference")
+            }
+            PolicyViolation::Recursion => {
+                write!(f, "pattern contains a recursion or subroutine call")
+            }
+            PolicyViolation::BackslashC => {
+                write!(f, "pattern contains \\C")
+            }
+            PolicyViolation::Callout(callout) => match callout.string() {
+                None => write!(
+                    f,
+                    "pattern contains callout {} at offset {}",
+                    callout.number(),
+                    callout.pattern_position()
+                ),
+                Some(string) => write!(
+                    f,
+                    "pattern contains callout {:?} at offset {}",
+                    string,
+                    callout.pattern_position()
+                ),
+            },
+            PolicyViolation::Lookbehind(len) => {
+                write!(f, "pattern contains a lookbehind of {} characters", len)
+            }
+        }
+    }
+}
+
+/// Returns true if and only if the given pattern, whose code units are
+/// given, contains a recursion or subroutine call.
+///
+/// Escaped characters, `\Q...\E` quoting, character classes and `(?#...)`
+/// comments are skipped, but extended mode comments aren't recognized, so
+/// calls inside them are reported as well.
+pub(crate) fn has_subroutine_call(pattern: &[u32]) -> bool {
+    let at = |i: usize| pattern.get(i).and_then(|&c| char::from_u32(c));
+    let mut i = 0;
+    while i < pattern.len() {
+        match at(i) {
+            Some('\\') => match at(i + 1) {
+                Some('g') if matches!(at(i + 2), Some('<' | '\'')) => return true,
+                Some('Q') => {
+                    i += 2;
+                    while i < pattern.len() && !(at(i) == Some('\\') && at(i + 1) == Some('E')) {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                _ => i += 1,
+            },
+            Some('[') => {
+                // A `]` right after the opening bracket is literal.
+                i += 1;
+                if at(i) == Some('^') {
+                    i += 1;
+                }
+                if at(i) == Some(']') {
+                    i += 1;
+                }
+                while i < pattern.len() && at(i) != Some(']') {
+                    match (at(i), at(i + 1)) {
+                        (Some('\\'), _) => i += 1,
+                        (Some('['), Some(':')) => {
+                            // Skip a POSIX class such as `[:alpha:]`.
+                            while i < pattern.len()
+                                && !(at(i) == Some(':') && at(i + 1) == Some(']'))
+                            {
+                                i += 1;
+                            }
+                            i += 1;
+                        }
+                        _ => {}
+                    }
+                    i += 1;
+                }
+            }
+            Some('(') => match (at(i + 1), at(i + 2)) {
+                (Some('?'), Some('0'..='9' | 'R' | '&')) => return true,
+                (Some('?'), Some('+' | '-')) if matches!(at(i + 3), Some('0'..='9')) => {
+                    return true
+                }
+                (Some('?'), Some('P')) if at(i + 3) == Some('>') => return true,
+                (Some('?'), Some('#')) => {
+                    while i < pattern.len() && at(i) != Some(')') {
+                        i += 1;
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+#[cfg(all(test, feature = "utf8"))]
+mod tests {
+    use super::{has_subroutine_call, PatternPolicy, PolicyViolation};
+    use crate::bytes::Regex;
+
+    fn check(policy: &PatternPolicy, pattern: &str) -> Result<(), PolicyViolation> {
+        policy.check(&Regex::new(pattern).unwrap())
+    }
+
+    #[test]
+    fn check_policy() {
+        let everything = r"(a)\1(?C1)\C(?<=abc)";
+        assert!(check(&PatternPolicy::new(), everything).is_ok());
+
+        let policy = PatternPolicy::new().deny_backrefs(true).clone();
+        assert!(matches!(
+            check(&policy, everything),
+            Err(PolicyViolation::Backref)
+        ));
+        assert!(check(&policy, "(a)").is_ok());
+
+        let policy = PatternPolicy::new().deny_recursion(true).clone();
+        assert!(matches!(
+            check(&policy, r"\((?:[^()]|(?R))*\)"),
+            Err(PolicyViolation::Recursion)
+        ));
+        assert!(check(&policy, everything).is_ok());
+
+        let policy = PatternPolicy::new().deny_backslash_c(true).clone();
+        assert!(matches!(
+            check(&policy, r"a\C"),
+            Err(PolicyViolation::BackslashC)
+        ));
+
+        let policy = PatternPolicy::new().allowed_callouts(Some([1])).clone();
+        assert!(check(&policy, "(?C1)a").is_ok());
+        match check(&policy, "(?C1)a(?C2)") {
+            Err(PolicyViolation::Callout(callout)) => assert_eq!(callout.number(), 2),
+            result => panic!("expected a callout violation, got {:?}", result),
+        }
+        let policy = policy.clone().deny_callouts(true).clone();
+        assert!(matches!(
+            check(&policy, "(?C1)a"),
+            Err(PolicyViolation::Callout(_))
+        ));
+
+        let policy = PatternPolicy::new().max_lookbehind(Some(2)).clone();
+        assert!(check(&policy, "(?<=ab)c").is_ok());
+        assert!(matches!(
+            check(&policy, everything),
+            Err(PolicyViolation::Lookbehind(3))
+        ));
+    }
+
+    #[test]
+    fn subroutine_calls() {
+        let yes = [
+            r"(?R)",
+            r"a(?0)",
+            r"(a)(?1)",
+            r"(a)(?-1)",
+            r"(?+1)(a)",
+            r"(?<n>a)(?&n)",
+            r"(?P<n>a)(?P>n)",
+            r"(?<n>a)\g<n>",
+            r"(a)\g'1'",
+        ];
+        for pattern in yes {
+            let units = pattern.bytes().map(u32::from).collect::<Vec<_>>();
+            assert!(has_subroutine_call(&units), "{:?}", pattern);
+        }
+        let no = [
+            r"(a)\1\g1\g{-1}",
+            r"(?(R)a|b)(?i)(?-i)",
+            r"\(?R)",
+            r"[(?R)]",
+            r"[](?R)]",
+            r"[[:alpha:](?R)]",
+            r"\Q(?R)\E",
+            r"(?#(?R)",
+        ];
+        for pattern in no {
+            let units = pattern.bytes().map(u32::from).collect::<Vec<_>>();
+            assert!(!has_subroutine_call(&units), "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn violation_display() {
+        let policy = PatternPolicy::new().deny_callouts(true).clone();
+        let err = check(&policy, "a(?C'log')").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"pattern contains callout "log" at offset 10"#
+        );
+        let err = check(&policy, "(?C7)").unwrap_err();
+        assert_eq!(err.to_string(), "pattern contains callout 7 at offset 5");
+        assert_eq!(
+            PolicyViolation::Lookbehind(3).to_string(),
+            "pattern contains a lookbehind of 3 characters"
+        );
+    }
+}
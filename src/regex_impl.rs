@@ -1,24 +1,55 @@
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::cmp;
 use std::collections::HashMap;
+use std::ffi::{CStr, CString};
 use std::fmt;
-use std::ops::Index;
-use std::sync::Arc;
+use std::ops::{Index, Range};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
 
+use libc::c_int;
 use log::debug;
 use pcre2_sys::{
-    PCRE2_CASELESS, PCRE2_DOTALL, PCRE2_EXTENDED, PCRE2_MULTILINE, PCRE2_NEVER_UTF,
-    PCRE2_NEWLINE_ANYCRLF, PCRE2_NO_UTF_CHECK, PCRE2_UCP, PCRE2_UNSET, PCRE2_UTF,
+    PCRE2_ALLOW_EMPTY_CLASS, PCRE2_ALT_BSUX, PCRE2_ANCHORED, PCRE2_AUTO_CALLOUT, PCRE2_BSR_ANYCRLF,
+    PCRE2_BSR_UNICODE, PCRE2_CASELESS, PCRE2_DFA_RESTART, PCRE2_DFA_SHORTEST, PCRE2_DOLLAR_ENDONLY,
+    PCRE2_DOTALL, PCRE2_DUPNAMES, PCRE2_ENDANCHORED, PCRE2_ERROR_BADOFFSET, PCRE2_ERROR_BADOPTION,
+    PCRE2_EXTENDED, PCRE2_EXTRA_ALLOW_SURROGATE_ESCAPES, PCRE2_EXTRA_ALT_BSUX,
+    PCRE2_EXTRA_BAD_ESCAPE_IS_LITERAL, PCRE2_EXTRA_ESCAPED_CR_IS_LF, PCRE2_EXTRA_MATCH_LINE,
+    PCRE2_EXTRA_MATCH_WORD, PCRE2_FIRSTLINE, PCRE2_JIT_INVALID_UTF, PCRE2_JIT_PARTIAL_HARD,
+    PCRE2_JIT_PARTIAL_SOFT, PCRE2_LITERAL, PCRE2_MATCH_INVALID_UTF, PCRE2_MATCH_UNSET_BACKREF,
+    PCRE2_MULTILINE, PCRE2_NEVER_BACKSLASH_C, PCRE2_NEVER_UTF, PCRE2_NEWLINE_ANY,
+    PCRE2_NEWLINE_ANYCRLF, PCRE2_NEWLINE_CR, PCRE2_NEWLINE_CRLF, PCRE2_NEWLINE_LF,
+    PCRE2_NEWLINE_NUL, PCRE2_NOTBOL, PCRE2_NOTEMPTY, PCRE2_NOTEMPTY_ATSTART, PCRE2_NOTEOL,
+    PCRE2_NO_AUTO_CAPTURE, PCRE2_NO_AUTO_POSSESS, PCRE2_NO_JIT, PCRE2_NO_START_OPTIMIZE,
+    PCRE2_NO_UTF_CHECK, PCRE2_PARTIAL_HARD, PCRE2_PARTIAL_SOFT, PCRE2_SUBSTITUTE_EXTENDED,
+    PCRE2_SUBSTITUTE_GLOBAL, PCRE2_SUBSTITUTE_LITERAL, PCRE2_SUBSTITUTE_MATCHED,
+    PCRE2_SUBSTITUTE_REPLACEMENT_ONLY, PCRE2_SUBSTITUTE_UNKNOWN_UNSET,
+    PCRE2_SUBSTITUTE_UNSET_EMPTY, PCRE2_UCP, PCRE2_UNGREEDY, PCRE2_UNSET, PCRE2_USE_OFFSET_LIMIT,
+    PCRE2_UTF,
 };
 use thread_local::ThreadLocal;
 
+use crate::cache::fnv1a;
 use crate::error::Error;
-use crate::ffi::{Code, CodeUnitWidth, CompileContext, MatchConfig, MatchData};
+use crate::ffi::{
+    default_bsr, default_newline, maketables, serialized_default_tables, version, CalloutBlock,
+    CalloutEntry, CalloutFn, CalloutResult, Code, CodeUnitWidth, CompileContext, MatchConfig,
+    MatchData, NameTable, Progress, RecursionGuard,
+};
+
+/// The number of integers in the workspace used by PCRE2's DFA matching
+/// algorithm. This is the same default that `pcre2test` uses.
+const DFA_WORKSPACE_SIZE: usize = 1000;
 
 /// Match represents a single match of a regex in a subject string.
 ///
 /// The lifetime parameter `'s` refers to the lifetime of the matched portion
 /// of the subject string.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(not(feature = "bstr"), derive(Debug))]
 pub struct Match<'s, W: CodeUnitWidth> {
     subject: &'s [W::SubjectChar],
     start: usize,
@@ -45,7 +76,7 @@ impl<'s, W: CodeUnitWidth> Match<'s, W> {
     }
 
     /// Creates a new match from the given subject string and byte offsets.
-    fn new(subject: &'s [W::SubjectChar], start: usize, end: usize) -> Self {
+    pub(crate) fn new(subject: &'s [W::SubjectChar], start: usize, end: usize) -> Self {
         Match {
             subject,
             start,
@@ -53,12 +84,350 @@ impl<'s, W: CodeUnitWidth> Match<'s, W> {
         }
     }
 
+    /// Returns the entire subject string that was searched.
+    #[cfg(feature = "utf16")]
+    pub(crate) fn subject(&self) -> &'s [W::SubjectChar] {
+        self.subject
+    }
+
     #[cfg(test)]
     pub(crate) fn as_pair(&self) -> (usize, usize) {
         (self.start, self.end)
     }
 }
 
+/// With the `bstr` feature, the matched text is shown, lossily decoded.
+#[cfg(feature = "bstr")]
+impl<'s, W: CodeUnitWidth> fmt::Debug for Match<'s, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Match")
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .field("text", &W::escape_subject(self.as_bytes()))
+            .finish()
+    }
+}
+
+/// How partial matches are treated by searches such as
+/// `Regex::find_partial_at`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PartialMode {
+    /// Report a partial match only if no complete match can be found.
+    ///
+    /// This corresponds to `PCRE2_PARTIAL_SOFT`.
+    Soft,
+    /// Report a partial match as soon as one is found, even if a complete
+    /// match could also be found. For example, searching `ab` for `ab|abc`
+    /// reports a partial match, since more input could change the result.
+    /// This is usually the right choice when processing input in chunks.
+    ///
+    /// This corresponds to `PCRE2_PARTIAL_HARD`.
+    Hard,
+}
+
+impl PartialMode {
+    fn to_raw(self) -> u32 {
+        match self {
+            PartialMode::Soft => PCRE2_PARTIAL_SOFT,
+            PartialMode::Hard => PCRE2_PARTIAL_HARD,
+        }
+    }
+}
+
+/// A newline convention, which determines what PCRE2 treats as a line
+/// terminator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Newline {
+    /// Carriage return only. This corresponds to `PCRE2_NEWLINE_CR`.
+    Cr,
+    /// Linefeed only. This corresponds to `PCRE2_NEWLINE_LF`.
+    Lf,
+    /// Carriage return followed by linefeed. This corresponds to
+    /// `PCRE2_NEWLINE_CRLF`.
+    CrLf,
+    /// Any Unicode line ending. This corresponds to `PCRE2_NEWLINE_ANY`.
+    Any,
+    /// Any of carriage return, linefeed or carriage return followed by
+    /// linefeed. This corresponds to `PCRE2_NEWLINE_ANYCRLF`.
+    AnyCrLf,
+    /// The NUL character. This corresponds to `PCRE2_NEWLINE_NUL`.
+    Nul,
+}
+
+impl Newline {
+    /// Converts a `PCRE2_NEWLINE_*` constant reported by PCRE2, or returns
+    /// `None` if the constant is unknown.
+    fn from_raw(raw: u32) -> Option<Newline> {
+        match raw {
+            PCRE2_NEWLINE_CR => Some(Newline::Cr),
+            PCRE2_NEWLINE_LF => Some(Newline::Lf),
+            PCRE2_NEWLINE_CRLF => Some(Newline::CrLf),
+            PCRE2_NEWLINE_ANY => Some(Newline::Any),
+            PCRE2_NEWLINE_ANYCRLF => Some(Newline::AnyCrLf),
+            PCRE2_NEWLINE_NUL => Some(Newline::Nul),
+            _ => None,
+        }
+    }
+
+    /// Converts to the corresponding `PCRE2_NEWLINE_*` constant.
+    fn to_raw(self) -> u32 {
+        match self {
+            Newline::Cr => PCRE2_NEWLINE_CR,
+            Newline::Lf => PCRE2_NEWLINE_LF,
+            Newline::CrLf => PCRE2_NEWLINE_CRLF,
+            Newline::Any => PCRE2_NEWLINE_ANY,
+            Newline::AnyCrLf => PCRE2_NEWLINE_ANYCRLF,
+            Newline::Nul => PCRE2_NEWLINE_NUL,
+        }
+    }
+}
+
+/// The set of characters that `\R` matches.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BsrConvention {
+    /// Any Unicode line ending. This corresponds to `PCRE2_BSR_UNICODE`.
+    Unicode,
+    /// Only carriage return, linefeed or carriage return followed by
+    /// linefeed. This corresponds to `PCRE2_BSR_ANYCRLF`.
+    AnyCrLf,
+}
+
+impl BsrConvention {
+    /// Converts a `PCRE2_BSR_*` constant reported by PCRE2, or returns `None`
+    /// if the constant is unknown.
+    fn from_raw(raw: u32) -> Option<BsrConvention> {
+        match raw {
+            PCRE2_BSR_UNICODE => Some(BsrConvention::Unicode),
+            PCRE2_BSR_ANYCRLF => Some(BsrConvention::AnyCrLf),
+            _ => None,
+        }
+    }
+
+    /// Converts to the corresponding `PCRE2_BSR_*` constant.
+    fn to_raw(self) -> u32 {
+        match self {
+            BsrConvention::Unicode => PCRE2_BSR_UNICODE,
+            BsrConvention::AnyCrLf => PCRE2_BSR_ANYCRLF,
+        }
+    }
+}
+
+/// Character tables, which determine how PCRE2 classifies and case folds
+/// characters with code points less than 256 when Unicode properties aren't
+/// in use.
+///
+/// By default, PCRE2 uses built-in tables for the "C" locale, so only ASCII
+/// letters, digits and the underscore are word characters. Tables built for
+/// another locale make `\w`, `\b`, `[[:alpha:]]` and caseless matching
+/// respect that locale, which is useful for searching text in a legacy single
+/// byte encoding such as Latin-1. Tables have no effect on characters when
+/// `ucp` is enabled.
+///
+/// Use `RegexBuilder::character_tables` to compile regexes with them. The
+/// tables are reference counted, so cloning them is cheap, and every regex
+/// compiled with clones of the same tables shares a single copy of them,
+/// even across builders.
+pub struct CharacterTables<W: CodeUnitWidth> {
+    locale: String,
+    tables: Arc<[u8]>,
+    _phantom: std::marker::PhantomData<W>,
+}
+
+impl<W: CodeUnitWidth> Clone for CharacterTables<W> {
+    fn clone(&self) -> Self {
+        CharacterTables {
+            locale: self.locale.clone(),
+            tables: Arc::clone(&self.tables),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<W: CodeUnitWidth> fmt::Debug for CharacterTables<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CharacterTables")
+            .field("locale", &self.locale)
+            .finish()
+    }
+}
+
+impl<W: CodeUnitWidth> CharacterTables<W> {
+    /// Build character tables for the current `LC_CTYPE` locale of the
+    /// process, as set by `setlocale`.
+    ///
+    /// This corresponds to `pcre2_maketables`.
+    pub fn current_locale() -> CharacterTables<W> {
+        // SAFETY: With a null locale, setlocale only queries the locale.
+        let name = unsafe { libc::setlocale(libc::LC_CTYPE, std::ptr::null()) };
+        let locale = if name.is_null() {
+            String::new()
+        } else {
+            // SAFETY: setlocale returned a NUL terminated string.
+            unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        CharacterTables {
+            locale,
+            tables: maketables::<W>().into(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Build character tables for the named locale, such as
+    /// `de_DE.ISO-8859-1`, without changing the locale of the process.
+    ///
+    /// If the locale isn't available, then `None` is returned.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd"
+    ))]
+    pub fn for_locale(name: &str) -> Option<CharacterTables<W>> {
+        let cname = CString::new(name).ok()?;
+        // SAFETY: The locale is only used on this thread, for the duration of
+        // building the tables, and is freed afterwards.
+        let tables = unsafe {
+            let locale = libc::newlocale(libc::LC_CTYPE_MASK, cname.as_ptr(), std::ptr::null_mut());
+            if locale.is_null() {
+                return None;
+            }
+            let old = libc::uselocale(locale);
+            let tables = maketables::<W>();
+            libc::uselocale(old);
+            libc::freelocale(locale);
+            tables
+        };
+        Some(CharacterTables {
+            locale: name.to_string(),
+            tables: tables.into(),
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    /// Returns the name of the locale these tables were built for.
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+}
+
+/// What is known about the start of every match of a regex, as returned by
+/// `Regex::first_code_unit`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FirstCodeUnit {
+    /// Every match starts with the given code unit.
+    ///
+    /// PCRE2 doesn't report whether the unit is matched caselessly, so if it
+    /// has another case, a match may start with that instead unless the
+    /// pattern is known to be case sensitive. Which cases a unit has is
+    /// determined by the character tables or the Unicode properties that
+    /// the regex was compiled with.
+    Fixed(u32),
+    /// Every match starts at the beginning of the subject or right after a
+    /// newline, such as for `^abc|^xyz` in multi-line mode.
+    StartOfLine,
+    /// Nothing is known about how a match starts. This is also the case for
+    /// anchored patterns.
+    Unknown,
+}
+
+/// A callout in a compiled pattern, as returned by `Regex::callouts`.
+///
+/// A callout is either numbered, such as `(?C1)`, or carries a string, such
+/// as `(?C"text")`. A callout without an argument, `(?C)`, has number `0`.
+#[derive(Clone, Debug)]
+pub struct CalloutInfo {
+    entry: CalloutEntry,
+}
+
+impl CalloutInfo {
+    /// Returns the number of this callout, which is `0` for string callouts.
+    pub fn number(&self) -> u32 {
+        self.entry.number
+    }
+
+    /// Returns the string argument of this callout, without its delimiters,
+    /// if it is a string callout.
+    ///
+    /// A delimiter that is doubled in the pattern in order to include it in
+    /// the string is reported as it appears in the pattern.
+    pub fn string(&self) -> Option<&str> {
+        self.entry.string.as_ref().map(|(_, string)| &**string)
+    }
+
+    /// Returns the offset in the pattern at which the string argument of
+    /// this callout starts, if it is a string callout.
+    pub fn string_offset(&self) -> Option<usize> {
+        self.entry.string.as_ref().map(|&(offset, _)| offset)
+    }
+
+    /// Returns the offset in the pattern of the item that follows this
+    /// callout.
+    pub fn pattern_position(&self) -> usize {
+        self.entry.pattern_position
+    }
+
+    /// Returns the length of the item in the pattern that follows this
+    /// callout.
+    pub fn next_item_length(&self) -> usize {
+        self.entry.next_item_length
+    }
+}
+
+/// The result of a search that permits partial matches, such as
+/// `Regex::find_partial_at`.
+pub enum PartialMatch<'s, W: CodeUnitWidth> {
+    /// A complete match.
+    Complete(Match<'s, W>),
+    /// A partial match, which spans from where a possible match starts to
+    /// the end of the subject.
+    Partial(Match<'s, W>),
+}
+
+impl<'s, W: CodeUnitWidth> fmt::Debug for PartialMatch<'s, W>
+where
+    Match<'s, W>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartialMatch::Complete(m) => f.debug_tuple("Complete").field(m).finish(),
+            PartialMatch::Partial(m) => f.debug_tuple("Partial").field(m).finish(),
+        }
+    }
+}
+
+impl<'s, W: CodeUnitWidth> PartialMatch<'s, W> {
+    /// Creates a new result from the most recent search with the given match
+    /// data, which must have found a complete or partial match.
+    fn new(subject: &'s [W::SubjectChar], match_data: &MatchData<W>) -> Self {
+        let ovector = match_data.ovector();
+        let m = Match::new(subject, ovector[0], ovector[1]);
+        if match_data.partial() {
+            PartialMatch::Partial(m)
+        } else {
+            PartialMatch::Complete(m)
+        }
+    }
+
+    /// Returns true if and only if this is a partial match.
+    pub fn is_partial(&self) -> bool {
+        matches!(self, PartialMatch::Partial(_))
+    }
+
+    /// Returns the matched portion of the subject, regardless of whether the
+    /// match is complete or partial.
+    pub fn into_match(self) -> Match<'s, W> {
+        match self {
+            PartialMatch::Complete(m) | PartialMatch::Partial(m) => m,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Config {
     /// PCRE2_CASELESS
@@ -69,8 +438,64 @@ struct Config {
     extended: bool,
     /// PCRE2_MULTILINE
     multi_line: bool,
-    /// PCRE2_NEWLINE_ANYCRLF
-    crlf: bool,
+    /// pcre2_set_newline, or PCRE2's default when unset
+    newline: Option<Newline>,
+    /// pcre2_set_bsr, or PCRE2's default when unset
+    bsr: Option<BsrConvention>,
+    /// pcre2_set_max_pattern_length
+    max_pattern_length: Option<usize>,
+    /// pcre2_set_parens_nest_limit
+    parens_nest_limit: Option<u32>,
+    /// Checked after compiling, since PCRE2 has no such limit
+    max_capture_groups: Option<usize>,
+    /// pcre2_set_compile_recursion_guard
+    recursion_guard: Option<CompileRecursionGuard>,
+    /// PCRE2_ENDANCHORED
+    end_anchored: bool,
+    /// PCRE2_USE_OFFSET_LIMIT
+    use_offset_limit: bool,
+    /// PCRE2_AUTO_CALLOUT, for checking deadlines
+    interruptible: bool,
+    /// PCRE2_EXTRA_MATCH_WORD
+    match_word: bool,
+    /// PCRE2_EXTRA_MATCH_LINE
+    match_line: bool,
+    /// PCRE2_EXTRA_BAD_ESCAPE_IS_LITERAL
+    bad_escape_is_literal: bool,
+    /// PCRE2_EXTRA_ALLOW_SURROGATE_ESCAPES
+    allow_surrogate_escapes: bool,
+    /// PCRE2_EXTRA_ESCAPED_CR_IS_LF
+    escaped_cr_is_lf: bool,
+    /// PCRE2_ALT_BSUX and PCRE2_EXTRA_ALT_BSUX
+    alt_bsux: bool,
+    /// PCRE2_LITERAL
+    literal: bool,
+    /// PCRE2_DUPNAMES
+    dupnames: bool,
+    /// PCRE2_NO_AUTO_CAPTURE
+    no_auto_capture: bool,
+    /// PCRE2_UNGREEDY
+    ungreedy: bool,
+    /// PCRE2_DOLLAR_ENDONLY
+    dollar_endonly: bool,
+    /// PCRE2_ALLOW_EMPTY_CLASS
+    allow_empty_class: bool,
+    /// PCRE2_ANCHORED
+    anchored: bool,
+    /// PCRE2_NO_AUTO_POSSESS
+    no_auto_possess: bool,
+    /// PCRE2_NO_START_OPTIMIZE
+    no_start_optimize: bool,
+    /// PCRE2_FIRSTLINE
+    firstline: bool,
+    /// PCRE2_MATCH_UNSET_BACKREF
+    match_unset_backref: bool,
+    /// PCRE2_NEVER_BACKSLASH_C
+    never_backslash_c: bool,
+    /// PCRE2_MATCH_INVALID_UTF
+    match_invalid_utf: bool,
+    /// How iterators proceed after an empty match.
+    empty_match_policy: EmptyMatchPolicy,
     /// PCRE2_UCP
     ucp: bool,
     /// PCRE2_UTF
@@ -81,10 +506,43 @@ struct Config {
     utf_check: bool,
     /// use pcre2_jit_compile
     jit: JITChoice,
+    /// PCRE2_JIT_PARTIAL_SOFT
+    jit_partial_soft: bool,
+    /// PCRE2_JIT_PARTIAL_HARD
+    jit_partial_hard: bool,
+    /// use pcre2_jit_match
+    jit_fast_path: bool,
+    /// JIT compile after this many searches
+    auto_jit: Option<usize>,
     /// Match-time specific configuration knobs.
     match_config: MatchConfig,
 }
 
+/// A callback installed with `RegexBuilder::compile_recursion_guard`.
+#[derive(Clone)]
+struct CompileRecursionGuard(RecursionGuard);
+
+impl fmt::Debug for CompileRecursionGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CompileRecursionGuard(..)")
+    }
+}
+
+/// A callback installed with `RegexBuilder::callout`.
+struct Callout<W: CodeUnitWidth>(CalloutFn<W>);
+
+impl<W: CodeUnitWidth> Clone for Callout<W> {
+    fn clone(&self) -> Self {
+        Callout(Arc::clone(&self.0))
+    }
+}
+
+impl<W: CodeUnitWidth> fmt::Debug for Callout<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Callout(..)")
+    }
+}
+
 #[derive(Clone, Debug)]
 enum JITChoice {
     /// Never do JIT compilation.
@@ -102,30 +560,420 @@ impl Default for Config {
             dotall: false,
             extended: false,
             multi_line: false,
-            crlf: false,
+            newline: None,
+            bsr: None,
+            max_pattern_length: None,
+            parens_nest_limit: None,
+            max_capture_groups: None,
+            recursion_guard: None,
+            end_anchored: false,
+            use_offset_limit: false,
+            interruptible: false,
+            match_word: false,
+            match_line: false,
+            bad_escape_is_literal: false,
+            allow_surrogate_escapes: false,
+            escaped_cr_is_lf: false,
+            alt_bsux: false,
+            literal: false,
+            dupnames: false,
+            no_auto_capture: false,
+            ungreedy: false,
+            dollar_endonly: false,
+            allow_empty_class: false,
+            anchored: false,
+            no_auto_possess: false,
+            no_start_optimize: false,
+            firstline: false,
+            match_unset_backref: false,
+            never_backslash_c: false,
+            match_invalid_utf: false,
+            empty_match_policy: EmptyMatchPolicy::default(),
             ucp: false,
             utf: false,
             never_utf: false,
             utf_check: true,
             jit: JITChoice::Never,
+            jit_partial_soft: false,
+            jit_partial_hard: false,
+            jit_fast_path: false,
+            auto_jit: None,
             match_config: MatchConfig::default(),
         }
     }
 }
 
+impl Config {
+    /// Returns the `PCRE2_JIT_*` options to JIT compile with, in addition to
+    /// `PCRE2_JIT_COMPLETE`.
+    fn jit_options(&self) -> u32 {
+        let mut options = 0;
+        if self.jit_partial_soft {
+            options |= PCRE2_JIT_PARTIAL_SOFT;
+        }
+        if self.jit_partial_hard {
+            options |= PCRE2_JIT_PARTIAL_HARD;
+        }
+        if self.match_invalid_utf {
+            // Newer PCRE2 releases infer this from PCRE2_MATCH_INVALID_UTF,
+            // but 10.34 requires it to be passed explicitly.
+            options |= PCRE2_JIT_INVALID_UTF;
+        }
+        options
+    }
+}
+
+/// The configuration of a `RegexBuilder` in the form that serde
+/// (de)serializes it, which is also how a serialized `Regex` records the
+/// configuration it was built with.
+///
+/// Each field corresponds to the `RegexBuilder` method of the same name,
+/// and missing fields take their default values when deserializing. The
+/// `version` field identifies this format. Deserializing any version other
+/// than `1`, or a field that isn't part of this format, is an error.
+///
+/// The options enabled by the unsafe methods `disable_utf_check` and
+/// `jit_fast_path` aren't part of this format, so that deserialized data
+/// can't enable them. They are dropped when serializing. Character tables,
+/// callouts, compile recursion guards and progress callbacks can't be
+/// serialized at all, so serializing a builder or regex that uses any of
+/// them fails instead.
+///
+/// This is only available when the `serde` feature is enabled.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct SerializedConfig {
+    /// The version of this format, which is `1`.
+    pub version: u32,
+    /// See `RegexBuilder::caseless`.
+    pub caseless: bool,
+    /// See `RegexBuilder::dotall`.
+    pub dotall: bool,
+    /// See `RegexBuilder::extended`.
+    pub extended: bool,
+    /// See `RegexBuilder::multi_line`.
+    pub multi_line: bool,
+    /// See `RegexBuilder::newline`. `None` means PCRE2's default.
+    pub newline: Option<Newline>,
+    /// See `RegexBuilder::backslash_r`. `None` means PCRE2's default.
+    pub backslash_r: Option<BsrConvention>,
+    /// See `RegexBuilder::max_pattern_length`.
+    pub max_pattern_length: Option<usize>,
+    /// See `RegexBuilder::parens_nest_limit`.
+    pub parens_nest_limit: Option<u32>,
+    /// See `RegexBuilder::max_capture_groups`.
+    pub max_capture_groups: Option<usize>,
+    /// See `RegexBuilder::end_anchored`.
+    pub end_anchored: bool,
+    /// See `RegexBuilder::use_offset_limit`.
+    pub use_offset_limit: bool,
+    /// See `RegexBuilder::offset_limit`.
+    pub offset_limit: Option<usize>,
+    /// See `RegexBuilder::interruptible`.
+    pub interruptible: bool,
+    /// See `RegexBuilder::match_word`.
+    pub match_word: bool,
+    /// See `RegexBuilder::match_line`.
+    pub match_line: bool,
+    /// See `RegexBuilder::bad_escape_is_literal`.
+    pub bad_escape_is_literal: bool,
+    /// See `RegexBuilder::allow_surrogate_escapes`.
+    pub allow_surrogate_escapes: bool,
+    /// See `RegexBuilder::escaped_cr_is_lf`.
+    pub escaped_cr_is_lf: bool,
+    /// See `RegexBuilder::alt_bsux`.
+    pub alt_bsux: bool,
+    /// See `RegexBuilder::literal`.
+    pub literal: bool,
+    /// See `RegexBuilder::dupnames`.
+    pub dupnames: bool,
+    /// See `RegexBuilder::no_auto_capture`.
+    pub no_auto_capture: bool,
+    /// See `RegexBuilder::ungreedy`.
+    pub ungreedy: bool,
+    /// See `RegexBuilder::dollar_endonly`.
+    pub dollar_endonly: bool,
+    /// See `RegexBuilder::allow_empty_class`.
+    pub allow_empty_class: bool,
+    /// See `RegexBuilder::anchored`.
+    pub anchored: bool,
+    /// See `RegexBuilder::no_auto_possess`.
+    pub no_auto_possess: bool,
+    /// See `RegexBuilder::no_start_optimize`.
+    pub no_start_optimize: bool,
+    /// See `RegexBuilder::firstline`.
+    pub firstline: bool,
+    /// See `RegexBuilder::match_unset_backref`.
+    pub match_unset_backref: bool,
+    /// See `RegexBuilder::never_backslash_c`.
+    pub never_backslash_c: bool,
+    /// See `RegexBuilder::match_invalid_utf`.
+    pub match_invalid_utf: bool,
+    /// See `RegexBuilder::empty_match_policy`.
+    pub empty_match_policy: EmptyMatchPolicy,
+    /// See `RegexBuilder::ucp`.
+    pub ucp: bool,
+    /// See `RegexBuilder::utf`.
+    pub utf: bool,
+    /// See `RegexBuilder::never_utf`.
+    pub never_utf: bool,
+    /// See `RegexBuilder::jit`. This takes precedence over
+    /// `jit_if_available`.
+    pub jit: bool,
+    /// See `RegexBuilder::jit_if_available`.
+    pub jit_if_available: bool,
+    /// See `RegexBuilder::jit_partial` with `PartialMode::Soft`.
+    pub jit_partial_soft: bool,
+    /// See `RegexBuilder::jit_partial` with `PartialMode::Hard`.
+    pub jit_partial_hard: bool,
+    /// See `RegexBuilder::auto_jit`.
+    pub auto_jit: Option<usize>,
+    /// See `RegexBuilder::max_jit_stack_size`.
+    pub max_jit_stack_size: Option<usize>,
+    /// See `RegexBuilder::match_limit`.
+    pub match_limit: Option<u32>,
+    /// See `RegexBuilder::heap_limit`.
+    pub heap_limit: Option<u32>,
+    /// See `RegexBuilder::depth_limit`.
+    pub depth_limit: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl Default for SerializedConfig {
+    fn default() -> SerializedConfig {
+        SerializedConfig::new(&Config::default())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SerializedConfig {
+    /// The version of the format written by this crate.
+    const VERSION: u32 = 1;
+
+    /// Describes the given configuration, leaving out the options that
+    /// aren't part of the format.
+    fn new(config: &Config) -> SerializedConfig {
+        let mc = &config.match_config;
+        SerializedConfig {
+            version: SerializedConfig::VERSION,
+            caseless: config.caseless,
+            dotall: config.dotall,
+            extended: config.extended,
+            multi_line: config.multi_line,
+            newline: config.newline,
+            backslash_r: config.bsr,
+            max_pattern_length: config.max_pattern_length,
+            parens_nest_limit: config.parens_nest_limit,
+            max_capture_groups: config.max_capture_groups,
+            end_anchored: config.end_anchored,
+            use_offset_limit: config.use_offset_limit,
+            offset_limit: mc.offset_limit,
+            interruptible: config.interruptible,
+            match_word: config.match_word,
+            match_line: config.match_line,
+            bad_escape_is_literal: config.bad_escape_is_literal,
+            allow_surrogate_escapes: config.allow_surrogate_escapes,
+            escaped_cr_is_lf: config.escaped_cr_is_lf,
+            alt_bsux: config.alt_bsux,
+            literal: config.literal,
+            dupnames: config.dupnames,
+            no_auto_capture: config.no_auto_capture,
+            ungreedy: config.ungreedy,
+            dollar_endonly: config.dollar_endonly,
+            allow_empty_class: config.allow_empty_class,
+            anchored: config.anchored,
+            no_auto_possess: config.no_auto_possess,
+            no_start_optimize: config.no_start_optimize,
+            firstline: config.firstline,
+            match_unset_backref: config.match_unset_backref,
+            never_backslash_c: config.never_backslash_c,
+            match_invalid_utf: config.match_invalid_utf,
+            empty_match_policy: config.empty_match_policy,
+            ucp: config.ucp,
+            utf: config.utf,
+            never_utf: config.never_utf,
+            jit: matches!(config.jit, JITChoice::Always),
+            jit_if_available: matches!(config.jit, JITChoice::Attempt),
+            jit_partial_soft: config.jit_partial_soft,
+            jit_partial_hard: config.jit_partial_hard,
+            auto_jit: config.auto_jit,
+            max_jit_stack_size: mc.max_jit_stack_size,
+            match_limit: mc.match_limit,
+            heap_limit: mc.heap_limit,
+            depth_limit: mc.depth_limit,
+        }
+    }
+
+    /// Describes the configuration of a builder with the given character
+    /// tables and callout, or returns an error if any of them can't be
+    /// serialized.
+    fn with_callbacks(config: &Config, tables: bool, callout: bool) -> Result<Self, String> {
+        if tables {
+            return Err("character tables can't be serialized".to_string());
+        }
+        if callout {
+            return Err("callouts can't be serialized".to_string());
+        }
+        if config.recursion_guard.is_some() {
+            return Err("compile recursion guards can't be serialized".to_string());
+        }
+        if config.match_config.progress.is_some() {
+            return Err("progress callbacks can't be serialized".to_string());
+        }
+        Ok(SerializedConfig::new(config))
+    }
+
+    /// Returns the configuration described by this value, or an error if
+    /// it's in an unknown version of the format.
+    fn into_config(self) -> Result<Config, String> {
+        if self.version != SerializedConfig::VERSION {
+            return Err(format!(
+                "unsupported regex configuration version {}",
+                self.version
+            ));
+        }
+        let jit = if self.jit {
+            JITChoice::Always
+        } else if self.jit_if_available {
+            JITChoice::Attempt
+        } else {
+            JITChoice::Never
+        };
+        Ok(Config {
+            caseless: self.caseless,
+            dotall: self.dotall,
+            extended: self.extended,
+            multi_line: self.multi_line,
+            newline: self.newline,
+            bsr: self.backslash_r,
+            max_pattern_length: self.max_pattern_length,
+            parens_nest_limit: self.parens_nest_limit,
+            max_capture_groups: self.max_capture_groups,
+            end_anchored: self.end_anchored,
+            use_offset_limit: self.use_offset_limit,
+            interruptible: self.interruptible,
+            match_word: self.match_word,
+            match_line: self.match_line,
+            bad_escape_is_literal: self.bad_escape_is_literal,
+            allow_surrogate_escapes: self.allow_surrogate_escapes,
+            escaped_cr_is_lf: self.escaped_cr_is_lf,
+            alt_bsux: self.alt_bsux,
+            literal: self.literal,
+            dupnames: self.dupnames,
+            no_auto_capture: self.no_auto_capture,
+            ungreedy: self.ungreedy,
+            dollar_endonly: self.dollar_endonly,
+            allow_empty_class: self.allow_empty_class,
+            anchored: self.anchored,
+            no_auto_possess: self.no_auto_possess,
+            no_start_optimize: self.no_start_optimize,
+            firstline: self.firstline,
+            match_unset_backref: self.match_unset_backref,
+            never_backslash_c: self.never_backslash_c,
+            match_invalid_utf: self.match_invalid_utf,
+            empty_match_policy: self.empty_match_policy,
+            ucp: self.ucp,
+            utf: self.utf,
+            never_utf: self.never_utf,
+            jit,
+            jit_partial_soft: self.jit_partial_soft,
+            jit_partial_hard: self.jit_partial_hard,
+            auto_jit: self.auto_jit,
+            match_config: MatchConfig {
+                max_jit_stack_size: self.max_jit_stack_size,
+                match_limit: self.match_limit,
+                heap_limit: self.heap_limit,
+                offset_limit: self.offset_limit,
+                depth_limit: self.depth_limit,
+                ..MatchConfig::default()
+            },
+            ..Config::default()
+        })
+    }
+}
+
+/// How iterators over successive non-overlapping matches, such as
+/// `Regex::find_iter`, proceed after finding a match of the empty string.
+///
+/// The two policies only differ for patterns that can match the empty
+/// string. For example, when searching `baaa` for `a*`, the `regex` crate
+/// reports the matches at `0..0` and `1..4`, while PCRE2 additionally
+/// reports an empty match at `4..4`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EmptyMatchPolicy {
+    /// Resume searching one position after an empty match, and skip empty
+    /// matches that immediately follow the previous match.
+    ///
+    /// This is what the `regex` crate does, and is the default.
+    #[default]
+    RegexCompatible,
+    /// After an empty match, look for a non-empty match at the same position
+    /// first, and only move on by one character if there isn't one. Empty
+    /// matches that immediately follow a non-empty match are reported.
+    ///
+    /// This is what `pcre2test` and `pcre2_substitute` do.
+    Pcre2Compatible,
+}
+
 /// A builder for configuring the compilation of a PCRE2 regex.
 /// This takes a phantom parameter to aid type inference.
-#[derive(Clone, Debug)]
+///
+/// When the `serde` feature is enabled, a builder (de)serializes as its
+/// configuration, in the format described by `SerializedConfig`.
+#[derive(Debug)]
 pub struct RegexBuilder<W: CodeUnitWidth> {
     config: Config,
+    tables: Option<CharacterTables<W>>,
+    callout: Option<Callout<W>>,
     _phantom: std::marker::PhantomData<W>,
 }
 
+#[cfg(feature = "serde")]
+impl<W: CodeUnitWidth> serde::Serialize for RegexBuilder<W> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let tables = self.tables.is_some();
+        SerializedConfig::with_callbacks(&self.config, tables, self.callout.is_some())
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, W: CodeUnitWidth> serde::Deserialize<'de> for RegexBuilder<W> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = SerializedConfig::deserialize(deserializer)?
+            .into_config()
+            .map_err(serde::de::Error::custom)?;
+        Ok(RegexBuilder {
+            config,
+            tables: None,
+            callout: None,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+}
+
+impl<W: CodeUnitWidth> Clone for RegexBuilder<W> {
+    fn clone(&self) -> Self {
+        RegexBuilder {
+            config: self.config.clone(),
+            tables: self.tables.clone(),
+            callout: self.callout.clone(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
 impl<W: CodeUnitWidth> RegexBuilder<W> {
     /// Create a new builder with a default configuration.
     pub fn new() -> Self {
         RegexBuilder {
             config: Config::default(),
+            tables: None,
+            callout: None,
             _phantom: std::marker::PhantomData,
         }
     }
@@ -136,6 +984,144 @@ impl<W: CodeUnitWidth> RegexBuilder<W> {
     /// If there was a problem compiling the pattern, then an error is
     /// returned.
     pub fn build<Pat: Into<W::Pattern>>(&self, pattern: Pat) -> Result<Regex<W>, Error> {
+        let pattern = pattern.into();
+        let code = Code::new(&pattern, self.compile_options(), self.compile_context())?;
+        self.finish(pattern, code, self.tables.is_some())
+    }
+
+    /// Load a regex from bytes produced by `Regex::serialize`, instead of
+    /// compiling `pattern`.
+    ///
+    /// The pattern is only used for `Regex::as_str` and friends; it isn't
+    /// compiled or compared against the serialized regex. The compile
+    /// options of this builder, including extra options such as
+    /// `match_word`, must be the same as the ones that were used to build
+    /// the serialized regex, since the match-time behavior of a `Regex`
+    /// (such as how empty matches advance in UTF mode) is derived from
+    /// them. If they differ, then an error is returned. Match-time
+    /// options such as `jit` are applied to the loaded regex as usual. The
+    /// loaded regex uses the character tables it was compiled with, rather
+    /// than those set with `character_tables`.
+    ///
+    /// An error is also returned if the bytes were produced by a different
+    /// version of PCRE2, for a different code unit width or on a host with a
+    /// different endianness or pointer width. The same goes for builders with
+    /// a `compile_recursion_guard`, `max_pattern_length` or
+    /// `parens_nest_limit`, since those only take effect while compiling.
+    /// The error's kind is `ErrorKind::Serialize` in every case. Use
+    /// `deserialize_or_build` to fall back to compiling the pattern instead.
+    ///
+    /// # Safety
+    ///
+    /// PCRE2 only validates the header of the serialized data, not the
+    /// compiled regex that follows it. The caller must guarantee that
+    /// `bytes` is exactly what `Regex::serialize` returned, for example by
+    /// only loading it from storage that can't be tampered with or by
+    /// checking a cryptographic digest first. Loading arbitrary bytes can
+    /// result in undefined behavior.
+    pub unsafe fn deserialize<Pat: Into<W::Pattern>>(
+        &self,
+        pattern: Pat,
+        bytes: &[u8],
+    ) -> Result<Regex<W>, Error> {
+        if self.has_compile_guards() {
+            return Err(Error::serialize(PCRE2_ERROR_BADOPTION));
+        }
+        let code = Code::deserialize(bytes)?;
+        let expected_newline = self
+            .config
+            .newline
+            .map_or_else(default_newline::<W>, Newline::to_raw);
+        let expected_bsr = self
+            .config
+            .bsr
+            .map_or_else(default_bsr::<W>, BsrConvention::to_raw);
+        let arg_options = code.arg_options()?;
+        if arg_options != self.compile_options()
+            || code.extra_options()? != self.extra_options()
+            || code.newline()? != expected_newline
+            || code.bsr()? != expected_bsr
+        {
+            return Err(Error::serialize(PCRE2_ERROR_BADOPTION));
+        }
+        let custom_tables = !serialized_default_tables::<W>(bytes);
+        self.finish(pattern.into(), code, custom_tables)
+    }
+
+    /// Load a regex from bytes produced by `Regex::serialize`, or compile
+    /// `pattern` if they can't be loaded.
+    ///
+    /// This is like `deserialize`, except any error from loading the bytes,
+    /// such as a PCRE2 version mismatch after an upgrade, results in
+    /// compiling `pattern` with this builder instead. An error is only
+    /// returned if compiling the pattern fails.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as for `deserialize` apply.
+    pub unsafe fn deserialize_or_build<Pat: Into<W::Pattern>>(
+        &self,
+        pattern: Pat,
+        bytes: &[u8],
+    ) -> Result<Regex<W>, Error> {
+        let pattern = pattern.into();
+        match self.deserialize(pattern.clone(), bytes) {
+            Ok(re) => Ok(re),
+            Err(err) => {
+                debug!(
+                    "loading serialized regex failed, compiling instead: {}",
+                    err
+                );
+                self.build(pattern)
+            }
+        }
+    }
+
+    /// Returns true if this builder limits compilation in a way that can't be
+    /// checked for a regex that is loaded instead of compiled.
+    pub(crate) fn has_compile_guards(&self) -> bool {
+        self.config.recursion_guard.is_some()
+            || self.config.max_pattern_length.is_some()
+            || self.config.parens_nest_limit.is_some()
+    }
+
+    /// Returns a description of everything that affects compiling `pattern`
+    /// with this builder, for use as a key in `RegexCache`.
+    ///
+    /// Only the settings that end up in the compiled code are included, so
+    /// match-time settings such as `match_limit` or `jit` don't change the
+    /// key. Custom character tables are identified by a hash of their
+    /// contents, not by their locale name. Builders with compile guards
+    /// aren't cached, so the guards never matter here.
+    pub(crate) fn cache_key(&self, pattern: &W::Pattern) -> String {
+        let (major, minor) = version();
+        let tables = self.tables.as_ref().map(|tables| fnv1a(&tables.tables[..]));
+        format!(
+            "pcre2 {}.{}, pcre2 crate {}, {}-bit code units, {} endian, {}-bit pointers\n\
+             options {:08x}, extra options {:08x}, newline {:?}, bsr {:?}, tables {:016x?}\n\
+             {:?}",
+            major,
+            minor,
+            env!("CARGO_PKG_VERSION"),
+            8 * std::mem::size_of::<W::SubjectChar>(),
+            if cfg!(target_endian = "big") {
+                "big"
+            } else {
+                "little"
+            },
+            usize::BITS,
+            self.compile_options(),
+            self.extra_options(),
+            self.config.newline,
+            self.config.bsr,
+            tables,
+            pattern,
+        )
+    }
+
+    /// Returns the `PCRE2_*` compile options corresponding to this builder's
+    /// configuration.
+    fn compile_options(&self) -> u32 {
         let mut options = 0;
         if self.config.caseless {
             options |= PCRE2_CASELESS;
@@ -159,39 +1145,155 @@ impl<W: CodeUnitWidth> RegexBuilder<W> {
         if self.config.never_utf {
             options |= PCRE2_NEVER_UTF;
         }
+        if self.config.end_anchored {
+            options |= PCRE2_ENDANCHORED;
+        }
+        if self.config.use_offset_limit || self.config.match_config.offset_limit.is_some() {
+            options |= PCRE2_USE_OFFSET_LIMIT;
+        }
+        if self.config.interruptible || self.config.match_config.progress.is_some() {
+            options |= PCRE2_AUTO_CALLOUT;
+        }
+        if self.config.alt_bsux {
+            options |= PCRE2_ALT_BSUX;
+        }
+        if self.config.literal {
+            options |= PCRE2_LITERAL;
+        }
+        if self.config.dupnames {
+            options |= PCRE2_DUPNAMES;
+        }
+        if self.config.no_auto_capture {
+            options |= PCRE2_NO_AUTO_CAPTURE;
+        }
+        if self.config.ungreedy {
+            options |= PCRE2_UNGREEDY;
+        }
+        if self.config.dollar_endonly {
+            options |= PCRE2_DOLLAR_ENDONLY;
+        }
+        if self.config.allow_empty_class {
+            options |= PCRE2_ALLOW_EMPTY_CLASS;
+        }
+        if self.config.anchored {
+            options |= PCRE2_ANCHORED;
+        }
+        if self.config.no_auto_possess {
+            options |= PCRE2_NO_AUTO_POSSESS;
+        }
+        if self.config.no_start_optimize {
+            options |= PCRE2_NO_START_OPTIMIZE;
+        }
+        if self.config.firstline {
+            options |= PCRE2_FIRSTLINE;
+        }
+        if self.config.match_unset_backref {
+            options |= PCRE2_MATCH_UNSET_BACKREF;
+        }
+        if self.config.never_backslash_c {
+            options |= PCRE2_NEVER_BACKSLASH_C;
+        }
+        if self.config.match_invalid_utf {
+            options |= PCRE2_MATCH_INVALID_UTF;
+        }
+        options
+    }
+
+    /// Returns the `PCRE2_EXTRA_*` compile options corresponding to this
+    /// builder's configuration.
+    fn extra_options(&self) -> u32 {
+        let mut options = 0;
+        if self.config.match_word {
+            options |= PCRE2_EXTRA_MATCH_WORD;
+        }
+        if self.config.match_line {
+            options |= PCRE2_EXTRA_MATCH_LINE;
+        }
+        if self.config.bad_escape_is_literal {
+            options |= PCRE2_EXTRA_BAD_ESCAPE_IS_LITERAL;
+        }
+        if self.config.allow_surrogate_escapes {
+            options |= PCRE2_EXTRA_ALLOW_SURROGATE_ESCAPES;
+        }
+        if self.config.escaped_cr_is_lf {
+            options |= PCRE2_EXTRA_ESCAPED_CR_IS_LF;
+        }
+        if self.config.alt_bsux {
+            options |= PCRE2_EXTRA_ALT_BSUX;
+        }
+        options
+    }
 
+    /// Returns a compile context corresponding to this builder's
+    /// configuration.
+    fn compile_context(&self) -> CompileContext<W> {
         let mut ctx = CompileContext::new();
-        if self.config.crlf {
-            ctx.set_newline(PCRE2_NEWLINE_ANYCRLF)
-                .expect("PCRE2_NEWLINE_ANYCRLF is a legal value");
+        let extra_options = self.extra_options();
+        if extra_options != 0 {
+            ctx.set_compile_extra_options(extra_options);
+        }
+        if let Some(newline) = self.config.newline {
+            ctx.set_newline(newline.to_raw())
+                .expect("a legal newline convention");
+        }
+        if let Some(bsr) = self.config.bsr {
+            ctx.set_bsr(bsr.to_raw()).expect("a legal \\R convention");
+        }
+        if let Some(len) = self.config.max_pattern_length {
+            ctx.set_max_pattern_length(len);
+        }
+        if let Some(limit) = self.config.parens_nest_limit {
+            ctx.set_parens_nest_limit(limit);
         }
+        if let Some(CompileRecursionGuard(ref guard)) = self.config.recursion_guard {
+            ctx.set_compile_recursion_guard(Arc::clone(guard));
+        }
+        if let Some(ref tables) = self.tables {
+            ctx.set_character_tables(Arc::clone(&tables.tables));
+        }
+        ctx
+    }
 
-        let pattern = pattern.into();
-        let mut code = Code::new(&pattern, options, ctx)?;
+    /// Applies match-time configuration to a compiled regex and wraps it up.
+    /// `custom_tables` is true if the regex was compiled with character
+    /// tables other than PCRE2's built-in ones.
+    fn finish(
+        &self,
+        pattern: W::Pattern,
+        mut code: Code<W>,
+        custom_tables: bool,
+    ) -> Result<Regex<W>, Error> {
+        if let Some(max) = self.config.max_capture_groups {
+            // The capture count includes the group for the entire match.
+            if code.capture_count()? - 1 > max {
+                return Err(Error::capture_limit());
+            }
+        }
+        let jit_options = self.config.jit_options();
         match self.config.jit {
             JITChoice::Never => {} // fallthrough
             JITChoice::Always => {
-                code.jit_compile()?;
+                code.jit_compile(jit_options)?;
             }
             JITChoice::Attempt => {
-                if let Err(err) = code.jit_compile() {
+                if let Err(err) = code.jit_compile(jit_options) {
                     debug!("JIT compilation failed: {}", err);
                 }
             }
         }
-        let capture_names = code.capture_names()?;
-        let mut idx = HashMap::new();
-        for (i, group) in capture_names.iter().enumerate() {
-            if let Some(ref name) = *group {
-                idx.insert(name.to_string(), i);
-            }
-        }
+        let auto_jit = match self.config.auto_jit {
+            Some(threshold) if !code.compiled_jit() => Some(Arc::new(AutoJit::new(threshold))),
+            _ => None,
+        };
+        let code = Arc::new(code);
         Ok(Regex {
             config: Arc::new(self.config.clone()),
-            pattern: pattern,
-            code: Arc::new(code),
-            capture_names: Arc::new(capture_names),
-            capture_names_idx: Arc::new(idx),
+            pattern,
+            auto_jit,
+            capture_names: Arc::new(CaptureNames::new(Arc::clone(&code))),
+            code,
+            callout: self.callout.clone(),
+            custom_tables,
             match_data: ThreadLocal::new(),
         })
     }
@@ -248,15 +1350,550 @@ impl<W: CodeUnitWidth> RegexBuilder<W> {
     ///
     /// This is disabled by default, in which case, only `\n` is recognized as
     /// a line terminator.
+    ///
+    /// Enabling this is equivalent to `newline(Newline::AnyCrLf)`. Disabling
+    /// it restores the default newline convention if `Newline::AnyCrLf` was
+    /// set, and leaves any other convention set with `newline` alone.
     pub fn crlf(&mut self, yes: bool) -> &mut Self {
-        self.config.crlf = yes;
+        if yes {
+            self.config.newline = Some(Newline::AnyCrLf);
+        } else if self.config.newline == Some(Newline::AnyCrLf) {
+            self.config.newline = None;
+        }
         self
     }
 
-    /// Enable Unicode matching mode.
+    /// Set the newline convention, which determines what anchors such as `^`
+    /// and `$` and the `.` metacharacter treat as a line terminator.
     ///
-    /// When enabled, the following patterns become Unicode aware: `\b`, `\B`,
-    /// `\d`, `\D`, `\s`, `\S`, `\w`, `\W`.
+    /// For example, `Newline::Cr` is useful for old Mac files, while
+    /// `Newline::Nul` is useful for the NUL delimited output of
+    /// `find -print0`. A pattern can still override this with a leading
+    /// `(*CR)`, `(*LF)`, `(*CRLF)`, `(*ANY)`, `(*ANYCRLF)` or `(*NUL)`.
+    ///
+    /// By default, PCRE2's build time default is used, which is almost
+    /// always `Newline::Lf`.
+    ///
+    /// This corresponds to `pcre2_set_newline`.
+    pub fn newline(&mut self, newline: Newline) -> &mut Self {
+        self.config.newline = Some(newline);
+        self
+    }
+
+    /// Set what `\R` matches: any Unicode line ending, or only `\r`, `\n`
+    /// and `\r\n`.
+    ///
+    /// A pattern can still override this with a leading `(*BSR_UNICODE)` or
+    /// `(*BSR_ANYCRLF)`. By default, PCRE2's build time default is used,
+    /// which is almost always `BsrConvention::Unicode`.
+    ///
+    /// This corresponds to `pcre2_set_bsr`.
+    pub fn backslash_r(&mut self, bsr: BsrConvention) -> &mut Self {
+        self.config.bsr = Some(bsr);
+        self
+    }
+
+    /// Set the maximum length of a pattern, in code units.
+    ///
+    /// Compiling a longer pattern fails with an error. Since the length is
+    /// measured by PCRE2 in code units of the pattern's width, this is more
+    /// accurate than checking the length of a pattern before compiling it.
+    /// This is useful for guarding against absurdly long patterns from
+    /// untrusted sources.
+    ///
+    /// By default, there is no limit.
+    ///
+    /// This corresponds to `pcre2_set_max_pattern_length`.
+    pub fn max_pattern_length(&mut self, len: usize) -> &mut Self {
+        self.config.max_pattern_length = Some(len);
+        self
+    }
+
+    /// Set the maximum depth of nested parentheses in a pattern.
+    ///
+    /// Compiling a pattern that nests parentheses, including non-capturing
+    /// groups and assertions, more deeply fails with an error. Lowering this
+    /// limit bounds the amount of stack that compiling untrusted patterns
+    /// may use.
+    ///
+    /// By default, PCRE2's build time default is used, which is usually 250.
+    ///
+    /// This corresponds to `pcre2_set_parens_nest_limit`.
+    pub fn parens_nest_limit(&mut self, limit: u32) -> &mut Self {
+        self.config.parens_nest_limit = Some(limit);
+        self
+    }
+
+    /// Set the maximum number of capturing groups in a pattern.
+    ///
+    /// Building a regex from a pattern with more capturing groups fails with
+    /// an error whose kind is `ErrorKind::CaptureLimit`. Every thread that
+    /// searches with a regex allocates room for the offsets of all of its
+    /// capturing groups, so this bounds the memory used by patterns from
+    /// untrusted sources. Named groups count, but non-capturing groups
+    /// don't.
+    ///
+    /// By default, there is no limit other than PCRE2's own limit of 65,535
+    /// capturing groups.
+    pub fn max_capture_groups(&mut self, limit: usize) -> &mut Self {
+        self.config.max_capture_groups = Some(limit);
+        self
+    }
+
+    /// Install a callback that decides whether compiling a pattern may
+    /// continue, given the current depth of nested parentheses.
+    ///
+    /// PCRE2's compiler uses recursion to handle nested parentheses, so this
+    /// can be used to bound the amount of stack that compiling untrusted
+    /// patterns uses, e.g., based on the stack space that remains on the
+    /// current thread. If the callback returns `false` (or panics), then
+    /// compiling fails with an error whose kind is `ErrorKind::Compile` and
+    /// whose code is `PCRE2_ERROR_PARENTHESES_STACK_CHECK`, instead of
+    /// overflowing the stack.
+    ///
+    /// For a fixed bound, `parens_nest_limit` is simpler.
+    ///
+    /// This corresponds to `pcre2_set_compile_recursion_guard`.
+    pub fn compile_recursion_guard<F>(&mut self, guard: F) -> &mut Self
+    where
+        F: Fn(u32) -> bool + Send + Sync + 'static,
+    {
+        // Panics in callbacks are caught before they reach PCRE2, so
+        // asserting unwind safety only keeps builders and regexes that store
+        // them usable with `catch_unwind`, as they were before callbacks.
+        let guard = AssertUnwindSafe(guard);
+        self.config.recursion_guard = Some(CompileRecursionGuard(Arc::new(move |depth| {
+            (*guard)(depth)
+        })));
+        self
+    }
+
+    /// Install a callback that is called whenever a search reaches a
+    /// callout in the pattern, such as `(?C1)` or `(?C"text")`.
+    ///
+    /// The callback receives the state of the search at the callout, such as
+    /// the callout's number or string, the current position in the subject
+    /// and the capturing groups matched so far, and decides how the search
+    /// proceeds: `CalloutResult::Continue` carries on matching,
+    /// `CalloutResult::Fail` makes the pattern fail at this point so that
+    /// PCRE2 backtracks, and `CalloutResult::Abort` stops the search, which
+    /// then returns an error whose kind is `ErrorKind::Callout` and whose
+    /// source is the error given by the callback. PCRE2 provides no way for
+    /// a callout to end the search with a match; use `(*ACCEPT)` in the
+    /// pattern for that. If the callback panics, then the search is aborted
+    /// and the panic is resumed once PCRE2 has returned.
+    ///
+    /// Callouts are reached during every kind of search, including DFA
+    /// searches and substitutions. The callback may be called more than once
+    /// at the same position due to backtracking, and not at all for callouts
+    /// that PCRE2 optimizes away. Without a callback, callouts do nothing.
+    /// The automatic callouts that `interruptible` and `progress` compile
+    /// into the pattern are never passed to the callback, and neither are
+    /// callouts numbered 255 in such patterns.
+    ///
+    /// Since callbacks can't be serialized, serializing a builder or regex
+    /// that has one with serde fails.
+    ///
+    /// This corresponds to `pcre2_set_callout`.
+    pub fn callout<F>(&mut self, callout: F) -> &mut Self
+    where
+        F: Fn(&CalloutBlock<'_, W>) -> CalloutResult + Send + Sync + 'static,
+    {
+        // See `compile_recursion_guard` for why this is unwind safe.
+        let callout = AssertUnwindSafe(callout);
+        self.callout = Some(Callout(Arc::new(move |block| (*callout)(block))));
+        self
+    }
+
+    /// Make searches interruptible, so that they can be given a deadline
+    /// with `MatchOptions::deadline` or `Regex::find_with_deadline`, or be
+    /// cancelled with a `CancellationToken`.
+    ///
+    /// PCRE2 can only be interrupted from a callout, so this compiles the
+    /// pattern with automatic callouts, which PCRE2 reaches before every
+    /// item in the pattern. This makes every search slower, since it
+    /// prevents some of PCRE2's optimizations and interrupts matching
+    /// frequently, but it bounds the time a search takes in a way that
+    /// doesn't depend on how fast the machine is, unlike `match_limit`.
+    ///
+    /// This is disabled by default. Installing a callback with `progress`
+    /// also makes searches interruptible.
+    pub fn interruptible(&mut self, yes: bool) -> &mut Self {
+        self.config.interruptible = yes;
+        self
+    }
+
+    /// Install a callback that reports the progress of long searches, e.g.
+    /// to keep a user interface responsive while searching a huge subject.
+    ///
+    /// The pattern is compiled with automatic callouts, which PCRE2 reaches
+    /// before every item in the pattern, and the callback is called once
+    /// every `interval` of them. It receives the offset in the subject at
+    /// which the current match attempt started, and the length of the
+    /// subject. The offset grows as the search moves through the subject, so
+    /// their ratio approximates how much of the subject has been searched.
+    /// The callback isn't called for searches that finish within `interval`
+    /// automatic callouts.
+    ///
+    /// Automatic callouts make every search slower, since they prevent
+    /// some of PCRE2's optimizations and interrupt matching frequently, so a
+    /// large interval, such as `100_000`, is recommended. Callouts numbered
+    /// 255 in the pattern are treated as automatic callouts.
+    ///
+    /// By default, no progress is reported. This corresponds to
+    /// `PCRE2_AUTO_CALLOUT`.
+    pub fn progress<F>(&mut self, interval: u32, callback: F) -> &mut Self
+    where
+        F: Fn(usize, usize) + Send + Sync + 'static,
+    {
+        // See `compile_recursion_guard` for why this is unwind safe.
+        let callback = AssertUnwindSafe(callback);
+        self.config.match_config.progress = Some(Progress {
+            interval: interval.max(1),
+            callback: Arc::new(move |start, len| (*callback)(start, len)),
+        });
+        self
+    }
+
+    /// Set the character tables to compile with, such as tables for a
+    /// particular locale. See `CharacterTables` for details.
+    ///
+    /// By default, PCRE2's built-in tables are used.
+    ///
+    /// This corresponds to `pcre2_set_character_tables`.
+    pub fn character_tables(&mut self, tables: CharacterTables<W>) -> &mut Self {
+        self.tables = Some(tables);
+        self
+    }
+
+    /// Require every match to end at the end of the subject.
+    ///
+    /// Unlike appending `\z` to the pattern, this applies to the pattern as a
+    /// whole, so it behaves as expected for patterns containing top-level
+    /// alternations. See `MatchOptions::end_anchored` for enabling this for
+    /// individual searches instead.
+    ///
+    /// This is disabled by default.
+    pub fn end_anchored(&mut self, yes: bool) -> &mut Self {
+        self.config.end_anchored = yes;
+        self
+    }
+
+    /// Require every match to be a whole word.
+    ///
+    /// This is like wrapping the pattern in `\b(?:...)\b`, except it's done
+    /// by PCRE2 itself, so it works with leading `(*...)` verbs. Since PCRE2
+    /// 10.43, `(?<!\w)` and `(?!\w)` are used instead of `\b`, so patterns
+    /// that start or end with a non-word character work as expected too.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_EXTRA_MATCH_WORD`.
+    pub fn match_word(&mut self, yes: bool) -> &mut Self {
+        self.config.match_word = yes;
+        self
+    }
+
+    /// Require every match to be a whole line.
+    ///
+    /// This is like wrapping the pattern in `^(?:...)$`, except it's done by
+    /// PCRE2 itself, so it works with leading `(*...)` verbs. Combined with
+    /// `multi_line`, a match may be any complete line of the subject, which
+    /// is useful for implementing `grep -x` without rewriting patterns.
+    /// Otherwise, a match must span the entire subject, save for a trailing
+    /// line terminator.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_EXTRA_MATCH_LINE`.
+    pub fn match_line(&mut self, yes: bool) -> &mut Self {
+        self.config.match_line = yes;
+        self
+    }
+
+    /// Treat unrecognized escape sequences in the pattern as literals instead
+    /// of reporting a compile error.
+    ///
+    /// For example, with this enabled, `\j` matches `j`. This is useful for
+    /// patterns pasted from other tools, but it can hide mistakes, since an
+    /// escape that was meant to have a special meaning silently matches a
+    /// literal character instead.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_EXTRA_BAD_ESCAPE_IS_LITERAL`.
+    pub fn bad_escape_is_literal(&mut self, yes: bool) -> &mut Self {
+        self.config.bad_escape_is_literal = yes;
+        self
+    }
+
+    /// Permit escapes for surrogate code points, such as `\x{d800}`, in UTF
+    /// mode.
+    ///
+    /// Surrogates aren't valid Unicode scalar values, so by default such
+    /// escapes are a compile error in UTF mode. With this enabled, patterns
+    /// containing them compile, but since a subject searched in UTF mode must
+    /// be valid UTF, those escapes never match. To search data containing
+    /// unpaired surrogates, such as UTF-32 from the wild, search it without UTF
+    /// mode, where surrogate escapes are always permitted and this has no
+    /// effect.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_EXTRA_ALLOW_SURROGATE_ESCAPES`.
+    pub fn allow_surrogate_escapes(&mut self, yes: bool) -> &mut Self {
+        self.config.allow_surrogate_escapes = yes;
+        self
+    }
+
+    /// Treat the escape `\r` in the pattern as `\n`.
+    ///
+    /// This is for compatibility with systems that historically treated the two
+    /// interchangeably. It only affects the `\r` escape, not literal carriage
+    /// return characters in the pattern or subject.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_EXTRA_ESCAPED_CR_IS_LF`.
+    pub fn escaped_cr_is_lf(&mut self, yes: bool) -> &mut Self {
+        self.config.escaped_cr_is_lf = yes;
+        self
+    }
+
+    /// Interpret `\u`, `\U` and `\x` in the pattern as ECMAScript does.
+    ///
+    /// With this enabled, `\uhhhh` and `\u{h...}` match the character with the
+    /// given hexadecimal code point, `\xhh` requires exactly two hexadecimal
+    /// digits, and `\U` matches `U`. A `\u` or `\x` that isn't followed by
+    /// valid digits matches a literal `u` or `x`. This is useful for running
+    /// regexes written for JavaScript through PCRE2.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_ALT_BSUX` combined with
+    /// `PCRE2_EXTRA_ALT_BSUX`.
+    pub fn alt_bsux(&mut self, yes: bool) -> &mut Self {
+        self.config.alt_bsux = yes;
+        self
+    }
+
+    /// Treat the pattern as a literal string, in which no characters have a
+    /// special meaning.
+    ///
+    /// This is faster and less error prone than escaping every metacharacter in
+    /// a search string, while still supporting options such as `caseless`,
+    /// `utf`, `match_word`, `match_line` and JIT compilation. Combining it with
+    /// options that only make sense for patterns with metacharacters, such as
+    /// `dotall`, `extended`, `multi_line` or `ucp`, results in a compile error.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_LITERAL`.
+    pub fn literal(&mut self, yes: bool) -> &mut Self {
+        self.config.literal = yes;
+        self
+    }
+
+    /// Permit more than one capture group to have the same name.
+    ///
+    /// This is common in patterns ported from Perl, where alternative branches
+    /// capture the same kind of thing, e.g., `(?<n>\d+)px|(?<n>\d+)em`. Looking
+    /// up such a name with `Captures::name` returns the group that participated
+    /// in the match.
+    ///
+    /// This is disabled by default, in which case duplicate names are a compile
+    /// error. A pattern can also enable this with `(?J)`.
+    ///
+    /// This corresponds to `PCRE2_DUPNAMES`.
+    pub fn dupnames(&mut self, yes: bool) -> &mut Self {
+        self.config.dupnames = yes;
+        self
+    }
+
+    /// Make unnamed groups such as `(a)` non-capturing, so that only named
+    /// groups capture.
+    ///
+    /// This keeps capture group numbers stable when large patterns are
+    /// generated from pieces, and reduces the amount of memory needed to record
+    /// captures. A pattern can also enable this with `(?n)`.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_NO_AUTO_CAPTURE`.
+    pub fn no_auto_capture(&mut self, yes: bool) -> &mut Self {
+        self.config.no_auto_capture = yes;
+        self
+    }
+
+    /// Invert the greediness of quantifiers, so that they're lazy by default
+    /// and greedy when followed by `?`.
+    ///
+    /// For example, with this enabled, `a+` matches as few `a`s as possible,
+    /// while `a+?` matches as many as possible. This corresponds to the `U`
+    /// flag, which a pattern can also set with `(?U)`.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_UNGREEDY`.
+    pub fn ungreedy(&mut self, yes: bool) -> &mut Self {
+        self.config.ungreedy = yes;
+        self
+    }
+
+    /// Make `$` match only at the very end of the subject.
+    ///
+    /// By default, `$` also matches before a line terminator at the end of the
+    /// subject, so a pattern such as `^\d+$` accepts `"42\n"`. With this
+    /// enabled, it doesn't, which is usually what validation wants. This has no
+    /// effect when `multi_line` is enabled.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_DOLLAR_ENDONLY`.
+    pub fn dollar_endonly(&mut self, yes: bool) -> &mut Self {
+        self.config.dollar_endonly = yes;
+        self
+    }
+
+    /// Treat `[]` as an empty character class that never matches, and `[^]` as
+    /// a class that matches any character, as ECMAScript does.
+    ///
+    /// By default, a `]` immediately after `[` or `[^` is a literal member of
+    /// the class, so `[]a]` matches `]` or `a`. With this enabled, `[]a]`
+    /// instead matches nothing followed by `a]`.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_ALLOW_EMPTY_CLASS`.
+    pub fn allow_empty_class(&mut self, yes: bool) -> &mut Self {
+        self.config.allow_empty_class = yes;
+        self
+    }
+
+    /// Require every match to start at the position where a search starts.
+    ///
+    /// This is like `MatchOptions::anchored`, but applies to every search with
+    /// the compiled regex, which lets PCRE2 skip scanning for a possible start
+    /// of a match entirely. Iterators such as `find_iter` then stop at the
+    /// first position that doesn't start a match, which suits tokenizers that
+    /// consume their input contiguously.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_ANCHORED` at compile time.
+    pub fn anchored(&mut self, yes: bool) -> &mut Self {
+        self.config.anchored = yes;
+        self
+    }
+
+    /// Disable the optimization that makes quantifiers possessive when what
+    /// follows them can't match what they repeat.
+    ///
+    /// For example, PCRE2 normally compiles `a+b` as `a++b`. This doesn't
+    /// change which strings match, but it can be observed through callouts and
+    /// it changes how much backtracking happens, so disabling it can help when
+    /// investigating the behavior of a pattern.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_NO_AUTO_POSSESS`.
+    pub fn no_auto_possess(&mut self, yes: bool) -> &mut Self {
+        self.config.no_auto_possess = yes;
+        self
+    }
+
+    /// Disable the optimizations that let PCRE2 skip starting positions at
+    /// which a match is impossible, such as looking for a required first
+    /// character.
+    ///
+    /// These don't change which strings match unless the pattern uses
+    /// backtracking control verbs such as `(*COMMIT)` or callouts, whose
+    /// behavior depends on which starting positions are tried. For example,
+    /// `(*COMMIT)abc` matches `xyzabc` by default, since matching only starts
+    /// at the `a`, but doesn't with this enabled.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_NO_START_OPTIMIZE`.
+    pub fn no_start_optimize(&mut self, yes: bool) -> &mut Self {
+        self.config.no_start_optimize = yes;
+        self
+    }
+
+    /// Require an unanchored match to start before or at the first newline
+    /// following the position where the search starts.
+    ///
+    /// The match itself may continue past the newline, so lookaheads spanning
+    /// several lines work, unlike when searching only the first line of the
+    /// subject. Which characters count as a newline is determined by
+    /// `newline`.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_FIRSTLINE`.
+    pub fn firstline(&mut self, yes: bool) -> &mut Self {
+        self.config.firstline = yes;
+        self
+    }
+
+    /// Make a backreference to a group that hasn't captured anything match the
+    /// empty string, as in JavaScript, instead of failing.
+    ///
+    /// For example, with this enabled, `(a)?\1b` matches `b`.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_MATCH_UNSET_BACKREF`.
+    pub fn match_unset_backref(&mut self, yes: bool) -> &mut Self {
+        self.config.match_unset_backref = yes;
+        self
+    }
+
+    /// Permit restricting where matches may start with
+    /// `MatchOptions::offset_limit`.
+    ///
+    /// PCRE2 requires this to be decided when a pattern is compiled, and
+    /// searching a range with a regex that wasn't built with this option
+    /// returns an error.
+    ///
+    /// This is disabled by default.
+    pub fn use_offset_limit(&mut self, yes: bool) -> &mut Self {
+        self.config.use_offset_limit = yes;
+        self
+    }
+
+    /// Set the largest offset at which a match may start for every search,
+    /// or `None` for no limit.
+    ///
+    /// This is the default for searches that don't set their own limit with
+    /// `MatchOptions::offset_limit`. Setting a limit implies
+    /// `use_offset_limit`, since PCRE2 rejects searches with an offset limit
+    /// otherwise.
+    ///
+    /// Only the start of a match is restricted: a match that starts at or
+    /// before the limit may extend past it.
+    ///
+    /// This corresponds to `pcre2_set_offset_limit`. By default, this is set
+    /// to `None`.
+    pub fn offset_limit(&mut self, limit: Option<usize>) -> &mut Self {
+        self.config.match_config.offset_limit = limit;
+        self
+    }
+
+    /// Set how iterators over successive matches, such as `find_iter`,
+    /// `captures_iter` and `replace_all` with a closure, proceed after
+    /// finding a match of the empty string.
+    ///
+    /// This is `EmptyMatchPolicy::RegexCompatible` by default.
+    pub fn empty_match_policy(&mut self, policy: EmptyMatchPolicy) -> &mut Self {
+        self.config.empty_match_policy = policy;
+        self
+    }
+
+    /// Enable Unicode matching mode.
+    ///
+    /// When enabled, the following patterns become Unicode aware: `\b`, `\B`,
+    /// `\d`, `\D`, `\s`, `\S`, `\w`, `\W`.
     ///
     /// When set, this implies UTF matching mode. It is not possible to enable
     /// Unicode matching mode without enabling UTF matching mode.
@@ -295,6 +1932,43 @@ impl<W: CodeUnitWidth> RegexBuilder<W> {
         self
     }
 
+    /// Enable UTF matching mode in a way that permits searching subjects that
+    /// aren't valid UTF.
+    ///
+    /// Invalid code unit sequences in the subject never match any part of the
+    /// pattern, not even `.`, but valid text on either side of them is
+    /// matched as usual. Searches neither pay for validating the subject nor
+    /// fail because it's invalid, which makes this the safe alternative to
+    /// `disable_utf_check` for scanning arbitrary data that is mostly text.
+    /// Enabling this implies `utf`. When the JIT is enabled, the regex is
+    /// JIT compiled with support for invalid UTF as well, so that such
+    /// searches don't fall back to PCRE2's interpreter.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_MATCH_INVALID_UTF`, along with
+    /// `PCRE2_JIT_INVALID_UTF` when JIT compiling, which require PCRE2 10.34
+    /// or newer.
+    pub fn match_invalid_utf(&mut self, yes: bool) -> &mut Self {
+        self.config.match_invalid_utf = yes;
+        self
+    }
+
+    /// Forbid `\C` in the pattern.
+    ///
+    /// `\C` matches a single code unit even in UTF mode, so it can split a
+    /// character and leave a match that isn't valid UTF, and it can make
+    /// matching slow. When compiling untrusted patterns, this makes PCRE2
+    /// reject patterns containing it with a compile error.
+    ///
+    /// This is disabled by default.
+    ///
+    /// This corresponds to `PCRE2_NEVER_BACKSLASH_C`.
+    pub fn never_backslash_c(&mut self, yes: bool) -> &mut Self {
+        self.config.never_backslash_c = yes;
+        self
+    }
+
     /// When UTF matching mode is enabled, this will disable the UTF checking
     /// that PCRE2 will normally perform automatically. If UTF matching mode
     /// is not enabled, then this has no effect.
@@ -309,7 +1983,7 @@ impl<W: CodeUnitWidth> RegexBuilder<W> {
     /// It is undefined behavior to disable the UTF check in UTF matching mode
     /// and search a subject string that is not valid UTF-8. When the UTF check
     /// is disabled, callers must guarantee that the subject string is valid
-    /// UTF-8.
+    /// UTF-8. The same applies to replacement strings.
     pub unsafe fn disable_utf_check(&mut self) -> &mut Self {
         self.config.utf_check = false;
         self
@@ -352,6 +2026,81 @@ impl<W: CodeUnitWidth> RegexBuilder<W> {
         self
     }
 
+    /// Additionally JIT compile the regex for partial matching in the given
+    /// mode. If the JIT is not enabled, then this has no effect.
+    ///
+    /// By default, the JIT only compiles code for complete matches, and
+    /// searches for partial matches, such as `find_partial_at` and the
+    /// streaming searches of `stream_matcher`, silently fall back to PCRE2's
+    /// interpreter. Each mode requires separate JIT code, so enabling both
+    /// modes roughly triples the memory used by the JIT. Streaming searches
+    /// use `PartialMode::Hard`.
+    ///
+    /// This is disabled for both modes by default.
+    ///
+    /// This corresponds to `PCRE2_JIT_PARTIAL_SOFT` and
+    /// `PCRE2_JIT_PARTIAL_HARD`.
+    pub fn jit_partial(&mut self, mode: PartialMode, yes: bool) -> &mut Self {
+        match mode {
+            PartialMode::Soft => self.config.jit_partial_soft = yes,
+            PartialMode::Hard => self.config.jit_partial_hard = yes,
+        }
+        self
+    }
+
+    /// When the regex is JIT compiled, search with PCRE2's JIT fast path
+    /// instead of its primary match routine.
+    ///
+    /// The fast path skips the sanity checks that PCRE2 normally performs on
+    /// the arguments of every search, which can be a measurable win when
+    /// searching many short subjects. Searches that use options the fast
+    /// path doesn't support, such as an anchored search, and searches of a
+    /// regex that wasn't JIT compiled use the primary match routine as
+    /// usual. Partial matches only use the fast path if the regex was JIT
+    /// compiled for their mode with `jit_partial`.
+    ///
+    /// This is disabled by default and has no effect unless `jit` or
+    /// `jit_if_available` is enabled.
+    ///
+    /// This corresponds to using `pcre2_jit_match`.
+    ///
+    /// # Safety
+    ///
+    /// The fast path never checks that the subject string is valid UTF. When
+    /// UTF matching mode is enabled, the same contract as for
+    /// `disable_utf_check` applies: callers must guarantee that every subject
+    /// string searched with the fast path is valid UTF-8.
+    pub unsafe fn jit_fast_path(&mut self, yes: bool) -> &mut Self {
+        self.config.jit_fast_path = yes;
+        self
+    }
+
+    /// JIT compile the regex once it has been searched the given number of
+    /// times, or never when `None` is given.
+    ///
+    /// JIT compilation can take much longer than compiling the pattern
+    /// itself, which is wasted on regexes that are rarely searched. With this
+    /// enabled, a regex is searched with PCRE2's interpreter at first and is
+    /// JIT compiled by the search that reaches the threshold. Other threads
+    /// keep searching with the interpreter in the meantime and switch to the
+    /// JIT compiled code once it's ready. Clones of the regex share the count
+    /// and the JIT compiled code.
+    ///
+    /// Every call that executes PCRE2's matching routine counts as a search,
+    /// so iterating over the matches of a subject counts once per match.
+    /// Searches with the DFA matching algorithm don't count.
+    ///
+    /// If the JIT isn't available or if JIT compilation returns an error,
+    /// then a debug message with the error will be emitted and the regex
+    /// will keep using the interpreter. This has no effect if the regex is
+    /// JIT compiled when it's built.
+    ///
+    /// This is disabled by default.
+    pub fn auto_jit(&mut self, searches: Option<usize>) -> &mut Self {
+        self.config.auto_jit = searches;
+        self
+    }
+
     /// Set the maximum size of PCRE2's JIT stack, in bytes. If the JIT is
     /// not enabled, then this has no effect.
     ///
@@ -363,46 +2112,210 @@ impl<W: CodeUnitWidth> RegexBuilder<W> {
     /// maximum size as its limit.
     ///
     /// Increasing the stack size can be useful for larger regular expressions.
+    /// Note that a JIT stack is created for each thread the regex is used
+    /// in. To share one stack between many regexes instead, see
+    /// `JitStack::install`, which takes precedence over this setting.
     ///
     /// By default, this is set to `None`.
     pub fn max_jit_stack_size(&mut self, bytes: Option<usize>) -> &mut Self {
         self.config.match_config.max_jit_stack_size = bytes;
         self
     }
-}
 
-/// A compiled PCRE2 regular expression.
-///
-/// This regex is safe to use from multiple threads simultaneously. For top
-/// performance, it is better to clone a new regex for each thread.
-pub struct Regex<W: CodeUnitWidth> {
-    /// The configuration used to build the regex.
-    config: Arc<Config>,
-    /// The original pattern string.
-    pattern: W::Pattern,
-    /// The underlying compiled PCRE2 object.
-    code: Arc<Code<W>>,
-    /// The capture group names for this regex.
-    capture_names: Arc<Vec<Option<String>>>,
-    /// A map from capture group name to capture group index.
-    capture_names_idx: Arc<HashMap<String, usize>>,
-    /// Mutable scratch data used by PCRE2 during matching.
+    /// Set the match limit, which bounds the amount of backtracking a single
+    /// search may perform.
     ///
-    /// We use the same strategy as Rust's regex crate here, such that each
-    /// thread gets its own match data to support using a Regex object from
-    /// multiple threads simultaneously. If some match data doesn't exist for
+    /// The limit is the maximum number of times PCRE2's internal match
+    /// function may be called during a search, which roughly counts how many
+    /// times it backtracks. When a search exceeds it, the search fails with
+    /// an error whose code is `PCRE2_ERROR_MATCHLIMIT`, instead of running
+    /// for an unbounded amount of time. The limit also applies to JIT
+    /// compiled regexes, although the count is computed differently there.
+    ///
+    /// A pattern can lower this limit, but not raise it, by starting with
+    /// `(*LIMIT_MATCH=d)`.
+    ///
+    /// This is the most important setting for running untrusted patterns,
+    /// since catastrophic backtracking is otherwise only bounded by the
+    /// length of the subject.
+    ///
+    /// By default, this is set to `None`, in which case PCRE2's built in
+    /// default of 10,000,000 is used.
+    pub fn match_limit(&mut self, limit: u32) -> &mut Self {
+        self.config.match_config.match_limit = Some(limit);
+        self
+    }
+
+    /// Set the heap limit, in kibibytes, which bounds how much heap memory
+    /// a single search may use for its backtracking frames.
+    ///
+    /// PCRE2 keeps backtracking frames in a vector that it grows on the heap
+    /// as needed, so long subjects can make a single search allocate a lot
+    /// of memory well before `match_limit` or `depth_limit` are reached.
+    /// When a search would exceed this limit, it fails with an error whose
+    /// code is `PCRE2_ERROR_HEAPLIMIT` instead.
+    ///
+    /// The limit doesn't apply to JIT compiled regexes, whose memory is
+    /// bounded by the size of the JIT stack instead (see
+    /// `max_jit_stack_size`).
+    ///
+    /// A pattern can lower this limit, but not raise it, by starting with
+    /// `(*LIMIT_HEAP=d)`.
+    ///
+    /// By default, this is set to `None`, in which case PCRE2's built in
+    /// default of 20,000,000 kibibytes is used.
+    pub fn heap_limit(&mut self, limit: u32) -> &mut Self {
+        self.config.match_config.heap_limit = Some(limit);
+        self
+    }
+
+    /// Set the depth limit, which bounds how deeply a single search may
+    /// nest while backtracking.
+    ///
+    /// Every time PCRE2 records a backtracking point, it nests one level
+    /// deeper, so the depth limit bounds the number of backtracking frames
+    /// that may be alive at once. This is independent of `match_limit`,
+    /// which bounds the total amount of work. When a search exceeds it, the
+    /// search fails with an error whose code is `PCRE2_ERROR_DEPTHLIMIT`.
+    ///
+    /// The limit doesn't apply to JIT compiled regexes, whose depth is
+    /// bounded by the size of the JIT stack instead (see
+    /// `max_jit_stack_size`).
+    ///
+    /// A pattern can lower this limit, but not raise it, by starting with
+    /// `(*LIMIT_DEPTH=d)`.
+    ///
+    /// By default, this is set to `None`, in which case PCRE2's built in
+    /// default is used, which is the same as its default match limit.
+    pub fn depth_limit(&mut self, limit: u32) -> &mut Self {
+        self.config.match_config.depth_limit = Some(limit);
+        self
+    }
+
+    /// Apply settings suited to patterns and subjects from untrusted sources,
+    /// which bound the resources that compiling and searching may use.
+    ///
+    /// This is a shorthand for:
+    ///
+    /// * `max_pattern_length(64 * 1024)`
+    /// * `parens_nest_limit(64)`
+    /// * `max_capture_groups(1_000)`
+    /// * `never_backslash_c(true)`, since `\C` can split a UTF encoded
+    ///   character and leave the search in the middle of it.
+    /// * `match_limit(1_000_000)`
+    /// * `depth_limit(10_000)`
+    /// * `heap_limit(64 * 1024)`, i.e. 64 MiB.
+    ///
+    /// Searches that exceed a limit fail with an error instead of running
+    /// for a long time or exhausting memory. Each setting can be adjusted by
+    /// calling the corresponding method after this one. Note that the limits
+    /// don't bound the time a search takes; see `interruptible` for that.
+    ///
+    /// The settings may become stricter in future releases.
+    pub fn hardened(&mut self) -> &mut Self {
+        self.max_pattern_length(64 * 1024)
+            .parens_nest_limit(64)
+            .max_capture_groups(1_000)
+            .never_backslash_c(true)
+            .match_limit(1_000_000)
+            .depth_limit(10_000)
+            .heap_limit(64 * 1024)
+    }
+}
+
+/// A compiled PCRE2 regular expression.
+///
+/// This regex is safe to use from multiple threads simultaneously. For top
+/// performance, it is better to clone a new regex for each thread.
+///
+/// When the `serde` feature is enabled, a regex serializes as its pattern
+/// and the configuration of the builder that built it, in the format
+/// described by `SerializedConfig`, and is compiled again when deserialized.
+pub struct Regex<W: CodeUnitWidth> {
+    /// The configuration used to build the regex.
+    config: Arc<Config>,
+    /// The original pattern string.
+    pattern: W::Pattern,
+    /// The underlying compiled PCRE2 object.
+    code: Arc<Code<W>>,
+    /// The state of automatic JIT compilation, if it's enabled.
+    auto_jit: Option<Arc<AutoJit<W>>>,
+    /// The capture group names for this regex, which are built on demand.
+    capture_names: Arc<CaptureNames<W>>,
+    /// The callback for callouts, if one was installed.
+    callout: Option<Callout<W>>,
+    /// Whether the regex was compiled with character tables other than
+    /// PCRE2's built-in ones.
+    custom_tables: bool,
+    /// Mutable scratch data used by PCRE2 during matching.
+    ///
+    /// We use the same strategy as Rust's regex crate here, such that each
+    /// thread gets its own match data to support using a Regex object from
+    /// multiple threads simultaneously. If some match data doesn't exist for
     /// a thread, then a new one is created on demand.
     match_data: ThreadLocal<RefCell<MatchData<W>>>,
 }
 
+/// The state of automatic JIT compilation, see `RegexBuilder::auto_jit`.
+struct AutoJit<W: CodeUnitWidth> {
+    /// The number of searches after which to JIT compile.
+    threshold: usize,
+    /// The number of searches so far.
+    searches: AtomicUsize,
+    /// Set by the search that reaches the threshold.
+    attempted: AtomicBool,
+    /// A JIT compiled copy of the regex's code, once it's ready.
+    code: OnceLock<Code<W>>,
+}
+
+impl<W: CodeUnitWidth> AutoJit<W> {
+    fn new(threshold: usize) -> AutoJit<W> {
+        AutoJit {
+            threshold,
+            searches: AtomicUsize::new(0),
+            attempted: AtomicBool::new(false),
+            code: OnceLock::new(),
+        }
+    }
+
+    /// Returns the state of automatic JIT compilation with the given JIT
+    /// compiled code already in place.
+    fn compiled(code: Code<W>) -> AutoJit<W> {
+        AutoJit {
+            threshold: 0,
+            searches: AtomicUsize::new(0),
+            attempted: AtomicBool::new(true),
+            code: OnceLock::from(code),
+        }
+    }
+
+    /// Count a search of `code` and JIT compile a copy of it if this search
+    /// reaches the threshold.
+    fn count(&self, code: &Code<W>, jit_options: u32) {
+        let searches = self.searches.fetch_add(1, Ordering::Relaxed) + 1;
+        if searches < self.threshold || self.attempted.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let mut code = code.copy();
+        match code.jit_compile(jit_options) {
+            Ok(()) => {
+                let _ = self.code.set(code);
+            }
+            Err(err) => debug!("automatic JIT compilation failed: {}", err),
+        }
+    }
+}
+
 impl<W: CodeUnitWidth> Clone for Regex<W> {
     fn clone(&self) -> Self {
         Self {
             config: Arc::clone(&self.config),
             pattern: self.pattern.clone(),
             code: Arc::clone(&self.code),
+            auto_jit: self.auto_jit.clone(),
             capture_names: Arc::clone(&self.capture_names),
-            capture_names_idx: Arc::clone(&self.capture_names_idx),
+            callout: self.callout.clone(),
+            custom_tables: self.custom_tables,
             match_data: ThreadLocal::new(),
         }
     }
@@ -414,6 +2327,54 @@ impl<W: CodeUnitWidth> fmt::Debug for Regex<W> {
     }
 }
 
+/// The serialized form of a `Regex`: its pattern and the configuration of the
+/// builder it was built with.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Regex")]
+struct SerdeRegex<P> {
+    pattern: P,
+    #[serde(default)]
+    config: SerializedConfig,
+}
+
+#[cfg(feature = "serde")]
+impl<W: CodeUnitWidth> serde::Serialize for Regex<W>
+where
+    W::Pattern: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let callout = self.callout.is_some();
+        let config = SerializedConfig::with_callbacks(&self.config, self.custom_tables, callout)
+            .map_err(serde::ser::Error::custom)?;
+        SerdeRegex {
+            pattern: &self.pattern,
+            config,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserializing a regex compiles its pattern again, so an invalid pattern
+/// results in a deserialization error.
+#[cfg(feature = "serde")]
+impl<'de, W: CodeUnitWidth> serde::Deserialize<'de> for Regex<W>
+where
+    W::Pattern: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let SerdeRegex { pattern, config } = SerdeRegex::<W::Pattern>::deserialize(deserializer)?;
+        let config = config.into_config().map_err(serde::de::Error::custom)?;
+        let builder = RegexBuilder {
+            config,
+            tables: None,
+            callout: None,
+            _phantom: std::marker::PhantomData,
+        };
+        builder.build(pattern).map_err(serde::de::Error::custom)
+    }
+}
+
 impl<W: CodeUnitWidth> Regex<W> {
     /// Compiles a regular expression using the default configuration.
     ///
@@ -443,12 +2404,26 @@ impl<W: CodeUnitWidth> Regex<W> {
     /// `subject`, returning the start and end byte indices with respect to
     /// `subject`.
     pub fn find_iter<'r, 's>(&'r self, subject: &'s [W::SubjectChar]) -> Matches<'r, 's, W> {
-        Matches {
+        self.find_iter_with_options(subject, &MatchOptions::default())
+    }
+
+    /// Returns an iterator over all matches in `subject`, including ones that
+    /// overlap with each other.
+    ///
+    /// After each match, the search resumes at the character following the
+    /// start of that match, rather than at its end. Thus, at most one match
+    /// is reported for each starting position, which is the leftmost-first
+    /// match at that position. For example, `aa` finds matches at `0`, `1`
+    /// and `2` in `aaaa`.
+    pub fn find_overlapping_iter<'r, 's>(
+        &'r self,
+        subject: &'s [W::SubjectChar],
+    ) -> OverlappingMatches<'r, 's, W> {
+        OverlappingMatches {
             re: self,
             match_data: self.match_data(),
-            subject: subject,
-            last_end: 0,
-            last_match: None,
+            subject,
+            start: Some(0),
         }
     }
 
@@ -467,8 +2442,8 @@ impl<W: CodeUnitWidth> Regex<W> {
             .captures_read(&mut locs, subject)?
             .map(move |_| Captures {
                 subject,
-                locs: locs,
-                idx: Arc::clone(&self.capture_names_idx),
+                locs,
+                names: Arc::clone(&self.capture_names),
             }))
     }
 
@@ -479,497 +2454,3024 @@ impl<W: CodeUnitWidth> Regex<W> {
         &'r self,
         subject: &'s [W::SubjectChar],
     ) -> CaptureMatches<'r, 's, W> {
-        CaptureMatches {
-            re: self,
-            subject: subject,
-            last_end: 0,
-            last_match: None,
-        }
-    }
-
-    /// Test helper to access capture name indexes.
-    #[cfg(test)]
-    pub(crate) fn get_capture_names_idxs(&self) -> &HashMap<String, usize> {
-        &self.capture_names_idx
+        self.captures_iter_with_options(subject, &MatchOptions::default())
     }
-}
 
-/// Advanced or  "lower level" search methods.
-impl<W: CodeUnitWidth> Regex<W> {
-    /// Returns the same as is_match, but starts the search at the given
-    /// offset.
+    /// Returns the start and end byte range of the leftmost-longest match in
+    /// `subject`, as found by PCRE2's DFA matching algorithm. If no match
+    /// exists, then `None` is returned.
     ///
-    /// The significance of the starting point is that it takes the surrounding
-    /// context into consideration. For example, the `\A` anchor can only
-    /// match when `start == 0`.
-    pub fn is_match_at(&self, subject: &[W::SubjectChar], start: usize) -> Result<bool, Error> {
-        assert!(
-            start <= subject.len(),
-            "start ({}) must be <= subject.len() ({})",
-            start,
-            subject.len()
-        );
-
-        let mut options = 0;
-        if !self.config.utf_check {
-            options |= PCRE2_NO_UTF_CHECK;
-        }
-
-        let match_data = self.match_data();
-        let mut match_data = match_data.borrow_mut();
-        // SAFETY: The only unsafe PCRE2 option we potentially use here is
-        // PCRE2_NO_UTF_CHECK, and that only occurs if the caller executes the
-        // `disable_utf_check` method, which propagates the safety contract to
-        // the caller.
-        Ok(unsafe { match_data.find(&self.code, subject, start, options)? })
-    }
-
-    /// Returns the same as find, but starts the search at the given
-    /// offset.
+    /// Unlike the standard matching algorithm used by `find`, the DFA
+    /// algorithm doesn't backtrack. Instead, it scans the subject once while
+    /// keeping track of every way in which the pattern could match. This
+    /// avoids the exponential running time (and the resulting match limit
+    /// errors) that some patterns exhibit on some subjects, which makes it
+    /// suitable for searching untrusted subjects. Since every alternative is
+    /// pursued at once, the longest match at the leftmost position is
+    /// reported, rather than the one that the pattern prefers.
     ///
-    /// The significance of the starting point is that it takes the surrounding
-    /// context into consideration. For example, the `\A` anchor can only
-    /// match when `start == 0`.
-    pub fn find_at<'s>(
+    /// The DFA algorithm doesn't support capture groups (which are all
+    /// treated as non-capturing), backreferences, conditions on groups,
+    /// `\C` in UTF mode and a few other features. Searching with a pattern
+    /// that uses one of them returns an error.
+    pub fn dfa_find<'s>(
         &self,
         subject: &'s [W::SubjectChar],
-        start: usize,
     ) -> Result<Option<Match<'s, W>>, Error> {
-        self.find_at_with_match_data(self.match_data(), subject, start)
+        self.dfa_find_at(subject, 0)
     }
 
-    /// Like find_at, but accepts match data instead of acquiring one itself.
+    /// Returns the start and end byte range of the leftmost-shortest match in
+    /// `subject`, as found by PCRE2's DFA matching algorithm. If no match
+    /// exists, then `None` is returned.
     ///
-    /// This is useful for implementing the iterator, which permits avoiding
-    /// the synchronization overhead of acquiring the match data.
-    #[inline(always)]
-    fn find_at_with_match_data<'s>(
+    /// This corresponds to `PCRE2_DFA_SHORTEST`, which stops the search as
+    /// soon as the first match is found at the leftmost position, instead of
+    /// continuing to look for longer ones. For example, `a+` finds `a` in
+    /// `aaa`. This is cheaper than `dfa_find` when only the existence or the
+    /// earliest end of a match is of interest. See `dfa_find` for details
+    /// on the DFA algorithm.
+    pub fn dfa_find_shortest<'s>(
         &self,
-        match_data: &RefCell<MatchData<W>>,
         subject: &'s [W::SubjectChar],
-        start: usize,
     ) -> Result<Option<Match<'s, W>>, Error> {
-        assert!(
-            start <= subject.len(),
-            "start ({}) must be <= subject.len() ({})",
-            start,
-            subject.len()
-        );
+        self.dfa_find_shortest_at(subject, 0)
+    }
 
-        let mut options = 0;
-        if !self.config.utf_check {
-            options |= PCRE2_NO_UTF_CHECK;
+    /// Returns true if and only if the regex matches the subject string
+    /// given, according to PCRE2's DFA matching algorithm. See `dfa_find`
+    /// for details.
+    pub fn dfa_is_match(&self, subject: &[W::SubjectChar]) -> Result<bool, Error> {
+        Ok(self.dfa_find_shortest(subject)?.is_some())
+    }
+
+    /// Returns an iterator over the fields of `subject` that are separated by
+    /// matches of this regex, where each field is paired with the match that
+    /// ended it.
+    ///
+    /// The last field is always yielded, paired with `None`, even when it is
+    /// empty. So for example, splitting `a,b,` on `,` yields `("a", ",")`,
+    /// `("b", ",")` and finally `("", None)`. Concatenating each field with
+    /// its delimiter reproduces the subject.
+    pub fn split_inclusive<'r, 's>(
+        &'r self,
+        subject: &'s [W::SubjectChar],
+    ) -> SplitInclusive<'r, 's, W> {
+        SplitInclusive {
+            finder: self.find_iter(subject),
+            last: 0,
         }
+    }
 
-        let mut match_data = match_data.borrow_mut();
-        // SAFETY: The only unsafe PCRE2 option we potentially use here is
-        // PCRE2_NO_UTF_CHECK, and that only occurs if the caller executes the
-        // `disable_utf_check` method, which propagates the safety contract to
-        // the caller.
-        if unsafe { !match_data.find(&self.code, subject, start, options)? } {
-            return Ok(None);
+    /// Returns an iterator over the fields of `subject` that are separated by
+    /// matches of this regex, where each field is paired with the capture
+    /// groups of the match that ended it.
+    ///
+    /// This is like `split_inclusive`, but provides access to the capture
+    /// groups of each delimiter, similar to how JavaScript's `String.split`
+    /// includes capture groups in its output. The last field is always
+    /// yielded, paired with `None`, even when it is empty.
+    pub fn split_captures<'r, 's>(
+        &'r self,
+        subject: &'s [W::SubjectChar],
+    ) -> SplitCaptures<'r, 's, W> {
+        SplitCaptures {
+            finder: self.captures_iter(subject),
+            last: 0,
         }
-        let ovector = match_data.ovector();
-        let (s, e) = (ovector[0], ovector[1]);
-        Ok(Some(Match::new(&subject[s..e], s, e)))
     }
 
-    /// This is like `captures`, but uses
-    /// [`CaptureLocations`](struct.CaptureLocations.html)
-    /// instead of
-    /// [`Captures`](struct.Captures.html) in order to amortize allocations.
+    /// Replaces the leftmost-first match in `subject` with the replacement
+    /// provided. If no match is found, then the subject is returned
+    /// unchanged, without copying it.
     ///
-    /// To create a `CaptureLocations` value, use the
-    /// `Regex::capture_locations` method.
+    /// The replacement can be anything that implements the
+    /// [`Replacer`](trait.Replacer.html) trait. Most commonly, this is either
+    /// a replacement template or a closure that computes the replacement
+    /// from the `Captures` of each match.
     ///
-    /// This returns the overall match if this was successful, which is always
-    /// equivalent to the `0`th capture group.
-    pub fn captures_read<'s>(
+    /// Replacement templates are handed to PCRE2's `pcre2_substitute`
+    /// routine, so they may refer to capture groups. `$n` or `${n}` is
+    /// replaced with the text of the `n`th capture group and `$name` or
+    /// `${name}` with the text of the named capture group `name`. A literal
+    /// `$` must be written as `$$`. Referring to a capture group that doesn't
+    /// exist results in an error. PCRE2's extended substitution syntax, which
+    /// additionally supports case forcing with `\U`, `\L` and `\E` as well
+    /// as default and conditional values with `${n:-default}` and
+    /// `${n:+set:unset}`, can be enabled by wrapping the template in
+    /// [`Extended`](struct.Extended.html).
+    ///
+    /// Matches are found in the same way as by `find_iter`, so empty matches
+    /// are handled according to the regex's `EmptyMatchPolicy`, regardless
+    /// of the kind of replacer or the number of replacements.
+    pub fn replace<'s, R: Replacer<W>>(
         &self,
-        locs: &mut CaptureLocations<W>,
         subject: &'s [W::SubjectChar],
-    ) -> Result<Option<Match<'s, W>>, Error> {
-        self.captures_read_at(locs, subject, 0)
+        rep: R,
+    ) -> Result<Cow<'s, [W::SubjectChar]>, Error> {
+        self.replacen(subject, 1, rep)
     }
 
-    /// Returns the same as `captures_read`, but starts the search at the given
-    /// offset and populates the capture locations given.
+    /// Replaces all non-overlapping matches in `subject` with the replacement
+    /// provided. If no match is found, then the subject is returned
+    /// unchanged, without copying it.
     ///
-    /// The significance of the starting point is that it takes the surrounding
-    /// context into consideration. For example, the `\A` anchor can only
-    /// match when `start == 0`.
-    pub fn captures_read_at<'s>(
+    /// See `replace` for details on how the replacement is interpreted.
+    pub fn replace_all<'s, R: Replacer<W>>(
         &self,
-        locs: &mut CaptureLocations<W>,
         subject: &'s [W::SubjectChar],
-        start: usize,
-    ) -> Result<Option<Match<'s, W>>, Error> {
-        assert!(
-            start <= subject.len(),
-            "start ({}) must be <= subject.len() ({})",
-            start,
-            subject.len()
-        );
+        rep: R,
+    ) -> Result<Cow<'s, [W::SubjectChar]>, Error> {
+        self.replacen(subject, 0, rep)
+    }
 
-        let mut options = 0;
-        if !self.config.utf_check {
-            options |= PCRE2_NO_UTF_CHECK;
-        }
-        // SAFETY: The only unsafe PCRE2 option we potentially use here is
-        // PCRE2_NO_UTF_CHECK, and that only occurs if the caller executes the
-        // `disable_utf_check` method, which propagates the safety contract to
-        // the caller.
-        if unsafe { !locs.data.find(&self.code, subject, start, options)? } {
-            return Ok(None);
+    /// Replaces at most `limit` non-overlapping matches in `subject` with the
+    /// replacement provided. If `limit` is `0`, then all non-overlapping
+    /// matches are replaced. If no match is found, then the subject is
+    /// returned unchanged, without copying it.
+    ///
+    /// See `replace` for details on how the replacement is interpreted.
+    pub fn replacen<'s, R: Replacer<W>>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        limit: usize,
+        rep: R,
+    ) -> Result<Cow<'s, [W::SubjectChar]>, Error> {
+        let mut dst = vec![];
+        if self.replacen_into(subject, limit, rep, &mut dst)? == 0 {
+            Ok(Cow::Borrowed(subject))
+        } else {
+            Ok(Cow::Owned(dst))
         }
-        let ovector = locs.data.ovector();
-        let (s, e) = (ovector[0], ovector[1]);
-        Ok(Some(Match::new(&subject[s..e], s, e)))
     }
-}
 
-/// Auxiliary methods.
-impl<W: CodeUnitWidth> Regex<W> {
-    /// Returns the original pattern string for this regex.
-    pub fn as_str(&self) -> &W::Pattern {
-        &self.pattern
+    /// Like `replace`, but appends the result to `dst` instead of returning
+    /// a new string. This permits reusing the same buffer across many calls.
+    ///
+    /// If no match is found, then the subject is appended to `dst`
+    /// unchanged. The number of replacements made is returned. If an error
+    /// occurs, `dst` is truncated back to its original length.
+    pub fn replace_into<R: Replacer<W>>(
+        &self,
+        subject: &[W::SubjectChar],
+        rep: R,
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        self.replacen_into(subject, 1, rep, dst)
     }
 
-    /// Returns a sequence of all capturing groups and their names, if present.
-    ///
-    /// The length of the slice returned is always equal to the result of
-    /// `captures_len`, which is the number of capturing groups (including the
-    /// capturing group for the entire pattern).
-    ///
-    /// Each entry in the slice is the name of the corresponding capturing
-    /// group, if one exists. The first capturing group (at index `0`) is
-    /// always unnamed.
+    /// Like `replace_all`, but appends the result to `dst` instead of
+    /// returning a new string. This permits reusing the same buffer across
+    /// many calls.
     ///
-    /// Capturing groups are indexed by the order of the opening parenthesis.
-    pub fn capture_names(&self) -> &[Option<String>] {
-        &self.capture_names
+    /// If no match is found, then the subject is appended to `dst`
+    /// unchanged. The number of replacements made is returned. If an error
+    /// occurs, `dst` is truncated back to its original length.
+    pub fn replace_all_into<R: Replacer<W>>(
+        &self,
+        subject: &[W::SubjectChar],
+        rep: R,
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        self.replacen_into(subject, 0, rep, dst)
     }
 
-    /// Returns the number of capturing groups in the pattern.
+    /// Like `replacen`, but appends the result to `dst` instead of returning
+    /// a new string. This permits reusing the same buffer across many calls.
     ///
-    /// This is always 1 more than the number of syntactic groups in the
-    /// pattern, since the first group always corresponds to the entire match.
-    pub fn captures_len(&self) -> usize {
-        self.code
-            .capture_count()
-            .expect("a valid capture count from PCRE2")
+    /// If no match is found, then the subject is appended to `dst`
+    /// unchanged. The number of replacements made is returned. If an error
+    /// occurs, `dst` is truncated back to its original length.
+    pub fn replacen_into<R: Replacer<W>>(
+        &self,
+        subject: &[W::SubjectChar],
+        limit: usize,
+        rep: R,
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        let len = dst.len();
+        let result = self.replacen_append(subject, limit, rep, dst);
+        if result.is_err() {
+            dst.truncate(len);
+        }
+        result
     }
 
-    /// Returns an empty set of capture locations that can be reused in
-    /// multiple calls to `captures_read` or `captures_read_at`.
-    pub fn capture_locations(&self) -> CaptureLocations<W> {
-        CaptureLocations {
-            code: Arc::clone(&self.code),
-            data: self.new_match_data(),
+    /// Does the work of `replacen_into`, but may leave partial output in
+    /// `dst` when an error occurs.
+    fn replacen_append<R: Replacer<W>>(
+        &self,
+        subject: &[W::SubjectChar],
+        limit: usize,
+        mut rep: R,
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        // All replacers find matches in the same way, which honors the
+        // `EmptyMatchPolicy` of this regex. Templates are then expanded by
+        // `pcre2_substitute` for each match.
+        if let Some(literal) = rep.no_expansion() {
+            return self.replace_each(subject, limit, dst, |_, dst| {
+                dst.extend_from_slice(&literal);
+                Ok(())
+            });
         }
+        let mut options = PCRE2_SUBSTITUTE_REPLACEMENT_ONLY;
+        if rep.extended() {
+            options |= PCRE2_SUBSTITUTE_EXTENDED;
+        }
+        if let Some(template) = rep.template() {
+            return self.replace_each(subject, limit, dst, |caps, dst| {
+                self.substitute_matched(&mut caps.locs, caps.subject, &template, options, dst)?;
+                Ok(())
+            });
+        }
+        self.replace_each(subject, limit, dst, |caps, dst| {
+            rep.replace_append(caps, dst);
+            Ok(())
+        })
     }
 
-    fn match_data(&self) -> &RefCell<MatchData<W>> {
-        let create = || RefCell::new(self.new_match_data());
-        self.match_data.get_or(create)
-    }
+    /// Checks that the given replacer can be used with this regex, without
+    /// searching any subject.
+    ///
+    /// For replacement templates, this reports syntax errors and references
+    /// to capture groups that don't exist in this regex, which would
+    /// otherwise only be reported once a replacement is attempted on a
+    /// subject that matches. Templates are checked by PCRE2 itself, and the
+    /// offset in the template at which it detected the problem is available
+    /// via `Error::offset`. Replacers that aren't templates, such as
+    /// closures and `NoExpand`, are always valid.
+    ///
+    /// Problems that depend on the match itself, such as references to
+    /// groups that didn't participate in a match, aren't detected.
+    pub fn check_replacement<R: Replacer<W>>(&self, mut rep: R) -> Result<(), Error> {
+        if rep.no_expansion().is_some() {
+            return Ok(());
+        }
+        let mut options = PCRE2_SUBSTITUTE_REPLACEMENT_ONLY;
+        if rep.extended() {
+            options |= PCRE2_SUBSTITUTE_EXTENDED;
+        }
+        let template = match rep.template() {
+            None => return Ok(()),
+            Some(template) => template,
+        };
 
-    fn new_match_data(&self) -> MatchData<W> {
-        MatchData::new(self.config.match_config.clone(), &self.code)
+        // PCRE2 only checks a template when it replaces a match, so replace
+        // the empty match of a pattern that has the same capture groups as
+        // this regex. Which parts of a template are checked may depend on
+        // whether groups are set, as in `${n:+set:unset}`, so this is done
+        // once with all groups set to the empty string and once with all of
+        // them unset.
+        let mut groups = String::new();
+        for name in &self.capture_names()[1..] {
+            match *name {
+                Some(ref name) => groups.push_str(&format!("(?<{}>)", name)),
+                None => groups.push_str("()"),
+            }
+        }
+        let mut compile_options = PCRE2_DUPNAMES;
+        if self.is_utf() {
+            compile_options |= PCRE2_UTF;
+        }
+        let unset = format!("(?:(*FAIL){})?", groups);
+        for (pattern, unset_empty) in [(groups, 0), (unset, PCRE2_SUBSTITUTE_UNSET_EMPTY)] {
+            let pattern = W::pattern_from_str(&pattern);
+            let code = Code::<W>::new(&pattern, compile_options, CompileContext::new())?;
+            let mut match_data = MatchData::new(MatchConfig::default(), &code);
+            let options = options | unset_empty;
+            // SAFETY: We don't use PCRE2_NO_UTF_CHECK.
+            unsafe { match_data.substitute(&code, &[], 0, options, &template, &mut vec![])? };
+        }
+        Ok(())
     }
-}
 
-/// CaptureLocations is a low level representation of the raw offsets of each
-/// submatch.
+    /// Replaces matches in `subject` with the given PCRE2 substitution
+    /// template, with the behavior of the substitution controlled by `opts`.
+    ///
+    /// Unless `opts` requests a global substitution, only the first match is
+    /// replaced. If no match is found and `replacement_only` is not enabled,
+    /// then the subject is returned unchanged, without copying it.
+    pub fn replace_with_options<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        replacement: &[W::SubjectChar],
+        opts: &SubstituteOptions,
+    ) -> Result<Cow<'s, [W::SubjectChar]>, Error> {
+        let mut dst = vec![];
+        let count = self.replace_with_options_into(subject, replacement, opts, &mut dst)?;
+        if count == 0 && !opts.replacement_only {
+            Ok(Cow::Borrowed(subject))
+        } else {
+            Ok(Cow::Owned(dst))
+        }
+    }
+
+    /// Like `replace_with_options`, but appends the result to `dst` instead
+    /// of returning a new string. The number of replacements made is
+    /// returned.
+    pub fn replace_with_options_into(
+        &self,
+        subject: &[W::SubjectChar],
+        replacement: &[W::SubjectChar],
+        opts: &SubstituteOptions,
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        self.substitute(subject, replacement, opts.to_raw(), dst)
+    }
+
+    /// Replaces the match most recently found with `locs` in `subject` with
+    /// the given PCRE2 substitution template, appending the result to `dst`.
+    ///
+    /// This uses `PCRE2_SUBSTITUTE_MATCHED`, which reuses the match already
+    /// recorded in `locs` (for example, by `captures_read`) instead of
+    /// searching `subject` again. `subject` must be the same subject that was
+    /// searched. Enable `replacement_only` in `opts` to append only the
+    /// expanded replacement. If `global` is enabled, then any matches after
+    /// the recorded one are found and replaced as usual.
+    ///
+    /// If `locs` doesn't contain a match, then this behaves as if the
+    /// subject didn't match at all. The number of replacements made is
+    /// returned.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `locs` was not created by this regex.
+    pub fn replace_matched_into(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &[W::SubjectChar],
+        replacement: &[W::SubjectChar],
+        opts: &SubstituteOptions,
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        assert!(
+            Arc::ptr_eq(&locs.code, &self.code),
+            "capture locations were created by a different regex"
+        );
+        if !locs.data.matched() {
+            if !opts.replacement_only {
+                dst.extend_from_slice(subject);
+            }
+            return Ok(0);
+        }
+        // PCRE2 trusts the offsets of the existing match, so make sure that
+        // they actually refer to this subject.
+        let in_bounds = locs
+            .data
+            .ovector()
+            .iter()
+            .all(|&offset| offset == PCRE2_UNSET || offset <= subject.len());
+        if !in_bounds {
+            return Err(Error::substitute(PCRE2_ERROR_BADOFFSET));
+        }
+        self.substitute_matched(locs, subject, replacement, opts.to_raw(), dst)
+    }
+
+    /// Replaces at most `limit` matches in `subject` (or all of them if
+    /// `limit` is `0`) with whatever `append` writes for each match, and
+    /// appends the result to `dst`. The number of replacements is returned.
+    fn replace_each<'s, F>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        limit: usize,
+        dst: &mut Vec<W::SubjectChar>,
+        mut append: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnMut(&mut Captures<'s, W>, &mut Vec<W::SubjectChar>) -> Result<(), Error>,
+    {
+        // This follows the same iteration strategy as `CaptureMatches`, but
+        // reuses a single set of capture locations for every match.
+        let mut caps = Captures {
+            subject,
+            locs: self.capture_locations(),
+            names: Arc::clone(&self.capture_names),
+        };
+        let (mut count, mut last_end) = (0, 0);
+        let (opts, mut pos) = (MatchOptions::default(), SearchPosition::new());
+        while limit == 0 || count < limit {
+            let m = match pos.next(self, subject, |start, options| {
+                self.captures_read_at_impl(&mut caps.locs, subject, start, &opts, options)
+            }) {
+                None => break,
+                Some(m) => m?,
+            };
+            dst.extend_from_slice(&subject[last_end..m.start()]);
+            append(&mut caps, dst)?;
+            last_end = m.end();
+            count += 1;
+        }
+        dst.extend_from_slice(&subject[last_end..]);
+        Ok(count)
+    }
+
+    /// Expand the given template for the match that was most recently found
+    /// with `locs` in `subject`, and append the result to `dst`. The given
+    /// options are passed to `pcre2_substitute` along with
+    /// `PCRE2_SUBSTITUTE_MATCHED`.
+    fn substitute_matched(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &[W::SubjectChar],
+        template: &[W::SubjectChar],
+        mut options: u32,
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        options |= PCRE2_SUBSTITUTE_MATCHED;
+        if !self.config.utf_check {
+            options |= PCRE2_NO_UTF_CHECK;
+        }
+        // SAFETY: The only unsafe PCRE2 option we potentially use here is
+        // PCRE2_NO_UTF_CHECK, and that only occurs if the caller executes the
+        // `disable_utf_check` method, which propagates the safety contract to
+        // the caller.
+        //
+        // PCRE2 requires the code of the search that found the match, which
+        // may have been JIT compiled automatically since.
+        let code = match self.auto_jit_code() {
+            Some(code) if locs.data.searched_with(code) => code,
+            _ => &self.code,
+        };
+        unsafe {
+            locs.data
+                .substitute(code, subject, 0, options, template, dst)
+        }
+    }
+
+    /// Run `pcre2_substitute` with the given options on the whole subject,
+    /// appending the result to `dst`. The number of substitutions is
+    /// returned.
+    fn substitute(
+        &self,
+        subject: &[W::SubjectChar],
+        replacement: &[W::SubjectChar],
+        mut options: u32,
+        dst: &mut Vec<W::SubjectChar>,
+    ) -> Result<usize, Error> {
+        if !self.config.utf_check {
+            options |= PCRE2_NO_UTF_CHECK;
+        }
+
+        let match_data = self.match_data();
+        let mut match_data = match_data.borrow_mut();
+        // SAFETY: The only unsafe PCRE2 option we potentially use here is
+        // PCRE2_NO_UTF_CHECK, and that only occurs if the caller executes the
+        // `disable_utf_check` method, which propagates the safety contract to
+        // the caller.
+        let code = self.search_code();
+        unsafe { match_data.substitute(code, subject, 0, options, replacement, dst) }
+    }
+
+    /// Test helper to access capture name indexes.
+    #[cfg(test)]
+    pub(crate) fn get_capture_names_idxs(&self) -> &HashMap<String, usize> {
+        self.capture_names.idx()
+    }
+}
+
+/// Advanced or  "lower level" search methods.
+impl<W: CodeUnitWidth> Regex<W> {
+    /// Returns the same as is_match, but starts the search at the given
+    /// offset.
+    ///
+    /// The significance of the starting point is that it takes the surrounding
+    /// context into consideration. For example, the `\A` anchor can only
+    /// match when `start == 0`.
+    pub fn is_match_at(&self, subject: &[W::SubjectChar], start: usize) -> Result<bool, Error> {
+        self.is_match_at_with_options(subject, start, &MatchOptions::default())
+    }
+
+    /// Like `is_match_at`, but uses the given match-time options.
+    pub fn is_match_at_with_options(
+        &self,
+        subject: &[W::SubjectChar],
+        start: usize,
+        opts: &MatchOptions,
+    ) -> Result<bool, Error> {
+        let match_data = self.match_data();
+        let mut match_data = match_data.borrow_mut();
+        self.search(&mut match_data, subject, start, opts, 0)
+    }
+
+    /// Returns the same as find, but starts the search at the given
+    /// offset.
+    ///
+    /// The significance of the starting point is that it takes the surrounding
+    /// context into consideration. For example, the `\A` anchor can only
+    /// match when `start == 0`.
+    pub fn find_at<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.find_at_with_options(subject, start, &MatchOptions::default())
+    }
+
+    /// Like `find_at`, but uses the given match-time options.
+    pub fn find_at_with_options<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        opts: &MatchOptions,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.find_at_with_match_data(self.match_data(), subject, start, opts, 0)
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `subject`, using the given match-time options for every search.
+    pub fn find_iter_with_options<'r, 's>(
+        &'r self,
+        subject: &'s [W::SubjectChar],
+        opts: &MatchOptions,
+    ) -> Matches<'r, 's, W> {
+        Matches {
+            re: self,
+            match_data: self.match_data(),
+            subject,
+            opts: opts.clone(),
+            pos: SearchPosition::new(),
+        }
+    }
+
+    /// Returns the leftmost-first complete or partial match in `subject`,
+    /// starting the search at the given offset.
+    ///
+    /// A partial match occurs when the end of the subject is reached in the
+    /// middle of a possible match, such as when searching `abc 20` for
+    /// `\d{4}`. A partial match spans from where the possible match starts to
+    /// the end of the subject. This is useful when the subject is only a
+    /// chunk of a larger input, since it indicates how much of the chunk
+    /// must be retained to find a match once more input is available.
+    ///
+    /// `mode` determines whether complete matches take priority over partial
+    /// ones. See `PartialMode` for details.
+    pub fn find_partial_at<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        mode: PartialMode,
+    ) -> Result<Option<PartialMatch<'s, W>>, Error> {
+        let match_data = self.match_data();
+        let mut match_data = match_data.borrow_mut();
+        let opts = MatchOptions::default();
+        if !self.search(&mut match_data, subject, start, &opts, mode.to_raw())? {
+            return Ok(None);
+        }
+        Ok(Some(PartialMatch::new(subject, &match_data)))
+    }
+
+    /// Like `find_partial_at`, but also populates the given capture
+    /// locations.
+    ///
+    /// Only complete matches have capture groups other than the `0`th group,
+    /// which corresponds to the overall match. For a partial match, the
+    /// other groups are unset.
+    pub fn captures_read_partial_at<'s>(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        mode: PartialMode,
+    ) -> Result<Option<PartialMatch<'s, W>>, Error> {
+        let opts = MatchOptions::default();
+        if !self.search(&mut locs.data, subject, start, &opts, mode.to_raw())? {
+            return Ok(None);
+        }
+        Ok(Some(PartialMatch::new(subject, &locs.data)))
+    }
+
+    /// Returns the leftmost-first match in `subject`, or an error whose kind
+    /// is `ErrorKind::TimedOut` if the search is still running when the
+    /// given deadline passes.
+    ///
+    /// This requires the regex to have been built with
+    /// `RegexBuilder::interruptible` enabled. Otherwise, an error is
+    /// returned. This is a shorthand for `find_at_with_options` with
+    /// `MatchOptions::deadline` set.
+    pub fn find_with_deadline<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        deadline: Instant,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        let mut opts = MatchOptions::new();
+        opts.deadline(Some(deadline));
+        self.find_at_with_options(subject, 0, &opts)
+    }
+
+    /// Returns the leftmost-first match in `subject` that lies entirely within
+    /// the given range. If no match exists, then `None` is returned.
+    ///
+    /// Unlike searching a slice of the subject, the whole subject remains
+    /// visible to the pattern, so anchors such as `^`, `$` and `\b`, as well
+    /// as lookaround assertions, take the text around the range into
+    /// account. Only where a match starts and ends is restricted: if the
+    /// leftmost-first match at some position extends past the end of the
+    /// range, then no match is reported at that position, even if the
+    /// pattern could match a shorter string there.
+    ///
+    /// If the regex was built with `RegexBuilder::use_offset_limit`, then
+    /// PCRE2 also stops looking for a match as soon as none can start
+    /// within the range.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the range is out of bounds for `subject`.
+    pub fn find_in<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        range: Range<usize>,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.search_in(subject, range, |start, opts| {
+            self.find_at_with_options(subject, start, opts)
+        })
+    }
+
+    /// Returns true if and only if there is a match in `subject` that lies
+    /// entirely within the given range.
+    ///
+    /// See `find_in` for details on how the range is interpreted.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the range is out of bounds for `subject`.
+    pub fn is_match_in(
+        &self,
+        subject: &[W::SubjectChar],
+        range: Range<usize>,
+    ) -> Result<bool, Error> {
+        Ok(self.find_in(subject, range)?.is_some())
+    }
+
+    /// Like `captures_read_at`, but only reports matches that lie entirely
+    /// within the given range.
+    ///
+    /// See `find_in` for details on how the range is interpreted.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the range is out of bounds for `subject`.
+    pub fn captures_read_in<'s>(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &'s [W::SubjectChar],
+        range: Range<usize>,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.search_in(subject, range, |start, opts| {
+            self.captures_read_at_with_options(locs, subject, start, opts)
+        })
+    }
+
+    /// Runs the given search from successive starting offsets until it
+    /// finds a match that lies entirely within `range` of `subject`.
+    ///
+    /// This panics if the range isn't a valid range of `subject`.
+    fn search_in<'s, F>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        range: Range<usize>,
+        mut search: F,
+    ) -> Result<Option<Match<'s, W>>, Error>
+    where
+        F: FnMut(usize, &MatchOptions) -> Result<Option<Match<'s, W>>, Error>,
+    {
+        assert!(
+            range.start <= range.end && range.end <= subject.len(),
+            "range ({:?}) must be within subject (length {})",
+            range,
+            subject.len()
+        );
+        let mut opts = MatchOptions::new();
+        // PCRE2 rejects offset limits for regexes that weren't compiled for
+        // them, in which case matches that start too late are dropped below.
+        if self.compile_arg_options() & PCRE2_USE_OFFSET_LIMIT != 0 {
+            opts.offset_limit(Some(range.end));
+        }
+        let mut start = range.start;
+        while let Some(m) = search(start, &opts)? {
+            if m.start() > range.end {
+                break;
+            }
+            if m.end() <= range.end {
+                return Ok(Some(m));
+            }
+            start = next_char(self, subject, m.start());
+        }
+        Ok(None)
+    }
+
+    /// Returns the same as `dfa_find`, but starts the search at the given
+    /// offset.
+    ///
+    /// The significance of the starting point is that it takes the surrounding
+    /// context into consideration. For example, the `\A` anchor can only
+    /// match when `start == 0`.
+    pub fn dfa_find_at<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.dfa_find_at_with_options(subject, start, 0)
+    }
+
+    /// Returns the same as `dfa_find_shortest`, but starts the search at the
+    /// given offset.
+    ///
+    /// The significance of the starting point is that it takes the surrounding
+    /// context into consideration. For example, the `\A` anchor can only
+    /// match when `start == 0`.
+    pub fn dfa_find_shortest_at<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.dfa_find_at_with_options(subject, start, PCRE2_DFA_SHORTEST)
+    }
+
+    /// Like `dfa_find_at`, but passes the given PCRE2 match options to
+    /// PCRE2 as is.
+    fn dfa_find_at_with_options<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        options: u32,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        let match_data = self.match_data();
+        let mut match_data = match_data.borrow_mut();
+        if self.dfa_search(&mut match_data, subject, start, options, None)? == 0 {
+            return Ok(None);
+        }
+        let ovector = match_data.ovector();
+        Ok(Some(Match::new(subject, ovector[0], ovector[1])))
+    }
+
+    /// Like `dfa_find`, but uses the given workspace instead of the one that
+    /// is kept for each thread.
+    ///
+    /// To create a `DfaWorkspace` value, use the `Regex::dfa_workspace`
+    /// method.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the workspace was created by a different `Regex`.
+    pub fn dfa_read<'s>(
+        &self,
+        ws: &mut DfaWorkspace<W>,
+        subject: &'s [W::SubjectChar],
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.dfa_read_at(ws, subject, 0)
+    }
+
+    /// Returns the same as `dfa_read`, but starts the search at the given
+    /// offset.
+    ///
+    /// The significance of the starting point is that it takes the surrounding
+    /// context into consideration. For example, the `\A` anchor can only
+    /// match when `start == 0`.
+    pub fn dfa_read_at<'s>(
+        &self,
+        ws: &mut DfaWorkspace<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.check_dfa_workspace(ws);
+        ws.restartable = false;
+        let workspace = Some(&mut ws.workspace[..]);
+        if self.dfa_search(&mut ws.data, subject, start, 0, workspace)? == 0 {
+            return Ok(None);
+        }
+        let ovector = ws.data.ovector();
+        Ok(Some(Match::new(subject, ovector[0], ovector[1])))
+    }
+
+    /// Like `dfa_read_at`, but permits partial matches.
+    ///
+    /// When a partial match is found, the state of the search is kept in the
+    /// given workspace, and the search can be continued on the next segment
+    /// of the input by calling `dfa_restart`. Unlike with `find_partial_at`,
+    /// the caller does not need to retain the earlier segments in order to
+    /// complete the match.
+    ///
+    /// `mode` determines whether complete matches take priority over partial
+    /// ones. See `PartialMode` for details.
+    ///
+    /// This panics if the workspace was created by a different `Regex`.
+    pub fn dfa_read_partial_at<'s>(
+        &self,
+        ws: &mut DfaWorkspace<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        mode: PartialMode,
+    ) -> Result<Option<PartialMatch<'s, W>>, Error> {
+        self.dfa_read_partial_with(ws, subject, start, mode.to_raw())
+    }
+
+    /// Continues a search that ended in a partial match on the next segment
+    /// of the input, using `PCRE2_DFA_RESTART`.
+    ///
+    /// The given workspace must be the one used by the previous call to
+    /// `dfa_read_partial_at` or `dfa_restart`, and that call must have
+    /// returned a partial match. Otherwise, this panics. This also panics if
+    /// the workspace was created by a different `Regex`.
+    ///
+    /// Only the match attempt that was in progress is continued. If it fails,
+    /// then no match is reported, even if a match starts later in `subject`.
+    /// When a match is completed, the returned match only covers the part of
+    /// it in `subject`, which always starts at offset `0`. As with
+    /// `dfa_read_partial_at`, another partial match permits restarting again
+    /// with a subsequent segment.
+    ///
+    /// In UTF mode, segments must not split a character, unless UTF checking
+    /// has been disabled, in which case doing so has unspecified behavior.
+    pub fn dfa_restart<'s>(
+        &self,
+        ws: &mut DfaWorkspace<W>,
+        subject: &'s [W::SubjectChar],
+        mode: PartialMode,
+    ) -> Result<Option<PartialMatch<'s, W>>, Error> {
+        self.check_dfa_workspace(ws);
+        assert!(
+            ws.restartable,
+            "DFA workspace does not hold a partial match to restart"
+        );
+        let options = mode.to_raw() | PCRE2_DFA_RESTART;
+        self.dfa_read_partial_with(ws, subject, 0, options)
+    }
+
+    /// Runs a DFA search that permits partial matches with the given PCRE2
+    /// options, and records whether the search can be restarted.
+    fn dfa_read_partial_with<'s>(
+        &self,
+        ws: &mut DfaWorkspace<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        options: u32,
+    ) -> Result<Option<PartialMatch<'s, W>>, Error> {
+        self.check_dfa_workspace(ws);
+        ws.restartable = false;
+        let workspace = Some(&mut ws.workspace[..]);
+        if self.dfa_search(&mut ws.data, subject, start, options, workspace)? == 0 {
+            return Ok(None);
+        }
+        ws.restartable = ws.data.partial();
+        Ok(Some(PartialMatch::new(subject, &ws.data)))
+    }
+
+    /// Panics if the given workspace wasn't created by this regex.
+    ///
+    /// PCRE2 sizes the match data and interprets the state of a restartable
+    /// search based on the regex that created them, so they must not be
+    /// used with any other.
+    fn check_dfa_workspace(&self, ws: &DfaWorkspace<W>) {
+        assert!(
+            Arc::ptr_eq(&ws.code, &self.code),
+            "DFA workspace was created by a different regex"
+        );
+    }
+
+    /// Run a single search with PCRE2's DFA matching algorithm with the given
+    /// match data and workspace, returning the number of matches found. The
+    /// given PCRE2 match options are passed to PCRE2 as is.
+    ///
+    /// Without a workspace, one that is kept in the match data is used.
+    fn dfa_search(
+        &self,
+        match_data: &mut MatchData<W>,
+        subject: &[W::SubjectChar],
+        start: usize,
+        mut options: u32,
+        workspace: Option<&mut [c_int]>,
+    ) -> Result<usize, Error> {
+        assert!(
+            start <= subject.len(),
+            "start ({}) must be <= subject.len() ({})",
+            start,
+            subject.len()
+        );
+
+        if !self.config.utf_check {
+            options |= PCRE2_NO_UTF_CHECK;
+        }
+        // SAFETY: The only unsafe PCRE2 option we potentially use here is
+        // PCRE2_NO_UTF_CHECK, and that only occurs if the caller executes the
+        // `disable_utf_check` method, which propagates the safety contract to
+        // the caller.
+        unsafe {
+            match workspace {
+                Some(workspace) => {
+                    match_data.dfa_find(&self.code, subject, start, options, workspace)
+                }
+                None => match_data.dfa_find_with_own_workspace(
+                    &self.code,
+                    subject,
+                    start,
+                    options,
+                    DFA_WORKSPACE_SIZE,
+                ),
+            }
+        }
+    }
+
+    /// Like find_at, but accepts match data instead of acquiring one itself.
+    /// The given PCRE2 match options are used in addition to `opts`.
+    ///
+    /// This is useful for implementing the iterator, which permits avoiding
+    /// the synchronization overhead of acquiring the match data.
+    #[inline(always)]
+    fn find_at_with_match_data<'s>(
+        &self,
+        match_data: &RefCell<MatchData<W>>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        opts: &MatchOptions,
+        options: u32,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        let mut match_data = match_data.borrow_mut();
+        if !self.search(&mut match_data, subject, start, opts, options)? {
+            return Ok(None);
+        }
+        let ovector = match_data.ovector();
+        let (s, e) = (ovector[0], ovector[1]);
+        Ok(Some(Match::new(subject, s, e)))
+    }
+
+    /// Find the next match of a streaming search, where `subject` holds the
+    /// input seen so far and `pos` tracks the progress through it.
+    ///
+    /// Unless `complete` is true, more input may follow `subject`. In that
+    /// case, `None` is returned when the search reaches the end of `subject`
+    /// in the middle of a possible match, and `pos` is left at the start of
+    /// the possible match so that the search can resume once more input is
+    /// available. If no match can start before the end of `subject`, then
+    /// `pos` is moved there.
+    pub(crate) fn find_stream_next<'s>(
+        &self,
+        pos: &mut SearchPosition,
+        subject: &'s [W::SubjectChar],
+        complete: bool,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        let (match_data, opts) = (self.match_data(), MatchOptions::default());
+        if complete {
+            let found = pos.next(self, subject, |start, options| {
+                self.find_at_with_match_data(match_data, subject, start, &opts, options)
+            });
+            return found.transpose();
+        }
+        let found = pos.next_partial(self, subject, |start, options| {
+            let mut match_data = match_data.borrow_mut();
+            let options = options | PCRE2_PARTIAL_HARD;
+            if !self.search(&mut match_data, subject, start, &opts, options)? {
+                return Ok(None);
+            }
+            Ok(Some(PartialMatch::new(subject, &match_data)))
+        });
+        match found.transpose()? {
+            Some(PartialMatch::Complete(m)) => Ok(Some(m)),
+            Some(PartialMatch::Partial(_)) => Ok(None),
+            None => {
+                pos.skip_to(subject.len());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Like `find_at_with_options`, but also returns the number that names
+    /// the last mark passed on the matching path.
+    ///
+    /// This is how a `RegexSet` tells which of its patterns matched.
+    ///
+    /// # Panics
+    ///
+    /// This panics if a match is found that didn't pass a mark named by a
+    /// decimal number.
+    pub(crate) fn find_marked_at<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        opts: &MatchOptions,
+    ) -> Result<Option<(usize, Match<'s, W>)>, Error> {
+        let mut match_data = self.match_data().borrow_mut();
+        if !self.search(&mut match_data, subject, start, opts, 0)? {
+            return Ok(None);
+        }
+        let ovector = match_data.ovector();
+        let m = Match::new(subject, ovector[0], ovector[1]);
+        Ok(Some((numbered_mark(&match_data), m)))
+    }
+
+    /// Like `find_marked_at`, but also records the capture groups of the
+    /// match in `locs`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if a match is found that didn't pass a mark named by a
+    /// decimal number.
+    pub(crate) fn captures_read_marked_at<'s>(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<(usize, Match<'s, W>)>, Error> {
+        let opts = MatchOptions::default();
+        if !self.search(&mut locs.data, subject, start, &opts, 0)? {
+            return Ok(None);
+        }
+        let ovector = locs.data.ovector();
+        let m = Match::new(subject, ovector[0], ovector[1]);
+        Ok(Some((numbered_mark(&locs.data), m)))
+    }
+
+    /// This is like `captures`, but uses
+    /// [`CaptureLocations`](struct.CaptureLocations.html)
+    /// instead of
+    /// [`Captures`](struct.Captures.html) in order to amortize allocations.
+    ///
+    /// To create a `CaptureLocations` value, use the
+    /// `Regex::capture_locations` method.
+    ///
+    /// This returns the overall match if this was successful, which is always
+    /// equivalent to the `0`th capture group.
+    pub fn captures_read<'s>(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &'s [W::SubjectChar],
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.captures_read_at(locs, subject, 0)
+    }
+
+    /// Returns the same as `captures_read`, but starts the search at the given
+    /// offset and populates the capture locations given.
+    ///
+    /// The significance of the starting point is that it takes the surrounding
+    /// context into consideration. For example, the `\A` anchor can only
+    /// match when `start == 0`.
+    pub fn captures_read_at<'s>(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.captures_read_at_with_options(locs, subject, start, &MatchOptions::default())
+    }
+
+    /// Like `captures_read_at`, but uses the given match-time options.
+    pub fn captures_read_at_with_options<'s>(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        opts: &MatchOptions,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        self.captures_read_at_impl(locs, subject, start, opts, 0)
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups
+    /// matched in `subject`, using the given match-time options for every
+    /// search.
+    pub fn captures_iter_with_options<'r, 's>(
+        &'r self,
+        subject: &'s [W::SubjectChar],
+        opts: &MatchOptions,
+    ) -> CaptureMatches<'r, 's, W> {
+        CaptureMatches {
+            re: self,
+            subject,
+            opts: opts.clone(),
+            pos: SearchPosition::new(),
+        }
+    }
+
+    /// Like `captures_read_at_with_options`, but the given PCRE2 match
+    /// options are used in addition to `opts`.
+    fn captures_read_at_impl<'s>(
+        &self,
+        locs: &mut CaptureLocations<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        opts: &MatchOptions,
+        options: u32,
+    ) -> Result<Option<Match<'s, W>>, Error> {
+        if !self.search(&mut locs.data, subject, start, opts, options)? {
+            return Ok(None);
+        }
+        let ovector = locs.data.ovector();
+        let (s, e) = (ovector[0], ovector[1]);
+        Ok(Some(Match::new(subject, s, e)))
+    }
+
+    /// Run a single search with the given match data, returning true if a
+    /// match was found. The given PCRE2 match options are used in addition
+    /// to `opts`.
+    fn search(
+        &self,
+        match_data: &mut MatchData<W>,
+        subject: &[W::SubjectChar],
+        start: usize,
+        opts: &MatchOptions,
+        mut options: u32,
+    ) -> Result<bool, Error> {
+        assert!(
+            start <= subject.len(),
+            "start ({}) must be <= subject.len() ({})",
+            start,
+            subject.len()
+        );
+
+        options |= opts.to_raw();
+        if !self.config.utf_check {
+            options |= PCRE2_NO_UTF_CHECK;
+        }
+        // Deadlines and cancellation are checked from automatic callouts.
+        let interrupts = opts.deadline.is_some() || opts.cancellation.is_some();
+        if interrupts && self.compile_arg_options() & PCRE2_AUTO_CALLOUT == 0 {
+            return Err(Error::matching(PCRE2_ERROR_BADOPTION));
+        }
+        let mut match_data = SearchOverrides::new(match_data, opts);
+        // SAFETY: The only unsafe PCRE2 option we potentially use here is
+        // PCRE2_NO_UTF_CHECK, and that only occurs if the caller executes the
+        // `disable_utf_check` method, which propagates the safety contract to
+        // the caller. Likewise, the JIT fast path is only used if the caller
+        // executes the `jit_fast_path` method. The code was JIT compiled for
+        // the requested matching mode and `start` was checked above, so the
+        // remaining obligations of `find_jit` are upheld.
+        let code = self.search_code();
+        if self.uses_jit_fast_path(code, options) {
+            unsafe { match_data.find_jit(code, subject, start, options) }
+        } else {
+            unsafe { match_data.find(code, subject, start, options) }
+        }
+    }
+
+    /// Returns true if a search with the given PCRE2 match options should
+    /// use the JIT fast path.
+    fn uses_jit_fast_path(&self, code: &Code<W>, options: u32) -> bool {
+        // PCRE2_NO_UTF_CHECK is implied by the fast path. Any other option
+        // would be silently ignored by it, so such searches go through
+        // pcre2_match instead. So do partial matches, unless the code was JIT
+        // compiled for the requested mode.
+        const SUPPORTED: u32 = PCRE2_NOTBOL
+            | PCRE2_NOTEOL
+            | PCRE2_NOTEMPTY
+            | PCRE2_NOTEMPTY_ATSTART
+            | PCRE2_NO_UTF_CHECK;
+        let jit_options = code.jit_options();
+        let partial_supported = if options & PCRE2_PARTIAL_HARD != 0 {
+            jit_options & PCRE2_JIT_PARTIAL_HARD != 0
+        } else if options & PCRE2_PARTIAL_SOFT != 0 {
+            jit_options & PCRE2_JIT_PARTIAL_SOFT != 0
+        } else {
+            true
+        };
+        let options = options & !(PCRE2_PARTIAL_HARD | PCRE2_PARTIAL_SOFT);
+        self.config.jit_fast_path
+            && code.compiled_jit()
+            && partial_supported
+            && options & !SUPPORTED == 0
+    }
+}
+
+/// Returns the number that names the last mark passed by the most recent
+/// match found with the given match data.
+///
+/// # Panics
+///
+/// This panics if that mark isn't named by a decimal number.
+fn numbered_mark<W: CodeUnitWidth>(match_data: &MatchData<W>) -> usize {
+    // SAFETY: The name points into the code that was searched, which is
+    // owned by the caller's regex and outlives the parsing below.
+    let name = unsafe { match_data.mark() }.filter(|name| !name.is_empty());
+    let mark = name.and_then(|name| {
+        name.iter().try_fold(0usize, |n, &c| {
+            let digit = char::from_u32(c.into())?.to_digit(10)?;
+            n.checked_mul(10)?.checked_add(digit as usize)
+        })
+    });
+    mark.expect("match didn't pass a numbered mark")
+}
+
+/// The offset limit, the match limits and the deadline of a search live in
+/// the match context, which is shared by all searches using the same match
+/// data. This overrides them for a single search, and restores them when
+/// dropped, including when a callout's panic unwinds through the search.
+struct SearchOverrides<'a, 'o, W: CodeUnitWidth> {
+    match_data: &'a mut MatchData<W>,
+    opts: &'o MatchOptions,
+}
+
+impl<'a, 'o, W: CodeUnitWidth> SearchOverrides<'a, 'o, W> {
+    fn new(match_data: &'a mut MatchData<W>, opts: &'o MatchOptions) -> Self {
+        if let Some(limit) = opts.offset_limit {
+            match_data.set_offset_limit(limit);
+        }
+        if opts.overrides_limits() {
+            match_data.set_limits(opts.match_limit, opts.depth_limit, opts.heap_limit);
+        }
+        if opts.deadline.is_some() || opts.cancellation.is_some() {
+            match_data.set_deadline(opts.deadline);
+            match_data.set_cancellation(opts.cancellation.as_ref().map(|t| Arc::clone(&t.0)));
+        }
+        SearchOverrides { match_data, opts }
+    }
+}
+
+impl<W: CodeUnitWidth> std::ops::Deref for SearchOverrides<'_, '_, W> {
+    type Target = MatchData<W>;
+
+    fn deref(&self) -> &MatchData<W> {
+        self.match_data
+    }
+}
+
+impl<W: CodeUnitWidth> std::ops::DerefMut for SearchOverrides<'_, '_, W> {
+    fn deref_mut(&mut self) -> &mut MatchData<W> {
+        self.match_data
+    }
+}
+
+impl<W: CodeUnitWidth> Drop for SearchOverrides<'_, '_, W> {
+    fn drop(&mut self) {
+        if self.opts.offset_limit.is_some() {
+            self.match_data.reset_offset_limit();
+        }
+        if self.opts.overrides_limits() {
+            self.match_data.reset_limits();
+        }
+        if self.opts.deadline.is_some() || self.opts.cancellation.is_some() {
+            self.match_data.set_deadline(None);
+            self.match_data.set_cancellation(None);
+        }
+    }
+}
+
+/// Auxiliary methods.
+impl<W: CodeUnitWidth> Regex<W> {
+    /// Returns the original pattern string for this regex.
+    pub fn as_str(&self) -> &W::Pattern {
+        &self.pattern
+    }
+
+    /// Returns a sequence of all capturing groups and their names, if present.
+    ///
+    /// The length of the slice returned is always equal to the result of
+    /// `captures_len`, which is the number of capturing groups (including the
+    /// capturing group for the entire pattern).
+    ///
+    /// Each entry in the slice is the name of the corresponding capturing
+    /// group, if one exists. The first capturing group (at index `0`) is
+    /// always unnamed.
+    ///
+    /// Capturing groups are indexed by the order of the opening parenthesis.
+    pub fn capture_names(&self) -> &[Option<String>] {
+        self.capture_names.names()
+    }
+
+    /// Returns an iterator over the named capturing groups of this regex,
+    /// which yields the index and the name of each group.
+    ///
+    /// Groups are yielded in alphabetical order of their names. Unlike
+    /// `capture_names`, this borrows the names from PCRE2's name table
+    /// directly, so it never allocates.
+    pub fn name_table(&self) -> NameTable<'_, W> {
+        self.code
+            .name_table()
+            .expect("a valid name table from PCRE2")
+    }
+
+    /// Returns the number of capturing groups in the pattern.
+    ///
+    /// This is always 1 more than the number of syntactic groups in the
+    /// pattern, since the first group always corresponds to the entire match.
+    pub fn captures_len(&self) -> usize {
+        self.code
+            .capture_count()
+            .expect("a valid capture count from PCRE2")
+    }
+
+    /// Returns what PCRE2 knows about how every match of this regex starts.
+    ///
+    /// This can be used to build a prefilter that quickly skips to candidate
+    /// positions in a subject, such as with `memchr`, before searching with
+    /// PCRE2. For example, every match of `(cat|cow)` starts with `c`.
+    ///
+    /// PCRE2 also records a first code unit for caseless patterns, where it
+    /// matches every case of the letter, but it doesn't report whether that
+    /// is so. A prefilter should therefore look for every case of a unit
+    /// that has other cases, unless the pattern is known to be case
+    /// sensitive. For example, every match of `(?i)cat` starts with `c` or
+    /// `C`, but this returns `FirstCodeUnit::Fixed` with `c` either way.
+    ///
+    /// This corresponds to `PCRE2_INFO_FIRSTCODETYPE` and
+    /// `PCRE2_INFO_FIRSTCODEUNIT`.
+    pub fn first_code_unit(&self) -> FirstCodeUnit {
+        let first = self
+            .code
+            .first_code_unit()
+            .expect("a valid first code unit from PCRE2");
+        match first {
+            (_, Some(unit)) => FirstCodeUnit::Fixed(unit),
+            (2, None) => FirstCodeUnit::StartOfLine,
+            _ => FirstCodeUnit::Unknown,
+        }
+    }
+
+    /// Returns the last literal code unit that every match of this regex
+    /// must contain, if PCRE2 recorded one.
+    ///
+    /// For example, every match of `a\d+z` contains `z`. Note that this
+    /// isn't necessarily the last code unit of a match: every match of
+    /// `ab\d+` contains `b`. PCRE2 only records this when it isn't the same
+    /// as the first code unit. For caseless patterns, PCRE2 records a code
+    /// unit that a match may contain in another case, but this isn't
+    /// reported, so a prefilter should look for every case of the unit
+    /// unless the pattern is known to be case sensitive.
+    ///
+    /// This corresponds to `PCRE2_INFO_LASTCODETYPE` and
+    /// `PCRE2_INFO_LASTCODEUNIT`.
+    pub fn required_code_unit(&self) -> Option<u32> {
+        self.code
+            .last_code_unit()
+            .expect("a valid last code unit from PCRE2")
+    }
+
+    /// Returns every callout in this regex, in the order in which they
+    /// appear in the pattern, without running a search.
+    ///
+    /// This can be used to validate patterns from an untrusted source, such
+    /// as checking that they only use a known set of callout numbers.
+    /// Automatic callouts enabled by `PCRE2_AUTO_CALLOUT` aren't included.
+    ///
+    /// This corresponds to `pcre2_callout_enumerate`.
+    pub fn callouts(&self) -> Vec<CalloutInfo> {
+        self.code
+            .callouts()
+            .expect("valid callouts from PCRE2")
+            .into_iter()
+            .map(|entry| CalloutInfo { entry })
+            .collect()
+    }
+
+    /// Serializes the compiled form of this regex into a sequence of bytes,
+    /// so that it can be stored and later loaded without compiling the
+    /// pattern again.
+    ///
+    /// The bytes are in PCRE2's serialization format, which can only be
+    /// loaded by the same version of PCRE2 with the same code unit width on
+    /// a host with the same endianness and pointer width. It includes the
+    /// character tables used by the regex, but not its JIT compiled code, nor
+    /// options that only apply at match time such as `RegexBuilder::jit` or
+    /// `RegexBuilder::disable_utf_check`.
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        self.code.serialize()
+    }
+
+    /// Returns the `PCRE2_*` compile option bits that are in effect for this
+    /// regex.
+    ///
+    /// Besides the options derived from the builder configuration, this
+    /// includes options switched on by settings at the start of the pattern,
+    /// such as `(*UTF)` or `(*UCP)`, as well as options that PCRE2 infers,
+    /// such as `PCRE2_ANCHORED` for a pattern that starts with `\A`. Note
+    /// that inline options such as `(?i)` only apply to part of a pattern,
+    /// so they aren't included.
+    ///
+    /// The bits can be tested against the constants exported by the
+    /// `pcre2-sys` crate.
+    pub fn compile_options(&self) -> u32 {
+        self.code
+            .all_options()
+            .expect("valid compile options from PCRE2")
+    }
+
+    /// Returns the `PCRE2_*` compile option bits that were derived from the
+    /// builder configuration, without any changes made by the pattern.
+    pub fn compile_arg_options(&self) -> u32 {
+        self.code
+            .arg_options()
+            .expect("valid compile options from PCRE2")
+    }
+
+    /// Returns true if and only if this regex is in UTF mode, either because
+    /// it was enabled on the builder or by the pattern, such as with
+    /// `(*UTF)`.
+    pub fn is_utf(&self) -> bool {
+        self.compile_options() & PCRE2_UTF != 0
+    }
+
+    /// Returns the newline convention that this regex was compiled with.
+    ///
+    /// This determines what `^`, `$` and `.` treat as a line terminator. It
+    /// reflects both the builder configuration and any `(*CR)`-style
+    /// setting at the start of the pattern.
+    ///
+    /// This returns `None` if PCRE2 reports a convention that this crate
+    /// doesn't know about, which a newer version of PCRE2 could add.
+    pub fn newline(&self) -> Option<Newline> {
+        let raw = self
+            .code
+            .newline()
+            .expect("a valid newline convention from PCRE2");
+        Newline::from_raw(raw)
+    }
+
+    /// Returns which characters `\R` matches in this regex.
+    ///
+    /// Like `newline`, this reflects any `(*BSR_ANYCRLF)` or
+    /// `(*BSR_UNICODE)` setting at the start of the pattern. As with
+    /// `newline`, `None` is returned for an unknown convention.
+    pub fn backslash_r(&self) -> Option<BsrConvention> {
+        let raw = self.code.bsr().expect("a valid \\R convention from PCRE2");
+        BsrConvention::from_raw(raw)
+    }
+
+    /// Returns the number of bytes of memory used by the compiled form of
+    /// this regex, including its JIT compiled code if there is any.
+    ///
+    /// This doesn't include the memory used by the `Regex` value itself, such
+    /// as the pattern string and the scratch space used by searches.
+    pub fn compiled_size(&self) -> usize {
+        self.current_code()
+            .size()
+            .expect("a valid compiled size from PCRE2")
+            + self.jit_size()
+    }
+
+    /// Returns the number of bytes of memory used by the JIT compiled code of
+    /// this regex, or `0` if it wasn't JIT compiled.
+    pub fn jit_size(&self) -> usize {
+        self.current_code()
+            .jit_size()
+            .expect("a valid JIT size from PCRE2")
+    }
+
+    /// Returns true if and only if this regex was JIT compiled.
+    ///
+    /// This is useful for detecting when `RegexBuilder::jit_if_available`
+    /// silently fell back to PCRE2's interpreter, such as when PCRE2 was
+    /// built without JIT support or when JIT compilation failed.
+    pub fn is_jit(&self) -> bool {
+        self.jit_size() > 0
+    }
+
+    /// JIT compile this regex, if it isn't JIT compiled already.
+    ///
+    /// This makes it possible to defer JIT compilation, which can take much
+    /// longer than compiling the pattern itself, until a regex turns out to
+    /// be searched often enough to benefit from it. The regex is JIT compiled
+    /// as configured by `RegexBuilder::jit_partial`, regardless of whether
+    /// `RegexBuilder::jit` was enabled.
+    ///
+    /// Clones of this regex aren't affected. If the compiled pattern is
+    /// shared with any of them, or with capture locations or DFA workspaces
+    /// created by this regex, then a copy of it is JIT compiled instead.
+    ///
+    /// If the JIT isn't available or if JIT compilation returns an error,
+    /// then the error is returned and the regex is left as is.
+    pub fn jit_compile(&mut self) -> Result<(), Error> {
+        if self.current_code().compiled_jit() {
+            return Ok(());
+        }
+        let jit_options = self.config.jit_options();
+        match Arc::get_mut(&mut self.code) {
+            Some(code) => {
+                code.jit_compile(jit_options)?;
+                self.auto_jit = None;
+            }
+            None => {
+                // Searches use the copy just like an automatically JIT
+                // compiled one, so that the code that capture locations and
+                // DFA workspaces were created with stays the same.
+                let mut code = self.code.copy();
+                code.jit_compile(jit_options)?;
+                self.auto_jit = Some(Arc::new(AutoJit::compiled(code)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the highest capturing group that is referred to
+    /// by a backreference in the pattern, or `0` if the pattern has no
+    /// backreferences.
+    ///
+    /// Named backreferences count as references to the group with that name.
+    /// Note that a reference inside a condition, such as `(?(1)a|b)`, also
+    /// counts.
+    pub fn max_backref(&self) -> usize {
+        self.code
+            .backref_max()
+            .expect("a valid maximum backreference from PCRE2")
+    }
+
+    /// Returns true if and only if the pattern contains a backreference.
+    ///
+    /// Patterns with backreferences can't be searched with the DFA matching
+    /// algorithm, and may take exponential time to search.
+    pub fn has_backrefs(&self) -> bool {
+        self.max_backref() > 0
+    }
+
+    /// Returns true if and only if the pattern contains `\C`, which matches
+    /// a single code unit even in UTF mode.
+    ///
+    /// See `RegexBuilder::never_backslash_c` for rejecting such patterns.
+    pub fn has_backslash_c(&self) -> bool {
+        self.code
+            .has_backslash_c()
+            .expect("a valid \\C flag from PCRE2")
+    }
+
+    /// Returns a lower bound on the length of any string that this regex
+    /// matches, as computed by PCRE2 when the pattern was compiled.
+    ///
+    /// The length is counted in characters, which differ from code units in
+    /// UTF mode. Since every character takes at least one code unit, a
+    /// subject with fewer code units than this can never match.
+    ///
+    /// The bound isn't necessarily exact. For example, it's `0` for patterns
+    /// with backreferences or recursion, which PCRE2 doesn't analyze.
+    pub fn min_match_len(&self) -> usize {
+        self.code
+            .min_length()
+            .expect("a valid minimum length from PCRE2")
+    }
+
+    /// Returns the maximum number of characters that any single lookbehind
+    /// assertion in the pattern moves back from the current position.
+    ///
+    /// This is useful when searching input in chunks, since at least this
+    /// many characters preceding the start of a search must be retained for
+    /// the pattern to see the same context as when searching all of the
+    /// input at once. Note that `\b` and `\B` count as a lookbehind of one
+    /// character in newer PCRE2 releases.
+    ///
+    /// This is only a heuristic for nested lookbehinds. For example,
+    /// `(?<=(?<!b)a)` has a maximum lookbehind of `1`, but inspects two
+    /// characters before the current position.
+    pub fn max_lookbehind(&self) -> usize {
+        self.code
+            .max_lookbehind()
+            .expect("a valid maximum lookbehind from PCRE2")
+    }
+
+    /// Returns an empty set of capture locations that can be reused in
+    /// multiple calls to `captures_read` or `captures_read_at`.
+    pub fn capture_locations(&self) -> CaptureLocations<W> {
+        CaptureLocations {
+            code: Arc::clone(&self.code),
+            auto_jit: self.auto_jit.clone(),
+            data: self.new_match_data(),
+        }
+    }
+
+    /// Returns a workspace for PCRE2's DFA matching algorithm that can be
+    /// reused in multiple calls to `dfa_read` or `dfa_read_at`.
+    ///
+    /// The workspace has room for the same number of integers as
+    /// `pcre2test` uses by default, which is enough for most patterns.
+    pub fn dfa_workspace(&self) -> DfaWorkspace<W> {
+        self.dfa_workspace_with_size(DFA_WORKSPACE_SIZE)
+    }
+
+    /// Returns a workspace for PCRE2's DFA matching algorithm with room for
+    /// the given number of integers.
+    ///
+    /// Complex patterns and long subjects may need a larger workspace than
+    /// the one created by `dfa_workspace`. When the workspace is too small,
+    /// searches fail with a `PCRE2_ERROR_DFA_WSSIZE` error. PCRE2 requires
+    /// a size of at least 20.
+    pub fn dfa_workspace_with_size(&self, size: usize) -> DfaWorkspace<W> {
+        DfaWorkspace {
+            code: Arc::clone(&self.code),
+            data: self.new_match_data(),
+            workspace: vec![0; size],
+            restartable: false,
+        }
+    }
+
+    /// Returns the code to execute a search with, counting the search if
+    /// automatic JIT compilation is enabled.
+    fn search_code(&self) -> &Code<W> {
+        if let Some(ref auto_jit) = self.auto_jit {
+            if auto_jit.code.get().is_none() {
+                auto_jit.count(&self.code, self.config.jit_options());
+            }
+        }
+        self.current_code()
+    }
+
+    /// Returns the code that searches are currently executed with.
+    fn current_code(&self) -> &Code<W> {
+        self.auto_jit_code().unwrap_or(&self.code)
+    }
+
+    /// Returns the code that was JIT compiled automatically, if any.
+    fn auto_jit_code(&self) -> Option<&Code<W>> {
+        self.auto_jit
+            .as_ref()
+            .and_then(|auto_jit| auto_jit.code.get())
+    }
+
+    fn match_data(&self) -> &RefCell<MatchData<W>> {
+        let create = || RefCell::new(self.new_match_data());
+        self.match_data.get_or(create)
+    }
+
+    /// Wraps capture locations populated by a search with this regex into
+    /// capture groups of the given subject.
+    pub(crate) fn captures_from_locations<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        locs: CaptureLocations<W>,
+    ) -> Captures<'s, W> {
+        Captures {
+            subject,
+            locs,
+            names: Arc::clone(&self.capture_names),
+        }
+    }
+
+    fn new_match_data(&self) -> MatchData<W> {
+        let mut data = MatchData::new(self.config.match_config.clone(), &self.code);
+        if let Some(Callout(ref callout)) = self.callout {
+            data.set_callout(Arc::clone(callout));
+        }
+        data
+    }
+}
+
+/// CaptureLocations is a low level representation of the raw offsets of each
+/// submatch.
+///
+/// Primarily, this type is useful when using `Regex` APIs such as
+/// `captures_read`, which permits amortizing the allocation in which capture
+/// match locations are stored.
+///
+/// In order to build a value of this type, you'll need to call the
+/// `capture_locations` method on the `Regex` being used to execute the search.
+/// The value returned can then be reused in subsequent searches.
+pub struct CaptureLocations<W: CodeUnitWidth> {
+    code: Arc<Code<W>>,
+    /// The automatic JIT state of the regex, whose code may have been used
+    /// to search instead of `code`.
+    auto_jit: Option<Arc<AutoJit<W>>>,
+    data: MatchData<W>,
+}
+
+impl<W: CodeUnitWidth> Clone for CaptureLocations<W> {
+    fn clone(&self) -> Self {
+        let mut data = MatchData::new(self.data.config().clone(), &self.code);
+        if let Some(callout) = self.data.callout() {
+            data.set_callout(Arc::clone(callout));
+        }
+        CaptureLocations {
+            code: Arc::clone(&self.code),
+            auto_jit: self.auto_jit.clone(),
+            data,
+        }
+    }
+}
+
+impl<W: CodeUnitWidth> fmt::Debug for CaptureLocations<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut offsets: Vec<Option<usize>> = vec![];
+        for &offset in self.data.ovector() {
+            if offset == PCRE2_UNSET {
+                offsets.push(None);
+            } else {
+                offsets.push(Some(offset));
+            }
+        }
+        write!(f, "CaptureLocations(")?;
+        f.debug_list().entries(offsets).finish()?;
+        write!(f, ")")
+    }
+}
+
+impl<W: CodeUnitWidth> CaptureLocations<W> {
+    /// Returns the start and end positions of the Nth capture group.
+    ///
+    /// This returns `None` if `i` is not a valid capture group or if the
+    /// capture group did not match anything.
+    ///
+    /// The positions returned are always byte indices with respect to the
+    /// original subject string matched.
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<(usize, usize)> {
+        let ovec = self.data.ovector();
+        let s = match ovec.get(i * 2) {
+            None => return None,
+            Some(&s) if s == PCRE2_UNSET => return None,
+            Some(&s) => s,
+        };
+        let e = match ovec.get(i * 2 + 1) {
+            None => return None,
+            Some(&e) if e == PCRE2_UNSET => return None,
+            Some(&e) => e,
+        };
+        Some((s, e))
+    }
+
+    /// Returns the total number of capturing groups.
+    ///
+    /// This is always at least `1` since every regex has at least `1`
+    /// capturing group that corresponds to the entire match.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.ovector().len() / 2
+    }
+
+    /// Returns the code of the search that found the current match, or
+    /// `None` if these locations don't hold a match found by a search.
+    fn matched_code(&self) -> Option<&Code<W>> {
+        if !self.data.matched() {
+            return None;
+        }
+        let auto_jit = self
+            .auto_jit
+            .as_ref()
+            .and_then(|auto_jit| auto_jit.code.get());
+        [Some(&*self.code), auto_jit]
+            .into_iter()
+            .flatten()
+            .find(|&code| self.data.searched_with(code))
+    }
+
+    /// Overwrite these locations with the match found by a search with
+    /// `other`, where group `i > 0` here is group `base + i` there.
+    pub(crate) fn copy_groups_from(&mut self, other: &CaptureLocations<W>, base: usize) {
+        let src = other.data.ovector();
+        let dst = self.data.ovector_mut();
+        let len = dst.len();
+        dst[..2].copy_from_slice(&src[..2]);
+        dst[2..].copy_from_slice(&src[2 * (base + 1)..2 * base + len]);
+    }
+}
+
+/// DfaWorkspace holds the scratch space used by PCRE2's DFA matching
+/// algorithm.
+///
+/// Primarily, this type is useful when using `Regex` APIs such as `dfa_read`,
+/// which permit choosing the size of the workspace and restarting a search
+/// that ended in a partial match. Searches such as `dfa_find` use a
+/// workspace of the default size that is kept for each thread instead.
+///
+/// In order to build a value of this type, you'll need to call the
+/// `dfa_workspace` method on the `Regex` being used to execute the search.
+/// The value returned can then be reused in subsequent searches.
+pub struct DfaWorkspace<W: CodeUnitWidth> {
+    code: Arc<Code<W>>,
+    data: MatchData<W>,
+    workspace: Vec<c_int>,
+    restartable: bool,
+}
+
+impl<W: CodeUnitWidth> Clone for DfaWorkspace<W> {
+    fn clone(&self) -> Self {
+        let mut data = MatchData::new(self.data.config().clone(), &self.code);
+        if let Some(callout) = self.data.callout() {
+            data.set_callout(Arc::clone(callout));
+        }
+        DfaWorkspace {
+            code: Arc::clone(&self.code),
+            data,
+            workspace: self.workspace.clone(),
+            restartable: self.restartable,
+        }
+    }
+}
+
+impl<W: CodeUnitWidth> fmt::Debug for DfaWorkspace<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DfaWorkspace({})", self.size())
+    }
+}
+
+impl<W: CodeUnitWidth> DfaWorkspace<W> {
+    /// Returns the number of integers that this workspace has room for.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.workspace.len()
+    }
+
+    /// Returns true if and only if the most recent search using this
+    /// workspace ended in a partial match that can be continued with
+    /// `Regex::dfa_restart`.
+    #[inline]
+    pub fn is_restartable(&self) -> bool {
+        self.restartable
+    }
+}
+
+/// The names of the capturing groups of a regex, which are only built from
+/// PCRE2's name table when they're first needed.
+///
+/// Many regexes are never asked about their group names, so this avoids
+/// allocating for every group on every compilation.
+struct CaptureNames<W: CodeUnitWidth> {
+    code: Arc<Code<W>>,
+    /// The name of every capturing group, if it has one.
+    names: OnceLock<Vec<Option<String>>>,
+    /// A map from capture group name to the index of the first capture group
+    /// with that name.
+    idx: OnceLock<HashMap<String, usize>>,
+    /// A map from each name shared by more than one capture group, which
+    /// requires `PCRE2_DUPNAMES`, to the indices of those groups in
+    /// ascending order.
+    dup_idx: OnceLock<HashMap<String, Vec<usize>>>,
+}
+
+impl<W: CodeUnitWidth> CaptureNames<W> {
+    fn new(code: Arc<Code<W>>) -> CaptureNames<W> {
+        CaptureNames {
+            code,
+            names: OnceLock::new(),
+            idx: OnceLock::new(),
+            dup_idx: OnceLock::new(),
+        }
+    }
+
+    fn names(&self) -> &[Option<String>] {
+        self.names.get_or_init(|| {
+            self.code
+                .capture_names()
+                .expect("valid capture names from PCRE2")
+        })
+    }
+
+    fn idx(&self) -> &HashMap<String, usize> {
+        self.idx.get_or_init(|| {
+            let mut idx = HashMap::new();
+            // Entries with the same name are sorted by group index.
+            for (i, name) in self
+                .code
+                .name_table()
+                .expect("a valid name table from PCRE2")
+            {
+                idx.entry(W::name_to_string(name)).or_insert(i);
+            }
+            idx
+        })
+    }
+
+    fn dup_idx(&self) -> &HashMap<String, Vec<usize>> {
+        self.dup_idx.get_or_init(|| {
+            let mut idx: HashMap<String, Vec<usize>> = HashMap::new();
+            for (i, name) in self
+                .code
+                .name_table()
+                .expect("a valid name table from PCRE2")
+            {
+                idx.entry(W::name_to_string(name)).or_default().push(i);
+            }
+            idx.retain(|_, indices| indices.len() > 1);
+            idx
+        })
+    }
+}
+
+/// Captures represents a group of captured byte strings for a single match.
+///
+/// The 0th capture always corresponds to the entire match. Each subsequent
+/// index corresponds to the next capture group in the regex. If a capture
+/// group is named, then the matched byte string is *also* available via the
+/// `name` method. (Note that the 0th capture is always unnamed and so must be
+/// accessed with the `get` method.)
+///
+/// Positions returned from a capture group are always byte indices.
+///
+/// `'s` is the lifetime of the matched subject string.
+pub struct Captures<'s, W: CodeUnitWidth> {
+    subject: &'s [W::SubjectChar],
+    locs: CaptureLocations<W>,
+    names: Arc<CaptureNames<W>>,
+}
+
+impl<'s, W: CodeUnitWidth> Captures<'s, W> {
+    /// Returns the match associated with the capture group at index `i`. If
+    /// `i` does not correspond to a capture group, or if the capture group
+    /// did not participate in the match, then `None` is returned.
+    pub fn get(&self, i: usize) -> Option<Match<'s, W>> {
+        self.locs
+            .get(i)
+            .map(|(s, e)| Match::new(self.subject, s, e))
+    }
+
+    /// Returns the match for the capture group named `name`. If `name` isn't a
+    /// valid capture group or didn't match anything, then `None` is returned.
+    ///
+    /// If the regex was built with `RegexBuilder::dupnames` and more than one
+    /// group is named `name`, then the match of the lowest numbered group
+    /// that participated in the match is returned.
+    pub fn name(&self, name: &str) -> Option<Match<'s, W>> {
+        if let Some(indices) = self.names.dup_idx().get(name) {
+            return indices.iter().find_map(|&i| self.get(i));
+        }
+        self.names.idx().get(name).and_then(|&i| self.get(i))
+    }
+
+    /// Returns the number of captured groups.
+    ///
+    /// This is always at least `1`, since every regex has at least one capture
+    /// group that corresponds to the full match.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.locs.len()
+    }
+
+    /// Expands all instances of `$name` in `replacement` to the corresponding
+    /// capture group `name`, and writes them to the `dst` buffer given.
+    ///
+    /// `name` may be an integer corresponding to the index of the capture
+    /// group or the name of a named capture group. If `name` isn't a valid
+    /// capture group or didn't participate in the match, then it is replaced
+    /// with the empty string. The longest possible name consisting of
+    /// `[_0-9A-Za-z]` is used; `${name}` can be used to delimit the name
+    /// explicitly. To write a literal `$`, use `$$`.
+    ///
+    /// For example, `$1a` refers to the capture group named `1a` rather than
+    /// to the group at index `1` followed by `a`. Use `${1}a` for the latter.
+    ///
+    /// This uses the same template syntax as the `regex` crate, which differs
+    /// from the syntax that `pcre2_substitute` understands. Templates passed
+    /// to `Regex::replace` and friends use PCRE2's syntax instead.
+    pub fn expand(&self, replacement: &[W::SubjectChar], dst: &mut Vec<W::SubjectChar>) {
+        let is_name_char = |c: u32| {
+            c == u32::from(b'_') || char::from_u32(c).is_some_and(|c| c.is_ascii_alphanumeric())
+        };
+
+        let mut rest = replacement;
+        while let Some(i) = rest.iter().position(|&c| c.into() == u32::from(b'$')) {
+            let dollar = rest[i];
+            dst.extend_from_slice(&rest[..i]);
+            rest = &rest[i + 1..];
+            match rest.first().map(|&c| c.into()) {
+                Some(c) if c == u32::from(b'$') => {
+                    dst.push(dollar);
+                    rest = &rest[1..];
+                    continue;
+                }
+                Some(c) if c == u32::from(b'{') => {
+                    let close = rest.iter().position(|&c| c.into() == u32::from(b'}'));
+                    if let Some(close) = close {
+                        self.expand_group(&rest[1..close], dst);
+                        rest = &rest[close + 1..];
+                        continue;
+                    }
+                }
+                Some(c) if is_name_char(c) => {
+                    let len = rest
+                        .iter()
+                        .position(|&c| !is_name_char(c.into()))
+                        .unwrap_or(rest.len());
+                    self.expand_group(&rest[..len], dst);
+                    rest = &rest[len..];
+                    continue;
+                }
+                _ => {}
+            }
+            // Not a valid group reference, so write the `$` literally.
+            dst.push(dollar);
+        }
+        dst.extend_from_slice(rest);
+    }
+
+    /// Expands `template` with `pcre2_substitute`, exactly as `Regex::replace`
+    /// and friends do, and appends the result to `dst`. When `extended` is
+    /// true, PCRE2's extended substitution syntax is used.
+    ///
+    /// Since there is no way to report errors here, nothing is appended if
+    /// PCRE2 rejects the template, or if these captures weren't found by a
+    /// search (for example, when they were taken from a `RegexSet` match).
+    fn substitute(
+        &self,
+        template: &[W::SubjectChar],
+        extended: bool,
+        dst: &mut Vec<W::SubjectChar>,
+    ) {
+        let code = match self.locs.matched_code() {
+            None => return,
+            Some(code) => code,
+        };
+        let mut options = PCRE2_SUBSTITUTE_REPLACEMENT_ONLY;
+        if extended {
+            options |= PCRE2_SUBSTITUTE_EXTENDED;
+        }
+        // SAFETY: None of the options disable any safety checks, and the
+        // match was found with `code` in `self.subject`. Errors are ignored,
+        // since nothing is written to `dst` unless the expansion succeeds.
+        let _ = unsafe {
+            self.locs
+                .data
+                .substitute_matched(code, self.subject, options, template, dst)
+        };
+    }
+
+    /// Writes the text of the capture group referred to by `name`, which is
+    /// either an index or a group name, to `dst`.
+    fn expand_group(&self, name: &[W::SubjectChar], dst: &mut Vec<W::SubjectChar>) {
+        let name: String = name
+            .iter()
+            .map(|&c| char::from_u32(c.into()).unwrap_or('\u{FFFD}'))
+            .collect();
+        let m = match name.parse::<usize>() {
+            Ok(i) => self.get(i),
+            Err(_) => self.name(&name),
+        };
+        if let Some(m) = m {
+            dst.extend_from_slice(m.as_bytes());
+        }
+    }
+}
+
+impl<'s, W: CodeUnitWidth> fmt::Debug for Captures<'s, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Captures")
+            .field(&CapturesDebug(self))
+            .finish()
+    }
+}
+
+struct CapturesDebug<'c, 's: 'c, W: CodeUnitWidth>(&'c Captures<'s, W>);
+
+impl<'c, 's, W: CodeUnitWidth> fmt::Debug for CapturesDebug<'c, 's, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.0.names.names();
+        let mut map = f.debug_map();
+        for slot in 0..self.0.len() {
+            let m = self
+                .0
+                .locs
+                .get(slot)
+                .map(|(s, e)| W::escape_subject(&self.0.subject[s..e]));
+            if let Some(Some(name)) = names.get(slot) {
+                map.entry(&name, &m);
+            } else {
+                map.entry(&slot, &m);
+            }
+        }
+        map.finish()
+    }
+}
+
+/// Get a group by index.
 ///
-/// Primarily, this type is useful when using `Regex` APIs such as
-/// `captures_read`, which permits amortizing the allocation in which capture
-/// match locations are stored.
+/// `'s` is the lifetime of the matched subject string.
 ///
-/// In order to build a value of this type, you'll need to call the
-/// `capture_locations` method on the `Regex` being used to execute the search.
-/// The value returned can then be reused in subsequent searches.
-pub struct CaptureLocations<W: CodeUnitWidth> {
-    code: Arc<Code<W>>,
-    data: MatchData<W>,
+/// The subject can't outlive the `Captures` object if this method is
+/// used, because of how `Index` is defined (normally `a[i]` is part
+/// of `a` and can't outlive it); to do that, use `get()` instead.
+///
+/// # Panics
+///
+/// If there is no group at the given index.
+impl<'s, W: CodeUnitWidth> Index<usize> for Captures<'s, W> {
+    type Output = [W::SubjectChar];
+
+    fn index(&self, i: usize) -> &Self::Output {
+        self.get(i)
+            .map(|m| m.as_bytes())
+            .unwrap_or_else(|| panic!("no group at index '{}'", i))
+    }
 }
 
-impl<W: CodeUnitWidth> Clone for CaptureLocations<W> {
-    fn clone(&self) -> Self {
-        CaptureLocations {
-            code: Arc::clone(&self.code),
-            data: MatchData::new(self.data.config().clone(), &self.code),
+/// Get a group by name.
+///
+/// `'s` is the lifetime of the matched subject string and `'i` is the lifetime
+/// of the group name (the index).
+///
+/// The text can't outlive the `Captures` object if this method is
+/// used, because of how `Index` is defined (normally `a[i]` is part
+/// of `a` and can't outlive it); to do that, use `name` instead.
+///
+/// # Panics
+///
+/// If there is no group named by the given value.
+impl<'s, 'i, W: CodeUnitWidth> Index<&'i str> for Captures<'s, W> {
+    type Output = [W::SubjectChar];
+
+    fn index<'a>(&'a self, name: &'i str) -> &'a [W::SubjectChar] {
+        self.name(name)
+            .map(|m| m.as_bytes())
+            .unwrap_or_else(|| panic!("no group named '{}'", name))
+    }
+}
+
+/// An iterator over all non-overlapping matches for a particular subject
+/// string.
+///
+/// The iterator yields matches (if no error occurred while searching)
+/// corresponding to the start and end of the match. The indices are byte
+/// offsets. The iterator stops when no more matches can be found, or after
+/// yielding an error.
+///
+/// `'r` is the lifetime of the compiled regular expression and `'s` is the
+/// lifetime of the subject string.
+pub struct Matches<'r, 's, W: CodeUnitWidth> {
+    re: &'r Regex<W>,
+    match_data: &'r RefCell<MatchData<W>>,
+    subject: &'s [W::SubjectChar],
+    opts: MatchOptions,
+    pos: SearchPosition,
+}
+
+impl<'r, 's, W: CodeUnitWidth> Iterator for Matches<'r, 's, W> {
+    type Item = Result<Match<'s, W>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (re, match_data, subject, opts) = (self.re, self.match_data, self.subject, &self.opts);
+        self.pos.next(re, subject, |start, options| {
+            re.find_at_with_match_data(match_data, subject, start, opts, options)
+        })
+    }
+}
+
+/// An iterator over all matches for a particular subject string, including
+/// overlapping ones.
+///
+/// `'r` is the lifetime of the compiled regular expression and `'s` is the
+/// lifetime of the subject string.
+pub struct OverlappingMatches<'r, 's, W: CodeUnitWidth> {
+    re: &'r Regex<W>,
+    match_data: &'r RefCell<MatchData<W>>,
+    subject: &'s [W::SubjectChar],
+    /// Where the next search starts, or `None` once the iterator is done.
+    start: Option<usize>,
+}
+
+impl<'r, 's, W: CodeUnitWidth> Iterator for OverlappingMatches<'r, 's, W> {
+    type Item = Result<Match<'s, W>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.start.take()?;
+        let opts = MatchOptions::default();
+        match self
+            .re
+            .find_at_with_match_data(self.match_data, self.subject, start, &opts, 0)
+        {
+            Err(err) => Some(Err(err)),
+            Ok(None) => None,
+            Ok(Some(m)) => {
+                let next = next_char(self.re, self.subject, m.start());
+                if next <= self.subject.len() {
+                    self.start = Some(next);
+                }
+                Some(Ok(m))
+            }
         }
     }
 }
 
-impl<W: CodeUnitWidth> fmt::Debug for CaptureLocations<W> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut offsets: Vec<Option<usize>> = vec![];
-        for &offset in self.data.ovector() {
-            if offset == PCRE2_UNSET {
-                offsets.push(None);
+/// An iterator that yields all non-overlapping capture groups matching a
+/// particular regular expression.
+///
+/// The iterator stops when no more matches can be found, or after yielding
+/// an error.
+///
+/// `'r` is the lifetime of the compiled regular expression and `'s` is the
+/// lifetime of the subject string.
+pub struct CaptureMatches<'r, 's, W: CodeUnitWidth> {
+    re: &'r Regex<W>,
+    subject: &'s [W::SubjectChar],
+    opts: MatchOptions,
+    pos: SearchPosition,
+}
+
+impl<'r, 's, W: CodeUnitWidth> Iterator for CaptureMatches<'r, 's, W> {
+    type Item = Result<Captures<'s, W>, Error>;
+
+    fn next(&mut self) -> Option<Result<Captures<'s, W>, Error>> {
+        let (re, subject, opts) = (self.re, self.subject, &self.opts);
+        let mut locs = re.capture_locations();
+        match self.pos.next(re, subject, |start, options| {
+            re.captures_read_at_impl(&mut locs, subject, start, opts, options)
+        })? {
+            Err(err) => Some(Err(err)),
+            Ok(_) => Some(Ok(Captures {
+                subject,
+                locs,
+                names: Arc::clone(&re.capture_names),
+            })),
+        }
+    }
+}
+
+/// The position of an iterator over successive non-overlapping matches,
+/// which takes care of handling empty matches according to the
+/// `EmptyMatchPolicy` of the regex being used.
+#[derive(Clone, Debug)]
+pub(crate) struct SearchPosition {
+    /// The offset at which the next search starts.
+    start: usize,
+    /// The end of the previous match, if there was one.
+    last_match: Option<usize>,
+    /// Whether the previous match was empty and a non-empty match should
+    /// be attempted at the same position before moving on.
+    retry_empty: bool,
+    /// Set once a search failed, after which no more matches are found.
+    failed: bool,
+}
+
+impl SearchPosition {
+    pub(crate) fn new() -> SearchPosition {
+        SearchPosition {
+            start: 0,
+            last_match: None,
+            retry_empty: false,
+            failed: false,
+        }
+    }
+
+    /// Returns the offset at which the next search starts.
+    pub(crate) fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Adjust this position after the first `n` code units of the subject
+    /// were removed. `n` must not exceed the current starting offset.
+    pub(crate) fn shift(&mut self, n: usize) {
+        debug_assert!(n <= self.start);
+        self.start -= n;
+        // A match that ended before the removed prefix can't be followed by
+        // any match that we'll find from now on.
+        self.last_match = self.last_match.and_then(|end| end.checked_sub(n));
+    }
+
+    /// Start the next search at `at` if that's past the current starting
+    /// offset. This must only be used to skip positions at which no match
+    /// can start.
+    pub(crate) fn skip_to(&mut self, at: usize) {
+        self.start = cmp::max(self.start, at);
+    }
+
+    /// Find the next match of `re` using `search`, which is called with the
+    /// offset to start searching at and any additional PCRE2 match options.
+    ///
+    /// Once `search` returns an error, no more matches are found.
+    fn next<'s, W, F>(
+        &mut self,
+        re: &Regex<W>,
+        subject: &'s [W::SubjectChar],
+        mut search: F,
+    ) -> Option<Result<Match<'s, W>, Error>>
+    where
+        W: CodeUnitWidth,
+        F: FnMut(usize, u32) -> Result<Option<Match<'s, W>>, Error>,
+    {
+        let found = self.next_partial(re, subject, |start, options| {
+            Ok(search(start, options)?.map(PartialMatch::Complete))
+        })?;
+        Some(found.map(PartialMatch::into_match))
+    }
+
+    /// Like `next`, but `search` may also report partial matches. When a
+    /// partial match is found, it is returned and the next search starts at
+    /// the beginning of the partial match, so that it can be resumed once
+    /// the subject has been extended.
+    fn next_partial<'s, W, F>(
+        &mut self,
+        re: &Regex<W>,
+        subject: &'s [W::SubjectChar],
+        mut search: F,
+    ) -> Option<Result<PartialMatch<'s, W>, Error>>
+    where
+        W: CodeUnitWidth,
+        F: FnMut(usize, u32) -> Result<Option<PartialMatch<'s, W>>, Error>,
+    {
+        loop {
+            if self.failed || self.start > subject.len() {
+                return None;
+            }
+            let options = if self.retry_empty {
+                PCRE2_NOTEMPTY_ATSTART | PCRE2_ANCHORED
             } else {
-                offsets.push(Some(offset));
+                0
+            };
+            let m = match search(self.start, options) {
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+                Ok(None) if self.retry_empty => {
+                    // There's no non-empty match at the position of the
+                    // previous empty match, so move past it and search as
+                    // usual.
+                    self.retry_empty = false;
+                    self.start = next_char_start(re, subject, self.start);
+                    continue;
+                }
+                Ok(None) => return None,
+                Ok(Some(PartialMatch::Partial(m))) => {
+                    self.start = m.start();
+                    return Some(Ok(PartialMatch::Partial(m)));
+                }
+                Ok(Some(PartialMatch::Complete(m))) => m,
+            };
+            match re.config.empty_match_policy {
+                EmptyMatchPolicy::RegexCompatible => {
+                    if m.start() == m.end() {
+                        // This is an empty match. To ensure we make progress,
+                        // start the next search at the smallest possible
+                        // starting position of the next match following
+                        // this one. In UTF mode, that's the start of the
+                        // next character, since a search must never start
+                        // in the middle of one.
+                        self.start = next_char(re, subject, m.end());
+                        // Don't accept empty matches immediately following a
+                        // match. Just move on to the next match.
+                        if Some(m.end()) == self.last_match {
+                            continue;
+                        }
+                    } else {
+                        self.start = m.end();
+                    }
+                    self.last_match = Some(m.end());
+                }
+                EmptyMatchPolicy::Pcre2Compatible => {
+                    self.start = m.end();
+                    self.retry_empty = m.start() == m.end();
+                }
             }
+            return Some(Ok(PartialMatch::Complete(m)));
         }
-        write!(f, "CaptureLocations(")?;
-        f.debug_list().entries(offsets).finish()?;
-        write!(f, ")")
     }
-}
+}
+
+/// Returns the offset of the character following the one at `at`, taking
+/// CRLF line terminators and UTF encoded codepoints into account in the same
+/// way as `pcre2test`.
+///
+/// The newline convention and UTF mode are those of the compiled regex, so
+/// that settings made in the pattern itself, such as `(*CRLF)` or `(*UTF)`,
+/// are taken into account.
+fn next_char_start<W: CodeUnitWidth>(
+    re: &Regex<W>,
+    subject: &[W::SubjectChar],
+    at: usize,
+) -> usize {
+    let unit = |i: usize| subject.get(i).map(|&c| c.into());
+    let crlf = matches!(
+        re.newline(),
+        Some(Newline::CrLf | Newline::Any | Newline::AnyCrLf)
+    );
+    if crlf && unit(at) == Some(u32::from(b'\r')) && unit(at + 1) == Some(u32::from(b'\n')) {
+        at + 2
+    } else {
+        next_char(re, subject, at)
+    }
+}
+
+/// Returns the offset of the character following the one at `at`. This is
+/// the next code unit, unless the regex is in UTF mode and the character at
+/// `at` is encoded with more than one code unit.
+fn next_char<W: CodeUnitWidth>(re: &Regex<W>, subject: &[W::SubjectChar], at: usize) -> usize {
+    let mut next = at + 1;
+    if re.is_utf() {
+        while subject
+            .get(next)
+            .is_some_and(|&c| W::is_utf_continuation(c))
+        {
+            next += 1;
+        }
+    }
+    next
+}
+
+/// An iterator over the fields of a subject separated by matches of a
+/// particular regular expression, where each field is paired with the match
+/// that ended it.
+///
+/// The last field is paired with `None`.
+///
+/// `'r` is the lifetime of the compiled regular expression and `'s` is the
+/// lifetime of the subject string.
+pub struct SplitInclusive<'r, 's, W: CodeUnitWidth> {
+    finder: Matches<'r, 's, W>,
+    last: usize,
+}
+
+impl<'r, 's, W: CodeUnitWidth> Iterator for SplitInclusive<'r, 's, W> {
+    type Item = Result<(&'s [W::SubjectChar], Option<Match<'s, W>>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let subject = self.finder.subject;
+        if self.last > subject.len() {
+            return None;
+        }
+        match self.finder.next() {
+            None => {
+                let field = &subject[self.last..];
+                self.last = subject.len() + 1;
+                Some(Ok((field, None)))
+            }
+            Some(Err(err)) => {
+                self.last = subject.len() + 1;
+                Some(Err(err))
+            }
+            Some(Ok(m)) => {
+                let field = &subject[self.last..m.start()];
+                self.last = m.end();
+                Some(Ok((field, Some(m))))
+            }
+        }
+    }
+}
+
+/// An iterator over the fields of a subject separated by matches of a
+/// particular regular expression, where each field is paired with the capture
+/// groups of the match that ended it.
+///
+/// The last field is paired with `None`.
+///
+/// `'r` is the lifetime of the compiled regular expression and `'s` is the
+/// lifetime of the subject string.
+pub struct SplitCaptures<'r, 's, W: CodeUnitWidth> {
+    finder: CaptureMatches<'r, 's, W>,
+    last: usize,
+}
+
+impl<'r, 's, W: CodeUnitWidth> Iterator for SplitCaptures<'r, 's, W> {
+    type Item = Result<(&'s [W::SubjectChar], Option<Captures<'s, W>>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let subject = self.finder.subject;
+        if self.last > subject.len() {
+            return None;
+        }
+        match self.finder.next() {
+            None => {
+                let field = &subject[self.last..];
+                self.last = subject.len() + 1;
+                Some(Ok((field, None)))
+            }
+            Some(Err(err)) => {
+                self.last = subject.len() + 1;
+                Some(Err(err))
+            }
+            Some(Ok(caps)) => {
+                let m = caps.get(0).unwrap();
+                let field = &subject[self.last..m.start()];
+                self.last = m.end();
+                Some(Ok((field, Some(caps))))
+            }
+        }
+    }
+}
+
+/// Options that apply to a single search, rather than being fixed when the
+/// regex is compiled.
+///
+/// These are accepted by the `*_with_options` variants of the search
+/// routines and iterators on `Regex`, such as `Regex::find_at_with_options`
+/// and `Regex::find_iter_with_options`. All options are disabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct MatchOptions {
+    anchored: bool,
+    end_anchored: bool,
+    no_jit: bool,
+    offset_limit: Option<usize>,
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+    match_limit: Option<u32>,
+    depth_limit: Option<u32>,
+    heap_limit: Option<u32>,
+    notbol: bool,
+    noteol: bool,
+    notempty: bool,
+    notempty_atstart: bool,
+}
+
+impl MatchOptions {
+    /// Create new match options with every option disabled.
+    pub fn new() -> MatchOptions {
+        MatchOptions::default()
+    }
+
+    /// Only attempt a match at the starting offset of the search, as if the
+    /// pattern began with `\G`. If the pattern doesn't match exactly there,
+    /// then no match is reported, without scanning the rest of the subject.
+    ///
+    /// Note that the JIT compiled form of a regex can't be used for anchored
+    /// searches unless the pattern itself is anchored, so PCRE2 falls back
+    /// to its interpreter when this is enabled.
+    ///
+    /// This corresponds to `PCRE2_ANCHORED`.
+    pub fn anchored(&mut self, yes: bool) -> &mut Self {
+        self.anchored = yes;
+        self
+    }
+
+    /// Require the match to end at the end of the subject. See
+    /// `RegexBuilder::end_anchored` for enabling this for every search.
+    ///
+    /// As with `anchored`, PCRE2 falls back to its interpreter for searches
+    /// that enable this, unless the regex was compiled with it.
+    ///
+    /// This corresponds to `PCRE2_ENDANCHORED`.
+    pub fn end_anchored(&mut self, yes: bool) -> &mut Self {
+        self.end_anchored = yes;
+        self
+    }
+
+    /// Use PCRE2's interpreter for this search even if the regex was JIT
+    /// compiled.
+    ///
+    /// This corresponds to `PCRE2_NO_JIT`.
+    pub fn no_jit(&mut self, yes: bool) -> &mut Self {
+        self.no_jit = yes;
+        self
+    }
+
+    /// Set the largest offset at which a match may start, or `None` for no
+    /// limit. Only the start of a match is restricted: a match that starts at
+    /// or before the limit may extend past it. To confine matches to a range
+    /// of the subject, use `Regex::find_in` instead.
+    ///
+    /// Searching with a limit requires the regex to have been built with
+    /// `RegexBuilder::use_offset_limit` enabled. Otherwise, an error is
+    /// returned.
+    ///
+    /// This corresponds to `pcre2_set_offset_limit`.
+    pub fn offset_limit(&mut self, limit: Option<usize>) -> &mut Self {
+        self.offset_limit = limit;
+        self
+    }
+
+    /// Set a deadline for this search, or `None` for no deadline. If the
+    /// search is still running when the deadline passes, then it fails with
+    /// an error whose kind is `ErrorKind::TimedOut`.
+    ///
+    /// Searching with a deadline requires the regex to have been built with
+    /// `RegexBuilder::interruptible` enabled. Otherwise, an error is
+    /// returned. The deadline is checked periodically while matching, so a
+    /// search may run slightly past it.
+    pub fn deadline(&mut self, deadline: Option<Instant>) -> &mut Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Set a token that cancels this search when `CancellationToken::cancel`
+    /// is called on it, or `None` for no token. A cancelled search fails with
+    /// an error whose kind is `ErrorKind::Cancelled`.
+    ///
+    /// As with `deadline`, this requires the regex to have been built with
+    /// `RegexBuilder::interruptible` enabled. Otherwise, an error is
+    /// returned.
+    pub fn cancellation(&mut self, token: Option<CancellationToken>) -> &mut Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Set the match limit for this search, or `None` to use the one the
+    /// regex was built with. See `RegexBuilder::match_limit` for details.
+    ///
+    /// Together with `depth_limit` and `heap_limit`, this makes it possible
+    /// to search untrusted input with strict limits and trusted input with
+    /// relaxed ones using the same regex. Note that a pattern that sets its
+    /// own limit, e.g. with `(*LIMIT_MATCH=d)`, can't be relaxed this way.
+    ///
+    /// This corresponds to `pcre2_set_match_limit`.
+    pub fn match_limit(&mut self, limit: Option<u32>) -> &mut Self {
+        self.match_limit = limit;
+        self
+    }
+
+    /// Set the depth limit for this search, or `None` to use the one the
+    /// regex was built with. See `RegexBuilder::depth_limit` for details.
+    ///
+    /// This corresponds to `pcre2_set_depth_limit`.
+    pub fn depth_limit(&mut self, limit: Option<u32>) -> &mut Self {
+        self.depth_limit = limit;
+        self
+    }
+
+    /// Set the heap limit, in kibibytes, for this search, or `None` to use
+    /// the one the regex was built with. See `RegexBuilder::heap_limit` for
+    /// details.
+    ///
+    /// This corresponds to `pcre2_set_heap_limit`.
+    pub fn heap_limit(&mut self, limit: Option<u32>) -> &mut Self {
+        self.heap_limit = limit;
+        self
+    }
+
+    /// Treat the start of the subject as not being the beginning of a line,
+    /// so that `^` can't match there (although in multi-line mode, it can
+    /// still match after a newline). This doesn't affect `\A`.
+    ///
+    /// This is useful when searching a slice cut out of a larger text.
+    ///
+    /// This corresponds to `PCRE2_NOTBOL`.
+    pub fn notbol(&mut self, yes: bool) -> &mut Self {
+        self.notbol = yes;
+        self
+    }
 
-impl<W: CodeUnitWidth> CaptureLocations<W> {
-    /// Returns the start and end positions of the Nth capture group.
+    /// Treat the end of the subject as not being the end of a line, so that
+    /// `$` can't match there (although in multi-line mode, it can still
+    /// match before a newline). This doesn't affect `\z` or `\Z`.
     ///
-    /// This returns `None` if `i` is not a valid capture group or if the
-    /// capture group did not match anything.
+    /// This corresponds to `PCRE2_NOTEOL`.
+    pub fn noteol(&mut self, yes: bool) -> &mut Self {
+        self.noteol = yes;
+        self
+    }
+
+    /// Don't accept empty matches. If the pattern could match the empty
+    /// string, then the search carries on looking for a non-empty match.
     ///
-    /// The positions returned are always byte indices with respect to the
-    /// original subject string matched.
-    #[inline]
-    pub fn get(&self, i: usize) -> Option<(usize, usize)> {
-        let ovec = self.data.ovector();
-        let s = match ovec.get(i * 2) {
-            None => return None,
-            Some(&s) if s == PCRE2_UNSET => return None,
-            Some(&s) => s,
-        };
-        let e = match ovec.get(i * 2 + 1) {
-            None => return None,
-            Some(&e) if e == PCRE2_UNSET => return None,
-            Some(&e) => e,
-        };
-        Some((s, e))
+    /// This corresponds to `PCRE2_NOTEMPTY`.
+    pub fn notempty(&mut self, yes: bool) -> &mut Self {
+        self.notempty = yes;
+        self
     }
 
-    /// Returns the total number of capturing groups.
+    /// Don't accept an empty match at the starting offset of the search,
+    /// but do accept empty matches elsewhere.
     ///
-    /// This is always at least `1` since every regex has at least `1`
-    /// capturing group that corresponds to the entire match.
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.data.ovector().len() / 2
+    /// This corresponds to `PCRE2_NOTEMPTY_ATSTART`.
+    pub fn notempty_atstart(&mut self, yes: bool) -> &mut Self {
+        self.notempty_atstart = yes;
+        self
+    }
+
+    /// Returns true if these options override any of the limits in the
+    /// match context.
+    fn overrides_limits(&self) -> bool {
+        self.match_limit.is_some() || self.depth_limit.is_some() || self.heap_limit.is_some()
+    }
+
+    /// Returns these options as flags suitable for `pcre2_match`.
+    fn to_raw(&self) -> u32 {
+        let mut options = 0;
+        if self.anchored {
+            options |= PCRE2_ANCHORED;
+        }
+        if self.end_anchored {
+            options |= PCRE2_ENDANCHORED;
+        }
+        if self.no_jit {
+            options |= PCRE2_NO_JIT;
+        }
+        if self.notbol {
+            options |= PCRE2_NOTBOL;
+        }
+        if self.noteol {
+            options |= PCRE2_NOTEOL;
+        }
+        if self.notempty {
+            options |= PCRE2_NOTEMPTY;
+        }
+        if self.notempty_atstart {
+            options |= PCRE2_NOTEMPTY_ATSTART;
+        }
+        options
     }
 }
 
-/// Captures represents a group of captured byte strings for a single match.
+/// A handle for cancelling searches from another thread.
 ///
-/// The 0th capture always corresponds to the entire match. Each subsequent
-/// index corresponds to the next capture group in the regex. If a capture
-/// group is named, then the matched byte string is *also* available via the
-/// `name` method. (Note that the 0th capture is always unnamed and so must be
-/// accessed with the `get` method.)
+/// Give a clone of the token to searches with `MatchOptions::cancellation`,
+/// and call `cancel` to make every search using it fail with an error whose
+/// kind is `ErrorKind::Cancelled`, e.g. when the client that requested a
+/// search disconnects. Cancelling is cooperative: searches check the token
+/// while matching, which requires the regex to have been built with
+/// `RegexBuilder::interruptible` enabled.
 ///
-/// Positions returned from a capture group are always byte indices.
+/// Once cancelled, a token stays cancelled, including for searches that
+/// start later.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new token that isn't cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancel every search using this token, including searches running on
+    /// other threads.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if and only if `cancel` was called on this token or on
+    /// one of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Options that control how `Regex::replace_with_options` performs a
+/// substitution.
 ///
-/// `'s` is the lifetime of the matched subject string.
-pub struct Captures<'s, W: CodeUnitWidth> {
-    subject: &'s [W::SubjectChar],
-    locs: CaptureLocations<W>,
-    idx: Arc<HashMap<String, usize>>,
+/// Each option corresponds to one of the `PCRE2_SUBSTITUTE_*` flags accepted
+/// by `pcre2_substitute`. All options are disabled by default, which replaces
+/// only the first match and fails on references to unknown or unset groups.
+#[derive(Clone, Debug, Default)]
+pub struct SubstituteOptions {
+    global: bool,
+    extended: bool,
+    literal: bool,
+    unknown_unset: bool,
+    unset_empty: bool,
+    replacement_only: bool,
 }
 
-impl<'s, W: CodeUnitWidth> Captures<'s, W> {
-    /// Returns the match associated with the capture group at index `i`. If
-    /// `i` does not correspond to a capture group, or if the capture group
-    /// did not participate in the match, then `None` is returned.
-    pub fn get(&self, i: usize) -> Option<Match<'s, W>> {
-        self.locs
-            .get(i)
-            .map(|(s, e)| Match::new(self.subject, s, e))
+impl SubstituteOptions {
+    /// Create new substitution options with every option disabled.
+    pub fn new() -> SubstituteOptions {
+        SubstituteOptions::default()
     }
 
-    /// Returns the match for the capture group named `name`. If `name` isn't a
-    /// valid capture group or didn't match anything, then `None` is returned.
-    pub fn name(&self, name: &str) -> Option<Match<'s, W>> {
-        self.idx.get(name).and_then(|&i| self.get(i))
+    /// Replace every non-overlapping match instead of only the first one.
+    ///
+    /// This corresponds to `PCRE2_SUBSTITUTE_GLOBAL`.
+    pub fn global(&mut self, yes: bool) -> &mut Self {
+        self.global = yes;
+        self
     }
 
-    /// Returns the number of captured groups.
+    /// Interpret the replacement using PCRE2's extended syntax. See
+    /// `Extended` for details.
     ///
-    /// This is always at least `1`, since every regex has at least one capture
-    /// group that corresponds to the full match.
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.locs.len()
+    /// This corresponds to `PCRE2_SUBSTITUTE_EXTENDED`.
+    pub fn extended(&mut self, yes: bool) -> &mut Self {
+        self.extended = yes;
+        self
     }
-}
 
-impl<'s, W: CodeUnitWidth> fmt::Debug for Captures<'s, W> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_tuple("Captures")
-            .field(&CapturesDebug(self))
-            .finish()
+    /// Insert the replacement verbatim, without interpreting `$` or any
+    /// other special characters.
+    ///
+    /// This corresponds to `PCRE2_SUBSTITUTE_LITERAL`.
+    pub fn literal(&mut self, yes: bool) -> &mut Self {
+        self.literal = yes;
+        self
     }
-}
 
-struct CapturesDebug<'c, 's: 'c, W: CodeUnitWidth>(&'c Captures<'s, W>);
+    /// Treat references to capture groups that don't exist in the pattern as
+    /// unset groups instead of reporting an error.
+    ///
+    /// This corresponds to `PCRE2_SUBSTITUTE_UNKNOWN_UNSET`. Note that unset
+    /// groups still cause an error unless `unset_empty` is also enabled.
+    pub fn unknown_unset(&mut self, yes: bool) -> &mut Self {
+        self.unknown_unset = yes;
+        self
+    }
 
-impl<'c, 's, W: CodeUnitWidth> fmt::Debug for CapturesDebug<'c, 's, W> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // We'd like to show something nice here, even if it means an
-        // allocation to build a reverse index.
-        let slot_to_name: HashMap<&usize, &String> =
-            self.0.idx.iter().map(|(a, b)| (b, a)).collect();
-        let mut map = f.debug_map();
-        for slot in 0..self.0.len() {
-            let m = self
-                .0
-                .locs
-                .get(slot)
-                .map(|(s, e)| W::escape_subject(&self.0.subject[s..e]));
-            if let Some(name) = slot_to_name.get(&slot) {
-                map.entry(&name, &m);
-            } else {
-                map.entry(&slot, &m);
-            }
+    /// Expand references to unset capture groups (including unknown groups
+    /// when `unknown_unset` is enabled) to the empty string instead of
+    /// reporting an error.
+    ///
+    /// This corresponds to `PCRE2_SUBSTITUTE_UNSET_EMPTY`.
+    pub fn unset_empty(&mut self, yes: bool) -> &mut Self {
+        self.unset_empty = yes;
+        self
+    }
+
+    /// Only output the replacements, omitting the parts of the subject that
+    /// weren't matched.
+    ///
+    /// This corresponds to `PCRE2_SUBSTITUTE_REPLACEMENT_ONLY`.
+    pub fn replacement_only(&mut self, yes: bool) -> &mut Self {
+        self.replacement_only = yes;
+        self
+    }
+
+    /// Returns these options as flags suitable for `pcre2_substitute`.
+    fn to_raw(&self) -> u32 {
+        let mut options = 0;
+        if self.global {
+            options |= PCRE2_SUBSTITUTE_GLOBAL;
         }
-        map.finish()
+        if self.extended {
+            options |= PCRE2_SUBSTITUTE_EXTENDED;
+        }
+        if self.literal {
+            options |= PCRE2_SUBSTITUTE_LITERAL;
+        }
+        if self.unknown_unset {
+            options |= PCRE2_SUBSTITUTE_UNKNOWN_UNSET;
+        }
+        if self.unset_empty {
+            options |= PCRE2_SUBSTITUTE_UNSET_EMPTY;
+        }
+        if self.replacement_only {
+            options |= PCRE2_SUBSTITUTE_REPLACEMENT_ONLY;
+        }
+        options
     }
 }
 
-/// Get a group by index.
-///
-/// `'s` is the lifetime of the matched subject string.
-///
-/// The subject can't outlive the `Captures` object if this method is
-/// used, because of how `Index` is defined (normally `a[i]` is part
-/// of `a` and can't outlive it); to do that, use `get()` instead.
+/// Replacer describes types that can be used to replace matches in a subject.
 ///
-/// # Panics
+/// Implementations are provided for replacement templates, such as
+/// `&[W::SubjectChar]` and `Vec<W::SubjectChar>`, which are expanded by PCRE2
+/// itself, and for closures of the form `FnMut(&Captures<W>) -> T` where
+/// `T: AsRef<[W::SubjectChar]>`, which compute each replacement from the
+/// capture groups of the corresponding match. To insert a string verbatim,
+/// wrap it in `NoExpand`.
 ///
-/// If there is no group at the given index.
-impl<'s, W: CodeUnitWidth> Index<usize> for Captures<'s, W> {
-    type Output = [W::SubjectChar];
+/// Calling `replace_append` on a template expands it with `pcre2_substitute`
+/// as well, so templates produce the same text either way. Since errors can't
+/// be reported by `replace_append`, nothing is appended for a template that
+/// PCRE2 rejects.
+pub trait Replacer<W: CodeUnitWidth> {
+    /// Appends text to `dst` to replace the current match.
+    ///
+    /// The current match is represented by `caps`, which is guaranteed to
+    /// have a match at capture group `0`.
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>);
 
-    fn index(&self, i: usize) -> &Self::Output {
-        self.get(i)
-            .map(|m| m.as_bytes())
-            .unwrap_or_else(|| panic!("no group at index '{}'", i))
+    /// Return a replacement template, if this replacer has one.
+    ///
+    /// When a template is returned, replacement is delegated to PCRE2's
+    /// `pcre2_substitute` routine and `replace_append` isn't called. The
+    /// default implementation returns `None`.
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        None
+    }
+
+    /// Return a fixed replacement string, if this replacer always inserts the
+    /// same text verbatim.
+    ///
+    /// When a string is returned, it is used as the replacement for every
+    /// match without any interpretation of `$` or other special characters,
+    /// and neither `template` nor `replace_append` is called. The default
+    /// implementation returns `None`.
+    fn no_expansion(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        None
+    }
+
+    /// Return true if the template returned by `template` uses PCRE2's
+    /// extended substitution syntax.
+    ///
+    /// The default implementation returns `false`.
+    fn extended(&mut self) -> bool {
+        false
+    }
+
+    /// Return a `Replacer` that borrows and wraps this `Replacer`.
+    ///
+    /// This is useful when you want to take a generic `Replacer` (which might
+    /// not be cloneable) and use it without consuming it, so it can be used
+    /// more than once.
+    fn by_ref(&mut self) -> ReplacerRef<'_, Self> {
+        ReplacerRef(self)
     }
 }
 
-/// Get a group by name.
+/// By-reference adaptor for a `Replacer`.
 ///
-/// `'s` is the lifetime of the matched subject string and `'i` is the lifetime
-/// of the group name (the index).
+/// Returned by [`Replacer::by_ref`](trait.Replacer.html#method.by_ref).
+#[derive(Debug)]
+pub struct ReplacerRef<'a, R: ?Sized>(&'a mut R);
+
+impl<'a, W: CodeUnitWidth, R: Replacer<W> + ?Sized + 'a> Replacer<W> for ReplacerRef<'a, R> {
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        self.0.replace_append(caps, dst)
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        self.0.no_expansion()
+    }
+
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        self.0.template()
+    }
+
+    fn extended(&mut self) -> bool {
+        self.0.extended()
+    }
+}
+
+/// A replacement template that uses PCRE2's extended substitution syntax.
 ///
-/// The text can't outlive the `Captures` object if this method is
-/// used, because of how `Index` is defined (normally `a[i]` is part
-/// of `a` and can't outlive it); to do that, use `name` instead.
+/// In addition to the usual `$n` and `${name}` group references, the
+/// extended syntax supports:
 ///
-/// # Panics
+/// * Backslash escapes such as `\n` and `\x{41}`, so a literal backslash
+///   must be written as `\\`.
+/// * Case forcing with `\U` (upper case), `\L` (lower case) and `\E`
+///   (stop forcing case), and `\u` and `\l` to force the case of the next
+///   character only.
+/// * `${n:-default}`, which inserts `default` when group `n` is unset or
+///   empty.
+/// * `${n:+set:unset}`, which inserts `set` when group `n` is set and `unset`
+///   otherwise.
 ///
-/// If there is no group named by the given value.
-impl<'s, 'i, W: CodeUnitWidth> Index<&'i str> for Captures<'s, W> {
-    type Output = [W::SubjectChar];
+/// Calling `Replacer::replace_append` directly expands the template with
+/// PCRE2 as well. When the wrapped replacer has no template, it is used as
+/// is.
+#[derive(Clone, Debug)]
+pub struct Extended<T>(pub T);
 
-    fn index<'a>(&'a self, name: &'i str) -> &'a [W::SubjectChar] {
-        self.name(name)
-            .map(|m| m.as_bytes())
-            .unwrap_or_else(|| panic!("no group named '{}'", name))
+impl<W: CodeUnitWidth, T: Replacer<W>> Replacer<W> for Extended<T> {
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        match self.0.template() {
+            Some(template) => caps.substitute(&template, true, dst),
+            None => self.0.replace_append(caps, dst),
+        }
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        self.0.no_expansion()
+    }
+
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        self.0.template()
+    }
+
+    fn extended(&mut self) -> bool {
+        true
     }
 }
 
-/// An iterator over all non-overlapping matches for a particular subject
-/// string.
-///
-/// The iterator yields matches (if no error occurred while searching)
-/// corresponding to the start and end of the match. The indices are byte
-/// offsets. The iterator stops when no more matches can be found.
+/// A replacement string that is inserted verbatim.
 ///
-/// `'r` is the lifetime of the compiled regular expression and `'s` is the
-/// lifetime of the subject string.
-pub struct Matches<'r, 's, W: CodeUnitWidth> {
-    re: &'r Regex<W>,
-    match_data: &'r RefCell<MatchData<W>>,
-    subject: &'s [W::SubjectChar],
-    last_end: usize,
-    last_match: Option<usize>,
+/// Unlike a replacement template, no `$` group references (or any other
+/// special syntax) are interpreted, which makes this suitable for inserting
+/// arbitrary text, such as user input, in place of each match.
+#[derive(Clone, Debug)]
+pub struct NoExpand<'t, C>(pub &'t [C]);
+
+impl<W: CodeUnitWidth> Replacer<W> for NoExpand<'_, W::SubjectChar> {
+    fn replace_append(&mut self, _: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        dst.extend_from_slice(self.0);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        Some(Cow::Borrowed(self.0))
+    }
 }
 
-impl<'r, 's, W: CodeUnitWidth> Iterator for Matches<'r, 's, W> {
-    type Item = Result<Match<'s, W>, Error>;
+impl<W: CodeUnitWidth> Replacer<W> for &[W::SubjectChar] {
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        caps.substitute(self, false, dst);
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.last_end > self.subject.len() {
-            return None;
-        }
-        let res = self
-            .re
-            .find_at_with_match_data(self.match_data, self.subject, self.last_end);
-        let m = match res {
-            Err(err) => return Some(Err(err)),
-            Ok(None) => return None,
-            Ok(Some(m)) => m,
-        };
-        if m.start() == m.end() {
-            // This is an empty match. To ensure we make progress, start
-            // the next search at the smallest possible starting position
-            // of the next match following this one.
-            self.last_end = m.end() + 1;
-            // Don't accept empty matches immediately following a match.
-            // Just move on to the next match.
-            if Some(m.end()) == self.last_match {
-                return self.next();
-            }
-        } else {
-            self.last_end = m.end();
-        }
-        self.last_match = Some(m.end());
-        Some(Ok(m))
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        Some(Cow::Borrowed(self))
     }
 }
 
-/// An iterator that yields all non-overlapping capture groups matching a
-/// particular regular expression.
-///
-/// The iterator stops when no more matches can be found.
-///
-/// `'r` is the lifetime of the compiled regular expression and `'s` is the
-/// lifetime of the subject string.
-pub struct CaptureMatches<'r, 's, W: CodeUnitWidth> {
-    re: &'r Regex<W>,
-    subject: &'s [W::SubjectChar],
-    last_end: usize,
-    last_match: Option<usize>,
+impl<W: CodeUnitWidth, const N: usize> Replacer<W> for &[W::SubjectChar; N] {
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        caps.substitute(&self[..], false, dst);
+    }
+
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        Some(Cow::Borrowed(&self[..]))
+    }
 }
 
-impl<'r, 's, W: CodeUnitWidth> Iterator for CaptureMatches<'r, 's, W> {
-    type Item = Result<Captures<'s, W>, Error>;
+impl<W: CodeUnitWidth> Replacer<W> for Vec<W::SubjectChar> {
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        caps.substitute(self, false, dst);
+    }
 
-    fn next(&mut self) -> Option<Result<Captures<'s, W>, Error>> {
-        if self.last_end > self.subject.len() {
-            return None;
-        }
-        let mut locs = self.re.capture_locations();
-        let res = self
-            .re
-            .captures_read_at(&mut locs, self.subject, self.last_end);
-        let m = match res {
-            Err(err) => return Some(Err(err)),
-            Ok(None) => return None,
-            Ok(Some(m)) => m,
-        };
-        if m.start() == m.end() {
-            // This is an empty match. To ensure we make progress, start
-            // the next search at the smallest possible starting position
-            // of the next match following this one.
-            self.last_end = m.end() + 1;
-            // Don't accept empty matches immediately following a match.
-            // Just move on to the next match.
-            if Some(m.end()) == self.last_match {
-                return self.next();
-            }
-        } else {
-            self.last_end = m.end();
-        }
-        self.last_match = Some(m.end());
-        Some(Ok(Captures {
-            subject: self.subject,
-            locs: locs,
-            idx: Arc::clone(&self.re.capture_names_idx),
-        }))
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl<W: CodeUnitWidth> Replacer<W> for &Vec<W::SubjectChar> {
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        caps.substitute(self, false, dst);
+    }
+
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl<'a, W: CodeUnitWidth> Replacer<W> for Cow<'a, [W::SubjectChar]> {
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        caps.substitute(self, false, dst);
+    }
+
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl<'a, W: CodeUnitWidth> Replacer<W> for &'a Cow<'a, [W::SubjectChar]> {
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        caps.substitute(self, false, dst);
+    }
+
+    fn template(&mut self) -> Option<Cow<'_, [W::SubjectChar]>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl<W, F, T> Replacer<W> for F
+where
+    W: CodeUnitWidth,
+    F: FnMut(&Captures<'_, W>) -> T,
+    T: AsRef<[W::SubjectChar]>,
+{
+    fn replace_append(&mut self, caps: &Captures<'_, W>, dst: &mut Vec<W::SubjectChar>) {
+        dst.extend_from_slice((*self)(caps).as_ref());
     }
 }
@@ -0,0 +1,1015 @@
+/*!
+This module provides matching of many patterns against a subject at once.
+*/
+
+use std::error;
+use std::fmt;
+
+use pcre2_sys::{
+    PCRE2_CASELESS, PCRE2_DOTALL, PCRE2_EXTENDED, PCRE2_MULTILINE, PCRE2_UCP, PCRE2_UTF,
+};
+
+use crate::error::Error;
+use crate::ffi::CodeUnitWidth;
+use crate::policy::has_subroutine_call;
+use crate::regex_impl::{
+    BsrConvention, CaptureLocations, Captures, CharacterTables, Match, MatchOptions, Newline,
+    Regex, RegexBuilder,
+};
+
+/// A set of compiled PCRE2 regular expressions that are matched against a
+/// subject together.
+///
+/// This is useful when a subject needs to be tested against many patterns,
+/// such as when routing log lines to handlers. The patterns are identified
+/// by their index, in the order in which they were given.
+///
+/// When possible, the patterns are compiled into a single regex that tries
+/// each of them in turn at every position, so that a search makes one pass
+/// over the subject regardless of the number of patterns. Patterns that
+/// can't be combined without changing what they match, such as those that
+/// use backreferences, subroutine calls, `\K` or backtracking control verbs,
+/// are instead searched one at a time. Either way, the results are the same.
+///
+/// This set is safe to use from multiple threads simultaneously, in the
+/// same way as `Regex`.
+pub struct RegexSet<W: CodeUnitWidth> {
+    regexes: Vec<Regex<W>>,
+    /// All patterns combined into one, where each alternative passes a mark
+    /// named after the index of its pattern.
+    combined: Option<Regex<W>>,
+    /// For each pattern, the number of capture groups in the combined regex
+    /// that come before its own, or nothing if there's no combined regex.
+    group_bases: Vec<usize>,
+}
+
+impl<W: CodeUnitWidth> Clone for RegexSet<W> {
+    fn clone(&self) -> Self {
+        RegexSet {
+            regexes: self.regexes.clone(),
+            combined: self.combined.clone(),
+            group_bases: self.group_bases.clone(),
+        }
+    }
+}
+
+impl<W: CodeUnitWidth> fmt::Debug for RegexSet<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RegexSet")
+            .field(&self.patterns().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<W: CodeUnitWidth> RegexSet<W> {
+    /// Compiles each of the given patterns using the default configuration.
+    ///
+    /// If any of the patterns is invalid, then the error for the first such
+    /// pattern is returned.
+    ///
+    /// To configure compilation options for the patterns, use the
+    /// [`RegexSetBuilder`](struct.RegexSetBuilder.html).
+    pub fn new<I, P>(patterns: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<W::Pattern>,
+    {
+        let mut builder = RegexSetBuilder::new();
+        for pattern in patterns {
+            builder.pattern(pattern);
+        }
+        builder.build()
+    }
+
+    /// Returns an empty set, which never matches.
+    pub fn empty() -> Self {
+        RegexSet {
+            regexes: Vec::new(),
+            combined: None,
+            group_bases: Vec::new(),
+        }
+    }
+
+    /// Returns true if and only if any of the patterns in this set match the
+    /// subject string given.
+    ///
+    /// This stops searching as soon as a match is found, which makes it
+    /// cheaper than `matches` when only the existence of a match is of
+    /// interest.
+    pub fn is_match(&self, subject: &[W::SubjectChar]) -> Result<bool, Error> {
+        if let Some(ref combined) = self.combined {
+            return combined.is_match(subject);
+        }
+        for re in &self.regexes {
+            if re.is_match(subject)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns which of the patterns in this set match the subject string
+    /// given.
+    ///
+    /// Every pattern is searched on its own, unless a single search finds
+    /// that none of them match.
+    pub fn matches(&self, subject: &[W::SubjectChar]) -> Result<SetMatches, Error> {
+        if self.combined.is_some() && !self.is_match(subject)? {
+            let matched = vec![false; self.regexes.len()];
+            return Ok(SetMatches { matched });
+        }
+        let matched = self
+            .regexes
+            .iter()
+            .map(|re| re.is_match(subject))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(SetMatches { matched })
+    }
+
+    /// Returns the leftmost match in `subject` across all patterns in this
+    /// set, along with the index of the pattern that matched. If no pattern
+    /// matches, then `None` is returned.
+    ///
+    /// Each pattern finds its leftmost-first match as with `Regex::find`,
+    /// and the one that starts earliest is returned. When several patterns
+    /// match at the same position, the one that comes first in the set wins,
+    /// regardless of the length of its match.
+    pub fn find<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+    ) -> Result<Option<(usize, Match<'s, W>)>, Error> {
+        self.find_at(subject, 0)
+    }
+
+    /// Returns the same as `find`, but starts the search at the given offset.
+    ///
+    /// The significance of the starting point is that it takes the surrounding
+    /// context into consideration. For example, the `\A` anchor can only
+    /// match when `start == 0`.
+    pub fn find_at<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<(usize, Match<'s, W>)>, Error> {
+        self.search(subject, start, &MatchOptions::default())
+    }
+
+    /// Returns the capture groups of the leftmost match in `subject` across
+    /// all patterns in this set, along with the index of the pattern that
+    /// matched. If no pattern matches, then `None` is returned.
+    ///
+    /// The winning match is chosen in the same way as `find`, and its
+    /// capture groups are those of the winning pattern.
+    pub fn captures<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+    ) -> Result<Option<(usize, Captures<'s, W>)>, Error> {
+        self.captures_at(subject, 0)
+    }
+
+    /// Returns the same as `captures`, but starts the search at the given
+    /// offset.
+    ///
+    /// The significance of the starting point is that it takes the surrounding
+    /// context into consideration. For example, the `\A` anchor can only
+    /// match when `start == 0`.
+    pub fn captures_at<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<(usize, Captures<'s, W>)>, Error> {
+        let mut locs = self.capture_locations();
+        let i = match self.captures_read_at(&mut locs, subject, start)? {
+            None => return Ok(None),
+            Some((i, _)) => i,
+        };
+        let caps = self.regexes[i].captures_from_locations(subject, locs.locs.swap_remove(i));
+        Ok(Some((i, caps)))
+    }
+
+    /// This is like `captures_at`, but uses
+    /// [`SetCaptureLocations`](struct.SetCaptureLocations.html)
+    /// instead of `Captures` in order to amortize allocations.
+    ///
+    /// To create a `SetCaptureLocations` value, use the
+    /// `RegexSet::capture_locations` method.
+    ///
+    /// This returns the overall match and the index of the pattern that
+    /// matched, whose capture groups are then available from `locs`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `locs` was created by a set with a different number of
+    /// patterns.
+    pub fn captures_read_at<'s>(
+        &self,
+        locs: &mut SetCaptureLocations<W>,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+    ) -> Result<Option<(usize, Match<'s, W>)>, Error> {
+        assert_eq!(
+            locs.locs.len(),
+            self.regexes.len(),
+            "capture locations were created by a different set"
+        );
+        locs.pattern = None;
+        let best = if let (Some(re), Some(combined)) = (&self.combined, &mut locs.combined) {
+            let best = re.captures_read_marked_at(combined, subject, start)?;
+            // The winning pattern's groups are a contiguous range of the
+            // combined regex's groups.
+            if let Some((i, _)) = best {
+                locs.locs[i].copy_groups_from(combined, self.group_bases[i]);
+            }
+            best
+        } else {
+            self.leftmost(start, |i, re| {
+                re.captures_read_at(&mut locs.locs[i], subject, start)
+            })?
+        };
+        locs.pattern = best.as_ref().map(|&(i, _)| i);
+        Ok(best)
+    }
+
+    /// Returns an iterator that splits `subject` into tokens, where each
+    /// token is matched by one of the patterns in this set.
+    ///
+    /// See [`Lexer`](struct.Lexer.html) for details.
+    pub fn lexer<'r, 's>(&'r self, subject: &'s [W::SubjectChar]) -> Lexer<'r, 's, W> {
+        Lexer {
+            set: self,
+            subject,
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the leftmost match in `subject` across all patterns using the
+    /// given match-time options, along with the index of its pattern.
+    /// Patterns that come first win ties.
+    fn search<'s>(
+        &self,
+        subject: &'s [W::SubjectChar],
+        start: usize,
+        opts: &MatchOptions,
+    ) -> Result<Option<(usize, Match<'s, W>)>, Error> {
+        if let Some(ref combined) = self.combined {
+            return combined.find_marked_at(subject, start, opts);
+        }
+        self.leftmost(start, |_, re| re.find_at_with_options(subject, start, opts))
+    }
+
+    /// Runs the given search for each pattern in order, and returns the
+    /// match that starts earliest along with the index of its pattern.
+    /// Patterns that come first win ties.
+    fn leftmost<'s, F>(
+        &self,
+        start: usize,
+        mut search: F,
+    ) -> Result<Option<(usize, Match<'s, W>)>, Error>
+    where
+        F: FnMut(usize, &Regex<W>) -> Result<Option<Match<'s, W>>, Error>,
+    {
+        let mut best: Option<(usize, Match<'s, W>)> = None;
+        for (i, re) in self.regexes.iter().enumerate() {
+            if let Some(m) = search(i, re)? {
+                if best.as_ref().is_none_or(|(_, b)| m.start() < b.start()) {
+                    best = Some((i, m));
+                }
+            }
+            // No later pattern can win against a match at the start.
+            if best.as_ref().is_some_and(|(_, b)| b.start() == start) {
+                break;
+            }
+        }
+        Ok(best)
+    }
+
+    /// Returns an empty set of capture locations that can be reused in
+    /// multiple calls to `captures_read_at`.
+    pub fn capture_locations(&self) -> SetCaptureLocations<W> {
+        SetCaptureLocations {
+            locs: self.regexes.iter().map(Regex::capture_locations).collect(),
+            combined: self.combined.as_ref().map(Regex::capture_locations),
+            pattern: None,
+        }
+    }
+
+    /// Returns the number of patterns in this set.
+    pub fn len(&self) -> usize {
+        self.regexes.len()
+    }
+
+    /// Returns true if and only if this set has no patterns.
+    pub fn is_empty(&self) -> bool {
+        self.regexes.is_empty()
+    }
+
+    /// Returns the patterns in this set, in the order in which they were
+    /// given.
+    pub fn patterns(&self) -> impl ExactSizeIterator<Item = &W::Pattern> + '_ {
+        self.regexes.iter().map(Regex::as_str)
+    }
+}
+
+/// SetCaptureLocations holds the capture locations of every pattern in a
+/// `RegexSet`, and exposes those of the pattern that won the most recent
+/// search.
+///
+/// In order to build a value of this type, you'll need to call the
+/// `capture_locations` method on the `RegexSet` being used to execute the
+/// search. The value returned can then be reused in subsequent searches.
+pub struct SetCaptureLocations<W: CodeUnitWidth> {
+    locs: Vec<CaptureLocations<W>>,
+    /// The locations for searches with the set's combined regex, if any.
+    combined: Option<CaptureLocations<W>>,
+    pattern: Option<usize>,
+}
+
+impl<W: CodeUnitWidth> Clone for SetCaptureLocations<W> {
+    fn clone(&self) -> Self {
+        SetCaptureLocations {
+            locs: self.locs.clone(),
+            combined: self.combined.clone(),
+            pattern: self.pattern,
+        }
+    }
+}
+
+impl<W: CodeUnitWidth> fmt::Debug for SetCaptureLocations<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SetCaptureLocations")
+            .field("pattern", &self.pattern)
+            .field("locations", &self.pattern.map(|i| &self.locs[i]))
+            .finish()
+    }
+}
+
+impl<W: CodeUnitWidth> SetCaptureLocations<W> {
+    /// Returns the index of the pattern that won the most recent search, or
+    /// `None` if that search didn't find a match.
+    #[inline]
+    pub fn pattern(&self) -> Option<usize> {
+        self.pattern
+    }
+
+    /// Returns the start and end positions of the Nth capture group of the
+    /// pattern that won the most recent search.
+    ///
+    /// This returns `None` if the most recent search didn't find a match, if
+    /// `i` is not a valid capture group of the winning pattern or if the
+    /// capture group did not match anything.
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<(usize, usize)> {
+        self.locs[self.pattern?].get(i)
+    }
+
+    /// Returns the total number of capturing groups of the pattern that won
+    /// the most recent search, or `0` if that search didn't find a match.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pattern.map_or(0, |i| self.locs[i].len())
+    }
+
+    /// Returns true if and only if the most recent search didn't find a
+    /// match.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none()
+    }
+}
+
+/// A builder for configuring the compilation of a `RegexSet`.
+///
+/// Options set on the builder apply to every pattern in the set, while the
+/// options given along with an individual pattern override them for that
+/// pattern only. Patterns are numbered in the order in which they are added.
+#[derive(Debug)]
+pub struct RegexSetBuilder<W: CodeUnitWidth> {
+    builder: RegexBuilder<W>,
+    patterns: Vec<(W::Pattern, PatternOptions)>,
+}
+
+impl<W: CodeUnitWidth> Clone for RegexSetBuilder<W> {
+    fn clone(&self) -> Self {
+        RegexSetBuilder {
+            builder: self.builder.clone(),
+            patterns: self.patterns.clone(),
+        }
+    }
+}
+
+impl<W: CodeUnitWidth> Default for RegexSetBuilder<W> {
+    fn default() -> Self {
+        RegexSetBuilder::new()
+    }
+}
+
+impl<W: CodeUnitWidth> RegexSetBuilder<W> {
+    /// Create a new builder with a default configuration and no patterns.
+    pub fn new() -> Self {
+        RegexSetBuilder {
+            builder: RegexBuilder::new(),
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Add a pattern that is compiled with the options of this builder.
+    pub fn pattern<P: Into<W::Pattern>>(&mut self, pattern: P) -> &mut Self {
+        self.pattern_with_options(pattern, &PatternOptions::new())
+    }
+
+    /// Add a pattern whose compilation options differ from the options of
+    /// this builder as specified by `opts`.
+    pub fn pattern_with_options<P: Into<W::Pattern>>(
+        &mut self,
+        pattern: P,
+        opts: &PatternOptions,
+    ) -> &mut Self {
+        self.patterns.push((pattern.into(), opts.clone()));
+        self
+    }
+
+    /// Compile the patterns that were added into a set using the current
+    /// configuration.
+    ///
+    /// If any of the patterns is invalid, then the error for the first such
+    /// pattern is returned.
+    pub fn build(&self) -> Result<RegexSet<W>, Error> {
+        let regexes = self
+            .patterns
+            .iter()
+            .map(|(pattern, opts)| {
+                let mut builder = self.builder.clone();
+                opts.apply(&mut builder);
+                builder.build(pattern.clone())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let (combined, group_bases) = match self.combine(&regexes) {
+            Some((combined, group_bases)) => (Some(combined), group_bases),
+            None => (None, Vec::new()),
+        };
+        Ok(RegexSet {
+            regexes,
+            combined,
+            group_bases,
+        })
+    }
+
+    /// Compile the given patterns of this builder into a single regex, where
+    /// each pattern becomes an alternative that first passes a mark named
+    /// after its index. The leftmost-first match of this regex is then the
+    /// leftmost match across all patterns, with patterns that come first
+    /// winning ties. This also returns, for each pattern, the number of
+    /// capture groups in the combined regex that come before its own.
+    ///
+    /// This returns `None` if combining the patterns could change what they
+    /// match, or if the combined pattern fails to compile, e.g., because it
+    /// exceeds a limit that the patterns on their own don't.
+    fn combine(&self, regexes: &[Regex<W>]) -> Option<(Regex<W>, Vec<usize>)> {
+        // A single pattern is searched in one pass already.
+        if regexes.len() < 2 {
+            return None;
+        }
+        let mut parts = Vec::with_capacity(3 * regexes.len());
+        let mut group_bases = Vec::with_capacity(regexes.len());
+        let mut groups = 0;
+        for (i, re) in regexes.iter().enumerate() {
+            group_bases.push(groups);
+            groups += re.captures_len() - 1;
+            let options = re.compile_arg_options();
+            let pattern = W::pattern_units(re.as_str());
+            if !is_combinable(&pattern, options & PCRE2_EXTENDED != 0) {
+                return None;
+            }
+            // The options that may differ between patterns are set for each
+            // alternative, and everything else comes from the builder.
+            let (mut on, mut off) = (String::new(), String::new());
+            for &(option, flag) in &[
+                (PCRE2_CASELESS, 'i'),
+                (PCRE2_MULTILINE, 'm'),
+                (PCRE2_DOTALL, 's'),
+                (PCRE2_EXTENDED, 'x'),
+            ] {
+                if options & option != 0 {
+                    on.push(flag);
+                } else {
+                    off.push(flag);
+                }
+            }
+            if !off.is_empty() {
+                off.insert(0, '-');
+            }
+            let bar = if i == 0 { "" } else { "|" };
+            let prefix = format!("{}(*MARK:{})(?{}{}:", bar, i, on, off);
+            parts.push(W::pattern_from_str(&prefix));
+            parts.push(re.as_str().clone());
+            parts.push(W::pattern_from_str(")"));
+        }
+        let mut builder = self.builder.clone();
+        // Different patterns may use the same group names.
+        builder.dupnames(true);
+        let combined = builder.build(W::pattern_concat(&parts)).ok()?;
+        // UTF and UCP can only be set for the pattern as a whole.
+        let modes = |re: &Regex<W>| re.compile_arg_options() & (PCRE2_UTF | PCRE2_UCP);
+        if regexes.iter().any(|re| modes(re) != modes(&combined)) {
+            return None;
+        }
+        if combined.captures_len() - 1 != groups {
+            return None;
+        }
+        Some((combined, group_bases))
+    }
+
+    /// Enables case insensitive matching for every pattern.
+    ///
+    /// See `RegexBuilder::caseless` for details.
+    pub fn caseless(&mut self, yes: bool) -> &mut Self {
+        self.builder.caseless(yes);
+        self
+    }
+
+    /// Enables "dot all" matching for every pattern.
+    ///
+    /// See `RegexBuilder::dotall` for details.
+    pub fn dotall(&mut self, yes: bool) -> &mut Self {
+        self.builder.dotall(yes);
+        self
+    }
+
+    /// Enables extended mode for every pattern.
+    ///
+    /// See `RegexBuilder::extended` for details.
+    pub fn extended(&mut self, yes: bool) -> &mut Self {
+        self.builder.extended(yes);
+        self
+    }
+
+    /// Enables multi-line mode for every pattern.
+    ///
+    /// See `RegexBuilder::multi_line` for details.
+    pub fn multi_line(&mut self, yes: bool) -> &mut Self {
+        self.builder.multi_line(yes);
+        self
+    }
+
+    /// Enables matching of CRLF as a line terminator for every pattern.
+    ///
+    /// See `RegexBuilder::crlf` for details.
+    pub fn crlf(&mut self, yes: bool) -> &mut Self {
+        self.builder.crlf(yes);
+        self
+    }
+
+    /// Sets the newline convention for every pattern.
+    ///
+    /// See `RegexBuilder::newline` for details.
+    pub fn newline(&mut self, newline: Newline) -> &mut Self {
+        self.builder.newline(newline);
+        self
+    }
+
+    /// Sets what `\R` matches for every pattern.
+    ///
+    /// See `RegexBuilder::backslash_r` for details.
+    pub fn backslash_r(&mut self, bsr: BsrConvention) -> &mut Self {
+        self.builder.backslash_r(bsr);
+        self
+    }
+
+    /// Sets the character tables to compile every pattern with.
+    ///
+    /// See `RegexBuilder::character_tables` for details.
+    pub fn character_tables(&mut self, tables: CharacterTables<W>) -> &mut Self {
+        self.builder.character_tables(tables);
+        self
+    }
+
+    /// Enables Unicode compatible matching for every pattern.
+    ///
+    /// See `RegexBuilder::ucp` for details.
+    pub fn ucp(&mut self, yes: bool) -> &mut Self {
+        self.builder.ucp(yes);
+        self
+    }
+
+    /// Enables UTF matching mode for every pattern.
+    ///
+    /// See `RegexBuilder::utf` for details.
+    pub fn utf(&mut self, yes: bool) -> &mut Self {
+        self.builder.utf(yes);
+        self
+    }
+
+    /// Enables PCRE2's JIT and returns an error if it's not available for
+    /// any of the patterns.
+    ///
+    /// See `RegexBuilder::jit` for details.
+    pub fn jit(&mut self, yes: bool) -> &mut Self {
+        self.builder.jit(yes);
+        self
+    }
+
+    /// Enables PCRE2's JIT if it's available for every pattern.
+    ///
+    /// See `RegexBuilder::jit_if_available` for details.
+    pub fn jit_if_available(&mut self, yes: bool) -> &mut Self {
+        self.builder.jit_if_available(yes);
+        self
+    }
+}
+
+/// Compilation options for a single pattern of a `RegexSet`, which override
+/// the options of its `RegexSetBuilder`.
+///
+/// Options that aren't set on this value are taken from the builder.
+#[derive(Clone, Debug, Default)]
+pub struct PatternOptions {
+    caseless: Option<bool>,
+    dotall: Option<bool>,
+    extended: Option<bool>,
+    multi_line: Option<bool>,
+    ucp: Option<bool>,
+    utf: Option<bool>,
+}
+
+impl PatternOptions {
+    /// Create new pattern options that don't override any option.
+    pub fn new() -> PatternOptions {
+        PatternOptions::default()
+    }
+
+    /// Override whether case insensitive matching is enabled.
+    pub fn caseless(&mut self, yes: bool) -> &mut Self {
+        self.caseless = Some(yes);
+        self
+    }
+
+    /// Override whether "dot all" matching is enabled.
+    pub fn dotall(&mut self, yes: bool) -> &mut Self {
+        self.dotall = Some(yes);
+        self
+    }
+
+    /// Override whether extended mode is enabled.
+    pub fn extended(&mut self, yes: bool) -> &mut Self {
+        self.extended = Some(yes);
+        self
+    }
+
+    /// Override whether multi-line mode is enabled.
+    pub fn multi_line(&mut self, yes: bool) -> &mut Self {
+        self.multi_line = Some(yes);
+        self
+    }
+
+    /// Override whether Unicode compatible matching is enabled.
+    pub fn ucp(&mut self, yes: bool) -> &mut Self {
+        self.ucp = Some(yes);
+        self
+    }
+
+    /// Override whether UTF matching mode is enabled.
+    pub fn utf(&mut self, yes: bool) -> &mut Self {
+        self.utf = Some(yes);
+        self
+    }
+
+    /// Apply the overridden options to the given builder.
+    fn apply<W: CodeUnitWidth>(&self, builder: &mut RegexBuilder<W>) {
+        if let Some(yes) = self.caseless {
+            builder.caseless(yes);
+        }
+        if let Some(yes) = self.dotall {
+            builder.dotall(yes);
+        }
+        if let Some(yes) = self.extended {
+            builder.extended(yes);
+        }
+        if let Some(yes) = self.multi_line {
+            builder.multi_line(yes);
+        }
+        if let Some(yes) = self.ucp {
+            builder.ucp(yes);
+        }
+        if let Some(yes) = self.utf {
+            builder.utf(yes);
+        }
+    }
+}
+
+/// Returns true if and only if the given pattern, whose code units are
+/// given, can be made an alternative of a larger pattern without changing
+/// what it matches. `extended` is true if the pattern is compiled in
+/// extended mode.
+///
+/// This errs on the side of caution. It rejects anything that refers to a
+/// capture group by number or name, since the numbers shift in the larger
+/// pattern, as well as backtracking control verbs and leading option
+/// settings, `\K`, and anything that could swallow the parenthesis that
+/// closes the alternative, namely `\Q` quoting and extended mode comments.
+fn is_combinable(pattern: &[u32], extended: bool) -> bool {
+    if has_subroutine_call(pattern) {
+        return false;
+    }
+    let at = |i: usize| pattern.get(i).and_then(|&c| char::from_u32(c));
+    let mut extended_inline = false;
+    let mut i = 0;
+    while i < pattern.len() {
+        match at(i) {
+            Some('\\') => match at(i + 1) {
+                Some('1'..='9' | 'c' | 'g' | 'k' | 'K' | 'Q') => return false,
+                _ => i += 1,
+            },
+            Some('(') => match (at(i + 1), at(i + 2)) {
+                (Some('*'), _) => return false,
+                (Some('?'), Some('(')) => return false,
+                (Some('?'), Some('P')) if at(i + 3) == Some('=') => return false,
+                (Some('?'), _) => {
+                    extended_inline |= pattern[i + 2..]
+                        .iter()
+                        .map_while(|&c| char::from_u32(c))
+                        .take_while(|c| c.is_ascii_alphabetic() || *c == '-' || *c == '^')
+                        .any(|c| c == 'x');
+                }
+                _ => {}
+            },
+            Some('#') if extended || extended_inline => return false,
+            _ => {}
+        }
+        i += 1;
+    }
+    true
+}
+
+/// The patterns of a `RegexSet` that matched a subject string.
+///
+/// Patterns are identified by their index in the set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SetMatches {
+    matched: Vec<bool>,
+}
+
+impl SetMatches {
+    /// Returns true if and only if any pattern matched.
+    pub fn matched_any(&self) -> bool {
+        self.matched.contains(&true)
+    }
+
+    /// Returns true if and only if the pattern at the given index matched.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `index` is not less than the number of patterns in the
+    /// set.
+    pub fn matched(&self, index: usize) -> bool {
+        self.matched[index]
+    }
+
+    /// Returns the number of patterns in the set that was searched, which
+    /// includes patterns that didn't match.
+    pub fn len(&self) -> usize {
+        self.matched.len()
+    }
+
+    /// Returns true if and only if the set that was searched has no
+    /// patterns.
+    pub fn is_empty(&self) -> bool {
+        self.matched.is_empty()
+    }
+
+    /// Returns the indices of the patterns that matched, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.matched
+            .iter()
+            .enumerate()
+            .filter(|&(_, &yes)| yes)
+            .map(|(i, _)| i)
+    }
+}
+
+/// An iterator that splits a subject into consecutive tokens using the
+/// patterns of a `RegexSet`.
+///
+/// Each token starts where the previous one ended, as if every pattern began
+/// with `\G`. At each position, the patterns are tried in order and the first
+/// one that matches a non-empty token wins, even if a later pattern would
+/// match a longer one. The iterator yields the index of the winning pattern
+/// along with its match.
+///
+/// If no pattern matches a non-empty token at some position before the end
+/// of the subject, then a `LexError::NoMatch` error is yielded instead,
+/// since the lexer would otherwise make no progress. The iterator stops
+/// after yielding an error.
+///
+/// `'r` is the lifetime of the set and `'s` is the lifetime of the subject
+/// string.
+pub struct Lexer<'r, 's, W: CodeUnitWidth> {
+    set: &'r RegexSet<W>,
+    subject: &'s [W::SubjectChar],
+    pos: usize,
+    done: bool,
+}
+
+impl<'r, 's, W: CodeUnitWidth> Lexer<'r, 's, W> {
+    /// Returns the offset at which the next token starts.
+    ///
+    /// Once the iterator has yielded a `LexError::NoMatch` error, this is
+    /// the offset at which no token could be found.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the first pattern that matches a non-empty token at the
+    /// current position, along with its match.
+    fn next_token(&self) -> Result<Option<(usize, Match<'s, W>)>, Error> {
+        let mut opts = MatchOptions::new();
+        opts.anchored(true).notempty_atstart(true);
+        // Every match starts at the current position, so the leftmost one
+        // is the one of the first pattern that matches.
+        self.set.search(self.subject, self.pos, &opts)
+    }
+}
+
+impl<'r, 's, W: CodeUnitWidth> Iterator for Lexer<'r, 's, W> {
+    type Item = Result<(usize, Match<'s, W>), LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.pos >= self.subject.len() {
+            return None;
+        }
+        let result = match self.next_token() {
+            Err(err) => Err(LexError::Match(err)),
+            Ok(None) => Err(LexError::NoMatch(self.pos)),
+            Ok(Some((i, m))) => {
+                self.pos = m.end();
+                Ok((i, m))
+            }
+        };
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+/// An error that occurred while splitting a subject into tokens with a
+/// `Lexer`.
+#[derive(Clone, Debug)]
+pub enum LexError {
+    /// None of the patterns matched a non-empty token at the given offset.
+    NoMatch(usize),
+    /// An error occurred while matching one of the patterns.
+    Match(Error),
+}
+
+impl error::Error for LexError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LexError::NoMatch(_) => None,
+            LexError::Match(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::NoMatch(offset) => {
+                write!(f, "no token matches at offset {}", offset)
+            }
+            LexError::Match(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<Error> for LexError {
+    fn from(err: Error) -> LexError {
+        LexError::Match(err)
+    }
+}
+
+#[cfg(all(test, feature = "utf8"))]
+mod tests {
+    use super::is_combinable;
+    use crate::bytes::{PatternOptions, RegexSet, RegexSetBuilder};
+
+    fn combinable(pattern: &str, extended: bool) -> bool {
+        let units = pattern.bytes().map(u32::from).collect::<Vec<_>>();
+        is_combinable(&units, extended)
+    }
+
+    fn uncombined(set: &RegexSet) -> RegexSet {
+        let mut set = set.clone();
+        set.combined = None;
+        set
+    }
+
+    #[test]
+    fn combinable_patterns() {
+        let yes = [
+            r"\d+",
+            r"(?<word>\w+)|(b)",
+            r"(?i)foo(?-i:bar)",
+            r"\(\*\\1",
+            r"[#]a#",
+            r"(?#comment)a",
+        ];
+        for pattern in yes {
+            assert!(combinable(pattern, false), "{:?}", pattern);
+        }
+        let no = [
+            r"(a)\1",
+            r"(?<n>a)\k<n>",
+            r"(a)\g{1}",
+            r"(?1)(a)",
+            r"(?R)?",
+            r"(a)(?-1)",
+            r"(?<n>a)(?&n)",
+            r"(?P<n>a)(?P=n)",
+            r"(?(1)a|b)",
+            r"(*SKIP)a",
+            r"(*UTF)a",
+            r"a\Kb",
+            r"\Qa",
+            r"(?x)a#b",
+        ];
+        for pattern in no {
+            assert!(!combinable(pattern, false), "{:?}", pattern);
+        }
+        assert!(!combinable("a#b", true));
+    }
+
+    #[test]
+    fn combined() {
+        let set = RegexSet::new([r"\d+", r"[a-z]+"]).unwrap();
+        assert!(set.combined.is_some());
+        let set = RegexSet::new([r"(a)\1", "b"]).unwrap();
+        assert!(set.combined.is_none());
+        let set = RegexSet::new(["a"]).unwrap();
+        assert!(set.combined.is_none());
+
+        // UTF mode can only be set for a pattern as a whole.
+        let set = RegexSetBuilder::new()
+            .pattern("a")
+            .pattern_with_options("b", PatternOptions::new().utf(true))
+            .build()
+            .unwrap();
+        assert!(set.combined.is_none());
+        assert_eq!(set.find(b"ab").unwrap().map(|(i, _)| i), Some(0));
+    }
+
+    #[test]
+    fn combined_agrees() {
+        let set = RegexSetBuilder::new()
+            .pattern(r"(?<n>\d)\d*")
+            .pattern("(x)(y)?(z)")
+            .pattern_with_options("^B$", PatternOptions::new().caseless(true))
+            .pattern_with_options("^b.c", PatternOptions::new().multi_line(true).dotall(true))
+            .pattern_with_options("(?<n>[a-z]) +", PatternOptions::new().extended(true))
+            .pattern(r"\w+")
+            .pattern("")
+            .build()
+            .unwrap();
+        assert!(set.combined.is_some());
+        let single = uncombined(&set);
+        let (mut locs, mut single_locs) = (set.capture_locations(), single.capture_locations());
+        for subject in ["b", "x\nb\nc", "1z", "abc 42", "  ", "a2b", "axz"] {
+            let subject = subject.as_bytes();
+            assert_eq!(
+                set.is_match(subject).unwrap(),
+                single.is_match(subject).unwrap()
+            );
+            assert_eq!(
+                set.matches(subject).unwrap(),
+                single.matches(subject).unwrap()
+            );
+            for start in 0..=subject.len() {
+                let find = |set: &RegexSet| {
+                    set.find_at(subject, start)
+                        .unwrap()
+                        .map(|(i, m)| (i, m.as_pair()))
+                };
+                assert_eq!(find(&set), find(&single), "{:?} at {}", subject, start);
+                set.captures_read_at(&mut locs, subject, start).unwrap();
+                single
+                    .captures_read_at(&mut single_locs, subject, start)
+                    .unwrap();
+                assert_eq!(locs.pattern(), single_locs.pattern());
+                assert_eq!(locs.len(), single_locs.len());
+                for i in 0..locs.len() {
+                    assert_eq!(locs.get(i), single_locs.get(i));
+                }
+                let name = |set: &RegexSet| {
+                    set.captures_at(subject, start)
+                        .unwrap()
+                        .and_then(|(_, caps)| caps.name("n").map(|m| m.as_pair()))
+                };
+                assert_eq!(name(&set), name(&single));
+            }
+            let tokens = |set: &RegexSet| {
+                set.lexer(subject)
+                    .map(|t| t.ok().map(|(i, m)| (i, m.as_pair())))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(tokens(&set), tokens(&single));
+        }
+    }
+}
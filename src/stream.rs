@@ -0,0 +1,759 @@
+/*!
+This module provides searching of input that arrives in chunks, such as data
+read from a file or a socket, without requiring the whole input to be held in
+memory at once.
+*/
+
+use std::cmp;
+#[cfg(feature = "utf8")]
+use std::io;
+use std::iter::Peekable;
+
+use crate::error::Error;
+use crate::ffi::CodeUnitWidth;
+#[cfg(feature = "utf8")]
+use crate::ffi::CodeUnitWidth8;
+#[cfg(feature = "utf8")]
+use crate::regex_impl::Match;
+use crate::regex_impl::{Regex, SearchPosition};
+
+/// The number of bytes that are read from a reader at a time.
+#[cfg(feature = "utf8")]
+const READ_BUFFER_SIZE: usize = 64 * (1 << 10);
+
+impl<W: CodeUnitWidth> Regex<W> {
+    /// Returns a matcher for finding successive non-overlapping matches in
+    /// a stream of input that is provided in chunks.
+    ///
+    /// Matches are reported with offsets relative to the start of the stream.
+    /// See [`StreamMatcher`](struct.StreamMatcher.html) for details.
+    pub fn stream_matcher(&self) -> StreamMatcher<'_, W> {
+        StreamMatcher::new(self)
+    }
+
+    /// Returns a push-based scanner that finds successive non-overlapping
+    /// matches in input that is fed to it in chunks.
+    ///
+    /// Unlike `find_read_iter`, the scanner doesn't perform any I/O itself,
+    /// which makes it suitable for event driven or asynchronous code. See
+    /// [`Scanner`](struct.Scanner.html) for details.
+    pub fn scanner(&self) -> Scanner<'_, W> {
+        Scanner {
+            matcher: self.stream_matcher(),
+        }
+    }
+
+    /// Returns an iterator over successive non-overlapping matches in a
+    /// subject that is split into the given chunks, such as the pieces of a
+    /// rope, with offsets relative to the start of the first chunk.
+    ///
+    /// The matches are the same as those found by searching the
+    /// concatenation of the chunks, but the chunks are searched where they
+    /// are, and only a small window of input around the boundaries between
+    /// chunks is copied. See [`ChunkMatches`](struct.ChunkMatches.html) for
+    /// details.
+    pub fn find_chunks_iter<'r, 'c, I>(&'r self, chunks: I) -> ChunkMatches<'r, 'c, W, I::IntoIter>
+    where
+        I: IntoIterator<Item = &'c [W::SubjectChar]>,
+    {
+        let mut chunks = chunks.into_iter().peekable();
+        ChunkMatches {
+            re: self,
+            chunk: chunks.next().unwrap_or(&[]),
+            chunks,
+            chunk_offset: 0,
+            in_place: true,
+            window: vec![],
+            window_offset: 0,
+            copied: 0,
+            pos: SearchPosition::new(),
+            context: cmp::max(1, self.max_lookbehind()),
+            done: false,
+        }
+    }
+}
+
+#[cfg(feature = "utf8")]
+impl Regex<CodeUnitWidth8> {
+    /// Returns an iterator over successive non-overlapping matches in the
+    /// data read from `rdr`, with offsets relative to the start of the data.
+    ///
+    /// The data is read incrementally, and only the parts that could still
+    /// be part of a match are kept in memory, so this is suitable for
+    /// searching inputs that are too large to load at once. Matches that
+    /// straddle the boundaries between reads are found as usual. See
+    /// [`StreamMatcher`](struct.StreamMatcher.html) for details.
+    ///
+    /// Errors from reading are yielded as is, while errors from matching are
+    /// converted to I/O errors.
+    pub fn find_read_iter<R: io::Read>(&self, rdr: R) -> ReadMatches<'_, R> {
+        ReadMatches {
+            matcher: self.stream_matcher(),
+            rdr,
+            buf: vec![0; READ_BUFFER_SIZE],
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Returns an iterator over the records read from `rdr` that are
+    /// separated by matches of this regex.
+    ///
+    /// Each record is yielded as soon as the delimiter that ends it has been
+    /// read, and only the current record (along with any input needed to
+    /// find the delimiter) is kept in memory. As with `split_inclusive`, the
+    /// last record is always yielded, even when it is empty. So for example,
+    /// splitting `a\nb\n` on `\n` yields `a`, `b` and finally an empty
+    /// record.
+    ///
+    /// Errors from reading are yielded as is, while errors from matching are
+    /// converted to I/O errors.
+    pub fn split_read<R: io::Read>(&self, rdr: R) -> SplitRead<'_, R> {
+        SplitRead {
+            matches: self.find_read_iter(rdr),
+            record: vec![],
+            record_start: 0,
+            done: false,
+        }
+    }
+
+    /// Returns an iterator over the lines read from `rdr` that contain at
+    /// least one match, along with the non-overlapping matches in each line.
+    ///
+    /// Lines are terminated by `\n`, which is not included in the line that
+    /// is searched, so `$` matches at the end of every line. Note that a
+    /// preceding `\r` is kept. Use `RegexBuilder::crlf` for `$` to match
+    /// before it. Lines without any match are skipped, but still counted for
+    /// the purposes of line numbers.
+    ///
+    /// Errors from reading are yielded as is, while errors from matching are
+    /// converted to I/O errors.
+    pub fn line_matches<R: io::BufRead>(&self, rdr: R) -> LineMatches<'_, R> {
+        LineMatches {
+            re: self,
+            rdr,
+            line: vec![],
+            line_number: 0,
+            done: false,
+        }
+    }
+}
+
+/// A match found in a stream of input.
+///
+/// Unlike `Match`, this owns a copy of the matched text, since the input it
+/// was found in is usually discarded soon afterwards. Offsets are relative to
+/// the beginning of the stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamMatch<C> {
+    start: usize,
+    end: usize,
+    text: Vec<C>,
+}
+
+impl<C> StreamMatch<C> {
+    /// Returns the starting offset of the match in the stream.
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the ending offset of the match in the stream.
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the matched portion of the stream.
+    #[inline]
+    pub fn as_bytes(&self) -> &[C] {
+        &self.text
+    }
+
+    /// Returns the matched portion of the stream, without copying it.
+    #[inline]
+    pub fn into_bytes(self) -> Vec<C> {
+        self.text
+    }
+}
+
+/// Finds successive non-overlapping matches in input that is provided in
+/// chunks.
+///
+/// This is created by `Regex::stream_matcher`. Input is added with `push`,
+/// and matches are retrieved with `next_match`. Matches may span any number
+/// of chunks. Only the input that could still be part of a match is
+/// buffered, along with enough preceding input for lookbehind assertions
+/// (and anchors such as `\b`) to see the same context as they would when
+/// searching the whole input at once.
+///
+/// Since more input can change what a pattern matches, a match is only
+/// reported once the input that follows it can no longer affect it. For
+/// example, `\d+` doesn't match the `12` at the end of a chunk until either a
+/// non-digit is pushed or the end of the stream is signaled with `finish`.
+///
+/// Searches for possible matches use hard partial matching, so the JIT is
+/// only used for them if the regex was built with
+/// `RegexBuilder::jit_partial(PartialMode::Hard, true)`.
+///
+/// `'r` is the lifetime of the compiled regular expression.
+pub struct StreamMatcher<'r, W: CodeUnitWidth> {
+    re: &'r Regex<W>,
+    /// The buffered input.
+    buf: Vec<W::SubjectChar>,
+    /// The offset in the stream at which `buf` starts.
+    offset: usize,
+    /// The progress of the search through `buf`.
+    pos: SearchPosition,
+    /// The number of characters to keep before the start of the next search.
+    context: usize,
+    /// Whether `finish` has been called.
+    finished: bool,
+}
+
+impl<'r, W: CodeUnitWidth> StreamMatcher<'r, W> {
+    /// Create a new matcher that finds matches of the given regex in a
+    /// stream, starting with no input.
+    pub(crate) fn new(re: &'r Regex<W>) -> StreamMatcher<'r, W> {
+        StreamMatcher {
+            re,
+            buf: vec![],
+            offset: 0,
+            pos: SearchPosition::new(),
+            // Keep at least one character, so that anchors like `\A` and `^`
+            // can't match at the start of the buffer once input has been
+            // discarded.
+            context: cmp::max(1, re.max_lookbehind()),
+            finished: false,
+        }
+    }
+
+    /// Add the next chunk of input to the stream.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `finish` has been called.
+    pub fn push(&mut self, chunk: &[W::SubjectChar]) {
+        assert!(
+            !self.finished,
+            "cannot push input after finishing the stream"
+        );
+        self.discard();
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Signal the end of the stream.
+    ///
+    /// Afterwards, `next_match` reports any remaining matches, including ones
+    /// that were waiting for more input.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Returns the next match in the input pushed so far.
+    ///
+    /// `None` is returned when no further match can be reported without
+    /// more input, or, once `finish` has been called, when there are no
+    /// more matches.
+    pub fn next_match(&mut self) -> Result<Option<StreamMatch<W::SubjectChar>>, Error> {
+        let m = match self
+            .re
+            .find_stream_next(&mut self.pos, &self.buf, self.finished)?
+        {
+            None => return Ok(None),
+            Some(m) => m,
+        };
+        Ok(Some(StreamMatch {
+            start: self.offset + m.start(),
+            end: self.offset + m.end(),
+            text: m.as_bytes().to_vec(),
+        }))
+    }
+
+    /// Returns the offset in the stream of the earliest input that is still
+    /// needed to find matches.
+    ///
+    /// Everything before this offset has been discarded, or will be when
+    /// more input is pushed.
+    pub fn retained_offset(&self) -> usize {
+        self.offset + self.keep_from()
+    }
+
+    /// Returns the offset in `buf` of the earliest input that must be kept.
+    fn keep_from(&self) -> usize {
+        context_start::<W>(&self.buf, self.pos.start(), self.context)
+    }
+
+    /// Discard the buffered input that is no longer needed.
+    fn discard(&mut self) {
+        let n = self.keep_from();
+        self.buf.drain(..n);
+        self.offset += n;
+        self.pos.shift(n);
+    }
+}
+
+/// A push-based scanner for finding successive non-overlapping matches in
+/// input that is provided in chunks.
+///
+/// This is created by `Regex::scanner`. Each call to `feed` returns the
+/// matches that were completed by the given chunk, and `finish` returns the
+/// matches that were still waiting for more input when the input ends. The
+/// matches are the same as those found by a `StreamMatcher`, which this
+/// wraps.
+///
+/// `'r` is the lifetime of the compiled regular expression.
+pub struct Scanner<'r, W: CodeUnitWidth> {
+    matcher: StreamMatcher<'r, W>,
+}
+
+impl<'r, W: CodeUnitWidth> Scanner<'r, W> {
+    /// Feed the next chunk of input to the scanner, and return the matches
+    /// that can be reported so far.
+    pub fn feed(
+        &mut self,
+        chunk: &[W::SubjectChar],
+    ) -> Result<Vec<StreamMatch<W::SubjectChar>>, Error> {
+        self.matcher.push(chunk);
+        self.drain()
+    }
+
+    /// Signal the end of the input, and return the remaining matches.
+    pub fn finish(mut self) -> Result<Vec<StreamMatch<W::SubjectChar>>, Error> {
+        self.matcher.finish();
+        self.drain()
+    }
+
+    /// Returns the offset in the stream of the earliest input that the
+    /// scanner still holds on to. See `StreamMatcher::retained_offset`.
+    pub fn retained_offset(&self) -> usize {
+        self.matcher.retained_offset()
+    }
+
+    /// Returns all matches that can be reported without more input.
+    fn drain(&mut self) -> Result<Vec<StreamMatch<W::SubjectChar>>, Error> {
+        let mut matches = vec![];
+        while let Some(m) = self.matcher.next_match()? {
+            matches.push(m);
+        }
+        Ok(matches)
+    }
+}
+
+/// Returns the offset in `subject` that is `context` characters before `at`,
+/// or `0` if there aren't that many characters before it.
+fn context_start<W: CodeUnitWidth>(subject: &[W::SubjectChar], at: usize, context: usize) -> usize {
+    let mut at = cmp::min(at, subject.len());
+    for _ in 0..context {
+        if at == 0 {
+            break;
+        }
+        at -= 1;
+        // In UTF mode, step back over entire characters. Otherwise, this
+        // merely retains a little more than necessary.
+        while at > 0 && W::is_utf_continuation(subject[at]) {
+            at -= 1;
+        }
+    }
+    at
+}
+
+/// The smallest number of code units of a chunk that are copied at a time
+/// when searching across the boundary between two chunks.
+const MIN_WINDOW_SIZE: usize = 64;
+
+/// An iterator over successive non-overlapping matches in a subject that is
+/// split into chunks.
+///
+/// This is created by `Regex::find_chunks_iter`. Matches may span any number
+/// of chunks, and are reported with offsets relative to the start of the
+/// first chunk.
+///
+/// Each chunk is searched in place as far as possible. Near the boundary
+/// between two chunks, the end of the previous chunk (enough of it for
+/// lookbehind assertions and for a possible match that crosses the boundary)
+/// and the start of the next one are copied to a window, which grows until
+/// the search has moved far enough into the next chunk to continue there.
+/// So only matches that cross boundaries, and the input around them, are
+/// ever copied. Like `StreamMatcher`, this uses hard partial matching to
+/// find matches that may continue in the next chunk.
+///
+/// In UTF mode, chunks should be split at character boundaries, since PCRE2
+/// may otherwise report the split characters as invalid.
+///
+/// `'r` is the lifetime of the compiled regular expression, `'c` is the
+/// lifetime of the chunks and `I` is the type of the iterator over them.
+pub struct ChunkMatches<'r, 'c, W: CodeUnitWidth, I: Iterator<Item = &'c [W::SubjectChar]>> {
+    re: &'r Regex<W>,
+    chunks: Peekable<I>,
+    /// The current chunk.
+    chunk: &'c [W::SubjectChar],
+    /// The offset in the subject at which `chunk` starts.
+    chunk_offset: usize,
+    /// Whether `chunk` is searched directly, rather than `window`.
+    in_place: bool,
+    /// A copy of the input around the start of `chunk`, which ends with the
+    /// first `copied` code units of `chunk`.
+    window: Vec<W::SubjectChar>,
+    /// The offset in the subject at which `window` starts.
+    window_offset: usize,
+    copied: usize,
+    /// The progress of the search through `chunk` or `window`.
+    pos: SearchPosition,
+    /// The number of characters to keep before the start of the next search.
+    context: usize,
+    done: bool,
+}
+
+impl<'r, 'c, W, I> ChunkMatches<'r, 'c, W, I>
+where
+    W: CodeUnitWidth,
+    I: Iterator<Item = &'c [W::SubjectChar]>,
+{
+    /// Returns the next match, or `None` once the last chunk has been
+    /// searched.
+    fn next_match(&mut self) -> Result<Option<StreamMatch<W::SubjectChar>>, Error> {
+        loop {
+            let complete =
+                self.chunks.peek().is_none() && (self.in_place || self.copied == self.chunk.len());
+            let (subject, offset) = if self.in_place {
+                (self.chunk, self.chunk_offset)
+            } else {
+                (&*self.window, self.window_offset)
+            };
+            if let Some(m) = self.re.find_stream_next(&mut self.pos, subject, complete)? {
+                return Ok(Some(StreamMatch {
+                    start: offset + m.start(),
+                    end: offset + m.end(),
+                    text: m.as_bytes().to_vec(),
+                }));
+            }
+            if complete {
+                return Ok(None);
+            }
+            if self.in_place {
+                self.leave_chunk();
+            } else {
+                self.advance_window();
+            }
+        }
+    }
+
+    /// Continue with a window that starts with what's still needed from the
+    /// end of the current chunk, followed by the next chunk.
+    fn leave_chunk(&mut self) {
+        let keep = context_start::<W>(self.chunk, self.pos.start(), self.context);
+        self.window.clear();
+        self.window.extend_from_slice(&self.chunk[keep..]);
+        self.window_offset = self.chunk_offset + keep;
+        self.pos.shift(keep);
+        self.next_chunk();
+        self.in_place = false;
+    }
+
+    /// Continue searching the current chunk directly if the search has moved
+    /// far enough into it, and otherwise copy more of it to the window.
+    fn advance_window(&mut self) {
+        let boundary = self.window.len() - self.copied;
+        if let Some(at) = self.pos.start().checked_sub(boundary) {
+            // Lookbehind assertions must not need to see past the start of
+            // the chunk when it's searched directly.
+            if context_start::<W>(self.chunk, at, self.context) > 0 {
+                self.pos.shift(boundary);
+                self.window.clear();
+                self.in_place = true;
+                return;
+            }
+        }
+        if self.copied < self.chunk.len() {
+            let end = cmp::min(cmp::max(2 * self.copied, MIN_WINDOW_SIZE), self.chunk.len());
+            self.window.extend_from_slice(&self.chunk[self.copied..end]);
+            self.copied = end;
+            return;
+        }
+        // The whole chunk was copied without getting far enough into it, so
+        // drop what's no longer needed and move on to the next one.
+        let keep = context_start::<W>(&self.window, self.pos.start(), self.context);
+        self.window.drain(..keep);
+        self.window_offset += keep;
+        self.pos.shift(keep);
+        self.next_chunk();
+    }
+
+    /// Move on to the next chunk, which must exist.
+    fn next_chunk(&mut self) {
+        self.chunk_offset += self.chunk.len();
+        self.chunk = self.chunks.next().unwrap();
+        self.copied = 0;
+    }
+}
+
+impl<'r, 'c, W, I> Iterator for ChunkMatches<'r, 'c, W, I>
+where
+    W: CodeUnitWidth,
+    I: Iterator<Item = &'c [W::SubjectChar]>,
+{
+    type Item = Result<StreamMatch<W::SubjectChar>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_match().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+/// An iterator over successive non-overlapping matches in the data read from
+/// a reader.
+///
+/// This is created by `Regex::find_read_iter`. The iterator stops after
+/// yielding an error.
+///
+/// `'r` is the lifetime of the compiled regular expression and `R` is the
+/// type of the reader.
+#[cfg(feature = "utf8")]
+pub struct ReadMatches<'r, R> {
+    matcher: StreamMatcher<'r, CodeUnitWidth8>,
+    rdr: R,
+    buf: Vec<u8>,
+    eof: bool,
+    done: bool,
+}
+
+#[cfg(feature = "utf8")]
+impl<'r, R: io::Read> ReadMatches<'r, R> {
+    /// Returns the next match, and calls `data` with every chunk of data
+    /// that is read while looking for it.
+    fn next_with<F: FnMut(&[u8])>(&mut self, data: F) -> Option<io::Result<StreamMatch<u8>>> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_next(data);
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
+
+    /// Does the work of `next_with`, without stopping after errors.
+    fn read_next<F: FnMut(&[u8])>(&mut self, mut data: F) -> Option<io::Result<StreamMatch<u8>>> {
+        loop {
+            match self.matcher.next_match() {
+                Err(err) => return Some(Err(io::Error::other(err))),
+                Ok(Some(m)) => return Some(Ok(m)),
+                Ok(None) if self.eof => return None,
+                Ok(None) => {}
+            }
+            match self.rdr.read(&mut self.buf) {
+                Ok(0) => {
+                    self.eof = true;
+                    self.matcher.finish();
+                }
+                Ok(n) => {
+                    self.matcher.push(&self.buf[..n]);
+                    data(&self.buf[..n]);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "utf8")]
+impl<'r, R: io::Read> Iterator for ReadMatches<'r, R> {
+    type Item = io::Result<StreamMatch<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<StreamMatch<u8>>> {
+        self.next_with(|_| {})
+    }
+}
+
+/// A line that contains at least one match, as found by `LineMatches`.
+#[cfg(feature = "utf8")]
+#[derive(Clone, Debug)]
+pub struct LineMatch {
+    line_number: u64,
+    line: Vec<u8>,
+    matches: Vec<(usize, usize)>,
+}
+
+#[cfg(feature = "utf8")]
+impl LineMatch {
+    /// Returns the number of this line, where the first line is `1`.
+    pub fn line_number(&self) -> u64 {
+        self.line_number
+    }
+
+    /// Returns the contents of this line, without its `\n` terminator.
+    pub fn line(&self) -> &[u8] {
+        &self.line
+    }
+
+    /// Returns the successive non-overlapping matches in this line, with
+    /// offsets relative to the start of the line. There is always at least
+    /// one match.
+    pub fn matches(&self) -> impl Iterator<Item = Match<'_, CodeUnitWidth8>> + '_ {
+        self.matches
+            .iter()
+            .map(move |&(s, e)| Match::new(&self.line, s, e))
+    }
+
+    /// Returns the contents of this line, without copying it.
+    pub fn into_line(self) -> Vec<u8> {
+        self.line
+    }
+}
+
+/// An iterator over the lines read from a reader that contain at least one
+/// match.
+///
+/// This is created by `Regex::line_matches`. The iterator stops after
+/// yielding an error.
+///
+/// `'r` is the lifetime of the compiled regular expression and `R` is the
+/// type of the reader.
+#[cfg(feature = "utf8")]
+pub struct LineMatches<'r, R> {
+    re: &'r Regex<CodeUnitWidth8>,
+    rdr: R,
+    line: Vec<u8>,
+    line_number: u64,
+    done: bool,
+}
+
+#[cfg(feature = "utf8")]
+impl<'r, R: io::BufRead> LineMatches<'r, R> {
+    /// Returns the next line that contains a match, without stopping after
+    /// errors.
+    fn next_line(&mut self) -> Option<io::Result<LineMatch>> {
+        loop {
+            self.line.clear();
+            match self.rdr.read_until(b'\n', &mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) => return Some(Err(err)),
+            }
+            self.line_number += 1;
+            if self.line.last() == Some(&b'\n') {
+                self.line.pop();
+            }
+            let mut matches = vec![];
+            for result in self.re.find_iter(&self.line) {
+                match result {
+                    Err(err) => return Some(Err(io::Error::other(err))),
+                    Ok(m) => matches.push((m.start(), m.end())),
+                }
+            }
+            if !matches.is_empty() {
+                return Some(Ok(LineMatch {
+                    line_number: self.line_number,
+                    line: std::mem::take(&mut self.line),
+                    matches,
+                }));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "utf8")]
+impl<'r, R: io::BufRead> Iterator for LineMatches<'r, R> {
+    type Item = io::Result<LineMatch>;
+
+    fn next(&mut self) -> Option<io::Result<LineMatch>> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_line();
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
+}
+
+/// An iterator over the records read from a reader that are separated by
+/// matches of a particular regular expression.
+///
+/// This is created by `Regex::split_read`.
+///
+/// `'r` is the lifetime of the compiled regular expression and `R` is the
+/// type of the reader.
+#[cfg(feature = "utf8")]
+pub struct SplitRead<'r, R> {
+    matches: ReadMatches<'r, R>,
+    /// The data read since the end of the last delimiter.
+    record: Vec<u8>,
+    /// The offset in the stream at which `record` starts.
+    record_start: usize,
+    done: bool,
+}
+
+#[cfg(feature = "utf8")]
+impl<'r, R: io::Read> Iterator for SplitRead<'r, R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        if self.done {
+            return None;
+        }
+        let record = &mut self.record;
+        match self
+            .matches
+            .next_with(|data| record.extend_from_slice(data))
+        {
+            None => {
+                self.done = true;
+                Some(Ok(std::mem::take(&mut self.record)))
+            }
+            Some(Err(err)) => {
+                self.done = true;
+                Some(Err(err))
+            }
+            Some(Ok(m)) => {
+                let field = self.record[..m.start() - self.record_start].to_vec();
+                self.record.drain(..m.end() - self.record_start);
+                self.record_start = m.end();
+                Some(Ok(field))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "utf8"))]
+mod tests {
+    use super::context_start;
+    use crate::bytes::Regex;
+    use crate::ffi::CodeUnitWidth8;
+
+    #[test]
+    fn context_start_utf8() {
+        // The characters start at offsets 0, 1, 3 and 6.
+        let subject = "aé€b".as_bytes();
+        let start = |at, context| context_start::<CodeUnitWidth8>(subject, at, context);
+        assert_eq!(start(6, 0), 6);
+        assert_eq!(start(6, 1), 3);
+        assert_eq!(start(6, 2), 1);
+        assert_eq!(start(6, 9), 0);
+        assert_eq!(start(99, 1), 6);
+    }
+
+    #[test]
+    fn stream_matcher_discards_input() {
+        let re = Regex::new(r"(?<=x)\d+").unwrap();
+        let mut matcher = re.stream_matcher();
+        let mut count = 0;
+        for _ in 0..100 {
+            matcher.push(b"abc x12 ");
+            while let Some(m) = matcher.next_match().unwrap() {
+                assert_eq!(m.as_bytes(), b"12");
+                count += 1;
+            }
+        }
+        assert_eq!(count, 100);
+        // Only one character of lookbehind context is kept from the input
+        // that was searched before the last chunk.
+        assert_eq!(matcher.buf.len(), 9);
+        assert_eq!(matcher.retained_offset(), 799);
+    }
+}
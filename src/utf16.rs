@@ -0,0 +1,438 @@
+use std::borrow::Cow;
+#[cfg(windows)]
+use std::ffi::OsString;
+use std::ops::Range;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStringExt;
+
+use crate::error::Error;
+pub use crate::ffi::CalloutBlock as CalloutBlockImpl;
+pub use crate::ffi::CalloutResult;
+pub use crate::ffi::CodeUnitWidth16;
+pub use crate::ffi::JitStack as JitStackImpl;
+pub use crate::ffi::NameTable as NameTableImpl;
+pub use crate::jit::JitStackPool as JitStackPoolImpl;
+pub use crate::jit::PooledJitStack as PooledJitStackImpl;
+pub use crate::regex_impl::Captures as CapturesImpl;
+pub use crate::regex_impl::CharacterTables as CharacterTablesImpl;
+pub use crate::regex_impl::DfaWorkspace as DfaWorkspaceImpl;
+pub use crate::regex_impl::Match as MatchImpl;
+pub use crate::regex_impl::PartialMatch as PartialMatchImpl;
+pub use crate::regex_impl::{
+    BsrConvention, CalloutInfo, CancellationToken, EmptyMatchPolicy, Extended, FirstCodeUnit,
+    MatchOptions, Newline, NoExpand, PartialMode, Replacer, ReplacerRef, SubstituteOptions,
+};
+use crate::regex_impl::{Regex as RegexImpl, RegexBuilder as RegexBuilderImpl};
+pub use crate::set::Lexer as LexerImpl;
+pub use crate::set::RegexSet as RegexSetImpl;
+pub use crate::set::RegexSetBuilder as RegexSetBuilderImpl;
+pub use crate::set::SetCaptureLocations as SetCaptureLocationsImpl;
+pub use crate::set::{LexError, PatternOptions, SetMatches};
+pub use crate::stream::ChunkMatches as ChunkMatchesImpl;
+pub use crate::stream::Scanner as ScannerImpl;
+pub use crate::stream::StreamMatch as StreamMatchImpl;
+pub use crate::stream::StreamMatcher as StreamMatcherImpl;
+
+/// A compiled PCRE2 regular expression for matching sequences of UTF-16 code
+/// units.
+///
+/// This regex is safe to use from multiple threads simultaneously. For top
+/// performance, it is better to clone a new regex for each thread.
+pub type Regex = RegexImpl<CodeUnitWidth16>;
+
+/// A builder for configuring the compilation of a PCRE2 regex.
+pub type RegexBuilder = RegexBuilderImpl<CodeUnitWidth16>;
+
+/// Match represents a single match of a regex in a subject string.
+///
+/// The lifetime parameter `'s` refers to the lifetime of the matched portion
+/// of the subject string.
+pub type Match<'s> = MatchImpl<'s, CodeUnitWidth16>;
+
+/// A set of compiled PCRE2 regular expressions that are matched against a
+/// subject together.
+pub type RegexSet = RegexSetImpl<CodeUnitWidth16>;
+
+/// A builder for configuring the compilation of a `RegexSet`.
+pub type RegexSetBuilder = RegexSetBuilderImpl<CodeUnitWidth16>;
+
+/// The capture locations of every pattern in a `RegexSet`, which can be
+/// reused across searches.
+pub type SetCaptureLocations = SetCaptureLocationsImpl<CodeUnitWidth16>;
+
+/// An iterator that splits a subject into consecutive tokens using the
+/// patterns of a `RegexSet`.
+///
+/// The lifetime parameters `'r` and `'s` refer to the lifetimes of the set
+/// and of the subject string.
+pub type Lexer<'r, 's> = LexerImpl<'r, 's, CodeUnitWidth16>;
+
+/// An iterator over the named capturing groups of a regex, which borrows
+/// the names from the compiled regex.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type NameTable<'r> = NameTableImpl<'r, CodeUnitWidth16>;
+
+/// Captures represents a group of captured UTF-16 code unit sequences for a
+/// single match.
+///
+/// The lifetime parameter `'s` refers to the lifetime of the matched subject
+/// string.
+pub type Captures<'s> = CapturesImpl<'s, CodeUnitWidth16>;
+
+/// The state of a search when it reaches a callout.
+///
+/// The lifetime parameter `'a` refers to the lifetime of the subject and of
+/// the regex.
+pub type CalloutBlock<'a> = CalloutBlockImpl<'a, CodeUnitWidth16>;
+
+/// Character tables, which determine how PCRE2 classifies and case folds
+/// characters with code points less than 256.
+pub type CharacterTables = CharacterTablesImpl<CodeUnitWidth16>;
+
+/// A JIT stack, which can be installed for the current thread to be shared
+/// by every regex searched on it.
+pub type JitStack = JitStackImpl<CodeUnitWidth16>;
+
+/// A pool of JIT stacks of the same size.
+pub type JitStackPool = JitStackPoolImpl<CodeUnitWidth16>;
+
+/// A JIT stack taken from a `JitStackPool`, which is returned to the pool
+/// when dropped.
+///
+/// The lifetime parameter `'p` refers to the lifetime of the pool.
+pub type PooledJitStack<'p> = PooledJitStackImpl<'p, CodeUnitWidth16>;
+
+/// Scratch space for PCRE2's DFA matching algorithm, which can be reused
+/// across searches.
+pub type DfaWorkspace = DfaWorkspaceImpl<CodeUnitWidth16>;
+
+/// The result of a search that permits partial matches.
+///
+/// The lifetime parameter `'s` refers to the lifetime of the matched portion
+/// of the subject string.
+pub type PartialMatch<'s> = PartialMatchImpl<'s, CodeUnitWidth16>;
+
+/// A match found in a stream of input.
+///
+/// Offsets are relative to the beginning of the stream.
+pub type StreamMatch = StreamMatchImpl<u16>;
+
+/// Finds successive non-overlapping matches in input that is provided in
+/// chunks.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type StreamMatcher<'r> = StreamMatcherImpl<'r, CodeUnitWidth16>;
+
+/// An iterator over successive non-overlapping matches in a subject that is
+/// split into chunks.
+///
+/// The lifetime parameters `'r` and `'c` refer to the lifetimes of the regex
+/// and of the chunks, and `I` is the type of the iterator over the chunks.
+pub type ChunkMatches<'r, 'c, I> = ChunkMatchesImpl<'r, 'c, CodeUnitWidth16, I>;
+
+/// A push-based scanner for finding successive non-overlapping matches in
+/// input that is provided in chunks.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type Scanner<'r> = ScannerImpl<'r, CodeUnitWidth16>;
+
+impl RegexImpl<CodeUnitWidth16> {
+    /// Compiles a regular expression from a Rust string, which is transcoded
+    /// to UTF-16. See `Regex::new`.
+    ///
+    /// The regex is compiled with the default options, so enable
+    /// `RegexBuilder::utf` when searching subjects that contain characters
+    /// outside the Basic Multilingual Plane.
+    pub fn new_str(pattern: &str) -> Result<Regex, Error> {
+        RegexBuilder::new().build_str(pattern)
+    }
+}
+
+impl RegexBuilderImpl<CodeUnitWidth16> {
+    /// Compiles a regular expression from a Rust string, which is transcoded
+    /// to UTF-16. See `RegexBuilder::build`.
+    pub fn build_str(&self, pattern: &str) -> Result<Regex, Error> {
+        self.build(pattern.encode_utf16().collect::<Box<[u16]>>())
+    }
+}
+
+impl<'s> MatchImpl<'s, CodeUnitWidth16> {
+    /// Returns the matched portion of the subject as a Rust string.
+    ///
+    /// Unpaired surrogates are replaced with `U+FFFD`.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_bytes())
+    }
+
+    /// Returns the matched portion of the subject as an OS string, which
+    /// preserves unpaired surrogates.
+    #[cfg(windows)]
+    pub fn to_os_string(&self) -> OsString {
+        OsString::from_wide(self.as_bytes())
+    }
+
+    /// Returns the range of the match in the UTF-8 encoding of the subject,
+    /// i.e., in `String::from_utf16_lossy(subject)`.
+    ///
+    /// This takes time proportional to the end offset of the match. See
+    /// `utf8_offset`.
+    pub fn utf8_range(&self) -> Range<usize> {
+        let subject = self.subject();
+        let start = utf8_offset(subject, self.start());
+        start..start + utf8_offset(&subject[self.start()..], self.end() - self.start())
+    }
+}
+
+/// Converts an offset into a UTF-16 subject to the corresponding byte offset
+/// into its UTF-8 encoding.
+///
+/// Unpaired surrogates are counted as three bytes each, which is the length
+/// of `U+FFFD` that `String::from_utf16_lossy` replaces them with, and also
+/// their length in the WTF-8 encoding used by `OsStr` on Windows. This means
+/// that offsets of matches in paths or registry data obtained with
+/// `OsStrExt::encode_wide` can be used to slice the original `OsStr` with
+/// `OsStr::as_encoded_bytes`.
+///
+/// # Panics
+///
+/// This panics if `offset` is greater than the length of `subject`.
+pub fn utf8_offset(subject: &[u16], offset: usize) -> usize {
+    char::decode_utf16(subject[..offset].iter().copied())
+        .map(|result| result.map_or(3, char::len_utf8))
+        .sum()
+}
+
+/// Converts a byte offset into a Rust string to the corresponding offset into
+/// its UTF-16 encoding, as produced by `str::encode_utf16`.
+///
+/// This is the inverse of `utf8_offset`, and is useful for translating
+/// offsets into subjects such as `RegexBuilder::offset_limit` or the start
+/// offset of `Regex::find_at`.
+///
+/// # Panics
+///
+/// This panics if `offset` is greater than the length of `subject` or if it
+/// doesn't fall on a character boundary.
+pub fn utf16_offset(subject: &str, offset: usize) -> usize {
+    subject[..offset].chars().map(char::len_utf16).sum()
+}
+
+/// A buffer of UTF-16LE encoded bytes, viewed as UTF-16 code units so that
+/// it can be searched with a 16-bit regex.
+///
+/// If the bytes are suitably aligned and the target is little endian, the
+/// code units borrow the bytes directly. Otherwise, they are copied. A
+/// trailing odd byte isn't part of any code unit, so it's never searched.
+///
+/// The bytes don't need to be valid UTF-16. Unless `RegexBuilder::utf` is
+/// enabled, every code unit is matched as is, and otherwise
+/// `RegexBuilder::match_invalid_utf` permits searching data with unpaired
+/// surrogates.
+///
+/// The lifetime parameter `'b` refers to the lifetime of the bytes.
+#[derive(Clone, Debug)]
+pub struct Utf16LeBytes<'b> {
+    units: Cow<'b, [u16]>,
+}
+
+impl<'b> Utf16LeBytes<'b> {
+    /// View the given UTF-16LE encoded bytes as code units.
+    pub fn new(bytes: &'b [u8]) -> Utf16LeBytes<'b> {
+        let bytes = &bytes[..bytes.len() & !1];
+        // SAFETY: Every bit pattern is a valid `u16`.
+        let (prefix, units, _) = unsafe { bytes.align_to::<u16>() };
+        let units = if prefix.is_empty() && cfg!(target_endian = "little") {
+            Cow::Borrowed(units)
+        } else {
+            Cow::Owned(
+                bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect(),
+            )
+        };
+        Utf16LeBytes { units }
+    }
+
+    /// Returns the code units to search.
+    pub fn units(&self) -> &[u16] {
+        &self.units
+    }
+
+    /// Returns the range of bytes that correspond to the given match, which
+    /// must have been found in the code units of this buffer.
+    pub fn byte_range(&self, m: &Match<'_>) -> Range<usize> {
+        2 * m.start()..2 * m.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{utf16_offset, utf8_offset, CodeUnitWidth16, Regex, RegexBuilder, Utf16LeBytes};
+    use crate::is_jit_available;
+
+    fn b(string: &str) -> Box<[u16]> {
+        string.encode_utf16().collect::<Vec<_>>().into_boxed_slice()
+    }
+
+    fn find_iter_tuples(re: &Regex, subject: &[u16]) -> Vec<(usize, usize)> {
+        let mut tuples = vec![];
+        for result in re.find_iter(subject) {
+            let m = result.unwrap();
+            tuples.push((m.start(), m.end()));
+        }
+        tuples
+    }
+
+    #[test]
+    fn caseless() {
+        let re = RegexBuilder::new().caseless(true).build(b("a")).unwrap();
+        assert!(re.is_match(&b("A")).unwrap());
+
+        let re = RegexBuilder::new()
+            .caseless(true)
+            .ucp(true)
+            .build(b("β"))
+            .unwrap();
+        assert!(re.is_match(&b("Β")).unwrap());
+    }
+
+    #[test]
+    fn ucp() {
+        let re = RegexBuilder::new().ucp(false).build(b(r"\w")).unwrap();
+        assert!(!re.is_match(&b("β")).unwrap());
+
+        let re = RegexBuilder::new().ucp(true).build(b(r"\w")).unwrap();
+        assert!(re.is_match(&b("β")).unwrap());
+    }
+
+    #[test]
+    fn utf() {
+        let re = RegexBuilder::new().utf(false).build(b(".")).unwrap();
+        assert_eq!(re.find(&b("β")).unwrap().unwrap().as_pair(), (0, 1));
+        assert_eq!(re.find(&b("😃")).unwrap().unwrap().as_pair(), (0, 1));
+
+        let re = RegexBuilder::new().utf(true).build(b(".")).unwrap();
+        assert_eq!(re.find(&b("β")).unwrap().unwrap().as_pair(), (0, 1));
+        assert_eq!(re.find(&b("😃")).unwrap().unwrap().as_pair(), (0, 2));
+    }
+
+    #[test]
+    fn utf_with_lone_surrogate() {
+        let subject = &[b'a' as u16, 0xD800, b'b' as u16];
+        let re = RegexBuilder::new().utf(true).build(b("b")).unwrap();
+        assert!(re.is_match(subject).is_err());
+
+        let re = RegexBuilder::new()
+            .utf(true)
+            .match_invalid_utf(true)
+            .build(b("b"))
+            .unwrap();
+        assert_eq!(re.find(subject).unwrap().unwrap().as_pair(), (2, 3));
+    }
+
+    #[test]
+    fn fmt_debug_works() {
+        let subject = &b("x😃");
+        let re = RegexBuilder::new().utf(true).build(b("..")).unwrap();
+        let m = re.find(subject).unwrap().unwrap();
+        let _ = format!("{:?}", m);
+    }
+
+    #[test]
+    fn jit4lyfe() {
+        if is_jit_available::<CodeUnitWidth16>() {
+            let re = RegexBuilder::new().jit(true).build(b(r"\w")).unwrap();
+            assert!(re.is_match(&b("a")).unwrap());
+        } else {
+            RegexBuilder::new().jit(true).build(b(r"\w")).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn capture_names() {
+        let re = RegexBuilder::new()
+            .build(b(r"(?P<foo>abc)|(def)|(?P<a>ghi)|(?P<springsteen>jkl)"))
+            .unwrap();
+        assert_eq!(
+            re.capture_names().to_vec(),
+            vec![
+                None,
+                Some("foo".to_string()),
+                None,
+                Some("a".to_string()),
+                Some("springsteen".to_string()),
+            ]
+        );
+
+        let capture_names_idx = re.get_capture_names_idxs();
+        assert_eq!(capture_names_idx.len(), 3);
+        assert_eq!(capture_names_idx["foo"], 1);
+        assert_eq!(capture_names_idx["a"], 3);
+        assert_eq!(capture_names_idx["springsteen"], 4);
+    }
+
+    #[test]
+    fn find_iter_surrogate_pairs() {
+        let re = RegexBuilder::new().utf(true).build(b(r"(?:)")).unwrap();
+        assert_eq!(
+            find_iter_tuples(&re, &b("a😃")),
+            vec![(0, 0), (1, 1), (3, 3)]
+        );
+    }
+
+    #[test]
+    fn replace() {
+        let re = Regex::new(b(r"(?<first>\w+)\s+(\w+)")).unwrap();
+        let subject = &b("hello world foo bar");
+        assert_eq!(
+            &*re.replace(subject, &b("$2 ${first}")[..]).unwrap(),
+            &*b("world hello foo bar")
+        );
+    }
+
+    #[test]
+    fn wide_strings() {
+        let haystack = "C:\\Users\\bjørn\\😃.txt";
+        let subject: Vec<u16> = haystack.encode_utf16().collect();
+        let re = RegexBuilder::new()
+            .utf(true)
+            .build_str(r"[^\\]+\.txt$")
+            .unwrap();
+        let m = re.find(&subject).unwrap().unwrap();
+        assert_eq!(m.to_string_lossy(), "😃.txt");
+        assert_eq!(&haystack[m.utf8_range()], "😃.txt");
+        assert_eq!(utf16_offset(haystack, m.utf8_range().start), m.start());
+
+        let subject = &[0xD800, b'a' as u16, 0xDE03, b'b' as u16];
+        assert_eq!(utf8_offset(subject, 2), 4);
+        assert_eq!(utf8_offset(subject, 4), 8);
+        let re = Regex::new_str("b").unwrap();
+        assert_eq!(re.find(subject).unwrap().unwrap().utf8_range(), 7..8);
+    }
+
+    #[test]
+    fn utf16le_bytes() {
+        let encoded: Vec<u8> = "x <née> <😃>"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let re = RegexBuilder::new().utf(true).build_str("<[^>]*>").unwrap();
+
+        // Search the bytes both at the start of a buffer and after a leading
+        // byte, which misaligns them, with a trailing odd byte in both cases.
+        for pad in 0..2 {
+            let mut buf = vec![0; pad];
+            buf.extend_from_slice(&encoded);
+            buf.push(b'<');
+            let subject = Utf16LeBytes::new(&buf[pad..]);
+            assert_eq!(subject.units().len(), encoded.len() / 2);
+            let ranges: Vec<_> = re
+                .find_iter(subject.units())
+                .map(|m| subject.byte_range(&m.unwrap()))
+                .collect();
+            assert_eq!(ranges, vec![4..14, 16..24]);
+        }
+    }
+}
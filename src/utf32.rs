@@ -1,6 +1,29 @@
-use crate::ffi::CodeUnitWidth32;
+pub use crate::ffi::CalloutBlock as CalloutBlockImpl;
+pub use crate::ffi::CalloutResult;
+pub use crate::ffi::CodeUnitWidth32;
+pub use crate::ffi::JitStack as JitStackImpl;
+pub use crate::ffi::NameTable as NameTableImpl;
+pub use crate::jit::JitStackPool as JitStackPoolImpl;
+pub use crate::jit::PooledJitStack as PooledJitStackImpl;
+pub use crate::regex_impl::Captures as CapturesImpl;
+pub use crate::regex_impl::CharacterTables as CharacterTablesImpl;
+pub use crate::regex_impl::DfaWorkspace as DfaWorkspaceImpl;
 pub use crate::regex_impl::Match as MatchImpl;
+pub use crate::regex_impl::PartialMatch as PartialMatchImpl;
+pub use crate::regex_impl::{
+    BsrConvention, CalloutInfo, CancellationToken, EmptyMatchPolicy, Extended, FirstCodeUnit,
+    MatchOptions, Newline, NoExpand, PartialMode, Replacer, ReplacerRef, SubstituteOptions,
+};
 use crate::regex_impl::{Regex as RegexImpl, RegexBuilder as RegexBuilderImpl};
+pub use crate::set::Lexer as LexerImpl;
+pub use crate::set::RegexSet as RegexSetImpl;
+pub use crate::set::RegexSetBuilder as RegexSetBuilderImpl;
+pub use crate::set::SetCaptureLocations as SetCaptureLocationsImpl;
+pub use crate::set::{LexError, PatternOptions, SetMatches};
+pub use crate::stream::ChunkMatches as ChunkMatchesImpl;
+pub use crate::stream::Scanner as ScannerImpl;
+pub use crate::stream::StreamMatch as StreamMatchImpl;
+pub use crate::stream::StreamMatcher as StreamMatcherImpl;
 
 /// A compiled PCRE2 regular expression for matching sequences of Rust chars.
 ///
@@ -17,10 +40,111 @@ pub type RegexBuilder = RegexBuilderImpl<CodeUnitWidth32>;
 /// of the subject string.
 pub type Match<'s> = MatchImpl<'s, CodeUnitWidth32>;
 
+/// A set of compiled PCRE2 regular expressions that are matched against a
+/// subject together.
+pub type RegexSet = RegexSetImpl<CodeUnitWidth32>;
+
+/// A builder for configuring the compilation of a `RegexSet`.
+pub type RegexSetBuilder = RegexSetBuilderImpl<CodeUnitWidth32>;
+
+/// The capture locations of every pattern in a `RegexSet`, which can be
+/// reused across searches.
+pub type SetCaptureLocations = SetCaptureLocationsImpl<CodeUnitWidth32>;
+
+/// An iterator that splits a subject into consecutive tokens using the
+/// patterns of a `RegexSet`.
+///
+/// The lifetime parameters `'r` and `'s` refer to the lifetimes of the set
+/// and of the subject string.
+pub type Lexer<'r, 's> = LexerImpl<'r, 's, CodeUnitWidth32>;
+
+/// An iterator over the named capturing groups of a regex, which borrows
+/// the names from the compiled regex.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type NameTable<'r> = NameTableImpl<'r, CodeUnitWidth32>;
+
+/// Captures represents a group of captured char sequences for a single match.
+///
+/// The lifetime parameter `'s` refers to the lifetime of the matched subject
+/// string.
+pub type Captures<'s> = CapturesImpl<'s, CodeUnitWidth32>;
+
+/// The state of a search when it reaches a callout.
+///
+/// The lifetime parameter `'a` refers to the lifetime of the subject and of
+/// the regex.
+pub type CalloutBlock<'a> = CalloutBlockImpl<'a, CodeUnitWidth32>;
+
+/// Character tables, which determine how PCRE2 classifies and case folds
+/// characters with code points less than 256.
+pub type CharacterTables = CharacterTablesImpl<CodeUnitWidth32>;
+
+/// A JIT stack, which can be installed for the current thread to be shared
+/// by every regex searched on it.
+pub type JitStack = JitStackImpl<CodeUnitWidth32>;
+
+/// A pool of JIT stacks of the same size.
+pub type JitStackPool = JitStackPoolImpl<CodeUnitWidth32>;
+
+/// A JIT stack taken from a `JitStackPool`, which is returned to the pool
+/// when dropped.
+///
+/// The lifetime parameter `'p` refers to the lifetime of the pool.
+pub type PooledJitStack<'p> = PooledJitStackImpl<'p, CodeUnitWidth32>;
+
+/// Scratch space for PCRE2's DFA matching algorithm, which can be reused
+/// across searches.
+pub type DfaWorkspace = DfaWorkspaceImpl<CodeUnitWidth32>;
+
+/// The result of a search that permits partial matches.
+///
+/// The lifetime parameter `'s` refers to the lifetime of the matched portion
+/// of the subject string.
+pub type PartialMatch<'s> = PartialMatchImpl<'s, CodeUnitWidth32>;
+
+/// A match found in a stream of input.
+///
+/// Offsets are relative to the beginning of the stream.
+pub type StreamMatch = StreamMatchImpl<char>;
+
+/// Finds successive non-overlapping matches in input that is provided in
+/// chunks.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type StreamMatcher<'r> = StreamMatcherImpl<'r, CodeUnitWidth32>;
+
+/// An iterator over successive non-overlapping matches in a subject that is
+/// split into chunks.
+///
+/// The lifetime parameters `'r` and `'c` refer to the lifetimes of the regex
+/// and of the chunks, and `I` is the type of the iterator over the chunks.
+pub type ChunkMatches<'r, 'c, I> = ChunkMatchesImpl<'r, 'c, CodeUnitWidth32, I>;
+
+/// A push-based scanner for finding successive non-overlapping matches in
+/// input that is provided in chunks.
+///
+/// The lifetime parameter `'r` refers to the lifetime of the regex.
+pub type Scanner<'r> = ScannerImpl<'r, CodeUnitWidth32>;
+
+/// Reinterprets a slice of UTF-32 code units as a slice of characters, so
+/// that it can be searched without copying it.
+///
+/// This returns `None` if any of the code units isn't a valid Unicode scalar
+/// value, i.e., if it's a surrogate or greater than `0x10FFFF`.
+pub fn as_chars(subject: &[u32]) -> Option<&[char]> {
+    if subject.iter().any(|&c| char::from_u32(c).is_none()) {
+        return None;
+    }
+    // SAFETY: `char` has the same size and alignment as `u32`, and we've
+    // checked that every code unit is a valid `char`.
+    Some(unsafe { std::slice::from_raw_parts(subject.as_ptr().cast(), subject.len()) })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{CodeUnitWidth32, Regex, RegexBuilder};
-    use crate::is_jit_available;
+    use super::{as_chars, CodeUnitWidth32, Extended, Regex, RegexBuilder};
+    use crate::{is_jit_available, ErrorKind};
 
     fn b(string: &str) -> Box<[char]> {
         string.chars().collect::<Vec<_>>().into_boxed_slice()
@@ -232,6 +356,38 @@ mod tests {
     }
 
     #[test]
+    fn replace() {
+        let re = Regex::new(b(r"(?<first>\w+)\s+(\w+)")).unwrap();
+        let subject = &b("hello world foo bar");
+        assert_eq!(
+            &*re.replace(subject, &b("$2 ${first}")[..]).unwrap(),
+            &*b("world hello foo bar")
+        );
+        assert_eq!(
+            &*re.replace_all(subject, &b("$2 ${first}")[..]).unwrap(),
+            &*b("world hello bar foo")
+        );
+
+        // Code points that aren't valid `char`s are rejected.
+        let re = Regex::new(b("a")).unwrap();
+        for template in [r"\x{d800}", r"\x{110000}"] {
+            let err = re.replace(&b("a"), Extended(&b(template)[..])).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::Substitute));
+        }
+    }
+
+    #[test]
+    fn expand() {
+        let re = Regex::new(b(r"(?<first>\w+)\s+(\w+)")).unwrap();
+        let subject = b("hello world");
+        let caps = re.captures(&subject).unwrap().unwrap();
+        let mut dst = vec![];
+        caps.expand(&b("$2 ⇒ ${first} $$"), &mut dst);
+        assert_eq!(&*dst, &*b("world ⇒ hello $"));
+    }
+
+    #[test]
+    #[allow(clippy::useless_format)]
     fn max_jit_stack_size_does_something() {
         if !is_jit_available::<CodeUnitWidth32>() {
             return;
@@ -270,4 +426,15 @@ mod tests {
             .unwrap();
         assert!(re.is_match(&b(&hay)).unwrap());
     }
+
+    #[test]
+    fn u32_subjects() {
+        let subject: Vec<u32> = "aβ😃".chars().map(u32::from).collect();
+        let re = Regex::new(b("β")).unwrap();
+        let m = re.find(as_chars(&subject).unwrap()).unwrap().unwrap();
+        assert_eq!(m.as_pair(), (1, 2));
+
+        assert!(as_chars(&[0x61, 0xD800]).is_none());
+        assert!(as_chars(&[0x110000]).is_none());
+    }
 }
@@ -0,0 +1,363 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::error::Error;
+use crate::ffi::CodeUnitWidth8;
+use crate::regex_impl::{
+    CaptureMatches as CaptureMatchesImpl, Captures as CapturesImpl, Match as MatchImpl,
+    Matches as MatchesImpl, Regex as RegexImpl, RegexBuilder as RegexBuilderImpl,
+};
+
+/// A compiled PCRE2 regular expression for matching Rust strings.
+///
+/// Since a `&str` is always valid UTF-8, this regex is always compiled in
+/// UTF mode and PCRE2 doesn't check the validity of subjects before
+/// searching them, which is otherwise only possible with the unsafe
+/// `RegexBuilder::disable_utf_check`. The `\C` escape, which matches a
+/// single byte even in UTF mode, is rejected when compiling the pattern.
+///
+/// Use `bytes::RegexBuilder::build_str` to configure the regex.
+///
+/// This regex is safe to use from multiple threads simultaneously. For top
+/// performance, it is better to clone a new regex for each thread.
+#[derive(Clone)]
+pub struct Regex {
+    re: RegexImpl<CodeUnitWidth8>,
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Regex({:?})", self.re.as_str())
+    }
+}
+
+impl RegexBuilderImpl<CodeUnitWidth8> {
+    /// Compile the given pattern into a regex for matching Rust strings
+    /// using this builder's configuration. See
+    /// [`utf8::Regex`](../utf8/struct.Regex.html).
+    ///
+    /// UTF mode is enabled and `\C` is rejected regardless of how this
+    /// builder is configured.
+    pub fn build_str(&self, pattern: &str) -> Result<Regex, Error> {
+        let mut builder = self.clone();
+        builder.utf(true).never_backslash_c(true);
+        // SAFETY: Every subject is a `&str`, and every start offset is
+        // checked to be on a character boundary before searching. Since
+        // `\C` is rejected, matches can't end in the middle of a character
+        // either.
+        unsafe {
+            builder.disable_utf_check();
+        }
+        Ok(Regex {
+            re: builder.build(pattern)?,
+        })
+    }
+}
+
+impl Regex {
+    /// Compiles a regular expression using the default configuration.
+    ///
+    /// Once compiled, it can be used repeatedly to search, split or replace
+    /// text in a string.
+    ///
+    /// If an invalid expression is given, then an error is returned.
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        RegexBuilderImpl::<CodeUnitWidth8>::new().build_str(pattern)
+    }
+
+    /// Returns true if and only if the regex matches the subject string
+    /// given.
+    pub fn is_match(&self, subject: &str) -> Result<bool, Error> {
+        self.re.is_match(subject.as_bytes())
+    }
+
+    /// Returns the same as `is_match`, but starts the search at the given
+    /// offset.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `start` isn't on a character boundary of `subject`.
+    pub fn is_match_at(&self, subject: &str, start: usize) -> Result<bool, Error> {
+        check_start(subject, start);
+        self.re.is_match_at(subject.as_bytes(), start)
+    }
+
+    /// Returns the start and end byte range of the leftmost-first match in
+    /// `subject`. If no match exists, then `None` is returned.
+    pub fn find<'s>(&self, subject: &'s str) -> Result<Option<Match<'s>>, Error> {
+        self.find_at(subject, 0)
+    }
+
+    /// Returns the same as `find`, but starts the search at the given
+    /// offset.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `start` isn't on a character boundary of `subject`.
+    pub fn find_at<'s>(&self, subject: &'s str, start: usize) -> Result<Option<Match<'s>>, Error> {
+        check_start(subject, start);
+        let m = self.re.find_at(subject.as_bytes(), start)?;
+        Ok(m.map(|m| Match::new(subject, m)))
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `subject`, returning the start and end byte indices with respect to
+    /// `subject`.
+    pub fn find_iter<'r, 's>(&'r self, subject: &'s str) -> Matches<'r, 's> {
+        Matches {
+            subject,
+            it: self.re.find_iter(subject.as_bytes()),
+        }
+    }
+
+    /// Returns the capture groups corresponding to the leftmost-first match
+    /// in `subject`. Capture group `0` always corresponds to the entire
+    /// match. If no match is found, then `None` is returned.
+    pub fn captures<'s>(&self, subject: &'s str) -> Result<Option<Captures<'s>>, Error> {
+        let caps = self.re.captures(subject.as_bytes())?;
+        Ok(caps.map(|caps| Captures { subject, caps }))
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups
+    /// matched in `subject`.
+    pub fn captures_iter<'r, 's>(&'r self, subject: &'s str) -> CaptureMatches<'r, 's> {
+        CaptureMatches {
+            subject,
+            it: self.re.captures_iter(subject.as_bytes()),
+        }
+    }
+
+    /// Replaces the leftmost-first match in `subject` with the replacement
+    /// template given. If no match is found, then the subject is returned
+    /// unchanged, without copying it.
+    ///
+    /// The template is interpreted by PCRE2's `pcre2_substitute` routine.
+    /// See `bytes::Regex::replace` for its syntax.
+    pub fn replace<'s>(&self, subject: &'s str, rep: &str) -> Result<Cow<'s, str>, Error> {
+        self.replacen(subject, 1, rep)
+    }
+
+    /// Replaces all non-overlapping matches in `subject` with the
+    /// replacement template given. See `replace`.
+    pub fn replace_all<'s>(&self, subject: &'s str, rep: &str) -> Result<Cow<'s, str>, Error> {
+        self.replacen(subject, 0, rep)
+    }
+
+    /// Replaces at most `limit` non-overlapping matches in `subject` with
+    /// the replacement template given. If `limit` is `0`, then all
+    /// non-overlapping matches are replaced. See `replace`.
+    pub fn replacen<'s>(
+        &self,
+        subject: &'s str,
+        limit: usize,
+        rep: &str,
+    ) -> Result<Cow<'s, str>, Error> {
+        match self
+            .re
+            .replacen(subject.as_bytes(), limit, rep.as_bytes())?
+        {
+            Cow::Borrowed(_) => Ok(Cow::Borrowed(subject)),
+            Cow::Owned(replaced) => Ok(Cow::Owned(
+                String::from_utf8(replaced).expect("valid UTF-8 from pcre2_substitute"),
+            )),
+        }
+    }
+
+    /// Returns the original pattern string for this regex.
+    pub fn as_str(&self) -> &str {
+        self.re.as_str()
+    }
+
+    /// Returns a sequence of all capturing groups and their names, if
+    /// present. See `bytes::Regex::capture_names`.
+    pub fn capture_names(&self) -> &[Option<String>] {
+        self.re.capture_names()
+    }
+
+    /// Returns the number of capturing groups in the pattern.
+    ///
+    /// This is always 1 more than the number of syntactic groups in the
+    /// pattern, since the first group always corresponds to the entire match.
+    pub fn captures_len(&self) -> usize {
+        self.re.captures_len()
+    }
+}
+
+/// Panics if `start` isn't a valid place to start searching `subject`.
+///
+/// Searching from the middle of a character is undefined behavior when
+/// PCRE2's UTF check is disabled, so this must be called before every search
+/// with an explicit start offset.
+fn check_start(subject: &str, start: usize) {
+    assert!(
+        subject.is_char_boundary(start),
+        "start offset {} is not on a character boundary",
+        start,
+    );
+}
+
+/// Match represents a single match of a regex in a subject string.
+///
+/// The lifetime parameter `'s` refers to the lifetime of the matched portion
+/// of the subject string.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Match<'s> {
+    subject: &'s str,
+    start: usize,
+    end: usize,
+}
+
+impl<'s> Match<'s> {
+    /// Returns the starting byte offset of the match in the subject.
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the ending byte offset of the match in the subject.
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the matched portion of the subject string.
+    #[inline]
+    pub fn as_str(&self) -> &'s str {
+        &self.subject[self.start..self.end]
+    }
+
+    fn new(subject: &'s str, m: MatchImpl<'s, CodeUnitWidth8>) -> Match<'s> {
+        Match {
+            subject,
+            start: m.start(),
+            end: m.end(),
+        }
+    }
+}
+
+/// Captures represents a group of captured strings for a single match.
+///
+/// The 0th capture always corresponds to the entire match. Each subsequent
+/// index corresponds to the next capture group in the regex. Positions
+/// returned from a capture group are always byte indices.
+///
+/// `'s` is the lifetime of the matched subject string.
+pub struct Captures<'s> {
+    subject: &'s str,
+    caps: CapturesImpl<'s, CodeUnitWidth8>,
+}
+
+impl<'s> fmt::Debug for Captures<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.caps.fmt(f)
+    }
+}
+
+impl<'s> Captures<'s> {
+    /// Returns the match associated with the capture group at index `i`. If
+    /// `i` does not correspond to a capture group, or if the capture group
+    /// did not participate in the match, then `None` is returned.
+    pub fn get(&self, i: usize) -> Option<Match<'s>> {
+        self.caps.get(i).map(|m| Match::new(self.subject, m))
+    }
+
+    /// Returns the match for the capture group named `name`. If `name` isn't
+    /// a valid capture group or didn't match anything, then `None` is
+    /// returned.
+    pub fn name(&self, name: &str) -> Option<Match<'s>> {
+        self.caps.name(name).map(|m| Match::new(self.subject, m))
+    }
+
+    /// Returns the number of captured groups.
+    ///
+    /// This is always at least `1`, since every regex has at least one
+    /// capture group that corresponds to the full match.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.caps.len()
+    }
+
+    /// Expands all instances of `$name` in `replacement` to the
+    /// corresponding capture group `name`, and appends them to `dst`. See
+    /// `bytes::Captures::expand` for the syntax.
+    pub fn expand(&self, replacement: &str, dst: &mut String) {
+        let mut expanded = vec![];
+        self.caps.expand(replacement.as_bytes(), &mut expanded);
+        dst.push_str(&String::from_utf8(expanded).expect("valid UTF-8 from expansion"));
+    }
+}
+
+/// An iterator over all non-overlapping matches for a particular subject
+/// string.
+///
+/// `'r` is the lifetime of the compiled regular expression and `'s` is the
+/// lifetime of the subject string.
+pub struct Matches<'r, 's> {
+    subject: &'s str,
+    it: MatchesImpl<'r, 's, CodeUnitWidth8>,
+}
+
+impl<'r, 's> Iterator for Matches<'r, 's> {
+    type Item = Result<Match<'s>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let subject = self.subject;
+        self.it
+            .next()
+            .map(|result| result.map(|m| Match::new(subject, m)))
+    }
+}
+
+/// An iterator that yields all non-overlapping capture groups matching a
+/// particular regular expression.
+///
+/// `'r` is the lifetime of the compiled regular expression and `'s` is the
+/// lifetime of the subject string.
+pub struct CaptureMatches<'r, 's> {
+    subject: &'s str,
+    it: CaptureMatchesImpl<'r, 's, CodeUnitWidth8>,
+}
+
+impl<'r, 's> Iterator for CaptureMatches<'r, 's> {
+    type Item = Result<Captures<'s>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let subject = self.subject;
+        self.it
+            .next()
+            .map(|result| result.map(|caps| Captures { subject, caps }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Regex;
+    use crate::bytes::RegexBuilder;
+
+    #[test]
+    fn str_subjects() {
+        let re = Regex::new(r"(?<word>\S+)$").unwrap();
+        let subject = "grüße, wörld";
+        let m = re.find(subject).unwrap().unwrap();
+        assert_eq!(m.as_str(), "wörld");
+        assert_eq!(re.captures(subject).unwrap().unwrap().name("word"), Some(m));
+        assert_eq!(re.replace(subject, "<$word>").unwrap(), "grüße, <wörld>");
+
+        // Empty matches never split a character.
+        let re = Regex::new("").unwrap();
+        let ends: Vec<usize> = re.find_iter("aé").map(|m| m.unwrap().end()).collect();
+        assert_eq!(ends, vec![0, 1, 3]);
+
+        let re = RegexBuilder::new().caseless(true).build_str("É").unwrap();
+        assert!(re.is_match("é").unwrap());
+        assert!(Regex::new(r"\C").is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn start_inside_character() {
+        let re = Regex::new("a").unwrap();
+        let _ = re.find_at("éa", 1);
+    }
+}